@@ -67,6 +67,7 @@ export class CrossChainRelayer extends EventEmitter {
   private isRunning = false;
   private orderCache = new Map<string, CrossChainOrder>();
   private processingOrders = new Set<string>();
+  private revealCursor = 0;
 
   // Contract ABIs
   private readonly ETH_CONTRACT_ABI = [
@@ -111,6 +112,9 @@ export class CrossChainRelayer extends EventEmitter {
       this.startEthereumMonitoring();
       this.startPolkadotMonitoring();
 
+      // Pick up any secrets revealed on Polkadot while we were offline
+      await this.catchUpReveals();
+
       this.isRunning = true;
       console.log("✅ Cross-Chain Relayer started successfully");
 
@@ -325,6 +329,88 @@ export class CrossChainRelayer extends EventEmitter {
     }
   }
 
+  /**
+   * Replay the Polkadot reveal log from the last cursor and make sure every
+   * revealed secret is also known on the Ethereum side.
+   */
+  async catchUpReveals(pageSize: number = 100): Promise<number> {
+    if (!this.dotContract || !this.dotAccount) {
+      return 0;
+    }
+
+    let reconciled = 0;
+
+    try {
+      for (;;) {
+        const { result, output } = await this.dotContract.query.getRevealsSince(
+          this.dotAccount.address,
+          { gasLimit: -1 },
+          this.revealCursor,
+          pageSize
+        );
+
+        if (result.isErr || !output) {
+          console.error("❌ Failed to query reveal log");
+          break;
+        }
+
+        const json = output.toJSON() as any;
+        const page = (json?.ok?.ok ?? json?.ok) as
+          | { items: [number, string, string][]; nextOffset: number | null }
+          | undefined;
+        if (!page || page.items.length === 0) {
+          break;
+        }
+
+        for (const [index, hashlock, secret] of page.items) {
+          reconciled += await this.reconcileReveal(hashlock, secret);
+          this.revealCursor = Number(index) + 1;
+        }
+
+        if (page.nextOffset === null) {
+          break;
+        }
+      }
+
+      console.log(
+        `✅ Reveal catch-up complete (cursor ${this.revealCursor}, ${reconciled} reconciled)`
+      );
+    } catch (error) {
+      console.error("❌ Error catching up reveals:", error);
+    }
+
+    return reconciled;
+  }
+
+  /**
+   * Reveal a Polkadot-side secret on every cached Ethereum order sharing its hashlock
+   */
+  private async reconcileReveal(
+    hashlock: string,
+    secret: string
+  ): Promise<number> {
+    let reconciled = 0;
+
+    for (const order of this.orderCache.values()) {
+      if (order.secretHash.toLowerCase() !== hashlock.toLowerCase()) {
+        continue;
+      }
+
+      const ethSecret = await this.ethContract.getSecret(order.orderHash);
+      if (ethSecret === ethers.ZeroHash) {
+        console.log(`🔐 Revealing caught-up secret for ${order.orderHash}`);
+        const tx = await this.ethContract.revealSecret(order.orderHash, secret);
+        await tx.wait();
+        reconciled++;
+      }
+
+      order.secret = secret;
+      this.orderCache.set(order.orderHash, order);
+    }
+
+    return reconciled;
+  }
+
   /**
    * Evaluate and process a cross-chain order
    */
@@ -0,0 +1,172 @@
+/* eslint-disable @typescript-eslint/no-explicit-any */
+import { ApiPromise, WsProvider } from "@polkadot/api";
+import { Abi } from "@polkadot/api-contract";
+import { u8aToHex } from "@polkadot/util";
+import { keccakAsU8a } from "@polkadot/util-crypto";
+import { readFileSync, writeFileSync } from "fs";
+
+/**
+ * One call to the contract as it happened on chain, in the shape the contract's
+ * `replay` test support consumes (`polkadotrelayer/fixtures/replay/*.json`)
+ */
+export interface TraceCall {
+  block: number;
+  caller: string; // H160
+  value: string;
+  message: string; // ink! message label, e.g. "withdraw_fill"
+  args: unknown[];
+  outcome: { ok: unknown } | { err: string };
+}
+
+export interface ReplayTrace {
+  description: string;
+  chain: string;
+  contract: string;
+  deployer: string;
+  calls: TraceCall[];
+}
+
+/**
+ * pallet-revive address of a substrate account: accounts derived from an
+ * Ethereum key carry it in the first 20 bytes, all others hash to it
+ */
+export function toH160(accountId: Uint8Array): string {
+  const ethDerived = accountId.slice(20).every((byte) => byte === 0xee);
+  return u8aToHex(ethDerived ? accountId.slice(0, 20) : keccakAsU8a(accountId).slice(12));
+}
+
+/**
+ * Outcome of a call, recovered by dry-running it on the parent block. Events only
+ * say that a call reverted; the revert data holding the error is not stored.
+ * Calls earlier in the same block are not applied, so a dry run can disagree
+ * with the extrinsic's recorded success, in which case the extrinsic wins.
+ */
+async function recordOutcome(
+  api: ApiPromise,
+  abi: Abi,
+  parentHash: any,
+  call: { origin: any; dest: any; value: any; data: any },
+  message: any,
+  succeeded: boolean
+): Promise<TraceCall["outcome"]> {
+  const apiAt = await api.at(parentHash);
+  const dryRun: any = await apiAt.call.reviveApi.call(
+    call.origin,
+    call.dest,
+    call.value,
+    null,
+    null,
+    call.data
+  );
+
+  if (dryRun.result.isErr) {
+    return succeeded ? { ok: null } : { err: dryRun.result.asErr.toString() };
+  }
+
+  const returnType = message.returnType;
+  const output: any = abi.registry.createTypeUnsafe(
+    returnType.lookupName || returnType.type,
+    [dryRun.result.asOk.data.toU8a(true)]
+  );
+  // ink! wraps every message result in Result<_, LangError>
+  const result = output.isOk ? output.asOk : output;
+  if (result.isErr && !succeeded) {
+    return { err: result.asErr.type };
+  }
+  return { ok: result.isOk ? result.asOk.toJSON() : null };
+}
+
+/**
+ * Export every `revive.call` into `contract` between `fromBlock` and `toBlock`
+ * (inclusive), decoded against the contract metadata
+ */
+export async function exportReplayTrace(
+  api: ApiPromise,
+  abi: Abi,
+  contract: string,
+  fromBlock: number,
+  toBlock: number,
+  deployer?: string
+): Promise<ReplayTrace> {
+  const chain = (await api.rpc.system.chain()).toString();
+  const calls: TraceCall[] = [];
+
+  for (let number = fromBlock; number <= toBlock; number++) {
+    const hash = await api.rpc.chain.getBlockHash(number);
+    const { block } = await api.rpc.chain.getBlock(hash);
+    const events: any = await (await api.at(hash)).query.system.events();
+
+    for (const [index, extrinsic] of block.extrinsics.entries()) {
+      const { section, method, args } = extrinsic.method;
+      if (section !== "revive" || method !== "call" || !extrinsic.isSigned) {
+        continue;
+      }
+      const [dest, value, , , data] = args as any[];
+      if (dest.toHex().toLowerCase() !== contract.toLowerCase()) {
+        continue;
+      }
+
+      const decoded = abi.decodeMessage(data.toU8a(true));
+      const succeeded = events.some(
+        ({ phase, event }: any) =>
+          phase.isApplyExtrinsic &&
+          phase.asApplyExtrinsic.eqn(index) &&
+          api.events.system.ExtrinsicSuccess.is(event)
+      );
+      const origin = extrinsic.signer.toString();
+
+      calls.push({
+        block: number,
+        caller: toH160(extrinsic.signer.toU8a()),
+        value: value.toString(),
+        message: decoded.message.identifier,
+        args: decoded.args.map((arg) => arg.toJSON()),
+        outcome: await recordOutcome(
+          api,
+          abi,
+          block.header.parentHash,
+          { origin, dest, value, data },
+          decoded.message,
+          succeeded
+        ),
+      });
+    }
+  }
+
+  return {
+    description: `Calls to ${contract} on ${chain}, blocks ${fromBlock}..=${toBlock}`,
+    chain,
+    contract,
+    deployer: deployer ?? calls[0]?.caller ?? contract,
+    calls,
+  };
+}
+
+// npx ts-node lib/replay-trace.ts <ws-url> <metadata.json> <contract> <from> <to> [out] [deployer]
+if (require.main === module) {
+  const [wsUrl, metadataPath, contract, from, to, out, deployer] = process.argv.slice(2);
+  if (!wsUrl || !metadataPath || !contract || !from || !to) {
+    console.error(
+      "usage: replay-trace <ws-url> <metadata.json> <contract> <from> <to> [out] [deployer]"
+    );
+    process.exit(1);
+  }
+
+  (async () => {
+    const api = await ApiPromise.create({ provider: new WsProvider(wsUrl) });
+    const abi = new Abi(JSON.parse(readFileSync(metadataPath, "utf8")), api.registry.getChainProperties());
+    const trace = await exportReplayTrace(api, abi, contract, Number(from), Number(to), deployer);
+    const json = JSON.stringify(trace, null, 2);
+
+    if (out) {
+      writeFileSync(out, json + "\n");
+      console.log(`✅ Exported ${trace.calls.length} calls to ${out}`);
+    } else {
+      console.log(json);
+    }
+    await api.disconnect();
+  })().catch((error) => {
+    console.error("❌ Failed to export replay trace:", error);
+    process.exit(1);
+  });
+}
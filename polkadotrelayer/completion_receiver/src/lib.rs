@@ -0,0 +1,167 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+/// Sample target for `PolkadotPartialFills` order completion callbacks.
+///
+/// A maker registers this contract together with the selector of
+/// `on_order_completed` (`ink::selector_bytes!("on_order_completed")`) in the
+/// order options. Once the order is fully filled the swap contract calls it with
+/// the order id, the total filled source amount and the matching destination
+/// amount. Calls from any contract other than the configured source are ignored.
+///
+/// `with_behaviour` deploys a receiver that reverts or exhausts its gas instead,
+/// for exercising how the swap contract handles a broken callback.
+#[ink::contract]
+mod polkadot_completion_receiver {
+    use ink::storage::Mapping;
+    use scale::{Decode, Encode};
+    use scale_info::TypeInfo;
+
+    /// How `on_order_completed` answers a call from the source
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum Behaviour {
+        #[default]
+        Record,
+        Revert,
+        ExhaustGas,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct Completion {
+        pub total_filled: Balance,
+        pub total_dest: Balance,
+        pub block: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct CompletionRecorded {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        total_filled: Balance,
+        total_dest: Balance,
+    }
+
+    #[ink(storage)]
+    pub struct CompletionReceiver {
+        source: Address, // the PolkadotPartialFills instance allowed to report completions
+        completions: Mapping<[u8; 32], Completion>, // orderId => latest completion
+        completion_count: u32,
+        behaviour: Behaviour,
+    }
+
+    impl CompletionReceiver {
+        #[ink(constructor)]
+        pub fn new(source: Address) -> Self {
+            Self::with_behaviour(source, Behaviour::Record)
+        }
+
+        #[ink(constructor)]
+        pub fn with_behaviour(source: Address, behaviour: Behaviour) -> Self {
+            Self {
+                source,
+                completions: Mapping::default(),
+                completion_count: 0,
+                behaviour,
+            }
+        }
+
+        /// Called by the swap contract when an order becomes fully filled. Takes no
+        /// value and returns nothing, matching what the swap contract expects.
+        #[ink(message)]
+        pub fn on_order_completed(
+            &mut self,
+            order_id: [u8; 32],
+            total_filled: Balance,
+            total_dest: Balance,
+        ) {
+            if self.env().caller() != self.source {
+                return;
+            }
+            match self.behaviour {
+                Behaviour::Record => {}
+                Behaviour::Revert => panic!("completion rejected"),
+                Behaviour::ExhaustGas => loop {
+                    self.completion_count = self.completion_count.wrapping_add(1);
+                },
+            }
+
+            let completion = Completion {
+                total_filled,
+                total_dest,
+                block: self.env().block_number(),
+            };
+            self.completions.insert(order_id, &completion);
+            self.completion_count = self.completion_count.saturating_add(1);
+
+            self.env().emit_event(CompletionRecorded {
+                order_id,
+                total_filled,
+                total_dest,
+            });
+        }
+
+        #[ink(message)]
+        pub fn get_source(&self) -> Address {
+            self.source
+        }
+
+        #[ink(message)]
+        pub fn get_completion(&self, order_id: [u8; 32]) -> Option<Completion> {
+            self.completions.get(order_id)
+        }
+
+        #[ink(message)]
+        pub fn get_completion_count(&self) -> u32 {
+            self.completion_count
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        type TestEnv = ink::env::DefaultEnvironment;
+
+        #[ink::test]
+        fn test_records_completion_from_source() {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_block_number::<TestEnv>(42);
+            let mut receiver = CompletionReceiver::new(accounts.alice);
+
+            receiver.on_order_completed([0x01; 32], 997, 1994);
+
+            assert_eq!(receiver.get_completion([0x01; 32]), Some(Completion {
+                total_filled: 997,
+                total_dest: 1994,
+                block: 42,
+            }));
+            assert_eq!(receiver.get_completion_count(), 1);
+            assert_eq!(ink::env::test::recorded_events().count(), 1);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "completion rejected")]
+        fn test_reverting_behaviour() {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            let mut receiver = CompletionReceiver::with_behaviour(accounts.alice, Behaviour::Revert);
+
+            receiver.on_order_completed([0x01; 32], 997, 1994);
+        }
+
+        #[ink::test]
+        fn test_ignores_other_callers() {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            let mut receiver = CompletionReceiver::new(accounts.alice);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            receiver.on_order_completed([0x01; 32], 997, 1994);
+
+            assert_eq!(receiver.get_completion([0x01; 32]), None);
+            assert_eq!(receiver.get_completion_count(), 0);
+        }
+    }
+}
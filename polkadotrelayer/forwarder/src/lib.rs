@@ -0,0 +1,100 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+/// Contract account for the `PolkadotPartialFills` end-to-end tests.
+///
+/// Its deployer acts through `forward`, so that a contract can hold a role such
+/// as the swap contract's admin. Having no payable fallback, it reverts every
+/// plain transfer, standing in for a recipient that refuses its payout.
+#[ink::contract]
+mod polkadot_forwarder {
+    use ink::prelude::vec::Vec;
+
+    /// Call data passed on as given, selector included
+    struct RawInput<'a>(&'a [u8]);
+
+    impl scale::Encode for RawInput<'_> {
+        fn size_hint(&self) -> usize {
+            self.0.len()
+        }
+
+        fn encode_to<T: scale::Output + ?Sized>(&self, dest: &mut T) {
+            dest.write(self.0);
+        }
+    }
+
+    /// Return data of a forwarded call, kept as it was encoded
+    struct RawOutput(Vec<u8>);
+
+    impl scale::Decode for RawOutput {
+        fn decode<I: scale::Input>(input: &mut I) -> Result<Self, scale::Error> {
+            let mut output = Vec::new();
+            while let Ok(byte) = input.read_byte() {
+                output.push(byte);
+            }
+            Ok(RawOutput(output))
+        }
+    }
+
+    #[ink(storage)]
+    pub struct Forwarder {
+        owner: Address, // the deployer, the only account `forward` acts for
+    }
+
+    impl Forwarder {
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self { owner: Self::env().caller() }
+        }
+
+        /// Call `callee` with `input`, a selector followed by the SCALE-encoded
+        /// arguments, and return its return value, still encoded. A message that
+        /// returns an error has its changes reverted but still reports the error.
+        #[ink(message)]
+        pub fn forward(&mut self, callee: Address, input: Vec<u8>) -> Vec<u8> {
+            use ink::env::call::{build_call, ExecutionInput, Selector};
+
+            assert_eq!(self.env().caller(), self.owner, "only the deployer forwards");
+            let (selector, args) = input.split_at(4.min(input.len()));
+            let selector: [u8; 4] = selector.try_into().unwrap_or_default();
+            let result = build_call::<Environment>()
+                .call(callee)
+                .exec_input(ExecutionInput::new(Selector::new(selector)).push_arg(RawInput(args)))
+                .returns::<RawOutput>()
+                .try_invoke();
+            match result {
+                Ok(Ok(RawOutput(output))) => output,
+                _ => panic!("forwarded call failed"),
+            }
+        }
+
+        #[ink(message)]
+        pub fn get_owner(&self) -> Address {
+            self.owner
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        type TestEnv = ink::env::DefaultEnvironment;
+
+        #[ink::test]
+        fn test_owned_by_deployer() {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let forwarder = Forwarder::new();
+            assert_eq!(forwarder.get_owner(), accounts.bob);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "only the deployer forwards")]
+        fn test_refuses_other_callers() {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let mut forwarder = Forwarder::new();
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            forwarder.forward(accounts.alice, Vec::new());
+        }
+    }
+}
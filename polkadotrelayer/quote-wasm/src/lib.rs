@@ -0,0 +1,354 @@
+//! Browser build of the contract's quoting logic, for UIs that want a fill quote or a
+//! creation check without a node round trip or a funded dry run.
+//!
+//! Every quote and check delegates to `polkadot_swap_quote`, which the contract calls
+//! for the same computations. Errors are the name of the contract `Error` variant the
+//! contract would return. `format_amount` and `parse_amount` convert between base
+//! units and whole tokens for display and input, with the decimals the contract
+//! reports in `get_order_display`. Build with
+//! `wasm-pack build --target web -- --features bindgen`.
+
+use polkadot_swap_quote as quote;
+pub use quote::{Balance, BlockNumber, NATIVE_DECIMALS};
+
+#[cfg(feature = "bindgen")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// The parts of an order (as returned by `get_order`) a fill quote depends on
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderTerms {
+    pub total_amount: Balance,
+    pub filled_amount: Balance,
+    pub min_fill_amount: Balance,
+    pub allow_partial_fills: bool,
+    pub dest_amount_per_unit: Balance,
+    pub source_decimals: Option<u8>,
+    pub dest_decimals: Option<u8>,
+}
+
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+impl OrderTerms {
+    #[cfg_attr(feature = "bindgen", wasm_bindgen(constructor))]
+    pub fn new(
+        total_amount: Balance,
+        filled_amount: Balance,
+        min_fill_amount: Balance,
+        allow_partial_fills: bool,
+        dest_amount_per_unit: Balance,
+        source_decimals: Option<u8>,
+        dest_decimals: Option<u8>,
+    ) -> Self {
+        Self {
+            total_amount,
+            filled_amount,
+            min_fill_amount,
+            allow_partial_fills,
+            dest_amount_per_unit,
+            source_decimals,
+            dest_decimals,
+        }
+    }
+}
+
+/// Creation arguments checked by `validate_create`
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CreateRequest {
+    pub total_amount: Balance,
+    pub min_fill_amount: Balance,
+    pub timelock: BlockNumber,
+    pub source_chain: u32,
+    pub dest_chain: u32,
+    pub max_fills: u32,
+}
+
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+impl CreateRequest {
+    #[cfg_attr(feature = "bindgen", wasm_bindgen(constructor))]
+    pub fn new(
+        total_amount: Balance,
+        min_fill_amount: Balance,
+        timelock: BlockNumber,
+        source_chain: u32,
+        dest_chain: u32,
+        max_fills: u32,
+    ) -> Self {
+        Self { total_amount, min_fill_amount, timelock, source_chain, dest_chain, max_fills }
+    }
+}
+
+/// Amount a fill is granted and the destination amount it is owed
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FillQuote {
+    pub fill_amount: Balance,
+    pub dest_amount: Balance,
+}
+
+/// What the taker is paid and the protocol fee kept for a fill of `amount`
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeEstimate {
+    pub net: Balance,
+    pub fee: Balance,
+}
+
+/// Fee split at `fee_bps`, the order's `fee_bps_applied` as read from `get_order`
+/// (zero for orders created inside a fee-free window)
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+pub fn estimate_fee(amount: Balance, fee_bps: u16) -> FeeEstimate {
+    let (net, fee) = quote::calculate_fees(amount, fee_bps);
+    FeeEstimate { net, fee }
+}
+
+/// What `fill_order` would grant for `requested` and the destination amount owed.
+/// A fill owed nothing after truncation is refused, as the contract refuses it.
+/// Taker-specific limits (reservations, vesting) are not known here.
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+pub fn simulate_fill(terms: &OrderTerms, requested: Balance) -> Result<FillQuote, String> {
+    let remaining = terms.total_amount.saturating_sub(terms.filled_amount);
+    let fill_amount = quote::clamp_fill(
+        requested,
+        remaining,
+        remaining,
+        terms.min_fill_amount,
+        terms.allow_partial_fills,
+    )
+    .map_err(error_name)?;
+    let dest_amount = quote::nonzero_dest_amount(
+        fill_amount,
+        terms.dest_amount_per_unit,
+        terms.source_decimals,
+        terms.dest_decimals,
+    )
+    .map_err(error_name)?;
+    Ok(FillQuote { fill_amount, dest_amount })
+}
+
+/// Smallest fill `simulate_fill` would accept on these terms: the order's minimum
+/// (or what remains, when less) raised to the smallest amount owed anything. `None`
+/// when no fill of what remains is owed a nonzero destination amount.
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+pub fn min_fill(terms: &OrderTerms) -> Option<Balance> {
+    let remaining = terms.total_amount.saturating_sub(terms.filled_amount);
+    let floor =
+        quote::min_nonzero_fill(terms.dest_amount_per_unit, terms.source_decimals, terms.dest_decimals)?;
+    let floor = floor.max(terms.min_fill_amount.min(remaining));
+    (floor <= remaining).then_some(floor)
+}
+
+/// Rate of a Dutch auction order at `current_block`, as `get_current_rate` reports
+/// it: pass it as `OrderTerms::dest_amount_per_unit` to quote a fill in that block.
+/// `creation_block` and `timelock` are the order's, as read from `get_order`.
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+pub fn dutch_auction_rate(
+    start_rate: Balance,
+    end_rate: Balance,
+    creation_block: BlockNumber,
+    timelock: BlockNumber,
+    current_block: BlockNumber,
+) -> Balance {
+    quote::dutch_auction_rate(start_rate, end_rate, creation_block, timelock, current_block)
+}
+
+/// The stateless creation checks, in the contract's order, against the timelock
+/// bounds read from the deployment at `current_block`
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+pub fn validate_create(
+    request: &CreateRequest,
+    current_block: BlockNumber,
+    min_timelock: BlockNumber,
+    max_timelock: BlockNumber,
+) -> Result<(), String> {
+    quote::validate_timelock_window(request.timelock, current_block, min_timelock, max_timelock)
+        .and_then(|()| quote::validate_chains(request.source_chain, request.dest_chain))
+        .and_then(|()| quote::validate_amounts(request.total_amount, request.min_fill_amount))
+        .and_then(|()| quote::validate_fill_config(request.max_fills))
+        .map_err(error_name)
+}
+
+/// `amount` base units as a decimal number of whole tokens with `decimals`
+/// places, trailing zeros dropped: `15000000000` at 10 decimals is `"1.5"`
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+pub fn format_amount(amount: Balance, decimals: u8) -> String {
+    let digits = format!("{:0>width$}", amount, width = decimals as usize + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals as usize);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+/// Base units of a whole-token amount such as `"1.5"`, the inverse of
+/// `format_amount`. Fails on anything but digits with at most one point, on more
+/// fractional digits than `decimals` and on amounts beyond `Balance`.
+#[cfg_attr(feature = "bindgen", wasm_bindgen)]
+pub fn parse_amount(input: &str, decimals: u8) -> Result<Balance, String> {
+    let input = input.trim();
+    let (whole, fraction) = input.split_once('.').unwrap_or((input, ""));
+    let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+        return Err("InvalidAmount".to_string());
+    }
+    if fraction.len() > decimals as usize {
+        return Err("TooManyDecimals".to_string());
+    }
+    let digits = format!("{}{:0<width$}", whole, fraction, width = decimals as usize);
+    digits.parse().map_err(|_| "AmountOverflow".to_string())
+}
+
+fn error_name(error: quote::QuoteError) -> String {
+    format!("{:?}", error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    const FIXTURES: &str = include_str!("../../fixtures/quotes.json");
+
+    fn balance(value: &Value) -> Balance {
+        value.as_str().expect("amounts are strings").parse().unwrap()
+    }
+
+    fn decimals(value: &Value) -> Option<u8> {
+        value.as_u64().map(|d| d as u8)
+    }
+
+    fn fixtures() -> Value {
+        serde_json::from_str(FIXTURES).expect("fixtures are JSON")
+    }
+
+    #[test]
+    fn test_estimate_fee_matches_fixtures() {
+        let fixtures = fixtures();
+        let fee_bps = fixtures["config"]["fee_bps"].as_u64().unwrap() as u16;
+        for case in fixtures["fees"].as_array().unwrap() {
+            let estimate = estimate_fee(balance(&case["amount"]), fee_bps);
+            assert_eq!(estimate.net, balance(&case["net"]), "{}", case);
+            assert_eq!(estimate.fee, balance(&case["fee"]), "{}", case);
+        }
+    }
+
+    #[test]
+    fn test_simulate_fill_matches_fixtures() {
+        for case in fixtures()["fills"].as_array().unwrap() {
+            let terms = &case["terms"];
+            let terms = OrderTerms::new(
+                balance(&terms["total_amount"]),
+                balance(&terms["filled_amount"]),
+                balance(&terms["min_fill_amount"]),
+                terms["allow_partial_fills"].as_bool().unwrap(),
+                balance(&terms["dest_amount_per_unit"]),
+                decimals(&terms["source_decimals"]),
+                decimals(&terms["dest_decimals"]),
+            );
+            let expected = &case["expected"];
+            let quote = simulate_fill(&terms, balance(&case["requested"]));
+            match expected["error"].as_str() {
+                Some(error) => assert_eq!(quote, Err(error.to_string()), "{}", case),
+                None => assert_eq!(
+                    quote,
+                    Ok(FillQuote {
+                        fill_amount: balance(&expected["fill_amount"]),
+                        dest_amount: balance(&expected["dest_amount"]),
+                    }),
+                    "{}",
+                    case
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_create_matches_fixtures() {
+        let fixtures = fixtures();
+        let config = &fixtures["config"];
+        let block = |key: &str| config[key].as_u64().unwrap() as BlockNumber;
+        for case in fixtures["creates"].as_array().unwrap() {
+            let request = CreateRequest::new(
+                balance(&case["total_amount"]),
+                balance(&case["min_fill_amount"]),
+                case["timelock"].as_u64().unwrap() as BlockNumber,
+                case["source_chain"].as_u64().unwrap() as u32,
+                case["dest_chain"].as_u64().unwrap() as u32,
+                case["max_fills"].as_u64().unwrap() as u32,
+            );
+            let outcome = validate_create(
+                &request,
+                block("current_block"),
+                block("min_timelock"),
+                block("max_timelock"),
+            );
+            let outcome = outcome.err().unwrap_or_else(|| "Ok".to_string());
+            assert_eq!(outcome, case["expected"].as_str().unwrap(), "{}", case);
+        }
+    }
+
+    #[test]
+    fn test_zero_minimum_fills() {
+        // A third of a unit per unit with no minimum
+        let terms = OrderTerms::new(10, 0, 0, true, quote::RATE_SCALE / 3, None, None);
+        assert_eq!(min_fill(&terms), Some(4));
+        assert_eq!(simulate_fill(&terms, 3), Err("FillAmountTooSmall".to_string()));
+        assert_eq!(simulate_fill(&terms, 4), Ok(FillQuote { fill_amount: 4, dest_amount: 1 }));
+        // Three planck left are never owed anything
+        let tail = OrderTerms { filled_amount: 7, ..terms };
+        assert_eq!(min_fill(&tail), None);
+        assert_eq!(simulate_fill(&tail, 3), Err("FillAmountTooSmall".to_string()));
+        // The order's own minimum still applies above the floor
+        let with_minimum = OrderTerms { min_fill_amount: 6, ..terms };
+        assert_eq!(min_fill(&with_minimum), Some(6));
+    }
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount(15_000_000_000, NATIVE_DECIMALS), "1.5");
+        assert_eq!(format_amount(1, NATIVE_DECIMALS), "0.0000000001");
+        assert_eq!(format_amount(0, NATIVE_DECIMALS), "0");
+        assert_eq!(format_amount(120_000_000_000, NATIVE_DECIMALS), "12");
+        assert_eq!(format_amount(14_500_000, 6), "14.5");
+        assert_eq!(format_amount(1_000, 0), "1000");
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("1.5", NATIVE_DECIMALS), Ok(15_000_000_000));
+        assert_eq!(parse_amount(" 12 ", NATIVE_DECIMALS), Ok(120_000_000_000));
+        assert_eq!(parse_amount("12.", NATIVE_DECIMALS), Ok(120_000_000_000));
+        assert_eq!(parse_amount("0.0000000001", NATIVE_DECIMALS), Ok(1));
+        assert_eq!(parse_amount("0.00000000001", NATIVE_DECIMALS), Err("TooManyDecimals".to_string()));
+        for input in ["", ".5", "1.2.3", "-1", "1e3", "1,5"] {
+            assert_eq!(parse_amount(input, NATIVE_DECIMALS), Err("InvalidAmount".to_string()), "{}", input);
+        }
+        let max = format_amount(Balance::MAX, 0);
+        assert_eq!(parse_amount(&format!("{}0", max), 0), Err("AmountOverflow".to_string()));
+    }
+
+    #[test]
+    fn test_parse_format_round_trip() {
+        let values = [
+            0,
+            1,
+            9,
+            10,
+            1_000_000_000,
+            10_000_000_000,
+            10_000_000_001,
+            123_456_789_000_000,
+            100_000_000_000_000_000,
+            Balance::MAX / 10,
+            Balance::MAX,
+        ];
+        for decimals in [0, 6, NATIVE_DECIMALS, 12, 18, 30, 38, 40] {
+            for value in values {
+                let formatted = format_amount(value, decimals);
+                assert_eq!(parse_amount(&formatted, decimals), Ok(value), "{} at {}", formatted, decimals);
+            }
+        }
+    }
+}
@@ -0,0 +1,517 @@
+#![cfg_attr(not(test), no_std)]
+
+//! Quoting and validation logic of `PolkadotPartialFills` with no ink! dependency.
+//!
+//! The contract calls these functions for its fee split, destination amounts,
+//! fill clamping, creation checks and id derivation, and `polkadot_swap_quote_wasm`
+//! exposes the same functions to the browser, so a client-side quote cannot
+//! drift from what the contract computes. `fixtures/quotes.json` pins both.
+
+pub type Balance = u128;
+pub type BlockNumber = u32;
+
+/// Fixed-point scale of `dest_amount_per_unit`
+pub const RATE_SCALE: Balance = 1_000_000_000_000;
+/// Assumed source decimals when only `dest_decimals` is given
+pub const DEFAULT_SOURCE_DECIMALS: u8 = 12;
+/// Assumed destination decimals when only `source_decimals` is given
+pub const DEFAULT_DEST_DECIMALS: u8 = 18;
+/// Decimals of DOT, the native token every order escrows
+pub const NATIVE_DECIMALS: u8 = 10;
+
+/// Length of the deployment salt appended to every id preimage
+pub const DEPLOYMENT_SALT_LEN: usize = 32;
+/// Upper bound on the data any id hashes, tag and salt included
+pub const MAX_ID_PREIMAGE_LEN: usize = 1 + 20 + 16 + 32 + 4 + 32 + 8 + DEPLOYMENT_SALT_LEN;
+
+/// Namespace tags leading the preimages of a tagged `IdScheme`, so an order id, a
+/// fill id and a contract id can never be derived from the same bytes
+pub const ORDER_ID_TAG: u8 = 0x01;
+pub const FILL_ID_TAG: u8 = 0x02;
+pub const CONTRACT_ID_TAG: u8 = 0x03;
+
+/// Failures of the checks below; each maps onto the contract `Error` of the same name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteError {
+    InvalidTimelock,
+    TimelockTooShort,
+    TimelockTooLong,
+    InvalidChainId,
+    InvalidFillAmount,
+    FillAmountTooSmall,
+    PartialFillsNotAllowed,
+}
+
+/// `amount * bps / 10_000` rounded down, without the intermediate product
+/// overflowing; exactly zero at zero bps
+pub fn bps_of(amount: Balance, bps: u16) -> Balance {
+    if bps == 0 {
+        return 0;
+    }
+    let bps = bps as Balance;
+    amount / 10_000 * bps + amount % 10_000 * bps / 10_000
+}
+
+/// Split `amount` into (net, fee). Callers pass the order's `fee_bps_applied`
+/// once it exists, never the live `protocol_fee_bps`.
+pub fn calculate_fees(amount: Balance, fee_bps: u16) -> (Balance, Balance) {
+    let fee = bps_of(amount, fee_bps);
+    (amount - fee, fee)
+}
+
+/// Rescales a whole-token amount or rate by `10^(dest_decimals - source_decimals)`;
+/// unchanged without decimal metadata
+pub fn rescale_to_base_units(
+    amount: Balance,
+    source_decimals: Option<u8>,
+    dest_decimals: Option<u8>,
+) -> Balance {
+    if source_decimals.is_none() && dest_decimals.is_none() {
+        return amount;
+    }
+
+    let source_decimals = source_decimals.unwrap_or(DEFAULT_SOURCE_DECIMALS);
+    let dest_decimals = dest_decimals.unwrap_or(DEFAULT_DEST_DECIMALS);
+    if dest_decimals >= source_decimals {
+        amount.saturating_mul(10u128.pow((dest_decimals - source_decimals) as u32))
+    } else {
+        amount / 10u128.pow((source_decimals - dest_decimals) as u32)
+    }
+}
+
+/// Destination base units owed for `fill_amount` source base units.
+///
+/// Without decimal metadata the rate converts base units directly. With it, the
+/// rate prices one whole source token in whole destination tokens and the result
+/// is rescaled by `10^(dest_decimals - source_decimals)`.
+pub fn dest_amount(
+    fill_amount: Balance,
+    dest_amount_per_unit: Balance,
+    source_decimals: Option<u8>,
+    dest_decimals: Option<u8>,
+) -> Balance {
+    let raw = (fill_amount * dest_amount_per_unit) / RATE_SCALE;
+    rescale_to_base_units(raw, source_decimals, dest_decimals)
+}
+
+/// Rate of a Dutch auction order at `current_block`: `start_rate` at creation,
+/// moving linearly to `end_rate` at the timelock and held there after. Rounded
+/// toward `start_rate`, without the intermediate product overflowing.
+pub fn dutch_auction_rate(
+    start_rate: Balance,
+    end_rate: Balance,
+    creation_block: BlockNumber,
+    timelock: BlockNumber,
+    current_block: BlockNumber,
+) -> Balance {
+    let duration = timelock.saturating_sub(creation_block) as Balance;
+    if duration == 0 {
+        return end_rate;
+    }
+    let elapsed = current_block.clamp(creation_block, timelock).saturating_sub(creation_block) as Balance;
+    let moved = |spread: Balance| spread / duration * elapsed + spread % duration * elapsed / duration;
+    if start_rate >= end_rate {
+        start_rate - moved(start_rate - end_rate)
+    } else {
+        start_rate + moved(end_rate - start_rate)
+    }
+}
+
+/// `dest_amount` of a granted fill, refusing a fill owed nothing at the destination.
+/// On an order created without a minimum this is the only floor on a fill.
+pub fn nonzero_dest_amount(
+    fill_amount: Balance,
+    dest_amount_per_unit: Balance,
+    source_decimals: Option<u8>,
+    dest_decimals: Option<u8>,
+) -> Result<Balance, QuoteError> {
+    match dest_amount(fill_amount, dest_amount_per_unit, source_decimals, dest_decimals) {
+        0 => Err(QuoteError::FillAmountTooSmall),
+        owed => Ok(owed),
+    }
+}
+
+/// Smallest fill owed a nonzero destination amount, `None` when no fill is: a zero
+/// rate, or a rate so small that truncation eats every amount up to `Balance::MAX`
+pub fn min_nonzero_fill(
+    dest_amount_per_unit: Balance,
+    source_decimals: Option<u8>,
+    dest_decimals: Option<u8>,
+) -> Option<Balance> {
+    if dest_amount_per_unit == 0 {
+        return None;
+    }
+    // Base units the rescaled amount must reach before it is nonzero
+    let units: Balance = match (source_decimals, dest_decimals) {
+        (None, None) => 1,
+        _ => {
+            let source_decimals = source_decimals.unwrap_or(DEFAULT_SOURCE_DECIMALS);
+            let dest_decimals = dest_decimals.unwrap_or(DEFAULT_DEST_DECIMALS);
+            10u128.checked_pow(source_decimals.saturating_sub(dest_decimals) as u32)?
+        }
+    };
+    // `raw >= units` exactly when `fill * rate >= units * RATE_SCALE`
+    let product = units.checked_mul(RATE_SCALE)?;
+    let fill = product.div_ceil(dest_amount_per_unit);
+    fill.checked_mul(dest_amount_per_unit).map(|_| fill)
+}
+
+/// Amount a fill of `requested` is granted: clamped to `available`, the part of
+/// `remaining` the taker may take, then checked against the order's minimum and
+/// its partial-fill setting
+pub fn clamp_fill(
+    requested: Balance,
+    remaining: Balance,
+    available: Balance,
+    min_fill_amount: Balance,
+    allow_partial_fills: bool,
+) -> Result<Balance, QuoteError> {
+    let granted = requested.min(available);
+    if granted < min_fill_amount && remaining > min_fill_amount {
+        return Err(QuoteError::FillAmountTooSmall);
+    }
+    if !allow_partial_fills && granted < remaining {
+        return Err(QuoteError::PartialFillsNotAllowed);
+    }
+    Ok(granted)
+}
+
+/// Timelock must lie within `[current_block + min_timelock, current_block + max_timelock]`
+pub fn validate_timelock_window(
+    timelock: BlockNumber,
+    current_block: BlockNumber,
+    min_timelock: BlockNumber,
+    max_timelock: BlockNumber,
+) -> Result<(), QuoteError> {
+    if timelock <= current_block {
+        return Err(QuoteError::InvalidTimelock);
+    }
+    if timelock < current_block + min_timelock {
+        return Err(QuoteError::TimelockTooShort);
+    }
+    if timelock > current_block + max_timelock {
+        return Err(QuoteError::TimelockTooLong);
+    }
+    Ok(())
+}
+
+pub fn validate_chains(source_chain: u32, dest_chain: u32) -> Result<(), QuoteError> {
+    if source_chain == dest_chain {
+        return Err(QuoteError::InvalidChainId);
+    }
+    Ok(())
+}
+
+/// A zero `min_fill_amount` sets no minimum; fills are then only held to owing a
+/// nonzero destination amount
+pub fn validate_amounts(total_amount: Balance, min_fill_amount: Balance) -> Result<(), QuoteError> {
+    if total_amount == 0 || min_fill_amount > total_amount {
+        return Err(QuoteError::InvalidFillAmount);
+    }
+    Ok(())
+}
+
+pub fn validate_fill_config(max_fills: u32) -> Result<(), QuoteError> {
+    if max_fills == 0 {
+        return Err(QuoteError::InvalidFillAmount);
+    }
+    Ok(())
+}
+
+/// How a deployment derives its ids. The contract picks it by storage version:
+/// version 3 appends the deployment salt, version 4 also leads with a namespace tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdScheme<'a> {
+    pub salt: Option<&'a [u8; DEPLOYMENT_SALT_LEN]>,
+    pub tagged: bool,
+}
+
+impl<'a> IdScheme<'a> {
+    /// Deployments from before the salt (storage version 2)
+    pub const PLAIN: IdScheme<'static> = IdScheme { salt: None, tagged: false };
+
+    /// Salted ids without a namespace tag (storage version 3)
+    pub fn salted(salt: &'a [u8; DEPLOYMENT_SALT_LEN]) -> Self {
+        Self { salt: Some(salt), tagged: false }
+    }
+
+    /// Salted, tagged ids (storage version 4 on)
+    pub fn tagged(salt: &'a [u8; DEPLOYMENT_SALT_LEN]) -> Self {
+        Self { salt: Some(salt), tagged: true }
+    }
+}
+
+/// Data an id hashes, built in place with no allocation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdPreimage {
+    bytes: [u8; MAX_ID_PREIMAGE_LEN],
+    len: usize,
+}
+
+impl IdPreimage {
+    fn new() -> Self {
+        Self { bytes: [0u8; MAX_ID_PREIMAGE_LEN], len: 0 }
+    }
+
+    fn put(mut self, bytes: &[u8]) -> Self {
+        self.bytes[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+        self
+    }
+
+    /// Starts a preimage with the namespace tag, when the scheme leads with one
+    fn tagged(scheme: IdScheme, tag: u8) -> Self {
+        let preimage = Self::new();
+        if scheme.tagged {
+            preimage.put(&[tag])
+        } else {
+            preimage
+        }
+    }
+
+    /// Appends the deployment salt, when the deployment mixes one in
+    fn salted(self, scheme: IdScheme) -> Self {
+        match scheme.salt {
+            Some(salt) => self.put(salt),
+            None => self,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+/// Data hashed into an order id: namespace tag, maker (raw H160 bytes), amount,
+/// hashlock, timelock, swap id, order counter and deployment salt, integers
+/// little-endian. Pinned by `fixtures/id_derivation.json`.
+pub fn order_id_preimage(
+    maker: &[u8; 20],
+    amount: Balance,
+    hashlock: &[u8; 32],
+    timelock: BlockNumber,
+    swap_id: &[u8; 32],
+    counter: u64,
+    scheme: IdScheme,
+) -> IdPreimage {
+    IdPreimage::tagged(scheme, ORDER_ID_TAG)
+        .put(maker)
+        .put(&amount.to_le_bytes())
+        .put(hashlock)
+        .put(&timelock.to_le_bytes())
+        .put(swap_id)
+        .put(&counter.to_le_bytes())
+        .salted(scheme)
+}
+
+/// Data hashed into a fill id: namespace tag, order id, taker, fill amount, block
+/// timestamp, block number and deployment salt
+pub fn fill_id_preimage(
+    order_id: &[u8; 32],
+    taker: &[u8; 20],
+    fill_amount: Balance,
+    block_timestamp: u64,
+    block_number: BlockNumber,
+    scheme: IdScheme,
+) -> IdPreimage {
+    IdPreimage::tagged(scheme, FILL_ID_TAG)
+        .put(order_id)
+        .put(taker)
+        .put(&fill_amount.to_le_bytes())
+        .put(&block_timestamp.to_le_bytes())
+        .put(&block_number.to_le_bytes())
+        .salted(scheme)
+}
+
+/// Data hashed into a contract id: namespace tag, order id, fill id, block
+/// timestamp, fill counter and deployment salt
+pub fn contract_id_preimage(
+    order_id: &[u8; 32],
+    fill_id: &[u8; 32],
+    block_timestamp: u64,
+    sequence: u64,
+    scheme: IdScheme,
+) -> IdPreimage {
+    IdPreimage::tagged(scheme, CONTRACT_ID_TAG)
+        .put(order_id)
+        .put(fill_id)
+        .put(&block_timestamp.to_le_bytes())
+        .put(&sequence.to_le_bytes())
+        .salted(scheme)
+}
+
+#[cfg(feature = "sha2")]
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    use sha2::Digest;
+    sha2::Sha256::digest(data).into()
+}
+
+#[cfg(feature = "sha2")]
+pub fn derive_order_id(
+    maker: &[u8; 20],
+    amount: Balance,
+    hashlock: &[u8; 32],
+    timelock: BlockNumber,
+    swap_id: &[u8; 32],
+    counter: u64,
+    scheme: IdScheme,
+) -> [u8; 32] {
+    sha256(order_id_preimage(maker, amount, hashlock, timelock, swap_id, counter, scheme).as_bytes())
+}
+
+#[cfg(feature = "sha2")]
+pub fn derive_fill_id(
+    order_id: &[u8; 32],
+    taker: &[u8; 20],
+    fill_amount: Balance,
+    block_timestamp: u64,
+    block_number: BlockNumber,
+    scheme: IdScheme,
+) -> [u8; 32] {
+    sha256(fill_id_preimage(order_id, taker, fill_amount, block_timestamp, block_number, scheme).as_bytes())
+}
+
+#[cfg(feature = "sha2")]
+pub fn derive_contract_id(
+    order_id: &[u8; 32],
+    fill_id: &[u8; 32],
+    block_timestamp: u64,
+    sequence: u64,
+    scheme: IdScheme,
+) -> [u8; 32] {
+    sha256(contract_id_preimage(order_id, fill_id, block_timestamp, sequence, scheme).as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fee_math_at_zero_bps() {
+        assert_eq!(calculate_fees(1000, 0), (1000, 0));
+        assert_eq!(calculate_fees(Balance::MAX, 0), (Balance::MAX, 0));
+        assert_eq!(calculate_fees(1000, 30), (997, 3));
+        // No intermediate overflow at the largest amounts
+        assert_eq!(bps_of(Balance::MAX, 1000), Balance::MAX / 10);
+        assert_eq!(bps_of(9_999, 1), 0);
+    }
+
+    #[test]
+    fn test_clamp_fill() {
+        // Clamped to what is available, then checked against the minimum
+        assert_eq!(clamp_fill(500, 997, 300, 100, true), Ok(300));
+        assert_eq!(clamp_fill(500, 997, 50, 100, true), Err(QuoteError::FillAmountTooSmall));
+        // The last sliver of an order may be below the minimum
+        assert_eq!(clamp_fill(500, 50, 50, 100, true), Ok(50));
+        assert_eq!(clamp_fill(500, 997, 997, 100, false), Err(QuoteError::PartialFillsNotAllowed));
+        assert_eq!(clamp_fill(997, 997, 997, 100, false), Ok(997));
+        // No minimum: any amount down to one planck
+        assert_eq!(clamp_fill(1, 997, 997, 0, true), Ok(1));
+    }
+
+    #[test]
+    fn test_zero_minimum_floor() {
+        assert_eq!(validate_amounts(1000, 0), Ok(()));
+        assert_eq!(validate_amounts(0, 0), Err(QuoteError::InvalidFillAmount));
+        assert_eq!(validate_amounts(1000, 1001), Err(QuoteError::InvalidFillAmount));
+
+        // One to one in base units: every planck is owed a planck
+        assert_eq!(min_nonzero_fill(RATE_SCALE, None, None), Some(1));
+        assert_eq!(nonzero_dest_amount(1, RATE_SCALE, None, None), Ok(1));
+        // A third of a unit per unit: two planck truncate to nothing
+        let third = RATE_SCALE / 3;
+        assert_eq!(min_nonzero_fill(third, None, None), Some(4));
+        assert_eq!(nonzero_dest_amount(3, third, None, None), Err(QuoteError::FillAmountTooSmall));
+        assert_eq!(nonzero_dest_amount(4, third, None, None), Ok(1));
+        // 12 to 6 decimals at 3 per token: a millionth of a token is the smallest owed unit
+        let floor = min_nonzero_fill(3 * RATE_SCALE, Some(12), Some(6)).unwrap();
+        assert_eq!(floor, 333_334);
+        assert_eq!(nonzero_dest_amount(floor - 1, 3 * RATE_SCALE, Some(12), Some(6)), Err(QuoteError::FillAmountTooSmall));
+        assert_eq!(nonzero_dest_amount(floor, 3 * RATE_SCALE, Some(12), Some(6)), Ok(1));
+        // Scaling up never truncates a whole fill away
+        assert_eq!(min_nonzero_fill(RATE_SCALE, Some(10), Some(18)), Some(1));
+        assert_eq!(min_nonzero_fill(0, None, None), None);
+        assert_eq!(min_nonzero_fill(1, Some(40), Some(0)), None);
+    }
+
+    #[test]
+    fn test_dutch_auction_rate() {
+        // Falling from 2.0 to 1.0 over blocks 100..500
+        let rate = |block| dutch_auction_rate(2 * RATE_SCALE, RATE_SCALE, 100, 500, block);
+        assert_eq!(rate(100), 2 * RATE_SCALE);
+        assert_eq!(rate(300), RATE_SCALE * 3 / 2);
+        assert_eq!(rate(500), RATE_SCALE);
+        // Held at the ends outside the auction
+        assert_eq!(rate(50), 2 * RATE_SCALE);
+        assert_eq!(rate(900), RATE_SCALE);
+        // Rising auctions and rounding toward the start rate
+        assert_eq!(dutch_auction_rate(0, 10, 0, 3, 1), 3);
+        assert_eq!(dutch_auction_rate(10, 0, 0, 3, 1), 7);
+        assert_eq!(dutch_auction_rate(Balance::MAX, 0, 0, 1 << 31, 1 << 30), Balance::MAX / 2 + 1);
+        assert_eq!(dutch_auction_rate(5, 1, 100, 100, 100), 1);
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn test_id_derivation_golden_vectors() {
+        let hex = |bytes: [u8; 32]| bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        let ids = |scheme: IdScheme| {
+            let order_id = derive_order_id(&[0x11; 20], 997, &[0x01; 32], 500, &[0x02; 32], 1, scheme);
+            let fill_id = derive_fill_id(&order_id, &[0x22; 20], 200, 1_700_000_000_000, 100, scheme);
+            let contract_id = derive_contract_id(&order_id, &fill_id, 1_700_000_000_000, 1, scheme);
+            [hex(order_id), hex(fill_id), hex(contract_id)]
+        };
+
+        // Deployments from before the salt (storage version 2)
+        assert_eq!(ids(IdScheme::PLAIN), [
+            "0d4f7a7a74679628f47826152607b6debdb8b58a4e60b7d89be9223449da5a6e",
+            "542920232a0f948b8c93c604400ad268437da3adfe1b05048dead286e7ca2c96",
+            "6ebe04207407ab4ce77a921519358cb61f251fbca46a930073d4928c6c87d271",
+        ]);
+        let salt = sha256(&[&b"thalaivarswap:deployment:"[..], &[0x33; 20]].concat());
+        assert_eq!(hex(salt), "ee238c76fe12ab3db54434433d6fd339d8d26393212f2b8fd3a8578577d4305a");
+        // Salted, before the namespace tags (storage version 3)
+        assert_eq!(ids(IdScheme::salted(&salt)), [
+            "9285ea8302cca332a72abeffacddcce4cbbd798ca1df22691e0bc87fda1aa29f",
+            "e336e1ea784ec088bce0277dc7055b823753035a994232683d6a412c6dacae47",
+            "cd5ac3a8b2c88c86c29545c19b44176c58b97ed9637a692e933a9a7cb8b57dd9",
+        ]);
+        assert_eq!(ids(IdScheme::tagged(&salt)), [
+            "87c3ac74d94ba8da17cde3638e0c63f864a7465ec85b5474bb327057f6f08a41",
+            "50e77e08343bebe1e975f35c94ba9dbdb7595349c9d89c15ff52378def195ee4",
+            "4fb5f51e31a73076b42a46ec4bd05e630b342efd902830a679175217a2664eb1",
+        ]);
+    }
+
+    #[test]
+    fn test_id_namespaces_cannot_share_a_preimage() {
+        // A fill preimage read back as contract id inputs: the taker and amount
+        // chosen so the untagged bytes spell an arbitrary fill id
+        let salt = [0x5a; DEPLOYMENT_SALT_LEN];
+        let mut taker = [0u8; 20];
+        taker.copy_from_slice(&[0xfe; 32][..20]);
+        let amount = Balance::from_le_bytes([[0xfe; 12].as_slice(), &[0x01; 4]].concat().try_into().unwrap());
+        for scheme in [IdScheme::PLAIN, IdScheme::salted(&salt), IdScheme::tagged(&salt)] {
+            let fill = fill_id_preimage(&[0x07; 32], &taker, amount, 0x0203_0405_0607_0809, 42, scheme);
+            let body = &fill.as_bytes()[scheme.tagged as usize..];
+            let word = |range: core::ops::Range<usize>| u64::from_le_bytes(body[range].try_into().unwrap());
+            let contract = contract_id_preimage(
+                body[..32].try_into().unwrap(),
+                body[32..64].try_into().unwrap(),
+                word(64..72),
+                word(72..80),
+                scheme,
+            );
+            // Only the tag keeps the two namespaces apart
+            assert_eq!(fill.as_bytes() == contract.as_bytes(), !scheme.tagged, "{:?}", scheme);
+        }
+
+        // An order preimage is as long as a salted fill preimage of another
+        // deployment; the leading tags still differ
+        let order = order_id_preimage(&[0x11; 20], 997, &[0x01; 32], 500, &[0x02; 32], 1, IdScheme::tagged(&salt));
+        let fill = fill_id_preimage(&[0x11; 32], &[0x22; 20], 200, 1, 1, IdScheme::tagged(&salt));
+        let contract = contract_id_preimage(&[0x11; 32], &[0x22; 32], 1, 1, IdScheme::tagged(&salt));
+        let tags = [order.as_bytes()[0], fill.as_bytes()[0], contract.as_bytes()[0]];
+        assert_eq!(tags, [ORDER_ID_TAG, FILL_ID_TAG, CONTRACT_ID_TAG]);
+    }
+}
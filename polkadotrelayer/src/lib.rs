@@ -4,9 +4,148 @@
 mod polkadotrelayer {
     use ink::prelude::vec::Vec;
     use ink::storage::Mapping;
+    use polkadot_swap_quote as quote;
     use scale::{Encode, Decode};
     use scale_info::TypeInfo;
 
+    // Supported feature combinations. Each is built and tested on its own with
+    // `cargo test --no-default-features --features <features>`, and
+    // `feature_matrix_tests` checks the messages it compiles in against
+    // `fixtures/messages.json`, so metadata only ever lists callable messages.
+    //
+    // | features                        | build                                         |
+    // |---------------------------------|-----------------------------------------------|
+    // | std, periphery, psp22 (default) | full deployment                               |
+    // | std, psp22                      | escrow core, protocol fees in a PSP22 token   |
+    // | std                             | escrow core, native protocol fees only        |
+    // | any of the above plus xcm       | adds `PayoutRoute::Xcm`, needing XCM host fns |
+
+    /// Upper bound on entries returned by a single `get_reveals_since` call
+    #[cfg(feature = "periphery")]
+    const MAX_REVEALS_PAGE: u32 = 100;
+    /// Upper bound on records returned by a single `get_updates_since` call
+    #[cfg(feature = "periphery")]
+    const MAX_UPDATES_PAGE: u32 = 100;
+    /// Upper bound on fills embedded in an `OrderFullState`
+    #[cfg(feature = "periphery")]
+    const MAX_FULL_STATE_FILLS: usize = 20;
+    /// Maximum length of the maker-supplied order tags
+    #[cfg(feature = "periphery")]
+    const MAX_ORDER_TAGS_LEN: usize = 64;
+    /// Percentage of `min_timelock` new orders must lock for while pending fills
+    /// exceed the congestion soft cap
+    const CONGESTED_MIN_TIMELOCK_PCT: u64 = 150;
+    /// Maximum length of the memo a taker attaches to a withdrawal
+    const MAX_WITHDRAWAL_MEMO_LEN: usize = 64;
+    /// Upper bound on entries returned by a single `get_admin_audit_log` call
+    const MAX_AUDIT_PAGE: u32 = 100;
+    /// Upper bound on fill ids returned by a single `get_order_fills` call
+    const MAX_ORDER_FILLS_PAGE: u32 = 100;
+    /// Upper bound on orders returned by `get_orders_sorted_by_price`
+    #[cfg(feature = "periphery")]
+    const MAX_SORTED_ORDERS: u32 = 20;
+    /// Upper bound on ids returned by a single page of the active, archived, swap,
+    /// maker or taker index
+    #[cfg(feature = "periphery")]
+    const MAX_INDEX_PAGE: u32 = 100;
+    /// Upper bound on orders returned by a single `get_open_orders` call; each entry
+    /// carries the full order, so pages are smaller than id pages
+    #[cfg(feature = "periphery")]
+    const MAX_OPEN_ORDERS_PAGE: u32 = 20;
+    /// Upper bound on fills returned by a single `get_fills_by_taker` call, which
+    /// carries each fill's state along with its id
+    #[cfg(feature = "periphery")]
+    const MAX_TAKER_FILLS_PAGE: u32 = 20;
+    /// Upper bound on active orders scanned by a single `get_expiring_soon` call
+    #[cfg(feature = "periphery")]
+    const MAX_EXPIRING_PAGE: u32 = 20;
+    /// Upper bound on orders processed by one `backfill_indices` call
+    #[cfg(feature = "periphery")]
+    const MAX_BACKFILL_BATCH: usize = 50;
+    /// Upper bound on orders visited by one `migrate_storage_to_v2` call
+    #[cfg(feature = "periphery")]
+    const MAX_MIGRATION_BATCH: usize = 50;
+    /// Upper bound on orders cancelled by one `batch_cancel_orders` call
+    #[cfg(feature = "periphery")]
+    const MAX_BATCH_CANCEL: usize = 20;
+    /// Blocks between proposing and applying a new `min_timelock` (~1 hour)
+    const MIN_TIMELOCK_GOVERNANCE_DELAY: BlockNumber = 600;
+    /// Blocks a staged upgrade waits before it can be finalized unchecked (~12 hours)
+    const UPGRADE_DELAY: BlockNumber = 7_200;
+    /// Maximum number of contracts notified through `IEventConsumer`
+    #[cfg(feature = "periphery")]
+    const MAX_STATS_CONSUMERS: usize = 5;
+    /// `IEventConsumer::consume_event` type of a fill; data is a `StatsEventData`
+    #[cfg(feature = "periphery")]
+    pub const STATS_EVENT_FILL: u8 = 0;
+    /// `IEventConsumer::consume_event` type of a withdrawal; data is a `StatsEventData`
+    #[cfg(feature = "periphery")]
+    pub const STATS_EVENT_WITHDRAW: u8 = 1;
+    /// Maximum number of tiers in the resolver deposit schedule
+    const MAX_DEPOSIT_TIERS: usize = 10;
+    /// Maximum number of orders linked to one group id
+    #[cfg(feature = "periphery")]
+    const MAX_GROUP_ORDERS: usize = 32;
+    /// Minimum blocks between two `announce_pending_fills` calls
+    #[cfg(feature = "periphery")]
+    const FILL_ANNOUNCEMENT_INTERVAL: BlockNumber = 100;
+    /// Upper bound on fills carried by one `PendingFillsAnnounced` event
+    #[cfg(feature = "periphery")]
+    const MAX_ANNOUNCED_FILLS: usize = 100;
+    /// Blocks a taker must wait after a refund before filling the same order again
+    const DEFAULT_REFUND_COOLDOWN: BlockNumber = 300;
+    /// Blocks a terminal order is kept before `cleanup_order` may purge it (a day of 6s blocks)
+    const DEFAULT_RETENTION_BLOCKS: BlockNumber = 14_400;
+    /// Blocks a `reserve` reservation holds capacity before it lapses
+    const DEFAULT_RESERVATION_BLOCKS: BlockNumber = 20;
+    /// Anti-spam bond taken by `reserve`, paid back when the reservation is used or released
+    const DEFAULT_RESERVATION_BOND: Balance = 100_000_000;
+    /// Upper bound on unexpired reservations held on one order at a time
+    const MAX_RESERVATIONS_PER_ORDER: usize = 8;
+    /// Largest share of an order's remaining amount one reservation may hold, in bps
+    const MAX_RESERVATION_SHARE_BPS: Balance = 5_000;
+    /// Upper bound on orders and on fills summed by one `get_account_position` call
+    #[cfg(feature = "periphery")]
+    const MAX_POSITION_SCAN: usize = 100;
+    /// Upper bound on orders scanned when the best or worst rate of a corridor leaves
+    /// and `CorridorDepth` recomputes it, and by `get_orders_sorted_by_price`
+    #[cfg(feature = "periphery")]
+    const MAX_CORRIDOR_SCAN: u32 = 100;
+    /// Most entries `export_state_chunk` returns per chunk
+    #[cfg(feature = "periphery")]
+    const MAX_EXPORT_CHUNK: u32 = 50;
+    /// Blocks an account must wait between two `map_address` updates
+    #[cfg(feature = "periphery")]
+    const DEFAULT_MAPPING_COOLDOWN: BlockNumber = 10;
+    /// Blocks `withdraw_protocol_fees` refuses to run for after its recipient refused a payout
+    const FEE_SWEEP_BACKOFF_BLOCKS: BlockNumber = 10;
+    /// Upper bound on token decimals; keeps `10^decimals` within `Balance`
+    const MAX_TOKEN_DECIMALS: u8 = 30;
+    /// Times a maker may replace an order's hashlock before its first fill
+    const MAX_REARMS: u8 = 1;
+    /// Times late fills may push out one order's timelock; see `AntiSnipe`
+    const MAX_TIMELOCK_EXTENSIONS: u8 = 3;
+    /// Upper bound on the takers one order's whitelist may name
+    const MAX_TAKER_WHITELIST: usize = 16;
+    /// Ref-time budget of a maker's completion callback
+    const COMPLETION_CALLBACK_REF_TIME: u64 = 5_000_000_000;
+    /// Proof-size budget of a maker's completion callback
+    const COMPLETION_CALLBACK_PROOF_SIZE: u64 = 256 * 1024;
+    /// The only message a completion callback may name
+    pub const ON_ORDER_COMPLETED_SELECTOR: [u8; 4] = ink::selector_bytes!("on_order_completed");
+    /// Version of the signed payload layout, carried in every `SigningDomain`
+    const SIGNING_DOMAIN_VERSION: u8 = 2;
+    /// Hashed with the contract's own address into its `deployment_salt`
+    const DEPLOYMENT_SALT_PREFIX: &[u8] = b"thalaivarswap:deployment:";
+    /// Leading bytes of every signed message, so that no signature checked here is
+    /// also a valid signature over an extrinsic or another protocol's payload
+    #[cfg_attr(not(feature = "periphery"), allow(dead_code))]
+    const SIGNING_PREFIX: &[u8] = b"thalaivarswap:signed:";
+    /// Leading bytes of every `SettlementSummary` digest
+    const SETTLEMENT_DIGEST_PREFIX: &[u8] = b"thalaivarswap:settlement:v1";
+    /// Leading bytes of every `OrderCommitment` digest
+    const ORDER_COMMITMENT_PREFIX: &[u8] = b"thalaivarswap:order-commitment:v2";
+
     /// Cross-chain address representation
     #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
     #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
@@ -29,871 +168,15749 @@ mod polkadotrelayer {
         pub swap_id: [u8; 32],
         pub source_chain: u32,
         pub dest_chain: u32,
-        pub dest_amount_per_unit: Balance, // Destination amount per source unit (scaled by 1e12)
-        pub fee: Balance,
+        pub dest_amount_per_unit: Balance, // Destination amount per source unit (scaled by 1e12); a Dutch auction's start rate
+        pub fee: Balance, // charged at creation: in the fee token, or natively by older orders
         pub allow_partial_fills: bool,
         pub max_fills: u32,
         pub current_fills: u32,
         pub sender_cross_address: Option<Vec<u8>>,
         pub receiver_cross_address: Option<Vec<u8>>,
+        pub vesting_schedule: Option<VestingSchedule>,
+        pub hash_algorithm: HashAlgorithm,
+        pub fills_paused: bool, // maker has stopped new fills; settlement is unaffected
+        pub fee_bps_applied: u16, // protocol_fee_bps in force at creation; later fee math uses this
+        // With either set, `dest_amount_per_unit` prices whole tokens instead of base units
+        pub source_decimals: Option<u8>,
+        pub dest_decimals: Option<u8>,
+        pub rearm_count: u8, // hashlock replacements through `rearm_order`, at most `MAX_REARMS`
+        pub completion_callback: Option<CompletionCallback>,
+        pub mispriced: bool, // rate outside the corridor's price band at creation; UIs should warn
+        pub intent_hash: [u8; 32], // sha256 of the SCALE-encoded `OrderIntent`, kept current
+        pub on_max_fills: MaxFillsPolicy,
+        pub order_type: OrderType,
+        pub creation_block: BlockNumber, // 0 for orders migrated from V1
     }
 
-    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    /// How an order prices its fills
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
     #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
-    pub struct FillExecution {
-        pub order_id: [u8; 32],
-        pub taker: Address,
-        pub fill_amount: Balance,
-        pub contract_id: [u8; 32],
-        pub withdrawn: bool,
-        pub refunded: bool,
-        pub preimage: Option<[u8; 32]>,
-        pub timestamp: u64,
+    pub enum OrderType {
+        /// Every fill at `dest_amount_per_unit`
+        #[default]
+        FixedPrice,
+        /// The rate moves linearly from `start_rate` at the creation block to
+        /// `end_rate` at the timelock; each fill is priced at the rate of its block.
+        /// `start_rate` must equal the order's `dest_amount_per_unit`.
+        DutchAuction { start_rate: Balance, end_rate: Balance },
     }
 
-    #[ink(storage)]
-    pub struct PolkadotPartialFills {
-        orders: Mapping<[u8; 32], PartialFillOrder>,
-        fills: Mapping<[u8; 32], FillExecution>,
-        order_fills: Mapping<[u8; 32], Vec<[u8; 32]>>, // orderId => fillIds[]
-        admin: Address,
-        address_mappings: Mapping<Address, CrossChainAddress>,
-        protocol_fee_bps: u16,
-        protocol_fees: Balance,
-        min_timelock: BlockNumber,
-        max_timelock: BlockNumber,
-        order_counter: u64,
-        fill_counter: u64,
+    /// What happens when an order reaches `max_fills` with liquidity left
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum MaxFillsPolicy {
+        /// The remainder stays escrowed until the maker cancels or the order expires
+        #[default]
+        Hold,
+        /// The fill that reaches the cap returns the remainder to the maker, leaving
+        /// the order fully filled at what was taken
+        AutoRefundRemainder,
+        /// The maker may raise `max_fills` through `increase_max_fills`
+        RaiseLimit,
     }
 
-    #[ink(event)]
-    pub struct PartialFillOrderCreated {
-        #[ink(topic)]
-        order_id: [u8; 32],
-        #[ink(topic)]
-        maker: Address,
-        total_amount: Balance,
-        min_fill_amount: Balance,
-        hashlock: [u8; 32],
-        timelock: BlockNumber,
-        swap_id: [u8; 32],
-        source_chain: u32,
-        dest_chain: u32,
-        dest_amount_per_unit: Balance,
-        allow_partial_fills: bool,
-        max_fills: u32,
+    impl PartialFillOrder {
+        /// Terms the maker committed to, as they stand now
+        pub fn intent(&self) -> OrderIntent {
+            OrderIntent {
+                maker: self.maker,
+                total_amount: self.total_amount,
+                min_fill_amount: self.min_fill_amount,
+                hashlock: self.hashlock,
+                timelock: self.timelock,
+                swap_id: self.swap_id,
+                source_chain: self.source_chain,
+                dest_chain: self.dest_chain,
+                dest_amount_per_unit: self.dest_amount_per_unit,
+                allow_partial_fills: self.allow_partial_fills,
+                max_fills: self.max_fills,
+                sender_cross_address: self.sender_cross_address.clone(),
+                receiver_cross_address: self.receiver_cross_address.clone(),
+                vesting_schedule: self.vesting_schedule.clone(),
+                hash_algorithm: self.hash_algorithm,
+                source_decimals: self.source_decimals,
+                dest_decimals: self.dest_decimals,
+                completion_callback: self.completion_callback.clone(),
+                on_max_fills: self.on_max_fills,
+                order_type: self.order_type,
+            }
+        }
     }
 
-    #[ink(event)]
-    pub struct OrderFilled {
-        #[ink(topic)]
-        order_id: [u8; 32],
-        #[ink(topic)]
-        fill_id: [u8; 32],
-        #[ink(topic)]
-        taker: Address,
-        fill_amount: Balance,
-        dest_amount: Balance,
-        contract_id: [u8; 32],
+    /// The economically relevant terms of an order: every creation argument the
+    /// order keeps, with `total_amount` as escrowed (the gross amount). Fill
+    /// progress, pausing and cancellation are state, not terms, and stay out. The
+    /// canonical encoding is SCALE in field order; see `fixtures/order_intent.json`.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct OrderIntent {
+        pub maker: Address,
+        pub total_amount: Balance,
+        pub min_fill_amount: Balance,
+        pub hashlock: [u8; 32],
+        pub timelock: BlockNumber,
+        pub swap_id: [u8; 32],
+        pub source_chain: u32,
+        pub dest_chain: u32,
+        pub dest_amount_per_unit: Balance,
+        pub allow_partial_fills: bool,
+        pub max_fills: u32,
+        pub sender_cross_address: Option<Vec<u8>>,
+        pub receiver_cross_address: Option<Vec<u8>>,
+        pub vesting_schedule: Option<VestingSchedule>,
+        pub hash_algorithm: HashAlgorithm,
+        pub source_decimals: Option<u8>,
+        pub dest_decimals: Option<u8>,
+        pub completion_callback: Option<CompletionCallback>,
+        pub on_max_fills: MaxFillsPolicy,
+        pub order_type: OrderType,
     }
 
-    #[ink(event)]
-    pub struct FillWithdrawn {
-        #[ink(topic)]
-        fill_id: [u8; 32],
-        #[ink(topic)]
-        secret: [u8; 32],
-        #[ink(topic)]
-        taker: Address,
+    impl OrderIntent {
+        /// sha256 of the canonical encoding
+        pub fn hash(&self) -> [u8; 32] {
+            use ink::env::hash::{Sha2x256, HashOutput};
+            let mut output = <Sha2x256 as HashOutput>::Type::default();
+            ink::env::hash_bytes::<Sha2x256>(&self.encode(), &mut output);
+            output
+        }
     }
 
-    #[ink(event)]
-    pub struct FillRefunded {
-        #[ink(topic)]
-        fill_id: [u8; 32],
-        #[ink(topic)]
-        maker: Address,
+    /// Hash function an order's hashlock is computed with
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum HashAlgorithm {
+        #[default]
+        Sha256,
+        Keccak256,
+        Blake2x256,
     }
 
-    #[ink(event)]
-    pub struct OrderCancelled {
-        #[ink(topic)]
-        order_id: [u8; 32],
+    /// `PartialFillOrder` as encoded before vesting, hash algorithm selection, fill
+    /// pausing and the fee snapshot were added; read only by `migrate_storage_to_v2`
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct PartialFillOrderV1 {
+        pub maker: Address,
+        pub total_amount: Balance,
+        pub filled_amount: Balance,
+        pub min_fill_amount: Balance,
+        pub hashlock: [u8; 32],
+        pub timelock: BlockNumber,
+        pub cancelled: bool,
+        pub swap_id: [u8; 32],
+        pub source_chain: u32,
+        pub dest_chain: u32,
+        pub dest_amount_per_unit: Balance,
+        pub fee: Balance,
+        pub allow_partial_fills: bool,
+        pub max_fills: u32,
+        pub current_fills: u32,
+        pub sender_cross_address: Option<Vec<u8>>,
+        pub receiver_cross_address: Option<Vec<u8>>,
     }
 
-    #[ink(event)]
-    pub struct AddressMapped {
-        #[ink(topic)]
-        account: Address,
-        cross_address: CrossChainAddress,
+    /// The current order layout
+    pub type PartialFillOrderV2 = PartialFillOrder;
+
+    impl From<PartialFillOrderV1> for PartialFillOrderV2 {
+        fn from(v1: PartialFillOrderV1) -> Self {
+            // V1 orders predate fee tokens, so `total_amount + fee` is the gross amount
+            let gross = v1.total_amount + v1.fee;
+            let fee_bps_applied = if gross == 0 { 0 } else { (v1.fee * 10_000 / gross) as u16 };
+            let mut order = Self {
+                maker: v1.maker,
+                total_amount: v1.total_amount,
+                filled_amount: v1.filled_amount,
+                min_fill_amount: v1.min_fill_amount,
+                hashlock: v1.hashlock,
+                timelock: v1.timelock,
+                cancelled: v1.cancelled,
+                swap_id: v1.swap_id,
+                source_chain: v1.source_chain,
+                dest_chain: v1.dest_chain,
+                dest_amount_per_unit: v1.dest_amount_per_unit,
+                fee: v1.fee,
+                allow_partial_fills: v1.allow_partial_fills,
+                max_fills: v1.max_fills,
+                current_fills: v1.current_fills,
+                sender_cross_address: v1.sender_cross_address,
+                receiver_cross_address: v1.receiver_cross_address,
+                vesting_schedule: None,
+                hash_algorithm: HashAlgorithm::Sha256,
+                fills_paused: false,
+                fee_bps_applied,
+                source_decimals: None,
+                dest_decimals: None,
+                rearm_count: 0,
+                completion_callback: None,
+                mispriced: false,
+                intent_hash: [0; 32],
+                on_max_fills: MaxFillsPolicy::Hold,
+                order_type: OrderType::FixedPrice,
+                creation_block: 0,
+            };
+            order.intent_hash = order.intent().hash();
+            order
+        }
     }
 
-    #[derive(Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
-    pub enum Error {
-        OrderAlreadyExists,
-        OrderNotFound,
-        FillNotFound,
-        InvalidTimelock,
-        InsufficientFunds,
-        UnauthorizedFill,
-        UnauthorizedWithdraw,
-        UnauthorizedRefund,
-        InvalidHashlock,
-        AlreadyProcessed,
-        TimelockNotExpired,
-        TimelockExpired,
-        TransferFailed,
-        InvalidChainId,
-        InvalidFillAmount,
-        OrderCancelled,
-        OrderCompleted,
-        PartialFillsNotAllowed,
-        MaxFillsReached,
-        FillAmountTooSmall,
-        InvalidFee,
-        Unauthorized,
-        ConversionError,
-        TimelockTooShort,
-        TimelockTooLong,
+    /// Layout version of the values held in contract storage
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct StorageVersion(pub u8);
+
+    impl StorageVersion {
+        pub const V1: Self = Self(1);
+        pub const V2: Self = Self(2);
+        /// Ids mix in the deployment salt. Deployments instantiated before keep
+        /// deriving unsalted ids, since fills re-derive their contract ids.
+        pub const V3: Self = Self(3);
+        /// Id preimages lead with a namespace tag. Deployments instantiated before
+        /// keep deriving untagged ids, for the same reason.
+        pub const V4: Self = Self(4);
+        pub const CURRENT: Self = Self::V4;
     }
 
-    impl PolkadotPartialFills {
-        #[ink(constructor)]
-        pub fn new() -> Self {
-            Self {
-                orders: Mapping::default(),
-                fills: Mapping::default(),
-                order_fills: Mapping::default(),
-                admin: Self::env().caller(),
-                address_mappings: Mapping::default(),
-                protocol_fee_bps: 30,
-                protocol_fees: 0,
-                min_timelock: 100,
-                max_timelock: 14400,
-                order_counter: 0,
-                fill_counter: 0,
-            }
-        }
-
-        /// Map cross-chain address for account
-        #[ink(message)]
-        pub fn map_address(&mut self, cross_address: CrossChainAddress) -> Result<(), Error> {
-            let caller = self.env().caller();
-            self.address_mappings.insert(caller, &cross_address);
-            
-            self.env().emit_event(AddressMapped {
-                account: caller,
-                cross_address,
-            });
-            
-            Ok(())
-        }
+    /// Code recorded by `upgrade_staged` that the contract has not switched to yet
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct StagedUpgrade {
+        pub code_hash: ink::H256,
+        pub staged_at: BlockNumber,
+        pub ready_at: BlockNumber, // finalizable from here on without a migration check
+        pub checked: bool, // the staged code's `check_migration` passed on this storage
+    }
 
-        /// Create new partial fill order
-        #[ink(message)]
-        #[ink(payable)]
-        pub fn create_partial_fill_order(
-            &mut self,
-            total_amount: Balance,
-            min_fill_amount: Balance,
-            hashlock: [u8; 32],
-            timelock: BlockNumber,
-            swap_id: [u8; 32],
-            source_chain: u32,
-            dest_chain: u32,
-            dest_amount_per_unit: Balance,
-            allow_partial_fills: bool,
-            max_fills: u32,
-            sender_cross_address: Option<Vec<u8>>,
-            receiver_cross_address: Option<Vec<u8>>,
-        ) -> Result<[u8; 32], Error> {
-            let maker = self.env().caller();
-            let transferred_amount = self.get_transferred_balance()?;
-            
-            self.validate_order_params(
-                total_amount,
-                min_fill_amount,
-                timelock,
-                source_chain,
-                dest_chain,
-                max_fills,
-            )?;
+    /// What `check_migration` read back from contract storage. Reading a value the
+    /// running code cannot decode traps, so a report is only ever returned when
+    /// everything it covers decoded.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct MigrationReport {
+        pub storage_version: StorageVersion,
+        pub admin: Address,
+        pub protocol_fee_bps: u16,
+        pub min_timelock: BlockNumber,
+        pub max_timelock: BlockNumber,
+        pub order_counter: u64,
+        pub fill_counter: u64,
+        pub total_locked: Balance,
+        pub sample_order: Option<[u8; 32]>, // the order read, with every one of its fills
+        pub sample_fills: u32,
+    }
 
-            if transferred_amount < total_amount {
-                return Err(Error::InsufficientFunds);
-            }
+    /// Gradual release of an order's fill capacity
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct VestingSchedule {
+        pub unlock_amount_per_block: Balance,
+        pub unlock_start_block: BlockNumber,
+    }
 
-            let (net_amount, fee) = self.calculate_fees(total_amount);
-            
-            let order_id = self.generate_order_id(
-                &maker,
-                net_amount,
-                &hashlock,
-                timelock,
-                &swap_id,
-            );
+    /// Optional order features accepted by `create_partial_fill_order_with_options`
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct OrderOptions {
+        pub vesting_schedule: Option<VestingSchedule>,
+        /// Parent strategy this order belongs to; fixed at creation
+        pub group_id: Option<[u8; 32]>,
+        /// Hashlock algorithm; the deployment's default when `None`
+        pub hash_algorithm: Option<HashAlgorithm>,
+        /// Token decimals on each side; see `PartialFillOrder::source_decimals`
+        pub source_decimals: Option<u8>,
+        pub dest_decimals: Option<u8>,
+        /// Contract notified once the order is fully filled
+        pub completion_callback: Option<CompletionCallback>,
+        /// Handling of liquidity left once `max_fills` is reached; `Hold` when `None`
+        pub on_max_fills: Option<MaxFillsPolicy>,
+        /// Announce fills in `FillsAggregated` roll-ups instead of one `OrderFilled` each
+        pub aggregate_fills: Option<FillAggregation>,
+        /// Blocks a fill landing this close to the timelock pushes it out by; see `AntiSnipe`
+        pub anti_snipe_extension: Option<BlockNumber>,
+        /// Pricing of the order's fills; `FixedPrice` when `None`
+        pub order_type: Option<OrderType>,
+    }
 
-            if self.orders.contains(&order_id) {
-                return Err(Error::OrderAlreadyExists);
-            }
+    /// Late-fill protection of an order. A fill landing `extension_blocks` or fewer
+    /// blocks before the timelock extends it, and so every pending fill's deadline,
+    /// by `extension_blocks`, at most `MAX_TIMELOCK_EXTENSIONS` times.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct AntiSnipe {
+        pub extension_blocks: BlockNumber,
+        pub extensions_used: u8,
+    }
 
-            let order = PartialFillOrder {
-                maker,
-                total_amount: net_amount,
-                filled_amount: 0,
-                min_fill_amount,
-                hashlock,
-                timelock,
-                cancelled: false,
-                swap_id,
-                source_chain,
-                dest_chain,
-                dest_amount_per_unit,
-                fee,
-                allow_partial_fills,
-                max_fills,
-                current_fills: 0,
-                sender_cross_address,
-                receiver_cross_address,
-            };
+    impl AntiSnipe {
+        /// Extensions late fills may still trigger
+        pub fn extensions_left(&self) -> u8 {
+            MAX_TIMELOCK_EXTENSIONS.saturating_sub(self.extensions_used)
+        }
+    }
 
-            self.orders.insert(&order_id, &order);
-            self.protocol_fees += fee;
+    /// When an aggregating order rolls its pending fills up into a `FillsAggregated`:
+    /// on the fill that makes `every_fills` pending, or on the first fill landing
+    /// `every_blocks` or more after the last aggregate. The fill that fully fills
+    /// or exhausts the order closes the roll-up early.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct FillAggregation {
+        pub every_fills: u32,
+        pub every_blocks: BlockNumber,
+    }
 
-            self.env().emit_event(PartialFillOrderCreated {
-                order_id,
-                maker,
-                total_amount: net_amount,
-                min_fill_amount,
-                hashlock,
-                timelock,
-                swap_id,
-                source_chain,
-                dest_chain,
-                dest_amount_per_unit,
-                allow_partial_fills,
-                max_fills,
-            });
+    /// Fills of an aggregating order not yet covered by a `FillsAggregated`. Nonces
+    /// are positions in `get_order_fills`, so every aggregated fill can be read back.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct FillAggregate {
+        pub config: FillAggregation,
+        /// Block of the last aggregate, or of creation before the first
+        pub since_block: BlockNumber,
+        /// Nonce of the first pending fill
+        pub next_nonce: u32,
+        pub fills_count: u32,
+        pub total_amount: Balance,
+        pub total_dest: Balance,
+    }
 
-            Ok(order_id)
+    /// Maker-registered contract called as `on_order_completed(order_id: [u8; 32],
+    /// total_filled: Balance, total_dest: Balance)` with no value and no return value,
+    /// each time the order becomes fully filled. `selector` must be
+    /// `ON_ORDER_COMPLETED_SELECTOR`, and `contract` may be neither this contract nor a
+    /// token it holds fees in. It runs with `COMPLETION_CALLBACK_REF_TIME` and
+    /// `COMPLETION_CALLBACK_PROOF_SIZE`; a failure is logged, never propagated.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct CompletionCallback {
+        pub contract: Address,
+        pub selector: [u8; 4],
+    }
+
+    /// Arguments of an order creation, as taken by `validate_create`
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct CreateOrderParams {
+        pub total_amount: Balance,
+        pub min_fill_amount: Balance,
+        pub hashlock: [u8; 32],
+        pub timelock: BlockNumber,
+        pub swap_id: [u8; 32],
+        pub source_chain: u32,
+        pub dest_chain: u32,
+        pub dest_amount_per_unit: Balance,
+        pub allow_partial_fills: bool,
+        pub max_fills: u32,
+        pub sender_cross_address: Option<Vec<u8>>,
+        pub receiver_cross_address: Option<Vec<u8>>,
+        pub options: OrderOptions,
+    }
+
+    /// What a creation with the given arguments would produce right now
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct CreatePreview {
+        pub order_id: [u8; 32],
+        pub fee: Balance,
+        pub net_amount: Balance, // amount escrowed as the order's `total_amount`, fees per fill
+        pub fee_bps_applied: u16,
+        pub mispriced: bool,
+    }
+
+    /// Settlement load of the deployment, read by relayers and the SDK to pick timelocks
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct Health {
+        pub total_locked: Balance,
+        /// Fills neither withdrawn nor refunded
+        pub pending_fill_count: u32,
+        /// Sum of their fill amounts
+        pub pending_fill_value: Balance,
+        pub congestion_soft_cap: Option<u32>,
+        /// Pending fills exceed the soft cap
+        pub congested: bool,
+        /// Minimum timelock a new order must use right now
+        pub effective_min_timelock: BlockNumber,
+        pub sunset: SunsetState,
+        /// Creation, fills and reservations are stopped by the admin
+        pub paused: bool,
+    }
+
+    /// Consistent view of the deployment for incident response, read by
+    /// `get_incident_snapshot` within one call and so at one block
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct IncidentSnapshot {
+        pub block: BlockNumber,
+        pub timestamp: u64,
+        pub config: ProtocolConfig,
+        pub health: Health,
+        pub accounting: ProtocolAccounting,
+        /// The requested order; `None` when none was requested or it does not exist
+        pub order: Option<OrderSnapshot>,
+    }
+
+    /// Protocol parameters as of an `IncidentSnapshot`
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct ProtocolConfig {
+        pub storage_version: StorageVersion,
+        pub admin: Address,
+        pub protocol_fee_bps: u16,
+        pub min_timelock: BlockNumber,
+        pub max_timelock: BlockNumber,
+        pub refund_cooldown_blocks: BlockNumber,
+        pub retention_blocks: BlockNumber,
+        pub value_scale: u128,
+    }
+
+    /// Counters and native-funds accounting as of an `IncidentSnapshot`
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct ProtocolAccounting {
+        pub order_counter: u64,
+        pub fill_counter: u64,
+        pub total_locked: Balance,
+        pub total_credits: Balance,
+        pub protocol_fees: Balance,
+        pub total_liabilities: Balance,
+    }
+
+    /// One order with every one of its fills, as of an `IncidentSnapshot`
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct OrderSnapshot {
+        pub order: PartialFillOrder,
+        pub status: OrderStatus,
+        pub fills: Vec<FillExecution>,
+        pub accounting: OrderAccounting,
+    }
+
+    /// Where an order's escrow stands, summed over its fills
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct OrderAccounting {
+        pub remaining: Balance,
+        pub reserved: Balance,
+        /// Fill amounts neither withdrawn nor refunded
+        pub pending: Balance,
+        pub withdrawn: Balance,
+        pub refunded: Balance,
+        /// Taker safety deposits held on pending fills
+        pub deposits_held: Balance,
+    }
+
+    /// What an arbitrary 32-byte id names in this deployment, as reported by `lookup_id`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub enum IdKind {
+        Order,
+        Fill,
+        /// The contract id of a fill, with that fill's id
+        Contract { fill_id: [u8; 32] },
+        Unknown,
+    }
+
+    /// End of life of a deployment, one way only. In `Sunset` no new orders or
+    /// reservations are taken while existing orders fill, settle and cancel as
+    /// before; `Retired` refuses every message but views.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum SunsetState {
+        #[default]
+        Live,
+        Sunset,
+        Retired,
+    }
+
+    /// Limit on what executors of one order's public actions collect over the order's
+    /// lifetime, so that an order cannot pay out more in rewards than it brought in
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum RewardCap {
+        /// Basis points of the protocol fee the order paid at creation
+        FeeShare(u16),
+        /// The same amount for every order
+        Flat(Balance),
+    }
+
+    /// An order's standing against the reward cap
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct RewardBudget {
+        /// Rewards the order may pay out in total; `None` while no cap is set
+        pub allowance: Option<Balance>,
+        pub paid: Balance,
+        /// `None` while no cap is set
+        pub remaining: Option<Balance>,
+    }
+
+    /// Clock an account's graduation from the beta cap runs on
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum GraduationClock {
+        /// Blocks since the contract was instantiated
+        #[default]
+        Instantiation,
+        /// Blocks since the account created its first order
+        FirstUse,
+    }
+
+    /// An account's standing against the beta cap
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct AccountCapStatus {
+        pub cap: Balance, // zero when the cap is disabled
+        /// Unfilled amount of the account's live orders
+        pub current: Balance,
+        /// Block from which the account is uncapped; `None` under `FirstUse` until
+        /// its first order
+        pub graduation_block: Option<BlockNumber>,
+        /// New escrow is held to `cap`
+        pub capped: bool,
+    }
+
+    /// An order's amounts with the decimals to format them by, so generic UIs need
+    /// not know the asset. Amounts stay in base units.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct OrderDisplay {
+        pub total_amount: Balance,
+        pub filled_amount: Balance,
+        pub remaining_amount: Balance,
+        pub min_fill_amount: Balance,
+        /// The order's `source_decimals`, else those of the native token
+        pub decimals: u8,
+        /// Destination amount owed for `remaining_amount`
+        pub dest_remaining_amount: Balance,
+        /// `None` when the order carries no destination decimals
+        pub dest_decimals: Option<u8>,
+    }
+
+    /// Final state of a withdrawn fill, which the relayer signs off-chain as proof
+    /// of settlement for the counterparty chain. The signed digest is sha256 over
+    /// `SETTLEMENT_DIGEST_PREFIX` and the fields packed in the order below, integers
+    /// big-endian and `receiver` as its sha256 (32 zero bytes if `None`), so a
+    /// Solidity verifier can rebuild it with `abi.encodePacked`. See
+    /// `fixtures/settlement.json`.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct SettlementSummary {
+        pub chain_id: u32, // `home_chain_id` of the deployment
+        pub contract: Address,
+        pub order_id: [u8; 32],
+        pub fill_id: [u8; 32],
+        pub taker: Address,
+        pub hashlock: [u8; 32],
+        pub secret: [u8; 32],
+        pub amount: Balance, // source amount filled; the taker is paid it less the fill's fee
+        pub receiver: Option<Vec<u8>>, // the order's `receiver_cross_address`
+        pub block: BlockNumber, // block of the withdrawal
+    }
+
+    impl SettlementSummary {
+        /// The bytes hashed by `digest`, prefix included
+        pub fn encode_packed(&self) -> Vec<u8> {
+            let mut data = Vec::from(SETTLEMENT_DIGEST_PREFIX);
+            data.extend_from_slice(&self.chain_id.to_be_bytes());
+            data.extend_from_slice(self.contract.as_bytes());
+            data.extend_from_slice(&self.order_id);
+            data.extend_from_slice(&self.fill_id);
+            data.extend_from_slice(self.taker.as_bytes());
+            data.extend_from_slice(&self.hashlock);
+            data.extend_from_slice(&self.secret);
+            data.extend_from_slice(&self.amount.to_be_bytes());
+            data.extend_from_slice(&packed_bytes_hash(&self.receiver));
+            data.extend_from_slice(&self.block.to_be_bytes());
+            data
+        }
+
+        /// sha256 of `encode_packed`
+        pub fn digest(&self) -> [u8; 32] {
+            packed_sha256(&self.encode_packed())
+        }
+    }
+
+    /// The economically binding terms of an order, which the relayer posts to
+    /// Ethereum as a 32-byte digest so that resolvers can check the parameters
+    /// claimed for an order against it. The digest is sha256 over
+    /// `ORDER_COMMITMENT_PREFIX` and the fields packed in the order below, integers
+    /// big-endian, `hash_algorithm` as one byte, `order_type` as one byte followed by
+    /// its end rate (`dest_amount_per_unit` for a fixed price) and each cross-chain
+    /// address as its sha256 (32 zero bytes if `None`). New fields are only ever
+    /// committed under a new prefix. Fill progress, pausing and other state are left
+    /// out, so the digest moves only when `OrderIntentChanged` announces new terms.
+    /// See `fixtures/commitment.json`.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct OrderCommitment {
+        pub chain_id: u32, // `home_chain_id` of the deployment
+        pub contract: Address,
+        pub order_id: [u8; 32],
+        pub maker: Address,
+        pub total_amount: Balance,
+        pub min_fill_amount: Balance,
+        pub hashlock: [u8; 32],
+        pub hash_algorithm: HashAlgorithm,
+        pub timelock: BlockNumber,
+        pub source_chain: u32,
+        pub dest_chain: u32,
+        pub dest_amount_per_unit: Balance,
+        pub order_type: OrderType,
+        pub sender: Option<Vec<u8>>, // the order's `sender_cross_address`
+        pub receiver: Option<Vec<u8>>, // the order's `receiver_cross_address`
+    }
+
+    impl OrderCommitment {
+        /// The bytes hashed by `digest`, prefix included
+        pub fn encode_packed(&self) -> Vec<u8> {
+            let mut data = Vec::from(ORDER_COMMITMENT_PREFIX);
+            data.extend_from_slice(&self.chain_id.to_be_bytes());
+            data.extend_from_slice(self.contract.as_bytes());
+            data.extend_from_slice(&self.order_id);
+            data.extend_from_slice(self.maker.as_bytes());
+            data.extend_from_slice(&self.total_amount.to_be_bytes());
+            data.extend_from_slice(&self.min_fill_amount.to_be_bytes());
+            data.extend_from_slice(&self.hashlock);
+            data.push(self.hash_algorithm as u8);
+            data.extend_from_slice(&self.timelock.to_be_bytes());
+            data.extend_from_slice(&self.source_chain.to_be_bytes());
+            data.extend_from_slice(&self.dest_chain.to_be_bytes());
+            data.extend_from_slice(&self.dest_amount_per_unit.to_be_bytes());
+            let (order_type, end_rate) = match self.order_type {
+                OrderType::FixedPrice => (0u8, self.dest_amount_per_unit),
+                OrderType::DutchAuction { end_rate, .. } => (1u8, end_rate),
+            };
+            data.push(order_type);
+            data.extend_from_slice(&end_rate.to_be_bytes());
+            data.extend_from_slice(&packed_bytes_hash(&self.sender));
+            data.extend_from_slice(&packed_bytes_hash(&self.receiver));
+            data
+        }
+
+        /// sha256 of `encode_packed`
+        pub fn digest(&self) -> [u8; 32] {
+            packed_sha256(&self.encode_packed())
+        }
+    }
+
+    fn packed_sha256(data: &[u8]) -> [u8; 32] {
+        use ink::env::hash::{Sha2x256, HashOutput};
+        let mut output = <Sha2x256 as HashOutput>::Type::default();
+        ink::env::hash_bytes::<Sha2x256>(data, &mut output);
+        output
+    }
+
+    /// A variable-length field as packed into a digest: its sha256, or 32 zero bytes
+    fn packed_bytes_hash(bytes: &Option<Vec<u8>>) -> [u8; 32] {
+        bytes.as_deref().map(packed_sha256).unwrap_or([0; 32])
+    }
+
+    /// Binds a signature to one deployment and payload layout version, in the
+    /// spirit of the EIP-712 domain separator
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct SigningDomain {
+        pub contract_address: Address,
+        pub chain_id: u32, // `home_chain_id` of the deployment
+        pub version: u8,
+        pub salt: [u8; 32], // `get_deployment_salt`, telling apart deployments at one address
+    }
+
+    /// Key a signature must come from
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub enum Signer {
+        Sr25519([u8; 32]), // public key
+        Ecdsa([u8; 20]), // Ethereum address of the secp256k1 key
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub enum MultiSignature {
+        Sr25519([u8; 64]),
+        Ecdsa([u8; 65]), // r, s and the recovery id
+    }
+
+    /// A payload that can be signed. The signed message is the blake2-256 digest of
+    /// `SIGNING_PREFIX`, the SCALE-encoded `SigningDomain`, `TYPE_NAME` and the
+    /// SCALE-encoded payload, so a signature over one payload type never verifies
+    /// as another, nor on another deployment. See `fixtures/signing.json`.
+    pub trait SignedPayload: Encode {
+        const TYPE_NAME: &'static [u8];
+    }
+
+    /// Consent of the holder of `cross_address` to `account` mapping to it
+    #[cfg(feature = "periphery")]
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct MapAddressPayload {
+        pub account: Address,
+        pub cross_address: CrossChainAddress,
+        pub valid_until_block: BlockNumber,
+    }
+
+    #[cfg(feature = "periphery")]
+    impl SignedPayload for MapAddressPayload {
+        const TYPE_NAME: &'static [u8] = b"MapAddress";
+    }
+
+    /// Renewed consent of the holder of `cross_address` to `account`'s existing
+    /// mapping; the fields of `MapAddressPayload` under a different type name, so
+    /// neither signature passes for the other
+    #[cfg(feature = "periphery")]
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct RenewMappingPayload {
+        pub account: Address,
+        pub cross_address: CrossChainAddress,
+        pub valid_until_block: BlockNumber,
+    }
+
+    #[cfg(feature = "periphery")]
+    impl SignedPayload for RenewMappingPayload {
+        const TYPE_NAME: &'static [u8] = b"RenewMapping";
+    }
+
+    /// Freshness of a mapping proven by signature: when control of the cross-chain
+    /// address was last shown, and the block after which the proof lapses (`None`
+    /// when the deployment sets no `mapping_attestation_ttl`)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct MappingAttestation {
+        pub attested_at_block: BlockNumber,
+        pub valid_until_block: Option<BlockNumber>,
+    }
+
+    impl MappingAttestation {
+        /// Whether the proof has lapsed by `block`; it still holds at `valid_until_block`
+        #[cfg_attr(not(feature = "periphery"), allow(dead_code))]
+        pub fn is_expired_at(&self, block: BlockNumber) -> bool {
+            self.valid_until_block.is_some_and(|until| block > until)
+        }
+    }
+
+    /// Outcome of `preflight_create`: the preview plus what the periphery hooks link
+    struct CreatePlan {
+        preview: CreatePreview,
+        #[cfg(feature = "periphery")]
+        fingerprint: [u8; 32],
+        #[cfg(feature = "periphery")]
+        group: Option<([u8; 32], Vec<[u8; 32]>)>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct FillExecution {
+        pub order_id: [u8; 32],
+        pub taker: Address,
+        pub fill_amount: Balance,
+        pub contract_id: [u8; 32],
+        pub withdrawn: bool,
+        pub refunded: bool,
+        pub preimage: Option<[u8; 32]>,
+        pub timestamp: u64, // collator-set and only loosely bounded; never compared, see `get_fill_created_block`
+        pub deposit: Balance, // taker safety deposit: back on withdrawal, to the maker on refund
+        pub requested_amount: Balance, // `fill_amount` as passed to `fill_order`, before clamping
+        pub fee: Balance, // protocol fee, kept out of the taker's payout on withdrawal
+        pub receiver: Address, // where the withdrawal payout goes; the taker unless named in `fill_order`
+    }
+
+    /// `FillExecution` as stored in full before `StoredFill`; read only by
+    /// `migrate_fills`
+    #[cfg(feature = "periphery")]
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct FillExecutionV1 {
+        pub order_id: [u8; 32],
+        pub taker: Address,
+        pub fill_amount: Balance,
+        pub contract_id: [u8; 32],
+        pub withdrawn: bool,
+        pub refunded: bool,
+        pub preimage: Option<[u8; 32]>,
+        pub timestamp: u64,
+        pub deposit: Balance,
+        pub requested_amount: Balance,
+    }
+
+    /// `FillExecution` as held in contract storage. `contract_id` is re-derived from
+    /// `sequence`, a revealed preimage lives in `fill_preimages`, the two status
+    /// booleans are bits of `flags` and integers are compact-encoded.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct StoredFill {
+        pub order_id: [u8; 32],
+        pub taker: Address,
+        #[codec(compact)]
+        pub fill_amount: Balance,
+        #[codec(compact)]
+        pub timestamp: u64,
+        #[codec(compact)]
+        pub deposit: Balance,
+        #[codec(compact)]
+        pub requested_amount: Balance,
+        #[codec(compact)]
+        pub sequence: u64, // fill_counter value `contract_id` was derived from
+        pub flags: u8,
+    }
+
+    impl StoredFill {
+        pub const WITHDRAWN: u8 = 1 << 0;
+        pub const REFUNDED: u8 = 1 << 1;
+        /// Migrated from `FillExecutionV1`: `contract_id` is kept in
+        /// `legacy_contract_ids`, as the counter value it came from is unknown
+        pub const LEGACY_ID: u8 = 1 << 2;
+
+        fn pack(fill: &FillExecution, sequence: u64) -> Self {
+            let mut flags = 0;
+            if fill.withdrawn {
+                flags |= Self::WITHDRAWN;
+            }
+            if fill.refunded {
+                flags |= Self::REFUNDED;
+            }
+            Self {
+                order_id: fill.order_id,
+                taker: fill.taker,
+                fill_amount: fill.fill_amount,
+                timestamp: fill.timestamp,
+                deposit: fill.deposit,
+                requested_amount: fill.requested_amount,
+                sequence,
+                flags,
+            }
+        }
+
+        fn has(&self, flag: u8) -> bool {
+            self.flags & flag != 0
+        }
+
+        /// Neither withdrawn nor refunded
+        fn is_pending(&self) -> bool {
+            !self.has(Self::WITHDRAWN | Self::REFUNDED)
+        }
+    }
+
+    /// Capacity held back on an order for one reserver. It stops counting against
+    /// other fillers at `expires_at`; the record and its bond stay until the reserver
+    /// fills the order or calls `release_reservation`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct Reservation {
+        pub amount: Balance,
+        pub expires_at: BlockNumber,
+        pub bond: Balance,
+    }
+
+    /// Per-chain parameters of the counterpart HTLC deployment
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct ChainConfig {
+        pub max_counterpart_timelock: u64, // Longest lock the counterpart HTLC accepts, in seconds
+    }
+
+    /// Where a payout goes. `Xcm` needs a deployment built with the `xcm` feature;
+    /// `is_payout_route_available` tells which routes a deployment takes.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub enum PayoutRoute {
+        /// Native transfer to the recipient
+        #[default]
+        Direct,
+        /// Booked to the recipient's internal credit, pulled with `withdraw_credit`
+        Credit,
+        /// Reserve transfer of the native asset to `beneficiary` on parachain `para_id`
+        Xcm { para_id: u32, beneficiary: [u8; 32] },
+    }
+
+    /// The settlement a quarantined payout belongs to
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub enum TransferContext {
+        FillWithdrawal([u8; 32]), // fill id
+        OrderFillsWithdrawal([u8; 32]), // order id
+        FillRefund([u8; 32]), // fill id
+        OrderCancel([u8; 32]), // order id
+        OrderCleanup([u8; 32]), // order id
+        RemainderReturn([u8; 32]), // order id
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum OrderStatus {
+        Active,
+        PartiallyFilled,
+        FullyFilled,
+        Cancelled,
+        Expired,
+        FillsPaused,
+    }
+
+    /// Price statistics over the non-refunded fills of an order
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct OrderFillPriceStats {
+        pub fill_count: u32,
+        pub total_source_amount: Balance,
+        pub total_dest_amount: Balance,
+        pub min_rate: Balance,
+        pub max_rate: Balance,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct FillTimingInfo {
+        pub fill_id: [u8; 32],
+        pub timestamp: u64,
+        pub created_block: Option<BlockNumber>, // `None` for fills created before it was recorded
+        pub expires_at: BlockNumber,
+        pub blocks_remaining: BlockNumber,
+    }
+
+    /// Everything known about an order, returned by a single call
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct OrderFullState {
+        pub order: PartialFillOrder,
+        pub fills: Vec<FillExecution>,
+        pub stats: OrderFillPriceStats,
+        pub timing_info: Vec<FillTimingInfo>,
+        pub tags: Option<Vec<u8>>,
+        pub remaining: Balance,
+        pub status: OrderStatus,
+        pub last_activity_block: Option<BlockNumber>,
+        pub anti_snipe: Option<AntiSnipe>,
+    }
+
+    /// One admin parameter change. Values are SCALE-encoded in the type of the
+    /// changed parameter; see `admin_fn` for what each `function_id` records.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct AdminAuditLog {
+        pub timestamp: u64,
+        pub block: BlockNumber,
+        pub function_id: u8,
+        pub old_value: Vec<u8>,
+        pub new_value: Vec<u8>,
+    }
+
+    /// `function_id` values of `AdminAuditLog` and the encoded value type of each
+    pub mod admin_fn {
+        /// `Address`
+        pub const UPDATE_ADMIN: u8 = 0;
+        /// `u16` basis points
+        pub const UPDATE_PROTOCOL_FEE: u8 = 1;
+        /// `(u32, Option<ChainConfig>)`
+        pub const REGISTER_CHAIN: u8 = 2;
+        /// `(u32, bool)` home chain id and enforcement flag
+        pub const SET_HOME_CHAIN: u8 = 3;
+        /// `HashAlgorithm`
+        pub const SET_DEFAULT_HASH_ALGORITHM: u8 = 4;
+        /// `u64` milliseconds
+        pub const SET_BLOCK_TIME_MS: u8 = 5;
+        /// `Option<(BlockNumber, BlockNumber)>` pending proposal
+        pub const PROPOSE_MIN_TIMELOCK: u8 = 6;
+        /// `BlockNumber` min_timelock
+        pub const APPLY_MIN_TIMELOCK: u8 = 7;
+        /// `Option<Address>`
+        #[cfg_attr(not(feature = "psp22"), allow(dead_code))]
+        pub const SET_FEE_TOKEN: u8 = 8;
+        /// `Option<(BlockNumber, BlockNumber)>` inclusive start and end
+        pub const SET_FEE_FREE_WINDOW: u8 = 9;
+        /// `Vec<(Balance, u16)>` threshold and deposit bps per tier
+        pub const SET_DEPOSIT_SCHEDULE: u8 = 10;
+        /// `Vec<Address>`
+        #[cfg(feature = "periphery")]
+        pub const SET_STATS_CONSUMERS: u8 = 11;
+        /// `bool`
+        #[cfg(feature = "periphery")]
+        pub const SET_REJECT_DUPLICATE_ORDERS: u8 = 12;
+        /// `BlockNumber`
+        pub const SET_REFUND_COOLDOWN: u8 = 13;
+        /// `BlockNumber`
+        #[cfg(feature = "periphery")]
+        pub const SET_MAPPING_COOLDOWN: u8 = 14;
+        /// `(Address, Option<CrossChainAddress>)` account and its mapping
+        #[cfg(feature = "periphery")]
+        pub const PURGE_MAPPING: u8 = 15;
+        /// `BlockNumber`
+        pub const SET_RETENTION_BLOCKS: u8 = 16;
+        /// `(BlockNumber, Balance)` reservation lifetime and bond
+        pub const SET_RESERVATION_PARAMS: u8 = 17;
+        /// `((u32, u32), Option<(Balance, Balance)>)` corridor and its band
+        pub const SET_PRICE_BAND: u8 = 18;
+        /// `bool`
+        pub const SET_REJECT_MISPRICED: u8 = 19;
+        /// `Option<BlockNumber>`
+        #[cfg(feature = "periphery")]
+        pub const SET_MAPPING_ATTESTATION_TTL: u8 = 20;
+        /// `Option<u32>` pending fill count
+        pub const SET_CONGESTION_SOFT_CAP: u8 = 21;
+        /// `(Balance, BlockNumber, GraduationClock)` cap, graduation blocks and clock
+        pub const SET_BETA_CAP: u8 = 22;
+        /// `Option<StagedUpgrade>`, on staging and on abort
+        pub const STAGE_UPGRADE: u8 = 23;
+        /// `Option<StagedUpgrade>` switched to, then `None`
+        pub const FINALIZE_UPGRADE: u8 = 24;
+        /// `SunsetState`, on entering sunset and on retiring
+        pub const SUNSET: u8 = 25;
+        /// `Option<RewardCap>`
+        pub const SET_REWARD_CAP: u8 = 26;
+        /// `bool`
+        pub const SET_PAUSED: u8 = 27;
+    }
+
+    /// Display helpers for the raw cross-chain address bytes carried by orders
+    #[cfg(feature = "periphery")]
+    pub mod address_format {
+        use ink::prelude::vec::Vec;
+        use scale::{Decode, Encode};
+        use scale_info::TypeInfo;
+
+        const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+        pub enum AddressFormat {
+            /// 20-byte account, shown as EIP-55 checksummed hex
+            Ethereum,
+            /// 32-byte public key, shown as `0x` hex
+            Substrate,
+            /// Already SS58-encoded text; 47 to 49 base58 characters for a 32-byte key
+            Ss58,
+            /// Anything else, shown as `0x` hex
+            Unknown,
+        }
+
+        /// Classify `bytes` by shape. `chain_id` is accepted for when the chain
+        /// registry records address formats; today no registered field does, so a
+        /// 20-byte value is Ethereum and a 32-byte value a Substrate key on any chain.
+        pub fn classify_address(_chain_id: u32, bytes: &[u8]) -> AddressFormat {
+            match bytes.len() {
+                20 => AddressFormat::Ethereum,
+                32 => AddressFormat::Substrate,
+                47..=49 if bytes.iter().all(|b| BASE58_ALPHABET.contains(b)) => AddressFormat::Ss58,
+                _ => AddressFormat::Unknown,
+            }
+        }
+
+        /// EIP-55 mixed-case `0x` hex of an Ethereum address, as ASCII
+        pub fn checksum_eth(address: [u8; 20]) -> [u8; 42] {
+            use ink::env::hash::{HashOutput, Keccak256};
+
+            let mut lower = [0u8; 40];
+            for (i, byte) in address.iter().enumerate() {
+                lower[2 * i] = hex_digit(byte >> 4);
+                lower[2 * i + 1] = hex_digit(byte & 0x0f);
+            }
+            let mut hash = <Keccak256 as HashOutput>::Type::default();
+            ink::env::hash_bytes::<Keccak256>(&lower, &mut hash);
+
+            let mut out = [0u8; 42];
+            out[..2].copy_from_slice(b"0x");
+            for (i, c) in lower.iter().enumerate() {
+                let nibble = if i % 2 == 0 { hash[i / 2] >> 4 } else { hash[i / 2] & 0x0f };
+                out[i + 2] = if nibble >= 8 { c.to_ascii_uppercase() } else { *c };
+            }
+            out
+        }
+
+        /// Display-ready ASCII for `bytes` according to `classify_address`
+        pub fn format_address(chain_id: u32, bytes: &[u8]) -> Vec<u8> {
+            match classify_address(chain_id, bytes) {
+                AddressFormat::Ethereum => {
+                    let mut address = [0u8; 20];
+                    address.copy_from_slice(bytes);
+                    checksum_eth(address).to_vec()
+                }
+                AddressFormat::Ss58 => bytes.to_vec(),
+                AddressFormat::Substrate | AddressFormat::Unknown => {
+                    let mut out = b"0x".to_vec();
+                    for byte in bytes {
+                        out.push(hex_digit(byte >> 4));
+                        out.push(hex_digit(byte & 0x0f));
+                    }
+                    out
+                }
+            }
+        }
+
+        fn hex_digit(nibble: u8) -> u8 {
+            b"0123456789abcdef"[nibble as usize]
+        }
+    }
+
+    /// Server-side filter for `get_open_orders`; unset fields match everything
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct OrderFilter {
+        pub source_chain: Option<u32>,
+        pub dest_chain: Option<u32>,
+        pub min_remaining: Option<Balance>,
+        pub maker: Option<Address>,
+        pub allow_partial_only: bool,
+        pub exclude_idle_blocks: Option<BlockNumber>, // drop orders `is_stale` at this threshold
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub enum OrderOrFill {
+        Order,
+        Fill,
+    }
+
+    /// What an `ExpiringItem` needs from whoever watches it
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub enum ExpiryAction {
+        /// The taker's withdrawal window closes within the horizon
+        WithdrawClosing,
+        /// The timelock has passed; the maker can reclaim funds
+        RefundAvailable,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct ExpiringItem {
+        pub id: [u8; 32],
+        pub kind: OrderOrFill,
+        pub blocks_remaining: BlockNumber,
+        pub action: ExpiryAction,
+    }
+
+    /// Aggregate view over the orders of one group
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct GroupSummary {
+        pub order_count: u32,
+        pub total_escrowed: Balance,
+        pub total_filled: Balance,
+        pub total_remaining: Balance,
+        pub orders: Vec<([u8; 32], OrderStatus)>,
+    }
+
+    /// One page of a list view. `next_offset` is the offset to pass for the next page,
+    /// `None` once the list is exhausted; `total` counts the whole list. Views that
+    /// filter return fewer items than the page covers, so a short page is not the end.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct Page<T> {
+        pub items: Vec<T>,
+        pub next_offset: Option<u64>,
+        pub total: u64,
+    }
+
+    /// Table `export_state_chunk` reads, with the type its chunks SCALE-encode
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub enum ExportKind {
+        /// `Vec<([u8; 32], PartialFillOrder, Option<BlockNumber>)>`: active orders,
+        /// then archived ones, each with its `get_last_activity_block`
+        Orders,
+        /// `Vec<([u8; 32], FillExecution)>`: pending fills
+        Fills,
+        /// `Vec<(Address, Balance)>`: nonzero credit balances
+        Credits,
+        /// `Vec<(Address, CrossChainAddress)>`: cross-chain address mappings
+        Mappings,
+    }
+
+    /// Position in an append-only log: the index of the next record to read. A
+    /// log page's `next_offset` is the cursor to resume from.
+    pub type Cursor = u64;
+
+    /// A record of the update log read by `get_updates_since`
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum Update {
+        OrderCreated { order_id: [u8; 32] },
+        /// A fill paid out to its taker (`withdrawn`) or back to the maker
+        FillSettled { order_id: [u8; 32], fill_id: [u8; 32], withdrawn: bool },
+        /// First reveal of a hashlock; `reveal_index` is its `get_reveals_since` position
+        Reveal { reveal_index: Cursor, hashlock: [u8; 32], secret: [u8; 32] },
+    }
+
+    impl<T> Page<T> {
+        /// Keeps the page position while filtering or converting its items
+        pub fn filter_map<U>(self, f: impl FnMut(T) -> Option<U>) -> Page<U> {
+            Page {
+                items: self.items.into_iter().filter_map(f).collect(),
+                next_offset: self.next_offset,
+                total: self.total,
+            }
+        }
+    }
+
+    /// What an account has at stake in the contract
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub struct AccountPosition {
+        /// Unfilled amounts of the account's uncancelled orders
+        pub orders_escrowed: Balance,
+        /// Amounts of fills the account took that are neither withdrawn nor refunded
+        pub fills_claimable: Balance,
+        /// Safety deposits posted on those fills
+        pub deposits_held: Balance,
+        pub credits: Balance,
+        /// Set when more than `MAX_POSITION_SCAN` orders or fills exist; page through
+        /// `get_account_position_ids` for the rest
+        pub truncated: bool,
+    }
+
+    /// Liquidity of one (source chain, dest chain) corridor over its active orders,
+    /// kept up to date as orders are created, filled, refunded and cancelled. Rates
+    /// cover fixed-price orders only: a Dutch auction's rate moves every block, so it
+    /// counts towards the liquidity but not `best_rate` or `worst_rate`.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct CorridorDepth {
+        /// Unfilled amounts of the corridor's active orders
+        pub total_remaining: Balance,
+        pub order_count: u32,
+        /// Highest rate among the active orders
+        pub best_rate: Option<Balance>,
+        /// Lowest rate among the active orders
+        pub worst_rate: Option<Balance>,
+        /// Set when an order at the best or worst rate left a corridor of more than
+        /// `MAX_CORRIDOR_SCAN` orders, so the rates were recomputed over that many
+        /// only; cleared by the next full recompute
+        pub rates_partial: bool,
+    }
+
+    /// Lifecycle latency of one (source chain, dest chain) corridor, as running sums
+    /// in blocks: creation to fill for every fill, fill to secret reveal for every
+    /// withdrawn fill. Orders and fills from before the blocks were recorded are not
+    /// counted; neither is a sample that would overflow a sum.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct CorridorLatency {
+        pub fill_count: u64,
+        pub time_to_fill_blocks: u64,
+        pub settle_count: u64,
+        pub time_to_settle_blocks: u64,
+    }
+
+    #[cfg_attr(not(feature = "periphery"), allow(dead_code))]
+    impl CorridorLatency {
+        /// Mean blocks from order creation to fill, rounded down
+        pub fn average_time_to_fill(&self) -> Option<u64> {
+            self.time_to_fill_blocks.checked_div(self.fill_count)
+        }
+
+        /// Mean blocks from fill to secret reveal, rounded down
+        pub fn average_time_to_settle(&self) -> Option<u64> {
+            self.time_to_settle_blocks.checked_div(self.settle_count)
+        }
+
+        fn with_fill(self, blocks: BlockNumber) -> Option<Self> {
+            Some(Self {
+                fill_count: self.fill_count.checked_add(1)?,
+                time_to_fill_blocks: self.time_to_fill_blocks.checked_add(blocks as u64)?,
+                ..self
+            })
+        }
+
+        fn with_settle(self, blocks: BlockNumber) -> Option<Self> {
+            Some(Self {
+                settle_count: self.settle_count.checked_add(1)?,
+                time_to_settle_blocks: self.time_to_settle_blocks.checked_add(blocks as u64)?,
+                ..self
+            })
+        }
+    }
+
+    /// Payload pushed to stats consumers:
+    /// (source_chain, dest_chain, fill_amount, dest_amount)
+    #[cfg(feature = "periphery")]
+    pub type StatsEventData = (u32, u32, Balance, Balance);
+
+    /// Error type returned by `IEventConsumer` contracts such as `PolkadotSwapStats`
+    #[cfg(feature = "periphery")]
+    #[cfg_attr(test, allow(dead_code))]
+    #[derive(Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub enum EventConsumerError {
+        UnauthorizedSource,
+        MalformedEvent,
+        UnknownEventType,
+    }
+
+    /// Error type returned by PSP22 token contracts
+    #[cfg(feature = "psp22")]
+    #[cfg_attr(test, allow(dead_code))]
+    #[derive(Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub enum Psp22Error {
+        Custom(ink::prelude::string::String),
+        InsufficientBalance,
+        InsufficientAllowance,
+        ZeroRecipientAddress,
+        ZeroSenderAddress,
+        SafeTransferCheckFailed(ink::prelude::string::String),
+    }
+
+    #[ink(storage)]
+    pub struct PolkadotPartialFills {
+        orders: Mapping<[u8; 32], PartialFillOrder>,
+        fills: Mapping<[u8; 32], StoredFill>,
+        fill_preimages: Mapping<[u8; 32], [u8; 32]>, // fillId => preimage, once withdrawn
+        fill_memos: Mapping<[u8; 32], Vec<u8>>, // fillId => memo given on withdrawal
+        fill_fees: Mapping<[u8; 32], Balance>, // fillId => protocol fee, if any
+        fill_receivers: Mapping<[u8; 32], Address>, // fillId => payout receiver, when not the taker
+        fill_rates: Mapping<[u8; 32], Balance>, // fillId => rate a Dutch auction fill was priced at
+        fill_withdrawn_at: Mapping<[u8; 32], BlockNumber>, // fillId => block of withdrawal
+        fill_created_at: Mapping<[u8; 32], BlockNumber>, // fillId => block of creation
+        legacy_contract_ids: Mapping<[u8; 32], [u8; 32]>, // fillId => contract id, for fills migrated from V1
+        order_created_at: Mapping<[u8; 32], BlockNumber>, // orderId => block of creation
+        contract_id_fills: Mapping<[u8; 32], [u8; 32]>, // contractId => fillId
+        order_fills: Mapping<[u8; 32], Vec<[u8; 32]>>, // orderId => fillIds[]
+        admin: Address,
+        protocol_fee_bps: u16,
+        protocol_fees: Balance,
+        min_timelock: BlockNumber,
+        max_timelock: BlockNumber,
+        order_counter: u64,
+        fill_counter: u64,
+        order_counter_high: u64, // all-time maximum of order_counter
+        fill_counter_high: u64, // all-time maximum of fill_counter
+        secret_registry: Mapping<[u8; 32], [u8; 32]>, // hashlock => secret
+        #[cfg(feature = "psp22")]
+        fee_token: Option<Address>, // PSP22 token collecting protocol fees instead of native DOT
+        #[cfg(feature = "psp22")]
+        psp22_protocol_fees: Mapping<Address, Balance>,
+        #[cfg(feature = "psp22")]
+        psp22_fee_tokens_outstanding: u32, // tokens with a nonzero `psp22_protocol_fees` balance
+        chain_registry: Mapping<u32, ChainConfig>,
+        block_time_ms: u64,
+        home_chain_id: u32, // chain id of this deployment as used in order source_chain
+        enforce_home_chain: bool, // strict mode: source_chain must equal home_chain_id
+        default_hash_algorithm: HashAlgorithm, // used by orders that do not pick one
+        admin_audit: Mapping<u64, AdminAuditLog>, // index => admin parameter change
+        storage_version: StorageVersion,
+        deployment_salt: [u8; 32], // sha256 of `DEPLOYMENT_SALT_PREFIX` and the contract address
+        fee_free_window: Option<(BlockNumber, BlockNumber)>, // (start, end), inclusive; no fees inside
+        fee_free_window_activated: bool, // FeeFreeWindowActivated emitted for the current window
+        deposit_schedule: Vec<(Balance, u16)>, // (fill threshold, deposit bps), thresholds ascending
+        refund_cooldown_blocks: BlockNumber,
+        refund_cooldowns: Mapping<([u8; 32], Address), BlockNumber>, // (order, taker) => cooldown end
+        audit_log_counter: u64,
+        pending_min_timelock: Option<(BlockNumber, BlockNumber)>, // (new_min, effective_at)
+        credits: Mapping<Address, Balance>, // payouts held internally for netting
+        total_credits: Balance,
+        total_locked: Balance, // native escrow of orders, unsettled fills and reservation bonds
+        retention_blocks: BlockNumber, // kept after an order turns terminal, before cleanup
+        order_settled_at: Mapping<[u8; 32], BlockNumber>, // orderId => last cancel/withdraw/refund block
+        order_last_activity: Mapping<[u8; 32], BlockNumber>, // orderId => last block a message acted on it
+        fill_aggregates: Mapping<[u8; 32], FillAggregate>, // orderId => pending roll-up, aggregating orders only
+        anti_snipe: Mapping<[u8; 32], AntiSnipe>, // orderId => late-fill protection, opted-in orders only
+        reservation_blocks: BlockNumber,
+        reservation_bond: Balance,
+        reservations: Mapping<([u8; 32], Address), Reservation>, // (order, reserver) => until released
+        order_reservers: Mapping<[u8; 32], Vec<Address>>, // orderId => reservers, pruned lazily
+        reservation_cooldowns: Mapping<([u8; 32], Address), BlockNumber>, // (order, reserver) => release cooldown end
+        price_bands: Mapping<(u32, u32), (Balance, Balance)>, // (source, dest chain) => (min, max)
+        reject_mispriced: bool, // refuse orders outside their band instead of flagging them
+        value_scale: u128, // native value units per `Balance` unit; fixed at deployment
+        fee_sweep_failed_at: Option<BlockNumber>, // last sweep whose recipient refused the fees
+        pending_fill_count: u32, // fills neither withdrawn nor refunded
+        pending_fill_value: Balance, // sum of their fill amounts
+        congestion_soft_cap: Option<u32>, // pending fills above which creation needs a longer timelock
+        congested: bool, // pending_fill_count above the soft cap, as last announced
+        beta_cap: Balance, // unfilled escrow an account may hold until it graduates; 0 disables
+        beta_graduation_blocks: BlockNumber, // blocks on `beta_graduation_clock` until graduation
+        beta_graduation_clock: GraduationClock,
+        instantiated_at: BlockNumber,
+        maker_escrow: Mapping<Address, Balance>, // maker => unfilled amount of its live orders
+        account_first_use: Mapping<Address, BlockNumber>, // maker => block of its first order
+        staged_upgrade: Option<StagedUpgrade>,
+        sunset: SunsetState, // only ever moves forward
+        reward_cap: Option<RewardCap>, // executor rewards one order may pay; `None` uncapped
+        rewards_paid: Mapping<[u8; 32], Balance>, // orderId => executor rewards paid so far
+        paused: bool, // emergency stop of creation, fills and reservations
+        taker_whitelist: Mapping<[u8; 32], Vec<Address>>, // orderId => only takers allowed to fill
+        periphery: Periphery,
+    }
+
+    /// Storage of the periphery: secondary indices, the reveal log, tags, address
+    /// mappings, stats consumers, the duplicate guard and fill announcements. None
+    /// of it is read when settling an order, so the escrow core builds without it.
+    #[cfg(feature = "periphery")]
+    #[ink::storage_item]
+    #[derive(Default)]
+    #[cfg_attr(feature = "std", derive(Debug))]
+    pub struct Periphery {
+        address_mappings: Mapping<Address, CrossChainAddress>,
+        reveal_log: Mapping<u64, ([u8; 32], [u8; 32])>, // index => (hashlock, secret)
+        reveal_counter: u64,
+        update_log: Mapping<u64, Update>, // event nonce => record, across all record kinds
+        event_nonce: u64, // next nonce of `update_log`
+        state_version: u64, // bumped by every write to the state `export_state_chunk` reads
+        order_tags: Mapping<[u8; 32], Vec<u8>>,
+        stats_consumers: Vec<Address>, // contracts implementing IEventConsumer
+        reject_duplicate_orders: bool, // refuse an order whose params fingerprint is already active
+        active_fingerprints: Mapping<[u8; 32], [u8; 32]>, // params fingerprint => active order id
+        order_fingerprints: Mapping<[u8; 32], [u8; 32]>, // order id => params fingerprint
+        taker_fills: Mapping<(Address, u32), [u8; 32]>, // (taker, index) => fillId; takers fill often
+        taker_fill_count: Mapping<Address, u32>,
+        group_orders: Mapping<[u8; 32], Vec<[u8; 32]>>, // groupId => orderIds[]
+        id_lists: Mapping<(IdList, u32), [u8; 32]>, // (list, position) => id
+        id_positions: Mapping<(IdList, [u8; 32]), u32>, // (list, id) => position
+        id_list_lens: Mapping<IdList, u32>,
+        last_announcement_block: BlockNumber,
+        mapping_cooldown_blocks: BlockNumber,
+        last_mapping_update: Mapping<Address, BlockNumber>, // account => block of its last mapping
+        mapping_attestations: Mapping<Address, MappingAttestation>, // account => freshness of its proven mapping
+        mapping_attestation_ttl: Option<BlockNumber>, // blocks a proof stays fresh; `None` never lapses
+        corridor_depths: Mapping<(u32, u32), CorridorDepth>, // (source chain, dest chain) => aggregates
+        corridor_contributions: Mapping<[u8; 32], Balance>, // order id => remaining counted in its corridor
+        corridor_latencies: Mapping<(u32, u32), CorridorLatency>, // (source chain, dest chain) => block sums
+    }
+
+    /// Enumerable id lists of the periphery. Each is stored as `(list, position) => id`
+    /// with the reverse position and a length, so adding or removing an id touches a
+    /// fixed number of cells however long the list grows. Removal moves the last id
+    /// into the freed position, so list order is not creation order.
+    #[cfg(feature = "periphery")]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum IdList {
+        /// Orders neither cancelled nor fully filled
+        ActiveOrders,
+        /// Cancelled or fully filled orders not yet cleaned up
+        ArchivedOrders,
+        /// Fills neither withdrawn nor refunded
+        PendingFills,
+        /// Orders of one maker
+        MakerOrders(Address),
+        /// Orders sharing one swap id
+        SwapOrders([u8; 32]),
+        /// Active orders of one (source chain, dest chain) corridor
+        CorridorOrders(u32, u32),
+        /// Accounts holding credit, as `account_key`s
+        Creditors,
+        /// Accounts with a cross-chain address mapping, as `account_key`s
+        MappedAccounts,
+    }
+
+    /// Placeholder for a core-only build, so the storage struct keeps one shape
+    #[cfg(not(feature = "periphery"))]
+    #[ink::storage_item]
+    #[derive(Default)]
+    #[cfg_attr(feature = "std", derive(Debug))]
+    pub struct Periphery {}
+
+    /// Version of the event topic layout below. Bumped whenever a field is
+    /// promoted to or demoted from a topic, since that breaks subscriptions.
+    const EVENT_LAYOUT_VERSION: u8 = 3;
+
+    // Event topic layout (v3). Topics are limited to ids and accounts that
+    // indexers filter on; secrets and amounts are carried as data only.
+    //
+    // | event                       | topics                   |
+    // |-----------------------------|--------------------------|
+    // | PartialFillOrderCreated     | order_id, maker, intent  |
+    // | OrderFilled                 | order_id, fill_id, taker |
+    // | FillWithdrawn               | order_id, fill_id, taker |
+    // | OrderFillsWithdrawn         | order_id, taker          |
+    // | FillRefunded                | order_id, fill_id, maker |
+    // | OrderCancelled              | order_id                 |
+    // | OrderFillsPausedChanged     | order_id                 |
+    // | TakerWhitelistChanged       | order_id                 |
+    // | AddressMapped               | account                  |
+    // | MinTimelockProposed         | -                        |
+    // | MinTimelockApplied          | -                        |
+    // | IndicesBackfilled           | -                        |
+    // | CreditWithdrawn             | account                  |
+    // | DefaultHashAlgorithmChanged | -                        |
+    // | StorageMigrated             | -                        |
+    // | FillsMigrated               | -                        |
+    // | FeeFreeWindowSet            | -                        |
+    // | FeeFreeWindowActivated      | -                        |
+    // | PendingFillsAnnounced       | -                        |
+    // | FillClamped                 | order_id, fill_id        |
+    // | OrderRearmed                | order_id                 |
+    // | CompletionCallbackFailed    | order_id                 |
+    // | MappingPurged               | account                  |
+    // | OrderCleanedUp              | order_id                 |
+    // | OrderReserved               | order_id, reserver       |
+    // | ReservationReleased         | order_id, reserver       |
+    // | ProtocolFeesWithdrawn       | admin                    |
+    // | FeeSweepFailed              | recipient                |
+    // | StuckFeesRedirected         | recipient                |
+    // | OrderIntentChanged          | order_id, intent_hash    |
+    // | OrderRemainderReturned      | order_id                 |
+    // | MaxFillsIncreased           | order_id                 |
+    // | CongestionModeChanged       | -                        |
+    // | TransferQuarantined         | to                       |
+    // | UpgradeStaged               | -                        |
+    // | UpgradeChecked              | -                        |
+    // | UpgradeAborted              | -                        |
+    // | UpgradeFinalized            | -                        |
+    // | OrderCommitted              | order_id, commitment     |
+    // | SunsetEntered               | -                        |
+    // | SunsetFinalized             | -                        |
+    // | FillsAggregated             | order_id                 |
+    // | TimelockAutoExtended        | order_id, fill_id        |
+    // | ProtocolPaused              | by                       |
+    // | ProtocolUnpaused            | by                       |
+    //
+    // Event order within one message. A message emits the event naming its action
+    // first and the events of what that action caused after it, so an indexer can
+    // apply events in transaction order. Batches emit per item, in input order,
+    // before any summary. Each sequence is emitted by one helper listed below and
+    // no event of a message is emitted before all of its state is written.
+    //
+    // | message                | order ([..] when applicable)                   | emitted by                 |
+    // |------------------------|------------------------------------------------|----------------------------|
+    // | create_*_order         | PartialFillOrderCreated, OrderCommitted,       | emit_creation_events       |
+    // |                        | [FeeFreeWindowActivated]                       |                            |
+    // | fill_order             | OrderFilled or [FillsAggregated] when the      | emit_fill_events           |
+    // |                        | order aggregates, [FillClamped],               |                            |
+    // |                        | [TimelockAutoExtended],                        |                            |
+    // |                        | [OrderIntentChanged], [ReservationReleased],   |                            |
+    // |                        | [OrderRemainderReturned],                      |                            |
+    // |                        | [TransferQuarantined],                         |                            |
+    // |                        | [CompletionCallbackFailed],                    |                            |
+    // |                        | [CongestionModeChanged]                        |                            |
+    // | withdraw_fill          | FillWithdrawn, [TransferQuarantined],          | emit_settlement_effects    |
+    // |                        | [CongestionModeChanged]                        |                            |
+    // | withdraw_order_fills   | FillWithdrawn per fill, OrderFillsWithdrawn,   | emit_order_fills_withdrawn |
+    // |                        | [TransferQuarantined],                         |                            |
+    // |                        | [CongestionModeChanged]                        |                            |
+    // | *refund_fill           | FillRefunded, [TransferQuarantined],           | emit_settlement_effects    |
+    // |                        | [CongestionModeChanged]                        |                            |
+    // | cancel_order           | OrderCancelled, [FillsAggregated],             | emit_closing_events        |
+    // |                        | [TransferQuarantined]                          |                            |
+    // | cleanup_order          | OrderCleanedUp, [FillsAggregated],             | emit_closing_events        |
+    // |                        | [TransferQuarantined]                          |                            |
+    // | rearm_order            | OrderRearmed, [OrderIntentChanged]             | emit_intent_change         |
+    // | increase_max_fills     | MaxFillsIncreased, [OrderIntentChanged]        | emit_intent_change         |
+    // | map_address_with_proof | AddressMapped, MappingAttested                 | attest_mapping             |
+    // | batch_cancel_orders    | OrderCancelled per order, in input order       | cancel_order               |
+
+    /// The events of one fill, emitted by `emit_fill_events` in field order
+    struct FillEvents {
+        filled: Option<OrderFilled>,
+        aggregated: Option<FillsAggregated>,
+        clamped: Option<FillClamped>,
+        extended: Option<TimelockAutoExtended>,
+        intent_changed: Option<OrderIntentChanged>,
+        reservation_released: Option<ReservationReleased>,
+        remainder_returned: Option<OrderRemainderReturned>,
+        remainder_quarantined: Option<TransferQuarantined>,
+        callback_failed: Option<CompletionCallbackFailed>,
+        congestion_changed: Option<CongestionModeChanged>,
+    }
+
+    #[ink(event)]
+    pub struct PartialFillOrderCreated {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        #[ink(topic)]
+        maker: Address,
+        #[ink(topic)]
+        intent_hash: [u8; 32],
+        total_amount: Balance,
+        min_fill_amount: Balance,
+        hashlock: [u8; 32],
+        hash_algorithm: HashAlgorithm,
+        timelock: BlockNumber,
+        swap_id: [u8; 32],
+        source_chain: u32,
+        dest_chain: u32,
+        dest_amount_per_unit: Balance,
+        allow_partial_fills: bool,
+        max_fills: u32,
+        source_decimals: Option<u8>,
+        dest_decimals: Option<u8>,
+        mispriced: bool,
+        on_max_fills: MaxFillsPolicy,
+    }
+
+    #[ink(event)]
+    pub struct OrderFilled {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        #[ink(topic)]
+        fill_id: [u8; 32],
+        #[ink(topic)]
+        taker: Address,
+        receiver: Address,
+        fill_amount: Balance,
+        dest_amount: Balance,
+        contract_id: [u8; 32],
+    }
+
+    /// Fills `from_nonce..=to_nonce` of an aggregating order, by position in
+    /// `get_order_fills`, announced together in place of their `OrderFilled`s.
+    /// Each aggregate starts at the nonce after the previous one's `to_nonce`.
+    #[ink(event)]
+    pub struct FillsAggregated {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        fills_count: u32,
+        total_amount: Balance,
+        total_dest: Balance,
+        from_nonce: u32,
+        to_nonce: u32,
+    }
+
+    /// A fill landing close to the timelock pushed it out; see `AntiSnipe`
+    #[ink(event)]
+    pub struct TimelockAutoExtended {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        #[ink(topic)]
+        fill_id: [u8; 32],
+        old_timelock: BlockNumber,
+        new_timelock: BlockNumber,
+        extensions_used: u8,
+    }
+
+    /// `fill_order` granted less than requested because the order was nearly exhausted
+    #[ink(event)]
+    pub struct FillClamped {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        #[ink(topic)]
+        fill_id: [u8; 32],
+        requested: Balance,
+        granted: Balance,
+    }
+
+    #[ink(event)]
+    pub struct FillWithdrawn {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        #[ink(topic)]
+        fill_id: [u8; 32],
+        #[ink(topic)]
+        taker: Address,
+        secret: [u8; 32],
+        route: PayoutRoute,
+        memo: Option<Vec<u8>>, // e.g. an exchange deposit tag; not used by the payout
+    }
+
+    /// Summary of a `withdraw_order_fills`, following one `FillWithdrawn` per fill
+    #[ink(event)]
+    pub struct OrderFillsWithdrawn {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        #[ink(topic)]
+        taker: Address,
+        fill_count: u32,
+        payout: Balance,
+        route: PayoutRoute,
+    }
+
+    /// One refund settlement: `maker_amount` (principal of a cancelled order, plus
+    /// the deposit when the maker refunds) and the forfeited deposit paid to the
+    /// `executor` of a public refund. `maker_credited` when the maker refused the
+    /// transfer and was credited instead.
+    #[ink(event)]
+    pub struct FillRefunded {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        #[ink(topic)]
+        fill_id: [u8; 32],
+        #[ink(topic)]
+        maker: Address,
+        route: PayoutRoute,
+        maker_amount: Balance,
+        maker_credited: bool,
+        executor: Address,
+        executor_amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct OrderCancelled {
+        #[ink(topic)]
+        order_id: [u8; 32],
+    }
+
+    #[ink(event)]
+    pub struct OrderFillsPausedChanged {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        paused: bool,
+    }
+
+    /// The takers allowed to fill an order; empty when anyone may again
+    #[ink(event)]
+    pub struct TakerWhitelistChanged {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        takers: Vec<Address>,
+    }
+
+    /// The maker's completion callback reverted, trapped or ran out of gas
+    #[ink(event)]
+    pub struct CompletionCallbackFailed {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        callee: Address,
+    }
+
+    #[ink(event)]
+    pub struct OrderRearmed {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        old_hashlock: [u8; 32],
+        new_hashlock: [u8; 32],
+    }
+
+    /// An `AutoRefundRemainder` order reached `max_fills`; `amount` went back to the maker
+    #[ink(event)]
+    pub struct OrderRemainderReturned {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct MaxFillsIncreased {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        old_max_fills: u32,
+        new_max_fills: u32,
+    }
+
+    /// Pending fills crossed the congestion soft cap, in either direction. While
+    /// `congested`, new orders must lock for `CONGESTED_MIN_TIMELOCK_PCT` of `min_timelock`.
+    #[ink(event)]
+    pub struct CongestionModeChanged {
+        congested: bool,
+        pending_fill_count: u32,
+        soft_cap: Option<u32>,
+    }
+
+    /// A `Direct` payout the recipient refused, as a contract reverting on receive
+    /// does, was booked to its credit instead so that the settlement still
+    /// completed. The recipient pulls it with `withdraw_credit`.
+    #[ink(event)]
+    pub struct TransferQuarantined {
+        #[ink(topic)]
+        to: Address,
+        amount: Balance,
+        context: TransferContext,
+    }
+
+    /// The terms of a live order changed; quotes made against the old hash are stale
+    #[ink(event)]
+    pub struct OrderIntentChanged {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        #[ink(topic)]
+        intent_hash: [u8; 32],
+        commitment: [u8; 32], // `get_order_commitment` under the new terms
+    }
+
+    /// The `OrderCommitment` digest of a new order, for the relayer to post to
+    /// Ethereum. `PartialFillOrderCreated` has no topic left to carry it.
+    #[ink(event)]
+    pub struct OrderCommitted {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        #[ink(topic)]
+        commitment: [u8; 32],
+    }
+
+    #[ink(event)]
+    pub struct AddressMapped {
+        #[ink(topic)]
+        account: Address,
+        cross_address: CrossChainAddress,
+    }
+
+    #[ink(event)]
+    pub struct MappingPurged {
+        #[ink(topic)]
+        account: Address,
+    }
+
+    #[ink(event)]
+    pub struct MappingAttested {
+        #[ink(topic)]
+        account: Address,
+        attested_at_block: BlockNumber,
+        valid_until_block: Option<BlockNumber>,
+    }
+
+    #[ink(event)]
+    pub struct OrderCleanedUp {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        fills_removed: u32,
+    }
+
+    #[ink(event)]
+    pub struct OrderReserved {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        #[ink(topic)]
+        reserver: Address,
+        amount: Balance,
+        expires_at: BlockNumber,
+        bond: Balance,
+    }
+
+    /// A reservation's bond was paid back; `consumed` when the reserver filled the order
+    #[ink(event)]
+    pub struct ReservationReleased {
+        #[ink(topic)]
+        order_id: [u8; 32],
+        #[ink(topic)]
+        reserver: Address,
+        amount: Balance,
+        bond: Balance,
+        consumed: bool,
+    }
+
+    #[ink(event)]
+    pub struct MinTimelockProposed {
+        new_min: BlockNumber,
+        effective_at: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct MinTimelockApplied {
+        new_min: BlockNumber,
+    }
+
+    /// Catch-up broadcast of unsettled fills; `order_ids[i]` owns `fill_ids[i]`
+    #[ink(event)]
+    pub struct PendingFillsAnnounced {
+        fill_ids: Vec<[u8; 32]>,
+        order_ids: Vec<[u8; 32]>,
+        block: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct IndicesBackfilled {
+        count: u32,
+    }
+
+    #[ink(event)]
+    pub struct CreditWithdrawn {
+        #[ink(topic)]
+        account: Address,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ProtocolFeesWithdrawn {
+        #[ink(topic)]
+        admin: Address,
+        amount: Balance,
+        route: PayoutRoute,
+    }
+
+    #[ink(event)]
+    pub struct FeeSweepFailed {
+        #[ink(topic)]
+        recipient: Address,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct StuckFeesRedirected {
+        #[ink(topic)]
+        recipient: Address,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct FeeFreeWindowSet {
+        start: BlockNumber,
+        end: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct FeeFreeWindowActivated {
+        block: BlockNumber,
+    }
+
+    /// No new orders or reservations from here on
+    #[ink(event)]
+    pub struct SunsetEntered {
+        block: BlockNumber,
+    }
+
+    /// Terminal: every message but views is refused from here on
+    #[ink(event)]
+    pub struct SunsetFinalized {
+        block: BlockNumber,
+    }
+
+    /// No new orders, fills or reservations until `ProtocolUnpaused`; settlement,
+    /// refunds and cancels carry on
+    #[ink(event)]
+    pub struct ProtocolPaused {
+        #[ink(topic)]
+        by: Address,
+        at_block: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct ProtocolUnpaused {
+        #[ink(topic)]
+        by: Address,
+        at_block: BlockNumber,
+    }
+
+    /// Finalizable from `ready_at`, or as soon as `check_staged_upgrade` passes
+    #[ink(event)]
+    pub struct UpgradeStaged {
+        code_hash: ink::H256,
+        ready_at: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct UpgradeChecked {
+        code_hash: ink::H256,
+        report: MigrationReport,
+    }
+
+    #[ink(event)]
+    pub struct UpgradeAborted {
+        code_hash: ink::H256,
+    }
+
+    #[ink(event)]
+    pub struct UpgradeFinalized {
+        code_hash: ink::H256,
+        checked: bool, // false when finalized on the delay alone
+    }
+
+    #[ink(event)]
+    pub struct StorageMigrated {
+        from_version: u8,
+        to_version: u8,
+        migrated_count: u32,
+    }
+
+    #[ink(event)]
+    pub struct FillsMigrated {
+        migrated_count: u32,
+    }
+
+    #[ink(event)]
+    pub struct DefaultHashAlgorithmChanged {
+        old: HashAlgorithm,
+        new: HashAlgorithm,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    pub enum Error {
+        OrderAlreadyExists,
+        OrderNotFound,
+        FillNotFound,
+        InvalidTimelock,
+        InsufficientFunds { required: Balance, received: Balance },
+        UnauthorizedFill,
+        UnauthorizedWithdraw,
+        UnauthorizedRefund,
+        InvalidHashlock,
+        AlreadyProcessed,
+        TimelockNotExpired,
+        TimelockExpired,
+        TransferFailed,
+        InvalidChainId,
+        InvalidFillAmount,
+        OrderCancelled,
+        OrderCompleted,
+        PartialFillsNotAllowed,
+        MaxFillsReached,
+        FillAmountTooSmall,
+        InvalidFee,
+        Unauthorized,
+        ConversionError,
+        TimelockTooShort,
+        TimelockTooLong,
+        TagsTooLong,
+        TimelockIncompatibleWithDestination,
+        NoPendingProposal,
+        ProposalNotReady,
+        BatchTooLarge,
+        FillExceedsUnlocked,
+        InsufficientCredit,
+        GroupFull,
+        CounterOverflow,
+        WrongSourceChain,
+        OrderFillsPaused,
+        AdminOperationExpired,
+        NoValueTransferred,
+        InvalidDepositSchedule,
+        TooManyConsumers,
+        InvalidDecimals,
+        AnnouncementTooSoon,
+        DuplicateOrder,
+        RefundCooldownActive,
+        HashlockInUse,
+        RearmLimitReached,
+        MappingCooldownActive,
+        OrderNotTerminal,
+        RetainedRecordsMissing,
+        RetentionPeriodActive,
+        InvalidSignature,
+        SignatureExpired,
+        CapacityReserved,
+        ReservationExists,
+        ReservationNotFound,
+        ReservationLimitReached,
+        PageTooLarge,
+        InvalidPriceBand,
+        PriceOutsideBand,
+        ValuePrecisionLoss,
+        InvalidValueScale,
+        PayoutRouteUnavailable,
+        FeesNotStuck,
+        FeeSweepBackoff,
+        MaxFillsNotRaisable,
+        MappingNotFound,
+        MemoTooLong,
+        BetaCapExceeded { cap: Balance, current: Balance },
+        UpgradeCodeMismatch,
+        MigrationCheckFailed,
+        UpgradeFailed,
+        SunsetActive,
+        ContractRetired,
+        NotInSunset,
+        LiabilitiesOutstanding { liabilities: Balance },
+        FillAlreadyExists,
+        ContractPaused,
+        InvalidAuctionRates,
+        TakerWhitelistLocked,
+        ReservationCooldownActive,
+        ReservationTooLarge,
+        InvalidCompletionCallback,
+        FeeTokensOutstanding { tokens: u32 },
+        NoFeesToWithdraw,
+        InvalidAggregation,
+    }
+
+    impl From<quote::QuoteError> for Error {
+        fn from(error: quote::QuoteError) -> Self {
+            match error {
+                quote::QuoteError::InvalidTimelock => Error::InvalidTimelock,
+                quote::QuoteError::TimelockTooShort => Error::TimelockTooShort,
+                quote::QuoteError::TimelockTooLong => Error::TimelockTooLong,
+                quote::QuoteError::InvalidChainId => Error::InvalidChainId,
+                quote::QuoteError::InvalidFillAmount => Error::InvalidFillAmount,
+                quote::QuoteError::FillAmountTooSmall => Error::FillAmountTooSmall,
+                quote::QuoteError::PartialFillsNotAllowed => Error::PartialFillsNotAllowed,
+            }
+        }
+    }
+
+    impl PolkadotPartialFills {
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self {
+                orders: Mapping::default(),
+                fills: Mapping::default(),
+                fill_preimages: Mapping::default(),
+                fill_memos: Mapping::default(),
+                fill_fees: Mapping::default(),
+                fill_receivers: Mapping::default(),
+                fill_rates: Mapping::default(),
+                fill_withdrawn_at: Mapping::default(),
+                fill_created_at: Mapping::default(),
+                legacy_contract_ids: Mapping::default(),
+                order_created_at: Mapping::default(),
+                contract_id_fills: Mapping::default(),
+                order_fills: Mapping::default(),
+                admin: Self::env().caller(),
+                protocol_fee_bps: 30,
+                protocol_fees: 0,
+                min_timelock: 100,
+                max_timelock: 14400,
+                order_counter: 0,
+                fill_counter: 0,
+                order_counter_high: 0,
+                fill_counter_high: 0,
+                secret_registry: Mapping::default(),
+                #[cfg(feature = "psp22")]
+                fee_token: None,
+                #[cfg(feature = "psp22")]
+                psp22_protocol_fees: Mapping::default(),
+                #[cfg(feature = "psp22")]
+                psp22_fee_tokens_outstanding: 0,
+                chain_registry: Mapping::default(),
+                block_time_ms: 6000,
+                home_chain_id: 0,
+                enforce_home_chain: false,
+                default_hash_algorithm: HashAlgorithm::Sha256,
+                admin_audit: Mapping::default(),
+                storage_version: StorageVersion::CURRENT,
+                fee_free_window: None,
+                fee_free_window_activated: false,
+                deposit_schedule: Vec::new(),
+                refund_cooldown_blocks: DEFAULT_REFUND_COOLDOWN,
+                refund_cooldowns: Mapping::default(),
+                audit_log_counter: 0,
+                pending_min_timelock: None,
+                credits: Mapping::default(),
+                total_credits: 0,
+                total_locked: 0,
+                retention_blocks: DEFAULT_RETENTION_BLOCKS,
+                order_settled_at: Mapping::default(),
+                order_last_activity: Mapping::default(),
+                fill_aggregates: Mapping::default(),
+                anti_snipe: Mapping::default(),
+                reservation_blocks: DEFAULT_RESERVATION_BLOCKS,
+                reservation_bond: DEFAULT_RESERVATION_BOND,
+                reservations: Mapping::default(),
+                order_reservers: Mapping::default(),
+                reservation_cooldowns: Mapping::default(),
+                price_bands: Mapping::default(),
+                reject_mispriced: false,
+                value_scale: 1,
+                fee_sweep_failed_at: None,
+                pending_fill_count: 0,
+                pending_fill_value: 0,
+                congestion_soft_cap: None,
+                congested: false,
+                beta_cap: 0,
+                beta_graduation_blocks: 0,
+                beta_graduation_clock: GraduationClock::Instantiation,
+                instantiated_at: Self::env().block_number(),
+                maker_escrow: Mapping::default(),
+                account_first_use: Mapping::default(),
+                staged_upgrade: None,
+                sunset: SunsetState::Live,
+                reward_cap: None,
+                rewards_paid: Mapping::default(),
+                paused: false,
+                taker_whitelist: Mapping::default(),
+                deployment_salt: Self::derive_deployment_salt(&Self::env().address()),
+                #[cfg(feature = "periphery")]
+                periphery: Periphery {
+                    mapping_cooldown_blocks: DEFAULT_MAPPING_COOLDOWN,
+                    ..Default::default()
+                },
+                #[cfg(not(feature = "periphery"))]
+                periphery: Periphery::default(),
+            }
+        }
+
+        /// Deploy with a known home chain id and strict source-chain checking enabled
+        #[ink(constructor)]
+        pub fn new_with_home_chain(home_chain_id: u32) -> Self {
+            let mut contract = Self::new();
+            contract.home_chain_id = home_chain_id;
+            contract.enforce_home_chain = true;
+            contract
+        }
+
+        /// Deploy with a different default hashlock algorithm
+        #[ink(constructor)]
+        pub fn new_with_hash_algorithm(default_hash_algorithm: HashAlgorithm) -> Self {
+            let mut contract = Self::new();
+            contract.default_hash_algorithm = default_hash_algorithm;
+            contract
+        }
+
+        /// Deploy on a runtime whose native value has more decimals than the `Balance`
+        /// amounts orders are kept in, e.g. pallet-revive mapping 10 decimal DOT to an
+        /// 18 decimal value uses a scale of `10^8`. Every value sent is divided by the
+        /// scale and every payout multiplied by it.
+        #[ink(constructor)]
+        pub fn new_with_value_scale(value_scale: u128) -> Result<Self, Error> {
+            if value_scale == 0 {
+                return Err(Error::InvalidValueScale);
+            }
+            let mut contract = Self::new();
+            contract.value_scale = value_scale;
+            Ok(contract)
+        }
+
+        /// Create new partial fill order. The value sent must cover `total_amount`;
+        /// anything beyond it goes back to the maker.
+        #[ink(message)]
+        #[ink(payable)]
+        pub fn create_partial_fill_order(
+            &mut self,
+            total_amount: Balance,
+            min_fill_amount: Balance,
+            hashlock: [u8; 32],
+            timelock: BlockNumber,
+            swap_id: [u8; 32],
+            source_chain: u32,
+            dest_chain: u32,
+            dest_amount_per_unit: Balance,
+            allow_partial_fills: bool,
+            max_fills: u32,
+            sender_cross_address: Option<Vec<u8>>,
+            receiver_cross_address: Option<Vec<u8>>,
+        ) -> Result<[u8; 32], Error> {
+            self.create_order(CreateOrderParams {
+                total_amount,
+                min_fill_amount,
+                hashlock,
+                timelock,
+                swap_id,
+                source_chain,
+                dest_chain,
+                dest_amount_per_unit,
+                allow_partial_fills,
+                max_fills,
+                sender_cross_address,
+                receiver_cross_address,
+                options: OrderOptions::default(),
+            })
+        }
+
+        /// Create new partial fill order with optional features (vesting, ...)
+        #[ink(message)]
+        #[ink(payable)]
+        pub fn create_partial_fill_order_with_options(
+            &mut self,
+            total_amount: Balance,
+            min_fill_amount: Balance,
+            hashlock: [u8; 32],
+            timelock: BlockNumber,
+            swap_id: [u8; 32],
+            source_chain: u32,
+            dest_chain: u32,
+            dest_amount_per_unit: Balance,
+            allow_partial_fills: bool,
+            max_fills: u32,
+            sender_cross_address: Option<Vec<u8>>,
+            receiver_cross_address: Option<Vec<u8>>,
+            options: OrderOptions,
+        ) -> Result<[u8; 32], Error> {
+            self.create_order(CreateOrderParams {
+                total_amount,
+                min_fill_amount,
+                hashlock,
+                timelock,
+                swap_id,
+                source_chain,
+                dest_chain,
+                dest_amount_per_unit,
+                allow_partial_fills,
+                max_fills,
+                sender_cross_address,
+                receiver_cross_address,
+                options,
+            })
+        }
+
+        /// Fill order (partial or full). The value sent is held as the taker's safety
+        /// deposit and must cover `get_required_deposit` for the fill, less whatever
+        /// of it the taker's credit covers. `receiver` is paid on withdrawal; the zero
+        /// address stands for the taker.
+        #[ink(message, payable)]
+        pub fn fill_order(
+            &mut self,
+            order_id: [u8; 32],
+            mut fill_amount: Balance,
+            receiver: Address,
+        ) -> Result<[u8; 32], Error> {
+            self.ensure_not_retired()?;
+            self.ensure_not_paused()?;
+            let taker = self.env().caller();
+            let mut order = self.get_order_or_error(&order_id)?;
+            if order.fills_paused {
+                return Err(Error::OrderFillsPaused);
+            }
+
+            self.validate_fill_request(&order, fill_amount)?;
+            self.validate_whitelisted(&order_id, &taker)?;
+            self.validate_refund_cooldown(&order_id, &taker)?;
+
+            if order.vesting_schedule.is_some()
+                && fill_amount > self.unlocked_amount(&order).saturating_sub(order.filled_amount)
+            {
+                return Err(Error::FillExceedsUnlocked);
+            }
+
+            let requested_amount = fill_amount;
+            let remaining_amount = order.total_amount - order.filled_amount;
+            let available = self.available_to(&order_id, remaining_amount, &taker)?;
+            fill_amount = quote::clamp_fill(
+                requested_amount,
+                remaining_amount,
+                available,
+                order.min_fill_amount,
+                order.allow_partial_fills,
+            )?;
+            // The floor on orders without a minimum, and the last sliver of any order
+            let rate = self.current_rate(&order);
+            let dest_amount = quote::nonzero_dest_amount(
+                fill_amount,
+                rate,
+                order.source_decimals,
+                order.dest_decimals,
+            )?;
+
+            let received = self.get_transferred_value()?;
+            let required_deposit = self.required_deposit(fill_amount);
+            // A shortfall in the value sent is drawn from the taker's credit
+            let from_credit = required_deposit.saturating_sub(received);
+            if from_credit > self.credits.get(&taker).unwrap_or(0) {
+                return Err(Error::InsufficientFunds { required: required_deposit, received });
+            }
+            let deposit = received + from_credit;
+
+            // Create fill execution
+            let fill_id = self.generate_fill_id(&order_id, &taker, fill_amount);
+            
+            if self.fills.contains(&fill_id) {
+                return Err(Error::FillAlreadyExists);
+            }
+
+            let contract_id = self.generate_contract_id(&order_id, &fill_id)?;
+
+            let fill = FillExecution {
+                order_id,
+                taker,
+                fill_amount,
+                contract_id,
+                withdrawn: false,
+                refunded: false,
+                preimage: None,
+                timestamp: self.env().block_timestamp(),
+                deposit,
+                requested_amount,
+                fee: Self::fill_fee(&order, fill_amount),
+                receiver: if receiver == Address::zero() { taker } else { receiver },
+            };
+
+            self.store_fill(&fill_id, &fill, self.fill_counter);
+            if fill.fee > 0 {
+                self.fill_fees.insert(&fill_id, &fill.fee);
+            }
+            if fill.receiver != taker {
+                self.fill_receivers.insert(&fill_id, &fill.receiver);
+            }
+            if order.order_type != OrderType::FixedPrice {
+                self.fill_rates.insert(&fill_id, &rate);
+            }
+            self.fill_created_at.insert(&fill_id, &self.env().block_number());
+            self.contract_id_fills.insert(&contract_id, &fill_id);
+            if from_credit > 0 {
+                self.use_credit_for_fill(taker, from_credit)?;
+            }
+            self.total_locked += received;
+            let congestion_changed = self.open_pending_fill(fill_amount);
+
+            // Update order state
+            order.filled_amount += fill_amount;
+            order.current_fills += 1;
+            let extended = self.extend_for_late_fill(order_id, fill_id, &mut order);
+            let (remainder, intent_changed) = self.close_at_max_fills(order_id, &mut order);
+            // The timelock is bound by the intent too, whether or not the cap was hit
+            let intent_changed = intent_changed.or_else(|| self.refresh_intent_hash(order_id, &mut order));
+            self.orders.insert(&order_id, &order);
+            self.touch_order(order_id);
+            self.release_maker_escrow(order.maker, fill_amount + remainder);
+
+            // Add to order fills tracking
+            let mut order_fill_list = self.order_fills.get(&order_id).unwrap_or_default();
+            order_fill_list.push(fill_id);
+            self.order_fills.insert(&order_id, &order_fill_list);
+
+            let aggregating = self.fill_aggregates.contains(&order_id);
+            let aggregated = self.aggregate_fill(order_id, &order, fill_amount, dest_amount);
+            #[cfg(feature = "periphery")]
+            self.on_fill_created(fill_id, &fill, &order, dest_amount);
+
+            let reservation_released = if self.reservations.contains((order_id, taker)) {
+                Some(self.settle_reservation(order_id, taker, true)?)
+            } else {
+                None
+            };
+            let remainder_quarantined = self.release_to_maker(
+                order.maker,
+                remainder,
+                PayoutRoute::Direct,
+                TransferContext::RemainderReturn(order_id),
+            )?;
+            let callback_failed = if order.filled_amount >= order.total_amount {
+                self.notify_completion(order_id, &order)
+            } else {
+                None
+            };
+
+            self.emit_fill_events(FillEvents {
+                filled: (!aggregating).then_some(OrderFilled {
+                    order_id,
+                    fill_id,
+                    taker,
+                    receiver: fill.receiver,
+                    fill_amount,
+                    dest_amount,
+                    contract_id,
+                }),
+                aggregated,
+                clamped: (fill_amount < requested_amount).then_some(FillClamped {
+                    order_id,
+                    fill_id,
+                    requested: requested_amount,
+                    granted: fill_amount,
+                }),
+                extended,
+                intent_changed,
+                reservation_released,
+                remainder_returned: (remainder > 0)
+                    .then_some(OrderRemainderReturned { order_id, amount: remainder }),
+                remainder_quarantined,
+                callback_failed,
+                congestion_changed,
+            });
+
+            Ok(fill_id)
+        }
+
+        /// Withdraw filled amount using preimage, paid along `route` (`Direct` if
+        /// `None`). `memo`, at most `MAX_WITHDRAWAL_MEMO_LEN` bytes, is kept with the
+        /// fill and carried by `FillWithdrawn` for receivers such as exchange deposit
+        /// addresses that need a tag; it does not affect the payout. The fill's
+        /// protocol fee is kept out of the payout and only now counts as collected.
+        /// Only the taker may withdraw (`UnauthorizedWithdraw` for anyone else, the
+        /// receiver included); the payout, deposit and all, goes to the fill's receiver.
+        #[ink(message)]
+        pub fn withdraw_fill(
+            &mut self,
+            fill_id: [u8; 32],
+            preimage: [u8; 32],
+            route: Option<PayoutRoute>,
+            memo: Option<Vec<u8>>,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            let caller = self.env().caller();
+            if memo.as_ref().is_some_and(|memo| memo.len() > MAX_WITHDRAWAL_MEMO_LEN) {
+                return Err(Error::MemoTooLong);
+            }
+            let route = self.payout_route(route)?;
+            let stored = self.get_stored_fill_or_error(&fill_id)?;
+            let mut fill = self.unpack_fill(&fill_id, &stored);
+            let order = self.get_order_or_error(&fill.order_id)?;
+
+            self.validate_fill_withdrawal(&fill, &order, &caller)?;
+            self.validate_preimage(&order, &preimage)?;
+
+            fill.withdrawn = true;
+            fill.preimage = Some(preimage);
+            self.restore_fill(&fill_id, &fill, &stored);
+            self.fill_withdrawn_at.insert(&fill_id, &self.env().block_number());
+            self.order_settled_at.insert(&fill.order_id, &self.env().block_number());
+            self.touch_order(fill.order_id);
+            self.record_reveal(order.hashlock, preimage);
+            if let Some(memo) = &memo {
+                self.fill_memos.insert(&fill_id, memo);
+            }
+            let payout = fill.fill_amount - fill.fee + fill.deposit;
+            self.total_locked -= fill.fill_amount + fill.deposit;
+            self.protocol_fees += fill.fee;
+            let congestion_changed = self.close_pending_fills(1, fill.fill_amount);
+
+            let quarantined = self.settle_or_quarantine(
+                fill.receiver,
+                payout,
+                route,
+                TransferContext::FillWithdrawal(fill_id),
+            )?;
+
+            #[cfg(feature = "periphery")]
+            self.on_fill_withdrawn(fill_id, &fill, &order);
+
+            self.env().emit_event(FillWithdrawn {
+                order_id: fill.order_id,
+                fill_id,
+                secret: preimage,
+                taker: fill.taker,
+                route,
+                memo,
+            });
+            self.emit_settlement_effects(quarantined, congestion_changed);
+
+            Ok(())
+        }
+
+        /// Withdraw every pending fill the caller holds on one order with a single
+        /// preimage, paying their sum in one payout along `route` (`Direct` if
+        /// `None`). Fills of other takers, fills paying out to a receiver other than
+        /// the caller (those go through `withdraw_fill`) and settled fills are
+        /// skipped. Returns the number of fills withdrawn; `FillNotFound` if there
+        /// were none.
+        #[ink(message)]
+        pub fn withdraw_order_fills(
+            &mut self,
+            order_id: [u8; 32],
+            preimage: [u8; 32],
+            route: Option<PayoutRoute>,
+        ) -> Result<u32, Error> {
+            self.ensure_not_retired()?;
+            let caller = self.env().caller();
+            let route = self.payout_route(route)?;
+            let order = self.get_order_or_error(&order_id)?;
+            self.validate_preimage(&order, &preimage)?;
+
+            let mut withdrawn = Vec::new();
+            let mut payout: Balance = 0;
+            let mut principal: Balance = 0;
+            let mut fees: Balance = 0;
+            for fill_id in self.order_fills.get(&order_id).unwrap_or_default() {
+                let Some(stored) = self.fills.get(&fill_id) else {
+                    continue;
+                };
+                let mut fill = self.unpack_fill(&fill_id, &stored);
+                if fill.taker != caller || fill.receiver != caller || fill.withdrawn || fill.refunded {
+                    continue;
+                }
+                self.validate_fill_withdrawal(&fill, &order, &caller)?;
+
+                fill.withdrawn = true;
+                fill.preimage = Some(preimage);
+                self.restore_fill(&fill_id, &fill, &stored);
+                self.fill_withdrawn_at.insert(&fill_id, &self.env().block_number());
+                payout += fill.fill_amount - fill.fee + fill.deposit;
+                principal += fill.fill_amount;
+                fees += fill.fee;
+                withdrawn.push((fill_id, fill));
+            }
+            if withdrawn.is_empty() {
+                return Err(Error::FillNotFound);
+            }
+
+            self.order_settled_at.insert(&order_id, &self.env().block_number());
+            self.touch_order(order_id);
+            self.record_reveal(order.hashlock, preimage);
+            self.total_locked -= payout + fees;
+            self.protocol_fees += fees;
+            let congestion_changed = self.close_pending_fills(withdrawn.len() as u32, principal);
+
+            let quarantined = self.settle_or_quarantine(
+                caller,
+                payout,
+                route,
+                TransferContext::OrderFillsWithdrawal(order_id),
+            )?;
+
+            #[cfg(feature = "periphery")]
+            for (fill_id, fill) in withdrawn.iter() {
+                self.on_fill_withdrawn(*fill_id, fill, &order);
+            }
+
+            let fill_count = withdrawn.len() as u32;
+            let fills = withdrawn
+                .iter()
+                .map(|(fill_id, fill)| FillWithdrawn {
+                    order_id,
+                    fill_id: *fill_id,
+                    secret: preimage,
+                    taker: fill.taker,
+                    route,
+                    memo: None,
+                })
+                .collect();
+            self.emit_order_fills_withdrawn(
+                fills,
+                OrderFillsWithdrawn {
+                    order_id,
+                    taker: caller,
+                    fill_count,
+                    payout,
+                    route,
+                },
+                quarantined,
+                congestion_changed,
+            );
+
+            Ok(fill_count)
+        }
+
+        /// Refund fill after timelock expires (maker only). The principal owed and the
+        /// taker's forfeited deposit are paid along `route` (`Direct` if `None`).
+        #[ink(message)]
+        pub fn refund_fill(
+            &mut self,
+            fill_id: [u8; 32],
+            route: Option<PayoutRoute>,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            let caller = self.env().caller();
+            let route = self.payout_route(route)?;
+            self.settle_fill_refund(fill_id, caller, true, route)
+        }
+
+        /// Refund an expired fill on the maker's behalf; callable by anyone. The taker's
+        /// forfeited deposit pays the caller along `route` (`Direct` if `None`), as far
+        /// as the order's reward budget reaches and to the maker beyond it, while
+        /// principal owed to the maker is transferred directly, or credited if the
+        /// maker refuses it, so a reverting maker cannot block the refund.
+        #[ink(message)]
+        pub fn public_refund_fill(
+            &mut self,
+            fill_id: [u8; 32],
+            route: Option<PayoutRoute>,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            let caller = self.env().caller();
+            let route = self.payout_route(route)?;
+            self.settle_fill_refund(fill_id, caller, false, route)
+        }
+
+        /// Both legs of a refund as one settlement: all state is written first, then
+        /// the executor's leg is paid (a failure there reverts the whole refund) and
+        /// then the maker's, which cannot fail once the executor was paid because a
+        /// refused transfer to the maker is quarantined
+        fn settle_fill_refund(
+            &mut self,
+            fill_id: [u8; 32],
+            executor: Address,
+            maker_only: bool,
+            route: PayoutRoute,
+        ) -> Result<(), Error> {
+            let stored = self.get_stored_fill_or_error(&fill_id)?;
+            let mut fill = self.unpack_fill(&fill_id, &stored);
+            let mut order = self.get_order_or_error(&fill.order_id)?;
+
+            self.validate_fill_refund(&fill, &order, maker_only.then_some(&executor))?;
+
+            fill.refunded = true;
+            self.restore_fill(&fill_id, &fill, &stored);
+            self.order_settled_at.insert(&fill.order_id, &self.env().block_number());
+            self.touch_order(fill.order_id);
+
+            // The refunded taker may not immediately take the same liquidity again
+            let cooldown_until = self.env().block_number().saturating_add(self.refund_cooldown_blocks);
+            self.refund_cooldowns.insert((fill.order_id, fill.taker), &cooldown_until);
+
+            // The refunded amount becomes unfilled liquidity again. On a live order it
+            // stays escrowed and reaches the maker through `cancel_order`; a cancelled
+            // order has already paid out its unfilled remainder, so it is paid here.
+            order.filled_amount -= fill.fill_amount;
+            order.current_fills -= 1;
+            self.orders.insert(&fill.order_id, &order);
+            if !order.cancelled {
+                self.lock_maker_escrow(order.maker, fill.fill_amount);
+            }
+            let congestion_changed = self.close_pending_fills(1, fill.fill_amount);
+            #[cfg(feature = "periphery")]
+            self.on_fill_refunded(fill_id, fill.order_id, &order);
+
+            let returned = if order.cancelled { fill.fill_amount } else { 0 };
+            let (maker_amount, executor_amount) = if executor == order.maker {
+                (returned + fill.deposit, 0)
+            } else {
+                // What the order's reward budget no longer covers stays with the maker
+                let reward = self.grant_reward(fill.order_id, &order, fill.deposit);
+                (returned + fill.deposit - reward, reward)
+            };
+
+            let context = TransferContext::FillRefund(fill_id);
+            let quarantined = if executor == order.maker {
+                self.release_to_maker(order.maker, maker_amount, route, context)?
+            } else {
+                if executor_amount > 0 {
+                    self.total_locked -= executor_amount;
+                    self.settle(executor, executor_amount, route)?;
+                }
+                self.release_to_maker(order.maker, maker_amount, PayoutRoute::Direct, context)?
+            };
+
+            self.env().emit_event(FillRefunded {
+                order_id: fill.order_id,
+                fill_id,
+                maker: order.maker,
+                route,
+                maker_amount,
+                maker_credited: quarantined.is_some(),
+                executor,
+                executor_amount,
+            });
+            self.emit_settlement_effects(quarantined, congestion_changed);
+
+            Ok(())
+        }
+
+        /// Pull native funds out of the caller's credit balance
+        #[ink(message)]
+        pub fn withdraw_credit(&mut self, amount: Balance) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            let caller = self.env().caller();
+            self.debit_credit(caller, amount)?;
+            self.execute_transfer(caller, amount)?;
+
+            self.env().emit_event(CreditWithdrawn { account: caller, amount });
+            Ok(())
+        }
+
+        /// Cancel order and refund remaining amount.
+        ///
+        /// Together with `refund_fill` this pays the maker exactly the escrowed amount,
+        /// whichever runs first: cancelling pays the unfilled remainder, including
+        /// amounts of fills refunded before it, and fills refunded afterwards pay out
+        /// individually. Both persist the order before transferring and go through
+        /// `release_to_maker`.
+        #[ink(message)]
+        pub fn cancel_order(&mut self, order_id: [u8; 32]) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            let caller = self.env().caller();
+            let mut order = self.get_order_or_error(&order_id)?;
+
+            if caller != order.maker {
+                return Err(Error::UnauthorizedRefund);
+            }
+
+            if order.cancelled {
+                return Err(Error::OrderCancelled);
+            }
+
+            order.cancelled = true;
+            self.orders.insert(&order_id, &order);
+            self.order_settled_at.insert(&order_id, &self.env().block_number());
+            self.touch_order(order_id);
+            #[cfg(feature = "periphery")]
+            {
+                self.archive_order(order_id);
+                self.sync_corridor_depth(order_id, &order);
+            }
+
+            let remaining_amount = order.total_amount - order.filled_amount;
+            self.release_maker_escrow(order.maker, remaining_amount);
+            let quarantined = self.release_to_maker(
+                order.maker,
+                remaining_amount,
+                PayoutRoute::Direct,
+                TransferContext::OrderCancel(order_id),
+            )?;
+
+            let aggregated = self.take_fill_aggregate(order_id);
+            self.env().emit_event(OrderCancelled { order_id });
+            self.emit_closing_events(aggregated, quarantined);
+
+            Ok(())
+        }
+
+        /// Purge a terminal order and its fills from storage (maker or admin). Allowed
+        /// once no fill is pending, every revealed secret is held by the secret
+        /// registry and `get_retention_block` has passed; see `check_cleanable`. The
+        /// unfilled remainder of an order that expired without being cancelled is paid
+        /// to the maker here, since nothing could release it once the order is gone.
+        #[ink(message)]
+        pub fn cleanup_order(&mut self, order_id: [u8; 32]) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            let caller = self.env().caller();
+            let order = self.get_order_or_error(&order_id)?;
+            if caller != order.maker && caller != self.admin {
+                return Err(Error::Unauthorized);
+            }
+            self.check_cleanable(order_id, &order)?;
+
+            let fill_ids = self.order_fills.take(&order_id).unwrap_or_default();
+            for fill_id in fill_ids.iter() {
+                if let Some(stored) = self.fills.take(fill_id) {
+                    self.refund_cooldowns.remove((order_id, stored.taker));
+                    let fill = self.unpack_fill(fill_id, &stored);
+                    self.contract_id_fills.remove(&fill.contract_id);
+                }
+                self.fill_preimages.remove(fill_id);
+                self.fill_memos.remove(fill_id);
+                self.fill_fees.remove(fill_id);
+                self.fill_receivers.remove(fill_id);
+                self.fill_rates.remove(fill_id);
+                self.fill_withdrawn_at.remove(fill_id);
+                self.fill_created_at.remove(fill_id);
+                self.legacy_contract_ids.remove(fill_id);
+            }
+            self.orders.remove(&order_id);
+            self.order_created_at.remove(&order_id);
+            self.order_settled_at.remove(&order_id);
+            self.order_last_activity.remove(&order_id);
+            let aggregated = self.take_fill_aggregate(order_id);
+            self.fill_aggregates.remove(&order_id);
+            self.anti_snipe.remove(&order_id);
+            self.rewards_paid.remove(&order_id);
+            self.order_reservers.remove(&order_id);
+            self.taker_whitelist.remove(&order_id);
+            self.bump_state_version();
+            #[cfg(feature = "periphery")]
+            self.on_order_cleaned(order_id, &order);
+
+            let mut quarantined = None;
+            if !order.cancelled {
+                let remaining = order.total_amount - order.filled_amount;
+                self.release_maker_escrow(order.maker, remaining);
+                quarantined = self.release_to_maker(
+                    order.maker,
+                    remaining,
+                    PayoutRoute::Direct,
+                    TransferContext::OrderCleanup(order_id),
+                )?;
+            }
+
+            self.env().emit_event(OrderCleanedUp {
+                order_id,
+                fills_removed: fill_ids.len() as u32,
+            });
+            self.emit_closing_events(aggregated, quarantined);
+            Ok(())
+        }
+
+        /// Whether `cleanup_order` would purge the order now
+        #[ink(message)]
+        pub fn is_cleanable(&self, order_id: [u8; 32]) -> bool {
+            self.orders
+                .get(&order_id)
+                .is_some_and(|order| self.check_cleanable(order_id, &order).is_ok())
+        }
+
+        /// First block at which a terminal order may be cleaned up; `None` while the
+        /// order is live or has pending fills
+        #[ink(message)]
+        pub fn get_retention_block(&self, order_id: [u8; 32]) -> Option<BlockNumber> {
+            let order = self.orders.get(&order_id)?;
+            let fills = self.stored_order_fills(order_id);
+            self.retention_block(order_id, &order, &fills).ok()
+        }
+
+        /// Hold `amount` of an order's remaining capacity for the caller until
+        /// `reservation_blocks` from now, returning that expiry block. Requires a bond of
+        /// at least `reservation_bond`, paid back when the caller fills the order or calls
+        /// `release_reservation`. One reservation per account and order, of at most
+        /// `MAX_RESERVATION_SHARE_BPS` of the remaining amount; an expired one must be
+        /// released before reserving again, and a released one blocks the account from
+        /// reserving the order for another `reservation_blocks`.
+        #[ink(message, payable)]
+        pub fn reserve(&mut self, order_id: [u8; 32], amount: Balance) -> Result<BlockNumber, Error> {
+            self.ensure_taking_new_business()?;
+            self.ensure_not_paused()?;
+            let reserver = self.env().caller();
+            let bond = self.get_transferred_value()?;
+            let required = self.reservation_bond;
+            if bond < required {
+                return Err(Error::InsufficientFunds { required, received: bond });
+            }
+
+            let order = self.get_order_or_error(&order_id)?;
+            if order.fills_paused {
+                return Err(Error::OrderFillsPaused);
+            }
+            self.validate_fill_request(&order, amount)?;
+            self.validate_whitelisted(&order_id, &reserver)?;
+            self.validate_refund_cooldown(&order_id, &reserver)?;
+            if self.reservations.contains((order_id, reserver)) {
+                return Err(Error::ReservationExists);
+            }
+
+            let current_block = self.env().block_number();
+            if self.reservation_cooldowns.get((order_id, reserver)).is_some_and(|until| current_block < until) {
+                return Err(Error::ReservationCooldownActive);
+            }
+            let remaining_amount = order.total_amount - order.filled_amount;
+            if amount > remaining_amount * MAX_RESERVATION_SHARE_BPS / 10_000 {
+                return Err(Error::ReservationTooLarge);
+            }
+            let mut reservers = self.order_reservers.get(&order_id).unwrap_or_default();
+            reservers.retain(|account| self.active_reservation(&order_id, account).is_some());
+            if reservers.len() >= MAX_RESERVATIONS_PER_ORDER {
+                return Err(Error::ReservationLimitReached);
+            }
+            if amount > self.available_to(&order_id, remaining_amount, &reserver)? {
+                return Err(Error::CapacityReserved);
+            }
+
+            let expires_at = current_block.saturating_add(self.reservation_blocks);
+            self.reservations.insert((order_id, reserver), &Reservation { amount, expires_at, bond });
+            self.bump_state_version();
+            reservers.push(reserver);
+            self.order_reservers.insert(&order_id, &reservers);
+            self.total_locked += bond;
+
+            self.env().emit_event(OrderReserved {
+                order_id,
+                reserver,
+                amount,
+                expires_at,
+                bond,
+            });
+            Ok(expires_at)
+        }
+
+        /// Drop the caller's reservation on an order, expired or not, and pay back its
+        /// bond. The caller may not reserve the order again for `reservation_blocks`.
+        #[ink(message)]
+        pub fn release_reservation(&mut self, order_id: [u8; 32]) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            let reserver = self.env().caller();
+            let released = self.settle_reservation(order_id, reserver, false)?;
+            self.env().emit_event(released);
+            Ok(())
+        }
+
+        /// Stop or resume new fills on an order (maker only). Existing fills can
+        /// still be withdrawn and refunded while paused.
+        #[ink(message)]
+        pub fn set_order_fill_paused(&mut self, order_id: [u8; 32], paused: bool) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            let caller = self.env().caller();
+            let mut order = self.get_order_or_error(&order_id)?;
+
+            if caller != order.maker {
+                return Err(Error::Unauthorized);
+            }
+
+            order.fills_paused = paused;
+            self.orders.insert(&order_id, &order);
+            self.touch_order(order_id);
+
+            self.env().emit_event(OrderFillsPausedChanged { order_id, paused });
+            Ok(())
+        }
+
+        /// Restrict fills and reservations of an order to `takers` (maker only), at
+        /// most `MAX_TAKER_WHITELIST` of them; an empty list lifts the restriction.
+        /// Only before the order's first fill, `TakerWhitelistLocked` after.
+        #[ink(message)]
+        pub fn set_taker_whitelist(&mut self, order_id: [u8; 32], takers: Vec<Address>) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            let caller = self.env().caller();
+            let order = self.get_order_or_error(&order_id)?;
+
+            if caller != order.maker {
+                return Err(Error::Unauthorized);
+            }
+            if order.current_fills > 0 {
+                return Err(Error::TakerWhitelistLocked);
+            }
+            if takers.len() > MAX_TAKER_WHITELIST {
+                return Err(Error::BatchTooLarge);
+            }
+
+            if takers.is_empty() {
+                self.taker_whitelist.remove(&order_id);
+            } else {
+                self.taker_whitelist.insert(&order_id, &takers);
+            }
+            self.touch_order(order_id);
+
+            self.env().emit_event(TakerWhitelistChanged { order_id, takers });
+            Ok(())
+        }
+
+        /// Whether `taker` passes the order's whitelist; every taker does on an order
+        /// without one
+        #[ink(message)]
+        pub fn is_whitelisted_taker(&self, order_id: [u8; 32], taker: Address) -> bool {
+            self.taker_whitelist
+                .get(&order_id)
+                .is_none_or(|takers| takers.contains(&taker))
+        }
+
+        /// Replace the hashlock of an order nobody has filled yet (maker only), e.g.
+        /// because the secret leaked before trading. Allowed `MAX_REARMS` times per
+        /// order; once any fill exists, even a refunded one, the hashlock is final.
+        /// The swap id is kept as is; with the periphery, the order also stops
+        /// counting as a duplicate of its old parameters.
+        #[ink(message)]
+        pub fn rearm_order(
+            &mut self,
+            order_id: [u8; 32],
+            new_hashlock: [u8; 32],
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            let caller = self.env().caller();
+            let mut order = self.get_order_or_error(&order_id)?;
+
+            if caller != order.maker {
+                return Err(Error::Unauthorized);
+            }
+            if order.cancelled {
+                return Err(Error::OrderCancelled);
+            }
+            if self.env().block_number() >= order.timelock {
+                return Err(Error::TimelockExpired);
+            }
+            if !self.order_fills.get(&order_id).unwrap_or_default().is_empty() {
+                return Err(Error::HashlockInUse);
+            }
+            if order.rearm_count >= MAX_REARMS {
+                return Err(Error::RearmLimitReached);
+            }
+            if new_hashlock == order.hashlock {
+                return Err(Error::InvalidHashlock);
+            }
+
+            let old_hashlock = core::mem::replace(&mut order.hashlock, new_hashlock);
+            order.rearm_count += 1;
+            let intent_changed = self.refresh_intent_hash(order_id, &mut order);
+            self.orders.insert(&order_id, &order);
+            self.touch_order(order_id);
+            #[cfg(feature = "periphery")]
+            self.forget_fingerprint(order_id);
+
+            self.env().emit_event(OrderRearmed { order_id, old_hashlock, new_hashlock });
+            self.emit_intent_change(intent_changed);
+            Ok(())
+        }
+
+        /// Raise the fill cap of a live `RaiseLimit` order (maker only)
+        #[ink(message)]
+        pub fn increase_max_fills(
+            &mut self,
+            order_id: [u8; 32],
+            new_max_fills: u32,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            let caller = self.env().caller();
+            let mut order = self.get_order_or_error(&order_id)?;
+
+            if caller != order.maker {
+                return Err(Error::Unauthorized);
+            }
+            if order.on_max_fills != MaxFillsPolicy::RaiseLimit {
+                return Err(Error::MaxFillsNotRaisable);
+            }
+            if order.cancelled {
+                return Err(Error::OrderCancelled);
+            }
+            if self.env().block_number() >= order.timelock {
+                return Err(Error::TimelockExpired);
+            }
+            if new_max_fills <= order.max_fills {
+                return Err(Error::InvalidFillAmount);
+            }
+
+            let old_max_fills = core::mem::replace(&mut order.max_fills, new_max_fills);
+            let intent_changed = self.refresh_intent_hash(order_id, &mut order);
+            self.orders.insert(&order_id, &order);
+            self.touch_order(order_id);
+
+            self.env().emit_event(MaxFillsIncreased { order_id, old_max_fills, new_max_fills });
+            self.emit_intent_change(intent_changed);
+            Ok(())
+        }
+
+        // View functions
+        #[ink(message)]
+        pub fn get_order(&self, order_id: [u8; 32]) -> Option<PartialFillOrder> {
+            self.orders.get(&order_id)
+        }
+
+        /// The order's amounts alongside the decimals to display them with
+        #[ink(message)]
+        pub fn get_order_display(&self, order_id: [u8; 32]) -> Option<OrderDisplay> {
+            let order = self.orders.get(&order_id)?;
+            let remaining_amount = order.total_amount - order.filled_amount;
+            Some(OrderDisplay {
+                total_amount: order.total_amount,
+                filled_amount: order.filled_amount,
+                remaining_amount,
+                min_fill_amount: order.min_fill_amount,
+                decimals: order.source_decimals.unwrap_or(quote::NATIVE_DECIMALS),
+                dest_remaining_amount: Self::compute_dest_amount(
+                    &order,
+                    self.current_rate(&order),
+                    remaining_amount,
+                ),
+                dest_decimals: order.dest_decimals,
+            })
+        }
+
+        /// SCALE encoding of the order as stored, for state-proof verification.
+        /// Fields are encoded in `PartialFillOrder` declaration order; see
+        /// `get_order_ink_storage_key` for where the value lives.
+        #[ink(message)]
+        pub fn get_order_raw(&self, order_id: [u8; 32]) -> Option<Vec<u8>> {
+            self.orders.get(&order_id).map(|order| order.encode())
+        }
+
+        /// ink!-level key of the `orders` entry of `order_id`: the SCALE encoding of
+        /// `(root_key: u32, order_id: [u8; 32])`, i.e. the 4-byte little-endian root key
+        /// of the `orders` mapping followed by the raw id. This is the key the contract
+        /// hands the host, not a trie key; a storage proof has to apply the runtime's
+        /// own key derivation to it.
+        #[ink(message)]
+        pub fn get_order_ink_storage_key(&self, order_id: [u8; 32]) -> Vec<u8> {
+            let orders_key = ink::storage::traits::StorageKey::key(&self.orders);
+            (orders_key, order_id).encode()
+        }
+
+        #[ink(message)]
+        pub fn get_fill(&self, fill_id: [u8; 32]) -> Option<FillExecution> {
+            self.load_fill(&fill_id)
+        }
+
+        /// Fill ids of an order in fill order, at most `MAX_ORDER_FILLS_PAGE` per page
+        #[ink(message)]
+        pub fn get_order_fills(
+            &self,
+            order_id: [u8; 32],
+            offset: u64,
+            limit: u32,
+        ) -> Result<Page<[u8; 32]>, Error> {
+            let fill_ids = self.order_fills.get(&order_id).unwrap_or_default();
+            Self::page(offset, limit, MAX_ORDER_FILLS_PAGE, fill_ids.len() as u64, |pos| {
+                fill_ids.get(pos as usize).copied()
+            })
+        }
+
+        #[ink(message)]
+        pub fn order_exists(&self, order_id: [u8; 32]) -> bool {
+            self.orders.contains(&order_id)
+        }
+
+        /// Largest `fill_amount` that `taker` could pass to `fill_order` right now, or the
+        /// error any fill would hit. Bounded by the remaining amount less what other
+        /// accounts have reserved, the vesting unlock and the fill count; `taker` matters
+        /// because of those reservations and because repeating an identical fill in the
+        /// same block collides on the fill id. Amounts above the result are clamped by
+        /// `fill_order`, so the value is meant as a UI maximum, not an exact requirement.
+        /// The result is never below `get_min_fill`. On an order without a minimum, a
+        /// remainder owed nothing at the destination is refused with `FillAmountTooSmall`.
+        #[ink(message)]
+        pub fn get_max_fillable_amount(
+            &self,
+            order_id: [u8; 32],
+            taker: Address,
+        ) -> Result<Balance, Error> {
+            self.ensure_not_retired()?;
+            self.ensure_not_paused()?;
+            let order = self.get_order_or_error(&order_id)?;
+            if order.fills_paused {
+                return Err(Error::OrderFillsPaused);
+            }
+            // Any non-zero amount passes the amount check; only order state can fail here
+            self.validate_fill_request(&order, 1)?;
+            self.validate_whitelisted(&order_id, &taker)?;
+            self.validate_refund_cooldown(&order_id, &taker)?;
+
+            let remaining_amount = order.total_amount - order.filled_amount;
+            let unlocked = match order.vesting_schedule {
+                Some(_) => self.unlocked_amount(&order).saturating_sub(order.filled_amount),
+                None => remaining_amount,
+            };
+            // The largest request `fill_order` takes; all-or-nothing orders take only
+            // what remains
+            let requested = if order.allow_partial_fills { unlocked } else { remaining_amount };
+            if requested == 0 || requested > unlocked {
+                return Err(Error::FillExceedsUnlocked);
+            }
+
+            // From here on, the same checks `fill_order` runs on the request
+            let available = self.available_to(&order_id, remaining_amount, &taker)?;
+            let max_amount = quote::clamp_fill(
+                requested,
+                remaining_amount,
+                available,
+                order.min_fill_amount,
+                order.allow_partial_fills,
+            )?;
+            quote::nonzero_dest_amount(
+                max_amount,
+                self.current_rate(&order),
+                order.source_decimals,
+                order.dest_decimals,
+            )?;
+            if self.fills.contains(&self.generate_fill_id(&order_id, &taker, max_amount)) {
+                return Err(Error::FillAlreadyExists);
+            }
+
+            Ok(max_amount)
+        }
+
+        /// `reserver`'s reservation on an order, kept after expiry until released
+        #[ink(message)]
+        pub fn get_reservation(&self, order_id: [u8; 32], reserver: Address) -> Option<Reservation> {
+            self.reservations.get((order_id, reserver))
+        }
+
+        /// Capacity currently held by unexpired reservations on an order
+        #[ink(message)]
+        pub fn get_reserved_amount(&self, order_id: [u8; 32]) -> Balance {
+            self.order_reservers
+                .get(&order_id)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|reserver| self.active_reservation(&order_id, reserver))
+                .map(|reservation| reservation.amount)
+                .sum()
+        }
+
+        /// Gross amount still open for fills; protocol fees come out of fills as
+        /// they are withdrawn, not out of this
+        #[ink(message)]
+        pub fn get_remaining_amount(&self, order_id: [u8; 32]) -> Balance {
+            if let Some(order) = self.orders.get(&order_id) {
+                if order.cancelled || order.filled_amount >= order.total_amount {
+                    return 0;
+                }
+                return order.total_amount - order.filled_amount;
+            }
+            0
+        }
+
+        /// Lifecycle state of an order, `None` if unknown. Cancelled and fully filled
+        /// orders keep that state past their timelock; any other order is `Expired`
+        /// from its timelock block on.
+        #[ink(message)]
+        pub fn get_order_status(&self, order_id: [u8; 32]) -> Option<OrderStatus> {
+            let order = self.orders.get(&order_id)?;
+            Some(self.compute_order_status(&order))
+        }
+
+        /// Preflight for the funding checks of `create_partial_fill_order`: reports the
+        /// error creation would return for `value` planck sent against `total_amount`
+        #[ink(message)]
+        pub fn simulate_create(&self, total_amount: Balance, value: Balance) -> Result<(), Error> {
+            Self::check_payment(total_amount, value)
+        }
+
+        /// Dry run of a creation by the caller sending `value` planck: runs the same
+        /// checks as `create_partial_fill_order_with_options` without changing state and
+        /// returns the error creation would return, or the fee and id it would produce.
+        /// A PSP22 fee pull is not attempted, so a missing allowance only shows on creation.
+        #[ink(message)]
+        pub fn validate_create(
+            &self,
+            params: CreateOrderParams,
+            value: Balance,
+        ) -> Result<CreatePreview, Error> {
+            self.preflight_create(&self.env().caller(), &params, value)
+                .map(|plan| plan.preview)
+        }
+
+        /// Portion of the order released for filling so far (the full amount without vesting)
+        #[ink(message)]
+        pub fn get_unlocked_amount(&self, order_id: [u8; 32]) -> Balance {
+            self.orders
+                .get(&order_id)
+                .map(|order| self.unlocked_amount(&order))
+                .unwrap_or(0)
+        }
+
+        /// Whether fills took the whole gross `total_amount`
+        #[ink(message)]
+        pub fn is_order_complete(&self, order_id: [u8; 32]) -> bool {
+            if let Some(order) = self.orders.get(&order_id) {
+                return order.filled_amount >= order.total_amount;
+            }
+            false
+        }
+
+        /// sha256 of the SCALE-encoded order, for checking a cached copy is current
+        #[ink(message)]
+        pub fn compute_order_hash(&self, order_id: [u8; 32]) -> Option<[u8; 32]> {
+            self.orders
+                .get(&order_id)
+                .map(|order| self.compute_sha256(&order.encode()))
+        }
+
+        /// Hash of the order's current terms; see `OrderIntent`. It changes exactly
+        /// when the terms do, so a resolver can tell a quoted order moved under it.
+        #[ink(message)]
+        pub fn get_intent_hash(&self, order_id: [u8; 32]) -> Option<[u8; 32]> {
+            self.orders.get(&order_id).map(|order| order.intent_hash)
+        }
+
+        #[ink(message)]
+        pub fn verify_order_hash(&self, order_id: [u8; 32], expected_hash: [u8; 32]) -> bool {
+            self.compute_order_hash(order_id) == Some(expected_hash)
+        }
+
+        #[ink(message)]
+        pub fn get_fill_secret(&self, fill_id: [u8; 32]) -> Option<[u8; 32]> {
+            self.fill_preimages.get(&fill_id)
+        }
+
+        /// Block the fill was created in. Unlike `FillExecution::timestamp`, which the
+        /// collator sets, this is what any ordering of fills should rely on.
+        #[ink(message)]
+        pub fn get_fill_created_block(&self, fill_id: [u8; 32]) -> Option<BlockNumber> {
+            self.fill_created_at.get(&fill_id)
+        }
+
+        /// Which namespace `id` belongs to in this deployment: an order, a fill, the
+        /// contract id of a fill, or none. Contract ids of fills created before they
+        /// were indexed report `Unknown`.
+        #[ink(message)]
+        pub fn lookup_id(&self, id: [u8; 32]) -> IdKind {
+            if self.orders.contains(&id) {
+                IdKind::Order
+            } else if self.fills.contains(&id) {
+                IdKind::Fill
+            } else if let Some(fill_id) = self.contract_id_fills.get(&id) {
+                IdKind::Contract { fill_id }
+            } else {
+                IdKind::Unknown
+            }
+        }
+
+        /// Block the order was created in; `None` for unknown orders and for orders
+        /// created before creation blocks were recorded
+        #[ink(message)]
+        pub fn get_order_created_block(&self, order_id: [u8; 32]) -> Option<BlockNumber> {
+            self.order_created_at.get(&order_id)
+        }
+
+        /// Memo the taker attached when withdrawing the fill, if any
+        #[ink(message)]
+        pub fn get_fill_memo(&self, fill_id: [u8; 32]) -> Option<Vec<u8>> {
+            self.fill_memos.get(&fill_id)
+        }
+
+        /// Settlement of a withdrawn fill for the relayer to sign; `None` until the
+        /// fill is withdrawn and once its order is cleaned up
+        #[ink(message)]
+        pub fn get_settlement_summary(&self, fill_id: [u8; 32]) -> Option<SettlementSummary> {
+            let block = self.fill_withdrawn_at.get(&fill_id)?;
+            let stored = self.fills.get(&fill_id)?;
+            let fill = self.unpack_fill(&fill_id, &stored);
+            let order = self.orders.get(&fill.order_id)?;
+            Some(SettlementSummary {
+                chain_id: self.home_chain_id,
+                contract: self.env().address(),
+                order_id: fill.order_id,
+                fill_id,
+                taker: fill.taker,
+                hashlock: order.hashlock,
+                secret: fill.preimage?,
+                amount: fill.fill_amount,
+                receiver: order.receiver_cross_address,
+                block,
+            })
+        }
+
+        /// Digest of `get_settlement_summary` the relayer signs off-chain
+        #[ink(message)]
+        pub fn sign_worthy_digest(&self, fill_id: [u8; 32]) -> Option<[u8; 32]> {
+            self.get_settlement_summary(fill_id).map(|summary| summary.digest())
+        }
+
+        /// Digest of the order's `OrderCommitment` under its current terms
+        #[ink(message)]
+        pub fn get_order_commitment(&self, order_id: [u8; 32]) -> Option<[u8; 32]> {
+            let order = self.orders.get(&order_id)?;
+            Some(self.order_commitment(order_id, &order).digest())
+        }
+
+        /// Last block its maker or a taker acted on the order: creation, fills,
+        /// settlements and maker amendments. `None` for unknown orders and for
+        /// orders untouched since before activity was recorded.
+        #[ink(message)]
+        pub fn get_last_activity_block(&self, order_id: [u8; 32]) -> Option<BlockNumber> {
+            self.order_last_activity.get(&order_id)
+        }
+
+        /// Roll-up state of an aggregating order: its boundaries and the fills not yet
+        /// announced. `None` for orders announcing each fill.
+        #[ink(message)]
+        pub fn get_fill_aggregate(&self, order_id: [u8; 32]) -> Option<FillAggregate> {
+            self.fill_aggregates.get(&order_id)
+        }
+
+        /// Late-fill protection of an order: how far a late fill pushes the timelock out
+        /// and how many extensions have been used. `None` for orders without it.
+        #[ink(message)]
+        pub fn get_anti_snipe(&self, order_id: [u8; 32]) -> Option<AntiSnipe> {
+            self.anti_snipe.get(&order_id)
+        }
+
+        /// Announce an aggregating order's pending fills now rather than at its next
+        /// boundary, e.g. once fills have stopped. Permissionless; returns the number
+        /// of fills announced.
+        #[ink(message)]
+        pub fn flush_fill_aggregate(&mut self, order_id: [u8; 32]) -> Result<u32, Error> {
+            self.ensure_not_retired()?;
+            self.get_order_or_error(&order_id)?;
+            let Some(event) = self.take_fill_aggregate(order_id) else {
+                return Ok(0);
+            };
+            let fills_count = event.fills_count;
+            self.env().emit_event(event);
+            Ok(fills_count)
+        }
+
+        /// Whether the order has seen no activity for at least `threshold_blocks`,
+        /// which usually means its maker abandoned it. A signal for resolvers only;
+        /// the order stays fillable and refundable as before. Orders without
+        /// recorded activity count as idle since block 0, unknown orders as not stale.
+        #[ink(message)]
+        pub fn is_stale(&self, order_id: [u8; 32], threshold_blocks: BlockNumber) -> bool {
+            self.orders.contains(&order_id) && self.idle_for(order_id, threshold_blocks)
+        }
+
+        /// Secret revealed for a hashlock, if any fill using it has been withdrawn
+        #[ink(message)]
+        pub fn get_revealed_secret(&self, hashlock: [u8; 32]) -> Option<[u8; 32]> {
+            self.secret_registry.get(&hashlock)
+        }
+
+        /// Admin parameter changes logged at or after `from`, oldest first, at most
+        /// `MAX_AUDIT_PAGE` per page
+        #[ink(message)]
+        pub fn get_admin_audit_log(
+            &self,
+            from: Cursor,
+            limit: u32,
+        ) -> Result<Page<AdminAuditLog>, Error> {
+            Self::page(from, limit, MAX_AUDIT_PAGE, self.audit_log_counter, |i| {
+                self.admin_audit.get(i)
+            })
+        }
+
+        #[ink(message)]
+        pub fn get_storage_version(&self) -> StorageVersion {
+            self.storage_version
+        }
+
+        /// Domain every signed payload checked by this deployment is bound to
+        #[ink(message)]
+        pub fn get_signing_domain(&self) -> SigningDomain {
+            SigningDomain {
+                contract_address: self.env().address(),
+                chain_id: self.home_chain_id,
+                version: SIGNING_DOMAIN_VERSION,
+                salt: self.deployment_salt,
+            }
+        }
+
+        /// Salt fixed at instantiation and mixed into every order, fill and contract
+        /// id and signing domain, so identical inputs on two deployments of this code
+        /// yield different ids. `None` on deployments from before storage version 3,
+        /// whose ids stay unsalted.
+        #[ink(message)]
+        pub fn get_deployment_salt(&self) -> Option<[u8; 32]> {
+            self.id_salt().copied()
+        }
+
+        /// All-time maximum of the (order, fill) id counters
+        #[ink(message)]
+        pub fn get_counter_high_watermarks(&self) -> (u64, u64) {
+            (self.order_counter_high, self.fill_counter_high)
+        }
+
+        #[ink(message)]
+        pub fn get_event_layout_version(&self) -> u8 {
+            EVENT_LAYOUT_VERSION
+        }
+
+        #[ink(message)]
+        pub fn get_admin(&self) -> Address {
+            self.admin
+        }
+
+        #[ink(message)]
+        pub fn get_protocol_fee_bps(&self) -> u16 {
+            self.protocol_fee_bps
+        }
+
+        /// Native fees collected so far: the fees of withdrawn fills
+        #[ink(message)]
+        pub fn get_protocol_fees(&self) -> Balance {
+            self.protocol_fees
+        }
+
+        #[ink(message)]
+        pub fn get_chain_config(&self, chain_id: u32) -> Option<ChainConfig> {
+            self.chain_registry.get(&chain_id)
+        }
+
+        #[ink(message)]
+        pub fn get_home_chain_id(&self) -> u32 {
+            self.home_chain_id
+        }
+
+        #[ink(message)]
+        pub fn is_home_chain_enforced(&self) -> bool {
+            self.enforce_home_chain
+        }
+
+        #[ink(message)]
+        pub fn get_default_hash_algorithm(&self) -> HashAlgorithm {
+            self.default_hash_algorithm
+        }
+
+        #[ink(message)]
+        pub fn get_min_timelock(&self) -> BlockNumber {
+            self.min_timelock
+        }
+
+        #[ink(message)]
+        pub fn get_staged_upgrade(&self) -> Option<StagedUpgrade> {
+            self.staged_upgrade
+        }
+
+        /// Decode a sample of critical storage: the configuration, the counters and,
+        /// if given, one order with all of its fills. Every version of this contract
+        /// exposes it, so that staged code can be checked against live storage by
+        /// `check_staged_upgrade` before the switch. The root storage is decoded
+        /// before any message runs, so a report also proves all of it readable.
+        #[ink(message)]
+        pub fn check_migration(&self, sample_order: Option<[u8; 32]>) -> Result<MigrationReport, Error> {
+            let mut sample_fills = 0;
+            if let Some(order_id) = sample_order {
+                self.get_order_or_error(&order_id)?;
+                for fill_id in self.order_fills.get(&order_id).unwrap_or_default() {
+                    self.load_fill(&fill_id).ok_or(Error::FillNotFound)?;
+                    sample_fills += 1;
+                }
+            }
+            Ok(MigrationReport {
+                storage_version: self.storage_version,
+                admin: self.admin,
+                protocol_fee_bps: self.protocol_fee_bps,
+                min_timelock: self.min_timelock,
+                max_timelock: self.max_timelock,
+                order_counter: self.order_counter,
+                fill_counter: self.fill_counter,
+                total_locked: self.total_locked,
+                sample_order,
+                sample_fills,
+            })
+        }
+
+        /// Pending `(new_min, effective_at)` proposal, if any
+        #[ink(message)]
+        pub fn get_pending_min_timelock(&self) -> Option<(BlockNumber, BlockNumber)> {
+            self.pending_min_timelock
+        }
+
+        /// Block of the last protocol fee sweep whose recipient refused the payout,
+        /// while the fees are still stuck
+        #[ink(message)]
+        pub fn get_fee_sweep_failed_at(&self) -> Option<BlockNumber> {
+            self.fee_sweep_failed_at
+        }
+
+        /// Whether payouts may take `route` on this deployment
+        #[ink(message)]
+        pub fn is_payout_route_available(&self, route: PayoutRoute) -> bool {
+            match route {
+                PayoutRoute::Direct | PayoutRoute::Credit => true,
+                PayoutRoute::Xcm { .. } => cfg!(feature = "xcm"),
+            }
+        }
+
+        /// Native value units per `Balance` unit: multiply amounts by this before
+        /// sending them as value, divide received value by it
+        #[ink(message)]
+        pub fn get_value_scale(&self) -> u128 {
+            self.value_scale
+        }
+
+        #[ink(message)]
+        pub fn get_block_time_ms(&self) -> u64 {
+            self.block_time_ms
+        }
+
+        /// Approximate wall-clock duration of `blocks` at the configured block time
+        #[ink(message)]
+        pub fn estimate_timelock_seconds(&self, blocks: BlockNumber) -> u64 {
+            (blocks as u64).saturating_mul(self.block_time_ms) / 1000
+        }
+
+        #[ink(message)]
+        pub fn get_credit(&self, account: Address) -> Balance {
+            self.credits.get(&account).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn get_total_credits(&self) -> Balance {
+            self.total_credits
+        }
+
+        #[ink(message)]
+        pub fn get_total_locked(&self) -> Balance {
+            self.total_locked
+        }
+
+        /// Pending settlement load and the timelock it currently requires of new orders
+        #[ink(message)]
+        pub fn get_health(&self) -> Health {
+            Health {
+                total_locked: self.total_locked,
+                pending_fill_count: self.pending_fill_count,
+                pending_fill_value: self.pending_fill_value,
+                congestion_soft_cap: self.congestion_soft_cap,
+                congested: self.congested,
+                effective_min_timelock: self.effective_min_timelock(),
+                sunset: self.sunset,
+                paused: self.paused,
+            }
+        }
+
+        /// Where `account` stands against the beta cap
+        #[ink(message)]
+        pub fn get_account_cap_status(&self, account: Address) -> AccountCapStatus {
+            AccountCapStatus {
+                cap: self.beta_cap,
+                current: self.maker_escrow.get(&account).unwrap_or(0),
+                graduation_block: self.beta_graduation_block(&account),
+                capped: self.is_beta_capped(&account),
+            }
+        }
+
+        #[ink(message)]
+        pub fn get_reward_cap(&self) -> Option<RewardCap> {
+            self.reward_cap
+        }
+
+        /// Rewards `order_id` has paid to executors of its public actions and what
+        /// its cap leaves; `None` for unknown orders
+        #[ink(message)]
+        pub fn get_reward_budget(&self, order_id: [u8; 32]) -> Option<RewardBudget> {
+            let order = self.orders.get(&order_id)?;
+            let paid = self.rewards_paid.get(&order_id).unwrap_or(0);
+            let allowance = self.reward_allowance(&order);
+            Some(RewardBudget {
+                allowance,
+                paid,
+                remaining: allowance.map(|allowance| allowance.saturating_sub(paid)),
+            })
+        }
+
+        /// Native funds the contract owes: escrow, credits and uncollected protocol fees.
+        /// The contract is solvent while its balance covers this amount.
+        #[ink(message)]
+        pub fn get_total_liabilities(&self) -> Balance {
+            self.total_locked + self.total_credits + self.protocol_fees
+        }
+
+        /// Configuration, health, accounting and, if given, one order with all of
+        /// its fills and its accounting, read together so that the numbers agree
+        #[ink(message)]
+        pub fn get_incident_snapshot(&self, order_id: Option<[u8; 32]>) -> IncidentSnapshot {
+            IncidentSnapshot {
+                block: self.env().block_number(),
+                timestamp: self.env().block_timestamp(),
+                config: ProtocolConfig {
+                    storage_version: self.storage_version,
+                    admin: self.admin,
+                    protocol_fee_bps: self.protocol_fee_bps,
+                    min_timelock: self.min_timelock,
+                    max_timelock: self.max_timelock,
+                    refund_cooldown_blocks: self.refund_cooldown_blocks,
+                    retention_blocks: self.retention_blocks,
+                    value_scale: self.value_scale,
+                },
+                health: self.get_health(),
+                accounting: ProtocolAccounting {
+                    order_counter: self.order_counter,
+                    fill_counter: self.fill_counter,
+                    total_locked: self.total_locked,
+                    total_credits: self.total_credits,
+                    protocol_fees: self.protocol_fees,
+                    total_liabilities: self.get_total_liabilities(),
+                },
+                order: order_id.and_then(|order_id| self.snapshot_order(order_id)),
+            }
+        }
+
+        #[ink(message)]
+        pub fn get_deposit_schedule(&self) -> Vec<(Balance, u16)> {
+            self.deposit_schedule.clone()
+        }
+
+        /// First block at which `taker` may fill `order_id` again after a refund
+        #[ink(message)]
+        pub fn get_refund_cooldown_until(
+            &self,
+            order_id: [u8; 32],
+            taker: Address,
+        ) -> Option<BlockNumber> {
+            self.refund_cooldowns
+                .get((order_id, taker))
+                .filter(|until| self.env().block_number() < *until)
+        }
+
+        #[ink(message)]
+        pub fn get_refund_cooldown_blocks(&self) -> BlockNumber {
+            self.refund_cooldown_blocks
+        }
+
+        #[ink(message)]
+        pub fn get_retention_blocks(&self) -> BlockNumber {
+            self.retention_blocks
+        }
+
+        /// Reservation lifetime in blocks and the minimum bond `reserve` takes
+        #[ink(message)]
+        pub fn get_reservation_params(&self) -> (BlockNumber, Balance) {
+            (self.reservation_blocks, self.reservation_bond)
+        }
+
+        /// Inclusive `(min, max)` band for rates from `source_chain` to `dest_chain`, in
+        /// base-unit rate terms (see `set_price_band`)
+        #[ink(message)]
+        pub fn get_price_band(
+            &self,
+            source_chain: u32,
+            dest_chain: u32,
+        ) -> Option<(Balance, Balance)> {
+            self.price_bands.get((source_chain, dest_chain))
+        }
+
+        #[ink(message)]
+        pub fn get_reject_mispriced(&self) -> bool {
+            self.reject_mispriced
+        }
+
+        /// Destination amount a fill of `fill_amount` in this block would be owed,
+        /// honouring the order's decimal metadata
+        #[ink(message)]
+        pub fn get_fill_quote(&self, order_id: [u8; 32], fill_amount: Balance) -> Option<Balance> {
+            let order = self.orders.get(&order_id)?;
+            Some(Self::compute_dest_amount(&order, self.current_rate(&order), fill_amount))
+        }
+
+        /// Rate a fill in this block is priced at: `dest_amount_per_unit`, or for a
+        /// Dutch auction the interpolated rate. Zero for unknown orders.
+        #[ink(message)]
+        pub fn get_current_rate(&self, order_id: [u8; 32]) -> Balance {
+            self.orders
+                .get(&order_id)
+                .map(|order| self.current_rate(&order))
+                .unwrap_or(0)
+        }
+
+        /// Protocol fee a fill of `fill_amount` keeps out of its payout on withdrawal
+        #[ink(message)]
+        pub fn get_fill_fee(&self, order_id: [u8; 32], fill_amount: Balance) -> Option<Balance> {
+            let order = self.orders.get(&order_id)?;
+            Some(Self::fill_fee(&order, fill_amount))
+        }
+
+        /// Smallest amount `fill_order` accepts on the order: its minimum, or what remains
+        /// when less, raised to the smallest fill owed a nonzero destination amount.
+        /// `None` for an unknown order or when no fill of what remains is owed anything.
+        #[ink(message)]
+        pub fn get_min_fill(&self, order_id: [u8; 32]) -> Option<Balance> {
+            let order = self.orders.get(&order_id)?;
+            let remaining = order.total_amount.saturating_sub(order.filled_amount);
+            let floor = quote::min_nonzero_fill(
+                self.current_rate(&order),
+                order.source_decimals,
+                order.dest_decimals,
+            )?;
+            let floor = floor.max(order.min_fill_amount.min(remaining));
+            (floor <= remaining).then_some(floor)
+        }
+
+        /// Safety deposit `fill_order` requires for `fill_amount`, after clamping the
+        /// amount to what remains on the order
+        #[ink(message)]
+        pub fn get_required_deposit(&self, order_id: [u8; 32], fill_amount: Balance) -> Balance {
+            let remaining = self.get_remaining_amount(order_id);
+            self.required_deposit(fill_amount.min(remaining))
+        }
+
+        #[ink(message)]
+        pub fn get_fee_free_window(&self) -> Option<(BlockNumber, BlockNumber)> {
+            self.fee_free_window
+        }
+
+        #[cfg(feature = "psp22")]
+        #[ink(message)]
+        pub fn get_fee_token(&self) -> Option<Address> {
+            self.fee_token
+        }
+
+        #[cfg(feature = "psp22")]
+        #[ink(message)]
+        pub fn get_psp22_protocol_fees(&self, token: Address) -> Balance {
+            self.psp22_protocol_fees.get(&token).unwrap_or(0)
+        }
+
+        // Admin functions
+        #[ink(message)]
+        pub fn update_admin(
+            &mut self,
+            new_admin: Address,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let old_admin = self.admin;
+            self.admin = new_admin;
+            self.record_admin_change(admin_fn::UPDATE_ADMIN, old_admin.encode(), new_admin.encode());
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn update_protocol_fee(
+            &mut self,
+            new_fee_bps: u16,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            if new_fee_bps > 1000 {
+                return Err(Error::InvalidFee);
+            }
+            let old_fee_bps = self.protocol_fee_bps;
+            self.protocol_fee_bps = new_fee_bps;
+            self.record_admin_change(
+                admin_fn::UPDATE_PROTOCOL_FEE,
+                old_fee_bps.encode(),
+                new_fee_bps.encode(),
+            );
+            Ok(())
+        }
+
+        /// Pay collected native protocol fees to the admin along `route` (`Direct` if
+        /// `None`). Returns `false` if the admin refused the payout: the fees stay
+        /// collected, the failure is recorded so `redirect_stuck_fees` may send them
+        /// elsewhere, and further sweeps back off for `FEE_SWEEP_BACKOFF_BLOCKS`.
+        #[ink(message)]
+        pub fn withdraw_protocol_fees(
+            &mut self,
+            route: Option<PayoutRoute>,
+            valid_until_block: BlockNumber,
+        ) -> Result<bool, Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let route = self.payout_route(route)?;
+            let current_block = self.env().block_number();
+            if let Some(failed_at) = self.fee_sweep_failed_at {
+                if current_block < failed_at.saturating_add(FEE_SWEEP_BACKOFF_BLOCKS) {
+                    return Err(Error::FeeSweepBackoff);
+                }
+            }
+
+            let fees = self.protocol_fees;
+            if fees == 0 {
+                return Err(Error::NoFeesToWithdraw);
+            }
+
+            // A refused payout is kept as state rather than reverted, so it can be
+            // told apart from a sweep that was never tried
+            if self.settle(self.admin, fees, route).is_err() {
+                self.fee_sweep_failed_at = Some(current_block);
+                self.env().emit_event(FeeSweepFailed { recipient: self.admin, amount: fees });
+                return Ok(false);
+            }
+            self.protocol_fees = 0;
+            self.fee_sweep_failed_at = None;
+
+            self.env().emit_event(ProtocolFeesWithdrawn { admin: self.admin, amount: fees, route });
+            Ok(true)
+        }
+
+        /// After a sweep whose recipient refused the fees, send them to
+        /// `new_recipient` instead. Needs no back-off: it cannot be retried against
+        /// the refusing recipient.
+        #[ink(message)]
+        pub fn redirect_stuck_fees(
+            &mut self,
+            new_recipient: Address,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let fees = self.protocol_fees;
+            if self.fee_sweep_failed_at.is_none() || fees == 0 {
+                return Err(Error::FeesNotStuck);
+            }
+
+            self.execute_transfer(new_recipient, fees)?;
+            self.protocol_fees = 0;
+            self.fee_sweep_failed_at = None;
+
+            self.env().emit_event(StuckFeesRedirected { recipient: new_recipient, amount: fees });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn register_chain(
+            &mut self,
+            chain_id: u32,
+            config: ChainConfig,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let old_config = self.chain_registry.get(&chain_id);
+            self.chain_registry.insert(&chain_id, &config);
+            self.record_admin_change(
+                admin_fn::REGISTER_CHAIN,
+                (chain_id, old_config).encode(),
+                (chain_id, Some(config)).encode(),
+            );
+            Ok(())
+        }
+
+        /// Set this deployment's chain id and toggle strict source-chain checking
+        #[ink(message)]
+        pub fn set_home_chain(
+            &mut self,
+            home_chain_id: u32,
+            enforce: bool,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let old = (self.home_chain_id, self.enforce_home_chain);
+            self.home_chain_id = home_chain_id;
+            self.enforce_home_chain = enforce;
+            self.record_admin_change(
+                admin_fn::SET_HOME_CHAIN,
+                old.encode(),
+                (home_chain_id, enforce).encode(),
+            );
+            Ok(())
+        }
+
+        /// Change the algorithm used by new orders that do not pick one; existing
+        /// orders keep the algorithm they were created with
+        #[ink(message)]
+        pub fn set_default_hash_algorithm(
+            &mut self,
+            algo: HashAlgorithm,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let old = self.default_hash_algorithm;
+            self.default_hash_algorithm = algo;
+            self.record_admin_change(admin_fn::SET_DEFAULT_HASH_ALGORITHM, old.encode(), algo.encode());
+
+            self.env().emit_event(DefaultHashAlgorithmChanged { old, new: algo });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_block_time_ms(
+            &mut self,
+            block_time_ms: u64,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            if block_time_ms == 0 {
+                return Err(Error::InvalidTimelock);
+            }
+            let old_block_time_ms = self.block_time_ms;
+            self.block_time_ms = block_time_ms;
+            self.record_admin_change(
+                admin_fn::SET_BLOCK_TIME_MS,
+                old_block_time_ms.encode(),
+                block_time_ms.encode(),
+            );
+            Ok(())
+        }
+
+        /// Propose a new `min_timelock`; it can be applied after `MIN_TIMELOCK_GOVERNANCE_DELAY` blocks
+        #[ink(message)]
+        pub fn propose_min_timelock(
+            &mut self,
+            new_min: BlockNumber,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            if new_min == 0 || new_min > self.max_timelock {
+                return Err(Error::InvalidTimelock);
+            }
+
+            let effective_at = self.env().block_number() + MIN_TIMELOCK_GOVERNANCE_DELAY;
+            let old_pending = self.pending_min_timelock;
+            self.pending_min_timelock = Some((new_min, effective_at));
+            self.record_admin_change(
+                admin_fn::PROPOSE_MIN_TIMELOCK,
+                old_pending.encode(),
+                self.pending_min_timelock.encode(),
+            );
+
+            self.env().emit_event(MinTimelockProposed { new_min, effective_at });
+            Ok(())
+        }
+
+        /// Apply the pending `min_timelock` proposal once its delay has passed (permissionless)
+        #[ink(message)]
+        pub fn apply_min_timelock(&mut self) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            let (new_min, effective_at) = self.pending_min_timelock.ok_or(Error::NoPendingProposal)?;
+            if self.env().block_number() < effective_at {
+                return Err(Error::ProposalNotReady);
+            }
+
+            let old_min = self.min_timelock;
+            self.min_timelock = new_min;
+            self.pending_min_timelock = None;
+            self.record_admin_change(admin_fn::APPLY_MIN_TIMELOCK, old_min.encode(), new_min.encode());
+
+            self.env().emit_event(MinTimelockApplied { new_min });
+            Ok(())
+        }
+
+        /// Stop taking new business ahead of a move to another deployment (admin,
+        /// irreversible). Order creation and reservations are refused; fills of
+        /// existing orders, settlement, cancels and fee withdrawal carry on.
+        #[ink(message)]
+        pub fn enter_sunset_mode(&mut self, valid_until_block: BlockNumber) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            if self.sunset != SunsetState::Live {
+                return Err(Error::SunsetActive);
+            }
+            self.sunset = SunsetState::Sunset;
+            self.record_admin_change(admin_fn::SUNSET, SunsetState::Live.encode(), self.sunset.encode());
+
+            self.env().emit_event(SunsetEntered { block: self.env().block_number() });
+            Ok(())
+        }
+
+        /// Retire a sunset deployment once it owes nothing (admin, irreversible). Waits
+        /// for escrow to reach zero as well as credits and uncollected fees, native and
+        /// PSP22 alike, since no withdrawal is possible afterwards.
+        #[ink(message)]
+        pub fn finalize_sunset(&mut self, valid_until_block: BlockNumber) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            if self.sunset != SunsetState::Sunset {
+                return Err(Error::NotInSunset);
+            }
+            let liabilities = self.get_total_liabilities();
+            if liabilities > 0 {
+                return Err(Error::LiabilitiesOutstanding { liabilities });
+            }
+            #[cfg(feature = "psp22")]
+            if self.psp22_fee_tokens_outstanding > 0 {
+                return Err(Error::FeeTokensOutstanding { tokens: self.psp22_fee_tokens_outstanding });
+            }
+            self.sunset = SunsetState::Retired;
+            self.record_admin_change(admin_fn::SUNSET, SunsetState::Sunset.encode(), self.sunset.encode());
+
+            self.env().emit_event(SunsetFinalized { block: self.env().block_number() });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_sunset_state(&self) -> SunsetState {
+            self.sunset
+        }
+
+        /// Emergency stop (admin), e.g. while a bridge fault is investigated. Order
+        /// creation, fills and reservations fail with `ContractPaused` until `unpause`;
+        /// withdrawals, refunds, cancels and credit withdrawals stay open so that no
+        /// funds are locked in by the pause. Pausing twice changes nothing, so that an
+        /// incident runbook can be repeated safely.
+        #[ink(message)]
+        pub fn pause(&mut self, valid_until_block: BlockNumber) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            if self.paused {
+                return Ok(());
+            }
+            self.paused = true;
+            self.record_admin_change(admin_fn::SET_PAUSED, false.encode(), true.encode());
+
+            let by = self.env().caller();
+            self.env().emit_event(ProtocolPaused { by, at_block: self.env().block_number() });
+            Ok(())
+        }
+
+        /// Lift an emergency stop (admin); a no-op unless paused
+        #[ink(message)]
+        pub fn unpause(&mut self, valid_until_block: BlockNumber) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            if !self.paused {
+                return Ok(());
+            }
+            self.paused = false;
+            self.record_admin_change(admin_fn::SET_PAUSED, true.encode(), false.encode());
+
+            let by = self.env().caller();
+            self.env().emit_event(ProtocolUnpaused { by, at_block: self.env().block_number() });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn is_paused(&self) -> bool {
+            self.paused
+        }
+
+        /// Record `code_hash`, already uploaded, as the next code of this contract
+        /// without switching to it. `finalize_upgrade` switches once the code's
+        /// migration check has passed or after `UPGRADE_DELAY` blocks. Staging again
+        /// replaces the staged code and restarts both.
+        #[ink(message)]
+        pub fn upgrade_staged(
+            &mut self,
+            code_hash: ink::H256,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let staged_at = self.env().block_number();
+            let ready_at = staged_at.saturating_add(UPGRADE_DELAY);
+            let old_staged = self.staged_upgrade;
+            self.staged_upgrade = Some(StagedUpgrade { code_hash, staged_at, ready_at, checked: false });
+            self.record_admin_change(
+                admin_fn::STAGE_UPGRADE,
+                old_staged.encode(),
+                self.staged_upgrade.encode(),
+            );
+
+            self.env().emit_event(UpgradeStaged { code_hash, ready_at });
+            Ok(())
+        }
+
+        /// Run the staged code's `check_migration` on this contract's storage, by
+        /// delegate call into `instance`, any contract deployed from that code. A
+        /// passing check lets `finalize_upgrade` go ahead before the delay.
+        #[ink(message)]
+        pub fn check_staged_upgrade(
+            &mut self,
+            instance: Address,
+            sample_order: Option<[u8; 32]>,
+            valid_until_block: BlockNumber,
+        ) -> Result<MigrationReport, Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let mut staged = self.staged_upgrade.ok_or(Error::NoPendingProposal)?;
+            if self.instance_code_hash(instance) != Some(staged.code_hash) {
+                return Err(Error::UpgradeCodeMismatch);
+            }
+
+            let report = self.delegate_check_migration(instance, sample_order)?;
+            staged.checked = true;
+            self.staged_upgrade = Some(staged);
+
+            self.env().emit_event(UpgradeChecked { code_hash: staged.code_hash, report: report.clone() });
+            Ok(report)
+        }
+
+        /// Drop the staged upgrade
+        #[ink(message)]
+        pub fn abort_upgrade(&mut self, valid_until_block: BlockNumber) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let staged = self.staged_upgrade.take().ok_or(Error::NoPendingProposal)?;
+            self.record_admin_change(
+                admin_fn::STAGE_UPGRADE,
+                Some(staged).encode(),
+                self.staged_upgrade.encode(),
+            );
+
+            self.env().emit_event(UpgradeAborted { code_hash: staged.code_hash });
+            Ok(())
+        }
+
+        /// Switch to the staged code once its migration check has passed or its
+        /// delay is over. The new code serves every call after this one.
+        #[ink(message)]
+        pub fn finalize_upgrade(&mut self, valid_until_block: BlockNumber) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let staged = self.staged_upgrade.ok_or(Error::NoPendingProposal)?;
+            if !staged.checked && self.env().block_number() < staged.ready_at {
+                return Err(Error::ProposalNotReady);
+            }
+
+            self.switch_code(staged.code_hash)?;
+            self.staged_upgrade = None;
+            self.record_admin_change(
+                admin_fn::FINALIZE_UPGRADE,
+                Some(staged).encode(),
+                self.staged_upgrade.encode(),
+            );
+
+            self.env().emit_event(UpgradeFinalized { code_hash: staged.code_hash, checked: staged.checked });
+            Ok(())
+        }
+
+        /// Blocks a refunded taker must wait before filling the same order again
+        #[ink(message)]
+        pub fn set_refund_cooldown(
+            &mut self,
+            blocks: BlockNumber,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let old_blocks = self.refund_cooldown_blocks;
+            self.refund_cooldown_blocks = blocks;
+            self.record_admin_change(
+                admin_fn::SET_REFUND_COOLDOWN,
+                old_blocks.encode(),
+                blocks.encode(),
+            );
+            Ok(())
+        }
+
+        /// Pending fill count above which the deployment is congested and new orders
+        /// need a longer timelock; `None` never congests. Takes effect immediately,
+        /// announcing a change of mode.
+        #[ink(message)]
+        pub fn set_congestion_soft_cap(
+            &mut self,
+            soft_cap: Option<u32>,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let old_soft_cap = self.congestion_soft_cap;
+            self.congestion_soft_cap = soft_cap;
+            self.record_admin_change(
+                admin_fn::SET_CONGESTION_SOFT_CAP,
+                old_soft_cap.encode(),
+                soft_cap.encode(),
+            );
+            let congestion_changed = self.refresh_congestion();
+            self.emit_congestion_change(congestion_changed);
+            Ok(())
+        }
+
+        /// Cap on the unfilled escrow each account may hold across its live orders
+        /// during beta, lifted `graduation_blocks` after instantiation or after the
+        /// account's first order, per `clock`. A `cap` of zero disables it. Applies to
+        /// new orders only; orders already over the cap are left as they are.
+        #[ink(message)]
+        pub fn set_beta_cap(
+            &mut self,
+            cap: Balance,
+            graduation_blocks: BlockNumber,
+            clock: GraduationClock,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let old = (self.beta_cap, self.beta_graduation_blocks, self.beta_graduation_clock);
+            self.beta_cap = cap;
+            self.beta_graduation_blocks = graduation_blocks;
+            self.beta_graduation_clock = clock;
+            self.record_admin_change(
+                admin_fn::SET_BETA_CAP,
+                old.encode(),
+                (cap, graduation_blocks, clock).encode(),
+            );
+            Ok(())
+        }
+
+        /// Cap on executor rewards per order, or `None` to leave them uncapped. Applies
+        /// to rewards paid from now on, counting those an order has already paid.
+        #[ink(message)]
+        pub fn set_reward_cap(
+            &mut self,
+            cap: Option<RewardCap>,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            if matches!(cap, Some(RewardCap::FeeShare(bps)) if bps > 10_000) {
+                return Err(Error::InvalidFee);
+            }
+            let old_cap = core::mem::replace(&mut self.reward_cap, cap);
+            self.record_admin_change(admin_fn::SET_REWARD_CAP, old_cap.encode(), cap.encode());
+            Ok(())
+        }
+
+        /// Blocks a terminal order is kept before `cleanup_order` may purge it
+        #[ink(message)]
+        pub fn set_retention_blocks(
+            &mut self,
+            blocks: BlockNumber,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let old_blocks = self.retention_blocks;
+            self.retention_blocks = blocks;
+            self.record_admin_change(
+                admin_fn::SET_RETENTION_BLOCKS,
+                old_blocks.encode(),
+                blocks.encode(),
+            );
+            Ok(())
+        }
+
+        /// Lifetime and minimum bond of new reservations; existing ones keep theirs
+        #[ink(message)]
+        pub fn set_reservation_params(
+            &mut self,
+            blocks: BlockNumber,
+            bond: Balance,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let old_params = (self.reservation_blocks, self.reservation_bond);
+            self.reservation_blocks = blocks;
+            self.reservation_bond = bond;
+            self.record_admin_change(
+                admin_fn::SET_RESERVATION_PARAMS,
+                old_params.encode(),
+                (blocks, bond).encode(),
+            );
+            Ok(())
+        }
+
+        /// Set or clear the band of plausible rates for one corridor. Bounds are in
+        /// base-unit terms scaled by `RATE_SCALE`, like `dest_amount_per_unit` of an
+        /// order without decimal metadata; rates of orders with decimals are rescaled
+        /// before the comparison. New orders outside the band are flagged `mispriced`,
+        /// or rejected while `reject_mispriced` is set. Existing orders are unaffected.
+        #[ink(message)]
+        pub fn set_price_band(
+            &mut self,
+            source_chain: u32,
+            dest_chain: u32,
+            band: Option<(Balance, Balance)>,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            if band.map_or(false, |(min_rate, max_rate)| min_rate > max_rate) {
+                return Err(Error::InvalidPriceBand);
+            }
+            let corridor = (source_chain, dest_chain);
+            let old_band = self.price_bands.get(corridor);
+            match band {
+                Some(band) => {
+                    self.price_bands.insert(corridor, &band);
+                }
+                None => self.price_bands.remove(corridor),
+            }
+            self.record_admin_change(
+                admin_fn::SET_PRICE_BAND,
+                (corridor, old_band).encode(),
+                (corridor, band).encode(),
+            );
+            Ok(())
+        }
+
+        /// Escalate out-of-band orders from a `mispriced` flag to `PriceOutsideBand`
+        #[ink(message)]
+        pub fn set_reject_mispriced(
+            &mut self,
+            reject: bool,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let old_reject = self.reject_mispriced;
+            self.reject_mispriced = reject;
+            self.record_admin_change(
+                admin_fn::SET_REJECT_MISPRICED,
+                old_reject.encode(),
+                reject.encode(),
+            );
+            Ok(())
+        }
+
+        /// Replace the resolver deposit schedule. Thresholds must be strictly
+        /// ascending and bps at most 10_000; an empty schedule disables deposits.
+        #[ink(message)]
+        pub fn set_deposit_schedule(
+            &mut self,
+            schedule: Vec<(Balance, u16)>,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            if schedule.len() > MAX_DEPOSIT_TIERS
+                || schedule.iter().any(|(_, bps)| *bps > 10_000)
+                || schedule.windows(2).any(|pair| pair[0].0 >= pair[1].0)
+            {
+                return Err(Error::InvalidDepositSchedule);
+            }
+
+            let old_schedule = core::mem::replace(&mut self.deposit_schedule, schedule);
+            self.record_admin_change(
+                admin_fn::SET_DEPOSIT_SCHEDULE,
+                old_schedule.encode(),
+                self.deposit_schedule.encode(),
+            );
+            Ok(())
+        }
+
+        /// Waive protocol fees on orders created in blocks `start..=end`
+        #[ink(message)]
+        pub fn set_fee_free_window(
+            &mut self,
+            start: BlockNumber,
+            end: BlockNumber,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            if start > end {
+                return Err(Error::InvalidTimelock);
+            }
+
+            let old_window = self.fee_free_window;
+            self.fee_free_window = Some((start, end));
+            self.fee_free_window_activated = false;
+            self.record_admin_change(
+                admin_fn::SET_FEE_FREE_WINDOW,
+                old_window.encode(),
+                self.fee_free_window.encode(),
+            );
+
+            self.env().emit_event(FeeFreeWindowSet { start, end });
+            Ok(())
+        }
+
+        /// Collect protocol fees in a PSP22 token, or in native DOT when `None`
+        #[cfg(feature = "psp22")]
+        #[ink(message)]
+        pub fn set_fee_token(
+            &mut self,
+            token: Option<Address>,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let old_token = self.fee_token;
+            self.fee_token = token;
+            self.record_admin_change(admin_fn::SET_FEE_TOKEN, old_token.encode(), token.encode());
+            Ok(())
+        }
+
+        #[cfg(feature = "psp22")]
+        #[ink(message)]
+        pub fn withdraw_psp22_fees(
+            &mut self,
+            token: Address,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+
+            let fees = self.psp22_protocol_fees.get(&token).unwrap_or(0);
+            if fees == 0 {
+                return Err(Error::NoFeesToWithdraw);
+            }
+
+            self.psp22_protocol_fees.insert(&token, &0);
+            if self.psp22_transfer(token, self.admin, fees).is_err() {
+                self.psp22_protocol_fees.insert(&token, &fees); // Restore on failure
+                return Err(Error::TransferFailed);
+            }
+            self.psp22_fee_tokens_outstanding = self.psp22_fee_tokens_outstanding.saturating_sub(1);
+
+            Ok(())
+        }
+
+        // Private helper functions
+        fn create_order(&mut self, params: CreateOrderParams) -> Result<[u8; 32], Error> {
+            let maker = self.env().caller();
+            let transferred_amount = self.get_transferred_value()?;
+            let plan = self.preflight_create(&maker, &params, transferred_amount)?;
+            let CreatePreview { order_id, fee, net_amount, fee_bps_applied, mispriced } = plan.preview;
+            // Commits the counter value the preview derived `order_id` from
+            Self::bump_counter(&mut self.order_counter, &mut self.order_counter_high)?;
+
+            let CreateOrderParams {
+                total_amount: _,
+                min_fill_amount,
+                hashlock,
+                timelock,
+                swap_id,
+                source_chain,
+                dest_chain,
+                dest_amount_per_unit,
+                allow_partial_fills,
+                max_fills,
+                sender_cross_address,
+                receiver_cross_address,
+                options,
+            } = params;
+
+            let mut order = PartialFillOrder {
+                maker,
+                total_amount: net_amount,
+                filled_amount: 0,
+                min_fill_amount,
+                hashlock,
+                timelock,
+                cancelled: false,
+                swap_id,
+                source_chain,
+                dest_chain,
+                dest_amount_per_unit,
+                fee,
+                allow_partial_fills,
+                max_fills,
+                current_fills: 0,
+                sender_cross_address,
+                receiver_cross_address,
+                vesting_schedule: options.vesting_schedule,
+                hash_algorithm: options.hash_algorithm.unwrap_or(self.default_hash_algorithm),
+                fills_paused: false,
+                fee_bps_applied,
+                source_decimals: options.source_decimals,
+                dest_decimals: options.dest_decimals,
+                rearm_count: 0,
+                completion_callback: options.completion_callback,
+                mispriced,
+                intent_hash: [0; 32],
+                on_max_fills: options.on_max_fills.unwrap_or_default(),
+                order_type: options.order_type.unwrap_or_default(),
+                creation_block: self.env().block_number(),
+            };
+            order.intent_hash = order.intent().hash();
+
+            self.orders.insert(&order_id, &order);
+            self.order_created_at.insert(&order_id, &self.env().block_number());
+            self.touch_order(order_id);
+            if let Some(config) = options.aggregate_fills {
+                self.fill_aggregates.insert(&order_id, &FillAggregate {
+                    config,
+                    since_block: self.env().block_number(),
+                    next_nonce: 0,
+                    fills_count: 0,
+                    total_amount: 0,
+                    total_dest: 0,
+                });
+            }
+            if let Some(extension_blocks) = options.anti_snipe_extension {
+                self.anti_snipe.insert(&order_id, &AntiSnipe { extension_blocks, extensions_used: 0 });
+            }
+            #[cfg(feature = "periphery")]
+            self.on_order_created(order_id, &order, plan.fingerprint, plan.group);
+            let window_activated = self.in_fee_free_window() && !self.fee_free_window_activated;
+            if window_activated {
+                self.fee_free_window_activated = true;
+            }
+            self.total_locked += net_amount;
+            self.lock_maker_escrow(maker, net_amount);
+            if !self.account_first_use.contains(maker) {
+                self.account_first_use.insert(maker, &self.env().block_number());
+            }
+            #[cfg(feature = "psp22")]
+            self.collect_fee(maker, fee)?;
+            // Overpayment goes straight back. A refused refund fails the message, which
+            // reverts the whole creation rather than leave the surplus unaccounted for.
+            let surplus = transferred_amount - net_amount;
+            if surplus > 0 {
+                self.execute_transfer(maker, surplus)?;
+            }
+
+            let created = PartialFillOrderCreated {
+                order_id,
+                maker,
+                intent_hash: order.intent_hash,
+                total_amount: net_amount,
+                min_fill_amount,
+                hashlock,
+                hash_algorithm: order.hash_algorithm,
+                timelock,
+                swap_id,
+                source_chain,
+                dest_chain,
+                dest_amount_per_unit,
+                allow_partial_fills,
+                max_fills,
+                source_decimals: options.source_decimals,
+                dest_decimals: options.dest_decimals,
+                mispriced,
+                on_max_fills: order.on_max_fills,
+            };
+            let commitment = self.order_commitment(order_id, &order).digest();
+            self.emit_creation_events(
+                created,
+                OrderCommitted { order_id, commitment },
+                window_activated.then(|| FeeFreeWindowActivated { block: self.env().block_number() }),
+            );
+
+            Ok(order_id)
+        }
+
+        /// Every creation-time check, in the order `create_order` applies them, with no
+        /// state change. `create_order` commits the returned plan; `validate_create`
+        /// reports it, so the two cannot disagree.
+        fn preflight_create(
+            &self,
+            maker: &Address,
+            params: &CreateOrderParams,
+            value: Balance,
+        ) -> Result<CreatePlan, Error> {
+            self.ensure_taking_new_business()?;
+            self.ensure_not_paused()?;
+            // An unfunded creation fails before any parameter is looked at
+            if value == 0 {
+                return Err(Error::NoValueTransferred);
+            }
+
+            self.validate_order_params(
+                params.total_amount,
+                params.min_fill_amount,
+                params.timelock,
+                params.source_chain,
+                params.dest_chain,
+                params.max_fills,
+            )?;
+            Self::validate_vesting(&params.options.vesting_schedule)?;
+            Self::validate_decimals(params.options.source_decimals, params.options.dest_decimals)?;
+            Self::validate_aggregation(&params.options.aggregate_fills)?;
+            self.validate_anti_snipe(params.options.anti_snipe_extension)?;
+            Self::validate_order_type(params.options.order_type, params.dest_amount_per_unit)?;
+            self.validate_completion_callback(&params.options.completion_callback)?;
+            let mispriced = self.is_mispriced(params);
+            if mispriced && self.reject_mispriced {
+                return Err(Error::PriceOutsideBand);
+            }
+
+            Self::check_payment(params.total_amount, value)?;
+
+            #[cfg(feature = "periphery")]
+            let fingerprint = self.admit_order(
+                maker,
+                params.total_amount,
+                &params.hashlock,
+                params.dest_chain,
+                params.dest_amount_per_unit,
+            )?;
+
+            // The full native amount is escrowed. Native fees are charged per fill, at the
+            // rate snapshot here; with a fee token the whole fee is pulled in PSP22 now.
+            let fee_bps_applied = if self.in_fee_free_window() { 0 } else { self.protocol_fee_bps };
+            let net_amount = params.total_amount;
+            let fee = match self.active_fee_token() {
+                Some(_) => Self::bps_of(net_amount, fee_bps_applied),
+                None => 0,
+            };
+            self.check_beta_cap(maker, net_amount)?;
+
+            let counter = Self::next_counter(self.order_counter, self.order_counter_high)?;
+            let order_id = self.derive_order_id(
+                maker,
+                net_amount,
+                &params.hashlock,
+                params.timelock,
+                &params.swap_id,
+                counter,
+            );
+
+            if self.orders.contains(&order_id) {
+                return Err(Error::OrderAlreadyExists);
+            }
+
+            #[cfg(feature = "periphery")]
+            let group = match params.options.group_id {
+                Some(group_id) => Some((group_id, self.group_with_room(group_id, maker)?)),
+                None => None,
+            };
+
+            Ok(CreatePlan {
+                preview: CreatePreview { order_id, fee, net_amount, fee_bps_applied, mispriced },
+                #[cfg(feature = "periphery")]
+                fingerprint,
+                #[cfg(feature = "periphery")]
+                group,
+            })
+        }
+
+        /// Value sent with the call in `Balance` units, zero allowed. Value that is not a
+        /// whole number of `value_scale` units is rejected rather than rounded away.
+        fn get_transferred_value(&self) -> Result<Balance, Error> {
+            let value = self.env().transferred_value();
+            let scale = ink::primitives::U256::from(self.value_scale);
+            if !(value % scale).is_zero() {
+                return Err(Error::ValuePrecisionLoss);
+            }
+            (value / scale).try_into().map_err(|_| Error::ConversionError)
+        }
+
+        /// The value sent with a creation must cover the order's total amount
+        fn check_payment(total_amount: Balance, received: Balance) -> Result<(), Error> {
+            if received == 0 {
+                return Err(Error::NoValueTransferred);
+            }
+            if received < total_amount {
+                return Err(Error::InsufficientFunds { required: total_amount, received });
+            }
+            Ok(())
+        }
+
+        /// Creation-time validation, composed from the reusable validators below.
+        ///
+        /// Which validators each state-changing message applies:
+        ///
+        /// | message                     | amounts | timelock_window | destination_timelock | chains | home_chain | fill_config |
+        /// |-----------------------------|---------|-----------------|----------------------|--------|------------|-------------|
+        /// | `create_partial_fill_order` |    x    |        x        |          x           |   x    |     x      |      x      |
+        ///
+        /// Amendment messages (increase/decrease/extend/renew) must add a row here
+        /// and call the same validators rather than re-implementing the checks, so
+        /// a given violation always maps to the same `Error` variant.
+        fn validate_order_params(
+            &self,
+            total_amount: Balance,
+            min_fill_amount: Balance,
+            timelock: BlockNumber,
+            source_chain: u32,
+            dest_chain: u32,
+            max_fills: u32,
+        ) -> Result<(), Error> {
+            self.validate_timelock_window(timelock)?;
+            self.validate_destination_timelock(timelock, dest_chain)?;
+            Self::validate_chains(source_chain, dest_chain)?;
+            self.validate_home_chain(source_chain)?;
+            Self::validate_amounts(total_amount, min_fill_amount)?;
+            Self::validate_fill_config(max_fills)?;
+            Ok(())
+        }
+
+        /// Timelock must lie within `[current_block + min, current_block + max_timelock]`,
+        /// with `min` from `effective_min_timelock`
+        fn validate_timelock_window(&self, timelock: BlockNumber) -> Result<(), Error> {
+            let current_block = self.env().block_number();
+            let min_timelock = self.effective_min_timelock();
+            quote::validate_timelock_window(timelock, current_block, min_timelock, self.max_timelock)
+                .map_err(Error::from)
+        }
+
+        /// `min_timelock`, raised to `CONGESTED_MIN_TIMELOCK_PCT` of itself while
+        /// congested, without exceeding `max_timelock`
+        fn effective_min_timelock(&self) -> BlockNumber {
+            if !self.congested {
+                return self.min_timelock;
+            }
+            let raised = self.min_timelock as u64 * CONGESTED_MIN_TIMELOCK_PCT / 100;
+            raised.min(self.max_timelock as u64) as BlockNumber
+        }
+
+        /// Count a new pending fill, returning the congestion change it caused
+        fn open_pending_fill(&mut self, fill_amount: Balance) -> Option<CongestionModeChanged> {
+            self.pending_fill_count += 1;
+            self.pending_fill_value += fill_amount;
+            self.refresh_congestion()
+        }
+
+        /// Stop counting `count` settled fills of `fill_amount` in total. Saturates,
+        /// since fills created before the counters existed were never counted.
+        fn close_pending_fills(&mut self, count: u32, fill_amount: Balance) -> Option<CongestionModeChanged> {
+            self.pending_fill_count = self.pending_fill_count.saturating_sub(count);
+            self.pending_fill_value = self.pending_fill_value.saturating_sub(fill_amount);
+            self.refresh_congestion()
+        }
+
+        /// Block from which `account` is no longer held to `beta_cap`; `None` under
+        /// `FirstUse` for an account that never created an order
+        fn beta_graduation_block(&self, account: &Address) -> Option<BlockNumber> {
+            let start = match self.beta_graduation_clock {
+                GraduationClock::Instantiation => self.instantiated_at,
+                GraduationClock::FirstUse => self.account_first_use.get(account)?,
+            };
+            Some(start.saturating_add(self.beta_graduation_blocks))
+        }
+
+        fn is_beta_capped(&self, account: &Address) -> bool {
+            self.beta_cap > 0
+                && self
+                    .beta_graduation_block(account)
+                    .is_none_or(|block| self.env().block_number() < block)
+        }
+
+        /// A capped maker may not take its unfilled escrow past `beta_cap`
+        fn check_beta_cap(&self, maker: &Address, amount: Balance) -> Result<(), Error> {
+            if !self.is_beta_capped(maker) {
+                return Ok(());
+            }
+            let current = self.maker_escrow.get(maker).unwrap_or(0);
+            if current.saturating_add(amount) > self.beta_cap {
+                return Err(Error::BetaCapExceeded { cap: self.beta_cap, current });
+            }
+            Ok(())
+        }
+
+        fn lock_maker_escrow(&mut self, maker: Address, amount: Balance) {
+            let escrow = self.maker_escrow.get(&maker).unwrap_or(0);
+            self.maker_escrow.insert(&maker, &(escrow + amount));
+        }
+
+        /// Saturates, since orders created before escrow was tracked were never counted
+        fn release_maker_escrow(&mut self, maker: Address, amount: Balance) {
+            let escrow = self.maker_escrow.get(&maker).unwrap_or(0).saturating_sub(amount);
+            if escrow == 0 {
+                self.maker_escrow.remove(&maker);
+            } else {
+                self.maker_escrow.insert(&maker, &escrow);
+            }
+        }
+
+        fn refresh_congestion(&mut self) -> Option<CongestionModeChanged> {
+            let soft_cap = self.congestion_soft_cap;
+            let congested = soft_cap.is_some_and(|cap| self.pending_fill_count > cap);
+            if congested == self.congested {
+                return None;
+            }
+            self.congested = congested;
+            Some(CongestionModeChanged {
+                congested,
+                pending_fill_count: self.pending_fill_count,
+                soft_cap,
+            })
+        }
+
+        /// The lock must also fit the destination chain's HTLC, when that chain is registered
+        fn validate_destination_timelock(&self, timelock: BlockNumber, dest_chain: u32) -> Result<(), Error> {
+            if let Some(config) = self.chain_registry.get(&dest_chain) {
+                let duration = timelock.saturating_sub(self.env().block_number());
+                if self.estimate_timelock_seconds(duration) > config.max_counterpart_timelock {
+                    return Err(Error::TimelockIncompatibleWithDestination);
+                }
+            }
+            Ok(())
+        }
+
+        fn validate_chains(source_chain: u32, dest_chain: u32) -> Result<(), Error> {
+            quote::validate_chains(source_chain, dest_chain).map_err(Error::from)
+        }
+
+        /// In strict mode the escrow side of an order must be this deployment's chain
+        fn validate_home_chain(&self, source_chain: u32) -> Result<(), Error> {
+            if self.enforce_home_chain && source_chain != self.home_chain_id {
+                return Err(Error::WrongSourceChain);
+            }
+            Ok(())
+        }
+
+        fn validate_amounts(total_amount: Balance, min_fill_amount: Balance) -> Result<(), Error> {
+            quote::validate_amounts(total_amount, min_fill_amount).map_err(Error::from)
+        }
+
+        fn validate_vesting(vesting_schedule: &Option<VestingSchedule>) -> Result<(), Error> {
+            if let Some(schedule) = vesting_schedule {
+                if schedule.unlock_amount_per_block == 0 {
+                    return Err(Error::InvalidFillAmount);
+                }
+            }
+            Ok(())
+        }
+
+        /// A roll-up must close after some number of fills
+        fn validate_aggregation(aggregation: &Option<FillAggregation>) -> Result<(), Error> {
+            if aggregation.is_some_and(|aggregation| aggregation.every_fills == 0) {
+                return Err(Error::InvalidAggregation);
+            }
+            Ok(())
+        }
+
+        /// An extension must be nonzero and no longer than a whole order may run
+        fn validate_anti_snipe(&self, extension: Option<BlockNumber>) -> Result<(), Error> {
+            if extension.is_some_and(|blocks| blocks == 0 || blocks > self.max_timelock) {
+                return Err(Error::InvalidTimelock);
+            }
+            Ok(())
+        }
+
+        /// A completion callback names `on_order_completed` on a contract the escrow
+        /// may call without risk to the funds it holds
+        fn validate_completion_callback(&self, callback: &Option<CompletionCallback>) -> Result<(), Error> {
+            if let Some(callback) = callback {
+                if callback.selector != ON_ORDER_COMPLETED_SELECTOR || self.is_protected_callee(callback.contract) {
+                    return Err(Error::InvalidCompletionCallback);
+                }
+            }
+            Ok(())
+        }
+
+        /// This contract and every token it holds fees in: a call into them from the
+        /// escrow could move other users' balances
+        #[cfg(feature = "psp22")]
+        fn is_protected_callee(&self, callee: Address) -> bool {
+            callee == self.env().address()
+                || self.fee_token == Some(callee)
+                || self.psp22_protocol_fees.contains(&callee)
+        }
+
+        #[cfg(not(feature = "psp22"))]
+        fn is_protected_callee(&self, callee: Address) -> bool {
+            callee == self.env().address()
+        }
+
+        /// A Dutch auction starts at the rate the order is created with and never
+        /// decays to a zero rate
+        fn validate_order_type(order_type: Option<OrderType>, dest_amount_per_unit: Balance) -> Result<(), Error> {
+            if matches!(
+                order_type,
+                Some(OrderType::DutchAuction { start_rate, end_rate })
+                    if start_rate != dest_amount_per_unit || end_rate == 0
+            ) {
+                return Err(Error::InvalidAuctionRates);
+            }
+            Ok(())
+        }
+
+        fn validate_decimals(source_decimals: Option<u8>, dest_decimals: Option<u8>) -> Result<(), Error> {
+            let too_large = |decimals: Option<u8>| decimals.is_some_and(|d| d > MAX_TOKEN_DECIMALS);
+            if too_large(source_decimals) || too_large(dest_decimals) {
+                return Err(Error::InvalidDecimals);
+            }
+            Ok(())
+        }
+
+        fn validate_fill_config(max_fills: u32) -> Result<(), Error> {
+            quote::validate_fill_config(max_fills).map_err(Error::from)
+        }
+
+        fn validate_fill_request(
+            &self,
+            order: &PartialFillOrder,
+            fill_amount: Balance,
+        ) -> Result<(), Error> {
+            if order.cancelled {
+                return Err(Error::OrderCancelled);
+            }
+
+            let current_block = self.env().block_number();
+            if current_block >= order.timelock {
+                return Err(Error::TimelockExpired);
+            }
+
+            if order.filled_amount >= order.total_amount {
+                return Err(Error::OrderCompleted);
+            }
+
+            if order.current_fills >= order.max_fills {
+                return Err(Error::MaxFillsReached);
+            }
+
+            if fill_amount == 0 {
+                return Err(Error::InvalidFillAmount);
+            }
+
+            Ok(())
+        }
+
+        /// Fills and reservations of an order with a whitelist are its takers' alone
+        fn validate_whitelisted(&self, order_id: &[u8; 32], taker: &Address) -> Result<(), Error> {
+            if !self.is_whitelisted_taker(*order_id, *taker) {
+                return Err(Error::UnauthorizedFill);
+            }
+            Ok(())
+        }
+
+        /// A taker whose fill on this order was refunded waits out the cooldown
+        fn validate_refund_cooldown(&self, order_id: &[u8; 32], taker: &Address) -> Result<(), Error> {
+            match self.refund_cooldowns.get((*order_id, *taker)) {
+                Some(until) if self.env().block_number() < until => Err(Error::RefundCooldownActive),
+                _ => Ok(()),
+            }
+        }
+
+        fn active_reservation(&self, order_id: &[u8; 32], reserver: &Address) -> Option<Reservation> {
+            self.reservations
+                .get((*order_id, *reserver))
+                .filter(|reservation| self.env().block_number() < reservation.expires_at)
+        }
+
+        /// What `taker` may fill out of `remaining_amount` once other accounts' unexpired
+        /// reservations are held back; `CapacityReserved` when they hold all of it
+        fn available_to(
+            &self,
+            order_id: &[u8; 32],
+            remaining_amount: Balance,
+            taker: &Address,
+        ) -> Result<Balance, Error> {
+            let reserved: Balance = self
+                .order_reservers
+                .get(order_id)
+                .unwrap_or_default()
+                .iter()
+                .filter(|reserver| *reserver != taker)
+                .filter_map(|reserver| self.active_reservation(order_id, reserver))
+                .map(|reservation| reservation.amount)
+                .sum();
+            match remaining_amount.saturating_sub(reserved) {
+                0 => Err(Error::CapacityReserved),
+                available => Ok(available),
+            }
+        }
+
+        /// Remove `reserver`'s reservation and pay back its bond, returning the event
+        /// for the caller to emit in its sequence. A reservation released unused
+        /// starts the reserver's cooldown on the order.
+        fn settle_reservation(
+            &mut self,
+            order_id: [u8; 32],
+            reserver: Address,
+            consumed: bool,
+        ) -> Result<ReservationReleased, Error> {
+            let reservation = self
+                .reservations
+                .take((order_id, reserver))
+                .ok_or(Error::ReservationNotFound)?;
+            if let Some(mut reservers) = self.order_reservers.get(&order_id) {
+                reservers.retain(|account| *account != reserver);
+                self.order_reservers.insert(&order_id, &reservers);
+            }
+            if !consumed {
+                let cooldown_until = self.env().block_number().saturating_add(self.reservation_blocks);
+                self.reservation_cooldowns.insert((order_id, reserver), &cooldown_until);
+            }
+            self.bump_state_version();
+            self.total_locked -= reservation.bond;
+            self.execute_transfer(reserver, reservation.bond)?;
+
+            Ok(ReservationReleased {
+                order_id,
+                reserver,
+                amount: reservation.amount,
+                bond: reservation.bond,
+                consumed,
+            })
+        }
+
+        fn validate_fill_withdrawal(
+            &self,
+            fill: &FillExecution,
+            order: &PartialFillOrder,
+            caller: &Address,
+        ) -> Result<(), Error> {
+            if *caller != fill.taker {
+                return Err(Error::UnauthorizedWithdraw);
+            }
+
+            if fill.withdrawn || fill.refunded {
+                return Err(Error::AlreadyProcessed);
+            }
+
+            let current_block = self.env().block_number();
+            if current_block >= order.timelock {
+                return Err(Error::TimelockExpired);
+            }
+
+            Ok(())
+        }
+
+        /// `caller` is the account that must be the maker, or `None` for a public refund
+        fn validate_fill_refund(
+            &self,
+            fill: &FillExecution,
+            order: &PartialFillOrder,
+            caller: Option<&Address>,
+        ) -> Result<(), Error> {
+            if caller.is_some_and(|caller| *caller != order.maker) {
+                return Err(Error::UnauthorizedRefund);
+            }
+
+            if fill.withdrawn || fill.refunded {
+                return Err(Error::AlreadyProcessed);
+            }
+
+            let current_block = self.env().block_number();
+            if current_block < order.timelock {
+                return Err(Error::TimelockNotExpired);
+            }
+
+            Ok(())
+        }
+
+        fn validate_preimage(
+            &self,
+            order: &PartialFillOrder,
+            preimage: &[u8; 32],
+        ) -> Result<(), Error> {
+            let hash = Self::hash_preimage(order.hash_algorithm, preimage);
+            if hash != order.hashlock {
+                return Err(Error::InvalidHashlock);
+            }
+            Ok(())
+        }
+
+        /// Register the secret for a hashlock and append it to the reveal log.
+        /// Only the first reveal of a hashlock is logged; later fills sharing
+        /// the same hashlock reveal nothing new.
+        fn record_reveal(&mut self, hashlock: [u8; 32], secret: [u8; 32]) {
+            if self.secret_registry.contains(&hashlock) {
+                return;
+            }
+
+            self.secret_registry.insert(&hashlock, &secret);
+            #[cfg(feature = "periphery")]
+            self.log_reveal(hashlock, secret);
+        }
+
+        /// Deposit for a fill of `fill_amount`: the bps of the highest tier whose
+        /// threshold the amount reaches, nothing below the first tier
+        fn required_deposit(&self, fill_amount: Balance) -> Balance {
+            let deposit_bps = self
+                .deposit_schedule
+                .iter()
+                .take_while(|(threshold, _)| fill_amount >= *threshold)
+                .last()
+                .map_or(0, |(_, bps)| *bps);
+            Self::bps_of(fill_amount, deposit_bps)
+        }
+
+        fn in_fee_free_window(&self) -> bool {
+            let current_block = self.env().block_number();
+            self.fee_free_window
+                .map_or(false, |(start, end)| start <= current_block && current_block <= end)
+        }
+
+        /// `amount * bps / 10_000` rounded down, without the intermediate product
+        /// overflowing; exactly zero at zero bps
+        fn bps_of(amount: Balance, bps: u16) -> Balance {
+            quote::bps_of(amount, bps)
+        }
+
+        /// Protocol fee owed by a fill of `fill_amount`, at the order's
+        /// `fee_bps_applied`. Orders whose fee was paid at creation, in a fee token or
+        /// before fees moved to fills, owe none.
+        fn fill_fee(order: &PartialFillOrder, fill_amount: Balance) -> Balance {
+            if order.fee > 0 {
+                return 0;
+            }
+            Self::bps_of(fill_amount, order.fee_bps_applied)
+        }
+
+        /// Protocol fee of the order filled in full, whenever it is charged
+        fn order_fee(order: &PartialFillOrder) -> Balance {
+            order.fee.max(Self::bps_of(order.total_amount, order.fee_bps_applied))
+        }
+
+        fn reward_allowance(&self, order: &PartialFillOrder) -> Option<Balance> {
+            self.reward_cap.map(|cap| match cap {
+                RewardCap::FeeShare(bps) => Self::bps_of(Self::order_fee(order), bps),
+                RewardCap::Flat(amount) => amount,
+            })
+        }
+
+        /// Grant up to `reward` to an executor of the order's public actions, as far
+        /// as its budget reaches, and record it as paid. Once the budget is spent the
+        /// action still runs, paying nothing.
+        fn grant_reward(&mut self, order_id: [u8; 32], order: &PartialFillOrder, reward: Balance) -> Balance {
+            let paid = self.rewards_paid.get(&order_id).unwrap_or(0);
+            let granted = match self.reward_allowance(order) {
+                Some(allowance) => reward.min(allowance.saturating_sub(paid)),
+                None => reward,
+            };
+            if granted > 0 {
+                self.rewards_paid.insert(&order_id, &(paid + granted));
+            }
+            granted
+        }
+
+        fn unlocked_amount(&self, order: &PartialFillOrder) -> Balance {
+            match &order.vesting_schedule {
+                None => order.total_amount,
+                Some(schedule) => {
+                    let elapsed = self
+                        .env()
+                        .block_number()
+                        .saturating_sub(schedule.unlock_start_block);
+                    (elapsed as Balance)
+                        .saturating_mul(schedule.unlock_amount_per_block)
+                        .min(order.total_amount)
+                }
+            }
+        }
+
+        /// Destination base units owed for `fill_amount` source base units at `rate`.
+        ///
+        /// Without decimal metadata the rate converts base units directly. With it, the
+        /// rate prices one whole source token in whole destination tokens and the result
+        /// is rescaled by `10^(dest_decimals - source_decimals)`.
+        fn compute_dest_amount(order: &PartialFillOrder, rate: Balance, fill_amount: Balance) -> Balance {
+            quote::dest_amount(fill_amount, rate, order.source_decimals, order.dest_decimals)
+        }
+
+        /// Rate a fill of the order landing in this block is priced at
+        fn current_rate(&self, order: &PartialFillOrder) -> Balance {
+            match order.order_type {
+                OrderType::FixedPrice => order.dest_amount_per_unit,
+                OrderType::DutchAuction { start_rate, end_rate } => quote::dutch_auction_rate(
+                    start_rate,
+                    end_rate,
+                    order.creation_block,
+                    order.timelock,
+                    self.env().block_number(),
+                ),
+            }
+        }
+
+        /// Rate an existing fill of the order was priced at
+        fn fill_rate(&self, fill_id: &[u8; 32], order: &PartialFillOrder) -> Balance {
+            self.fill_rates.get(fill_id).unwrap_or(order.dest_amount_per_unit)
+        }
+
+        /// Destination amount owed for everything filled on the order; a Dutch
+        /// auction's fills each at their own rate
+        fn filled_dest_amount(&self, order_id: &[u8; 32], order: &PartialFillOrder) -> Balance {
+            if order.order_type == OrderType::FixedPrice {
+                return Self::compute_dest_amount(order, order.dest_amount_per_unit, order.filled_amount);
+            }
+            self.order_fills
+                .get(order_id)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|fill_id| Some((fill_id, self.fills.get(fill_id)?)))
+                .filter(|(_, fill)| !fill.has(StoredFill::REFUNDED))
+                .map(|(fill_id, fill)| {
+                    Self::compute_dest_amount(order, self.fill_rate(fill_id, order), fill.fill_amount)
+                })
+                .sum()
+        }
+
+        /// Rescales a whole-token amount or rate by `10^(dest_decimals - source_decimals)`;
+        /// unchanged without decimal metadata
+        fn rescale_to_base_units(
+            amount: Balance,
+            source_decimals: Option<u8>,
+            dest_decimals: Option<u8>,
+        ) -> Balance {
+            quote::rescale_to_base_units(amount, source_decimals, dest_decimals)
+        }
+
+        /// Whether the rate, or either end of an auction's rates, lies outside its
+        /// corridor's price band, if one is set
+        fn is_mispriced(&self, params: &CreateOrderParams) -> bool {
+            let corridor = (params.source_chain, params.dest_chain);
+            let Some((min_rate, max_rate)) = self.price_bands.get(corridor) else {
+                return false;
+            };
+            let out_of_band = |rate| {
+                let rate = Self::rescale_to_base_units(
+                    rate,
+                    params.options.source_decimals,
+                    params.options.dest_decimals,
+                );
+                rate < min_rate || rate > max_rate
+            };
+            out_of_band(params.dest_amount_per_unit)
+                || matches!(
+                    params.options.order_type,
+                    Some(OrderType::DutchAuction { end_rate, .. }) if out_of_band(end_rate)
+                )
+        }
+
+        fn snapshot_order(&self, order_id: [u8; 32]) -> Option<OrderSnapshot> {
+            let order = self.orders.get(&order_id)?;
+            let fills: Vec<FillExecution> = self
+                .order_fills
+                .get(&order_id)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|fill_id| self.load_fill(fill_id))
+                .collect();
+
+            let mut accounting = OrderAccounting {
+                remaining: self.get_remaining_amount(order_id),
+                reserved: self.get_reserved_amount(order_id),
+                ..Default::default()
+            };
+            for fill in fills.iter() {
+                if fill.withdrawn {
+                    accounting.withdrawn += fill.fill_amount;
+                } else if fill.refunded {
+                    accounting.refunded += fill.fill_amount;
+                } else {
+                    accounting.pending += fill.fill_amount;
+                    accounting.deposits_held += fill.deposit;
+                }
+            }
+
+            Some(OrderSnapshot { status: self.compute_order_status(&order), order, fills, accounting })
+        }
+
+        fn compute_order_status(&self, order: &PartialFillOrder) -> OrderStatus {
+            if order.cancelled {
+                OrderStatus::Cancelled
+            } else if order.filled_amount >= order.total_amount {
+                OrderStatus::FullyFilled
+            } else if self.env().block_number() >= order.timelock {
+                OrderStatus::Expired
+            } else if order.fills_paused {
+                OrderStatus::FillsPaused
+            } else if order.filled_amount > 0 {
+                OrderStatus::PartiallyFilled
+            } else {
+                OrderStatus::Active
+            }
+        }
+
+        /// Every state-changing message but creation and reservation starts here
+        fn ensure_not_retired(&self) -> Result<(), Error> {
+            if self.sunset == SunsetState::Retired {
+                return Err(Error::ContractRetired);
+            }
+            Ok(())
+        }
+
+        /// Creation, fills and reservations; everything that settles or returns funds
+        /// skips this check
+        fn ensure_not_paused(&self) -> Result<(), Error> {
+            if self.paused {
+                return Err(Error::ContractPaused);
+            }
+            Ok(())
+        }
+
+        /// New orders and reservations are taken only while live
+        fn ensure_taking_new_business(&self) -> Result<(), Error> {
+            match self.sunset {
+                SunsetState::Live => Ok(()),
+                SunsetState::Sunset => Err(Error::SunsetActive),
+                SunsetState::Retired => Err(Error::ContractRetired),
+            }
+        }
+
+        fn get_order_or_error(&self, order_id: &[u8; 32]) -> Result<PartialFillOrder, Error> {
+            self.orders.get(order_id).ok_or(Error::OrderNotFound)
+        }
+
+        /// Retention policy of `cleanup_order`, one guard per error:
+        ///
+        /// 1. `OrderNotTerminal`: the order is live or a fill is still pending
+        /// 2. `RetainedRecordsMissing`: a withdrawn fill's secret is not in the
+        ///    secret registry, which must outlive the fill's preimage
+        /// 3. `RetentionPeriodActive`: the retention block has not been reached
+        fn check_cleanable(&self, order_id: [u8; 32], order: &PartialFillOrder) -> Result<(), Error> {
+            let fills = self.stored_order_fills(order_id);
+            let retention_block = self.retention_block(order_id, order, &fills)?;
+
+            let withdrawn = fills.iter().any(|fill| fill.has(StoredFill::WITHDRAWN));
+            if withdrawn && !self.secret_registry.contains(&order.hashlock) {
+                return Err(Error::RetainedRecordsMissing);
+            }
+
+            if self.env().block_number() < retention_block {
+                return Err(Error::RetentionPeriodActive);
+            }
+            Ok(())
+        }
+
+        /// Block the order turned terminal plus `retention_blocks`. An order is terminal
+        /// once cancelled, fully filled or expired with none of its fills pending; it
+        /// turned terminal at its last settlement, or at its timelock when it expired.
+        fn retention_block(
+            &self,
+            order_id: [u8; 32],
+            order: &PartialFillOrder,
+            fills: &[StoredFill],
+        ) -> Result<BlockNumber, Error> {
+            if fills.iter().any(StoredFill::is_pending) {
+                return Err(Error::OrderNotTerminal);
+            }
+
+            let settled_at = self.order_settled_at.get(&order_id).unwrap_or(0);
+            let terminal_block = if order.cancelled || order.filled_amount >= order.total_amount {
+                settled_at
+            } else if self.env().block_number() >= order.timelock {
+                settled_at.max(order.timelock)
+            } else {
+                return Err(Error::OrderNotTerminal);
+            };
+            Ok(terminal_block.saturating_add(self.retention_blocks))
+        }
+
+        fn stored_order_fills(&self, order_id: [u8; 32]) -> Vec<StoredFill> {
+            self.order_fills
+                .get(&order_id)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|fill_id| self.fills.get(fill_id))
+                .collect()
+        }
+
+        fn get_stored_fill_or_error(&self, fill_id: &[u8; 32]) -> Result<StoredFill, Error> {
+            self.fills.get(fill_id).ok_or(Error::FillNotFound)
+        }
+
+        /// A stored fill in its external `FillExecution` shape
+        fn load_fill(&self, fill_id: &[u8; 32]) -> Option<FillExecution> {
+            self.fills.get(fill_id).map(|stored| self.unpack_fill(fill_id, &stored))
+        }
+
+        fn unpack_fill(&self, fill_id: &[u8; 32], stored: &StoredFill) -> FillExecution {
+            let withdrawn = stored.has(StoredFill::WITHDRAWN);
+            FillExecution {
+                order_id: stored.order_id,
+                taker: stored.taker,
+                fill_amount: stored.fill_amount,
+                contract_id: if stored.has(StoredFill::LEGACY_ID) {
+                    self.legacy_contract_ids.get(fill_id).unwrap_or_default()
+                } else {
+                    self.derive_contract_id(&stored.order_id, fill_id, stored.timestamp, stored.sequence)
+                },
+                withdrawn,
+                refunded: stored.has(StoredFill::REFUNDED),
+                preimage: if withdrawn { self.fill_preimages.get(fill_id) } else { None },
+                timestamp: stored.timestamp,
+                deposit: stored.deposit,
+                requested_amount: stored.requested_amount,
+                fee: self.fill_fees.get(fill_id).unwrap_or(0),
+                receiver: self.fill_receivers.get(fill_id).unwrap_or(stored.taker),
+            }
+        }
+
+        /// Write `fill` in its stored form; `sequence` is the fill counter value its
+        /// `contract_id` was derived from
+        fn store_fill(&mut self, fill_id: &[u8; 32], fill: &FillExecution, sequence: u64) {
+            self.write_fill(fill_id, fill, StoredFill::pack(fill, sequence));
+        }
+
+        /// Write back an updated `fill` read from `stored`, keeping where its
+        /// `contract_id` comes from
+        fn restore_fill(&mut self, fill_id: &[u8; 32], fill: &FillExecution, stored: &StoredFill) {
+            let mut packed = StoredFill::pack(fill, stored.sequence);
+            packed.flags |= stored.flags & StoredFill::LEGACY_ID;
+            self.write_fill(fill_id, fill, packed);
+        }
+
+        fn write_fill(&mut self, fill_id: &[u8; 32], fill: &FillExecution, packed: StoredFill) {
+            self.bump_state_version();
+            self.fills.insert(fill_id, &packed);
+            if let Some(preimage) = fill.preimage {
+                self.fill_preimages.insert(fill_id, &preimage);
+            }
+        }
+
+        fn credit(&mut self, account: Address, amount: Balance) {
+            let balance = self.credits.get(&account).unwrap_or(0);
+            self.credits.insert(&account, &(balance + amount));
+            self.total_credits += amount;
+            self.bump_state_version();
+            #[cfg(feature = "periphery")]
+            self.id_list_insert(IdList::Creditors, Self::account_key(&account));
+        }
+
+        fn debit_credit(&mut self, account: Address, amount: Balance) -> Result<(), Error> {
+            let balance = self.credits.get(&account).unwrap_or(0);
+            if amount == 0 || amount > balance {
+                return Err(Error::InsufficientCredit);
+            }
+            self.credits.insert(&account, &(balance - amount));
+            self.total_credits -= amount;
+            self.bump_state_version();
+            #[cfg(feature = "periphery")]
+            if balance == amount {
+                self.id_list_remove(IdList::Creditors, Self::account_key(&account));
+            }
+            Ok(())
+        }
+
+        /// Spend credit in place of a fresh transfer: the part of a fill deposit the
+        /// value sent falls short of moves from the taker's credit into escrow
+        fn use_credit_for_fill(&mut self, taker: Address, amount: Balance) -> Result<(), Error> {
+            self.debit_credit(taker, amount)?;
+            self.total_locked += amount;
+            Ok(())
+        }
+
+        /// Record that a message acted on the order in this block
+        fn touch_order(&mut self, order_id: [u8; 32]) {
+            self.order_last_activity.insert(&order_id, &self.env().block_number());
+            self.bump_state_version();
+        }
+
+        /// Add a fill to the order's pending roll-up, when the order aggregates, and
+        /// close the roll-up if the fill crosses one of its boundaries
+        fn aggregate_fill(
+            &mut self,
+            order_id: [u8; 32],
+            order: &PartialFillOrder,
+            fill_amount: Balance,
+            dest_amount: Balance,
+        ) -> Option<FillsAggregated> {
+            let mut aggregate = self.fill_aggregates.get(&order_id)?;
+            aggregate.fills_count += 1;
+            aggregate.total_amount += fill_amount;
+            aggregate.total_dest += dest_amount;
+            self.fill_aggregates.insert(&order_id, &aggregate);
+
+            let closed = order.filled_amount >= order.total_amount || order.current_fills >= order.max_fills;
+            let due = aggregate.fills_count >= aggregate.config.every_fills
+                || self.env().block_number() >= aggregate.since_block.saturating_add(aggregate.config.every_blocks);
+            if closed || due {
+                self.take_fill_aggregate(order_id)
+            } else {
+                None
+            }
+        }
+
+        /// Close the order's pending roll-up, returning its `FillsAggregated` for the
+        /// caller to emit in its sequence; `None` when no fill is pending
+        fn take_fill_aggregate(&mut self, order_id: [u8; 32]) -> Option<FillsAggregated> {
+            let mut aggregate = self.fill_aggregates.get(&order_id)?;
+            if aggregate.fills_count == 0 {
+                return None;
+            }
+            let event = FillsAggregated {
+                order_id,
+                fills_count: aggregate.fills_count,
+                total_amount: aggregate.total_amount,
+                total_dest: aggregate.total_dest,
+                from_nonce: aggregate.next_nonce,
+                to_nonce: aggregate.next_nonce + aggregate.fills_count - 1,
+            };
+            aggregate.since_block = self.env().block_number();
+            aggregate.next_nonce += aggregate.fills_count;
+            aggregate.fills_count = 0;
+            aggregate.total_amount = 0;
+            aggregate.total_dest = 0;
+            self.fill_aggregates.insert(&order_id, &aggregate);
+            Some(event)
+        }
+
+        /// No message has acted on the order for at least `threshold_blocks`
+        fn idle_for(&self, order_id: [u8; 32], threshold_blocks: BlockNumber) -> bool {
+            let last_activity = self.order_last_activity.get(&order_id).unwrap_or(0);
+            self.env().block_number().saturating_sub(last_activity) >= threshold_blocks
+        }
+
+        fn order_commitment(&self, order_id: [u8; 32], order: &PartialFillOrder) -> OrderCommitment {
+            OrderCommitment {
+                chain_id: self.home_chain_id,
+                contract: self.env().address(),
+                order_id,
+                maker: order.maker,
+                total_amount: order.total_amount,
+                min_fill_amount: order.min_fill_amount,
+                hashlock: order.hashlock,
+                hash_algorithm: order.hash_algorithm,
+                timelock: order.timelock,
+                source_chain: order.source_chain,
+                dest_chain: order.dest_chain,
+                dest_amount_per_unit: order.dest_amount_per_unit,
+                order_type: order.order_type,
+                sender: order.sender_cross_address.clone(),
+                receiver: order.receiver_cross_address.clone(),
+            }
+        }
+
+        /// Recompute the intent hash after a change to a field `OrderIntent` binds,
+        /// returning the announcement if it moved. Callers persist the order afterwards
+        /// and emit the announcement after their own event.
+        fn refresh_intent_hash(
+            &self,
+            order_id: [u8; 32],
+            order: &mut PartialFillOrder,
+        ) -> Option<OrderIntentChanged> {
+            let intent_hash = order.intent().hash();
+            if intent_hash == order.intent_hash {
+                return None;
+            }
+            order.intent_hash = intent_hash;
+            let commitment = self.order_commitment(order_id, order).digest();
+            Some(OrderIntentChanged { order_id, intent_hash, commitment })
+        }
+
+        fn emit_intent_change(&self, intent_changed: Option<OrderIntentChanged>) {
+            if let Some(event) = intent_changed {
+                self.env().emit_event(event);
+            }
+        }
+
+        fn emit_creation_events(
+            &self,
+            created: PartialFillOrderCreated,
+            committed: OrderCommitted,
+            window_activated: Option<FeeFreeWindowActivated>,
+        ) {
+            self.env().emit_event(created);
+            self.env().emit_event(committed);
+            if let Some(event) = window_activated {
+                self.env().emit_event(event);
+            }
+        }
+
+        fn emit_fill_events(&self, events: FillEvents) {
+            if let Some(event) = events.filled {
+                self.env().emit_event(event);
+            }
+            if let Some(event) = events.aggregated {
+                self.env().emit_event(event);
+            }
+            if let Some(event) = events.clamped {
+                self.env().emit_event(event);
+            }
+            if let Some(event) = events.extended {
+                self.env().emit_event(event);
+            }
+            self.emit_intent_change(events.intent_changed);
+            if let Some(event) = events.reservation_released {
+                self.env().emit_event(event);
+            }
+            if let Some(event) = events.remainder_returned {
+                self.env().emit_event(event);
+            }
+            self.emit_quarantine(events.remainder_quarantined);
+            if let Some(event) = events.callback_failed {
+                self.env().emit_event(event);
+            }
+            self.emit_congestion_change(events.congestion_changed);
+        }
+
+        fn emit_order_fills_withdrawn(
+            &self,
+            fills: Vec<FillWithdrawn>,
+            summary: OrderFillsWithdrawn,
+            quarantined: Option<TransferQuarantined>,
+            congestion_changed: Option<CongestionModeChanged>,
+        ) {
+            for event in fills {
+                self.env().emit_event(event);
+            }
+            self.env().emit_event(summary);
+            self.emit_settlement_effects(quarantined, congestion_changed);
+        }
+
+        /// What follows the event of a settled fill: its quarantined payout, then
+        /// the congestion change it caused
+        fn emit_settlement_effects(
+            &self,
+            quarantined: Option<TransferQuarantined>,
+            congestion_changed: Option<CongestionModeChanged>,
+        ) {
+            self.emit_quarantine(quarantined);
+            self.emit_congestion_change(congestion_changed);
+        }
+
+        /// What follows the event of a closed order: the roll-up of its last
+        /// aggregated fills, then its quarantined payout
+        fn emit_closing_events(
+            &self,
+            aggregated: Option<FillsAggregated>,
+            quarantined: Option<TransferQuarantined>,
+        ) {
+            if let Some(event) = aggregated {
+                self.env().emit_event(event);
+            }
+            self.emit_quarantine(quarantined);
+        }
+
+        fn emit_quarantine(&self, quarantined: Option<TransferQuarantined>) {
+            if let Some(event) = quarantined {
+                self.env().emit_event(event);
+            }
+        }
+
+        fn emit_congestion_change(&self, congestion_changed: Option<CongestionModeChanged>) {
+            if let Some(event) = congestion_changed {
+                self.env().emit_event(event);
+            }
+        }
+
+        /// Applies `AutoRefundRemainder` to an order a fill just brought to its cap:
+        /// the order shrinks to what was filled and the freed remainder is returned
+        /// for the caller to pay out once the order is stored, with the intent change
+        /// Push out the timelock of an anti-snipe order for a fill landing within its
+        /// extension of it, while extensions are left. Callers refresh the intent hash.
+        fn extend_for_late_fill(
+            &mut self,
+            order_id: [u8; 32],
+            fill_id: [u8; 32],
+            order: &mut PartialFillOrder,
+        ) -> Option<TimelockAutoExtended> {
+            let mut anti_snipe = self.anti_snipe.get(&order_id)?;
+            let blocks_left = order.timelock.saturating_sub(self.env().block_number());
+            if blocks_left > anti_snipe.extension_blocks || anti_snipe.extensions_left() == 0 {
+                return None;
+            }
+            let old_timelock = order.timelock;
+            order.timelock = old_timelock.saturating_add(anti_snipe.extension_blocks);
+            anti_snipe.extensions_used += 1;
+            self.anti_snipe.insert(&order_id, &anti_snipe);
+            Some(TimelockAutoExtended {
+                order_id,
+                fill_id,
+                old_timelock,
+                new_timelock: order.timelock,
+                extensions_used: anti_snipe.extensions_used,
+            })
+        }
+
+        fn close_at_max_fills(
+            &self,
+            order_id: [u8; 32],
+            order: &mut PartialFillOrder,
+        ) -> (Balance, Option<OrderIntentChanged>) {
+            if order.on_max_fills != MaxFillsPolicy::AutoRefundRemainder
+                || order.current_fills < order.max_fills
+                || order.filled_amount >= order.total_amount
+            {
+                return (0, None);
+            }
+            let remainder = order.total_amount - order.filled_amount;
+            order.total_amount = order.filled_amount;
+            (remainder, self.refresh_intent_hash(order_id, order))
+        }
+
+        /// Return escrowed funds to a maker; the only place maker refunds leave
+        /// `total_locked`. Callers persist order state first.
+        fn release_to_maker(
+            &mut self,
+            maker: Address,
+            amount: Balance,
+            route: PayoutRoute,
+            context: TransferContext,
+        ) -> Result<Option<TransferQuarantined>, Error> {
+            if amount == 0 {
+                return Ok(None);
+            }
+            self.total_locked -= amount;
+            self.settle_or_quarantine(maker, amount, route, context)
+        }
+
+        /// `settle`, except that a `Direct` transfer the recipient refuses is booked
+        /// to its credit, returning the event announcing it. Maker refunds and taker
+        /// payouts go through here, so that a recipient that reverts on receive
+        /// cannot leave funds in escrow for good.
+        fn settle_or_quarantine(
+            &mut self,
+            to: Address,
+            amount: Balance,
+            route: PayoutRoute,
+            context: TransferContext,
+        ) -> Result<Option<TransferQuarantined>, Error> {
+            if route != PayoutRoute::Direct {
+                return self.settle(to, amount, route).map(|()| None);
+            }
+            if self.execute_transfer(to, amount).is_ok() {
+                return Ok(None);
+            }
+            self.credit(to, amount);
+            Ok(Some(TransferQuarantined { to, amount, context }))
+        }
+
+        /// `route`, or `Direct` when none was given, if this deployment supports it
+        fn payout_route(&self, route: Option<PayoutRoute>) -> Result<PayoutRoute, Error> {
+            let route = route.unwrap_or_default();
+            if !self.is_payout_route_available(route) {
+                return Err(Error::PayoutRouteUnavailable);
+            }
+            Ok(route)
+        }
+
+        /// Pay `amount` to `to` along `route`. Every payout a caller can route goes
+        /// through here; messages check the route with `payout_route` before
+        /// changing any state.
+        fn settle(&mut self, to: Address, amount: Balance, route: PayoutRoute) -> Result<(), Error> {
+            match route {
+                PayoutRoute::Direct => self.execute_transfer(to, amount),
+                PayoutRoute::Credit => {
+                    self.credit(to, amount);
+                    Ok(())
+                }
+                #[cfg(feature = "xcm")]
+                PayoutRoute::Xcm { para_id, beneficiary } => {
+                    self.xcm_transfer(para_id, beneficiary, amount)
+                }
+                #[cfg(not(feature = "xcm"))]
+                PayoutRoute::Xcm { .. } => Err(Error::PayoutRouteUnavailable),
+            }
+        }
+
+        /// Reserve-transfer `amount` of the native asset to `beneficiary` on sibling
+        /// parachain `para_id`, which buys its execution out of the transferred asset
+        #[cfg(all(feature = "xcm", not(test)))]
+        fn xcm_transfer(
+            &self,
+            para_id: u32,
+            beneficiary: [u8; 32],
+            amount: Balance,
+        ) -> Result<(), Error> {
+            use ink::xcm::prelude::*;
+
+            let value = amount.checked_mul(self.value_scale).ok_or(Error::ConversionError)?;
+            let on_destination: Xcm<()> = Xcm::builder_unsafe()
+                .buy_execution((Parent, value), Unlimited)
+                .deposit_asset(
+                    AllCounted(1),
+                    Location::new(0, [AccountId32 { network: None, id: beneficiary }]),
+                )
+                .build();
+            let message: Xcm<()> = Xcm::builder_unsafe()
+                .transfer_reserve_asset(
+                    (Here, value),
+                    Location::new(1, [Parachain(para_id)]),
+                    on_destination,
+                )
+                .build();
+
+            let message = VersionedXcm::V5(message);
+            let weight = self.env().xcm_weigh(&message).map_err(|_| Error::TransferFailed)?;
+            self.env()
+                .xcm_execute(&message, weight)
+                .map_err(|_| Error::TransferFailed)
+        }
+
+        #[cfg(all(feature = "xcm", test))]
+        fn xcm_transfer(
+            &self,
+            para_id: u32,
+            beneficiary: [u8; 32],
+            amount: Balance,
+        ) -> Result<(), Error> {
+            xcm_mock::send(para_id, beneficiary, amount);
+            Ok(())
+        }
+
+        /// Pays `amount` `Balance` units, i.e. `amount * value_scale` native value
+        fn execute_transfer(&self, to: Address, amount: Balance) -> Result<(), Error> {
+            let amount_u256: ink::primitives::U256 = amount.into();
+            self.env().transfer(to, amount_u256 * ink::primitives::U256::from(self.value_scale))
+                .map_err(|_| Error::TransferFailed)
+        }
+
+        /// Token protocol fees are collected in; `None` for native DOT
+        #[cfg(feature = "psp22")]
+        fn active_fee_token(&self) -> Option<Address> {
+            self.fee_token
+        }
+
+        #[cfg(not(feature = "psp22"))]
+        fn active_fee_token(&self) -> Option<Address> {
+            None
+        }
+
+        /// Book a creation fee, only charged with a fee token set: pulled from `maker`
+        /// in the token. Native fees are charged per fill; see `fill_fee`.
+        #[cfg(feature = "psp22")]
+        fn collect_fee(&mut self, maker: Address, fee: Balance) -> Result<(), Error> {
+            if let Some(token) = self.fee_token {
+                if fee > 0 {
+                    let contract = self.env().address();
+                    self.psp22_transfer_from(token, maker, contract, fee)?;
+                    let collected = self.psp22_protocol_fees.get(&token).unwrap_or(0);
+                    if collected == 0 {
+                        self.psp22_fee_tokens_outstanding += 1;
+                    }
+                    self.psp22_protocol_fees.insert(&token, &(collected + fee));
+                }
+            }
+            Ok(())
+        }
+
+        #[cfg(all(feature = "psp22", not(test)))]
+        fn psp22_transfer_from(
+            &self,
+            token: Address,
+            from: Address,
+            to: Address,
+            value: Balance,
+        ) -> Result<(), Error> {
+            use ink::env::call::{build_call, ExecutionInput, Selector};
+
+            build_call::<Environment>()
+                .call(token)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("PSP22::transfer_from")))
+                        .push_arg(from)
+                        .push_arg(to)
+                        .push_arg(value)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<Result<(), Psp22Error>>()
+                .try_invoke()
+                .map_err(|_| Error::TransferFailed)?
+                .map_err(|_| Error::TransferFailed)?
+                .map_err(|_| Error::TransferFailed)
+        }
+
+        #[cfg(all(feature = "psp22", not(test)))]
+        fn psp22_transfer(&self, token: Address, to: Address, value: Balance) -> Result<(), Error> {
+            use ink::env::call::{build_call, ExecutionInput, Selector};
+
+            build_call::<Environment>()
+                .call(token)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("PSP22::transfer")))
+                        .push_arg(to)
+                        .push_arg(value)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<Result<(), Psp22Error>>()
+                .try_invoke()
+                .map_err(|_| Error::TransferFailed)?
+                .map_err(|_| Error::TransferFailed)?
+                .map_err(|_| Error::TransferFailed)
+        }
+
+        fn instance_code_hash(&self, instance: Address) -> Option<ink::H256> {
+            self.env().code_hash(&instance).ok()
+        }
+
+        /// `check_migration` of `instance`'s code, run on this contract's storage
+        fn delegate_check_migration(
+            &self,
+            instance: Address,
+            sample_order: Option<[u8; 32]>,
+        ) -> Result<MigrationReport, Error> {
+            use ink::env::call::{build_call, ExecutionInput, Selector};
+
+            build_call::<Environment>()
+                .delegate(instance)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("check_migration")))
+                        .push_arg(sample_order),
+                )
+                .returns::<Result<MigrationReport, Error>>()
+                .try_invoke()
+                .map_err(|_| Error::MigrationCheckFailed)?
+                .map_err(|_| Error::MigrationCheckFailed)?
+        }
+
+        fn switch_code(&self, code_hash: ink::H256) -> Result<(), Error> {
+            self.env().set_code_hash(&code_hash).map_err(|_| Error::UpgradeFailed)
+        }
+
+        /// Run the maker's completion callback, if any. A failure is only reported
+        /// through the returned event, so a broken callback can never block the
+        /// completing fill. A callee that became a fee token after the order was
+        /// created is not called and counts as a failure.
+        fn notify_completion(
+            &self,
+            order_id: [u8; 32],
+            order: &PartialFillOrder,
+        ) -> Option<CompletionCallbackFailed> {
+            let callback = order.completion_callback.as_ref()?;
+            let total_dest = self.filled_dest_amount(&order_id, order);
+            let completed = !self.is_protected_callee(callback.contract)
+                && self.invoke_completion_callback(callback, order_id, order.filled_amount, total_dest);
+            (!completed).then_some(CompletionCallbackFailed { order_id, callee: callback.contract })
+        }
+
+        /// Whether the callback ran to completion within its gas budget
+        fn invoke_completion_callback(
+            &self,
+            callback: &CompletionCallback,
+            order_id: [u8; 32],
+            total_filled: Balance,
+            total_dest: Balance,
+        ) -> bool {
+            use ink::env::call::{build_call, ExecutionInput, Selector};
+
+            let result = build_call::<Environment>()
+                .call(callback.contract)
+                .ref_time_limit(COMPLETION_CALLBACK_REF_TIME)
+                .proof_size_limit(COMPLETION_CALLBACK_PROOF_SIZE)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ON_ORDER_COMPLETED_SELECTOR))
+                        .push_arg(order_id)
+                        .push_arg(total_filled)
+                        .push_arg(total_dest),
+                )
+                .returns::<()>()
+                .try_invoke();
+            matches!(result, Ok(Ok(())))
+        }
+
+        #[cfg(all(feature = "psp22", test))]
+        fn psp22_transfer_from(
+            &self,
+            token: Address,
+            from: Address,
+            to: Address,
+            value: Balance,
+        ) -> Result<(), Error> {
+            psp22_mock::transfer(token, from, to, value)
+        }
+
+        #[cfg(all(feature = "psp22", test))]
+        fn psp22_transfer(&self, token: Address, to: Address, value: Balance) -> Result<(), Error> {
+            psp22_mock::transfer(token, self.env().address(), to, value)
+        }
+
+        /// Positions `offset..offset + limit` of a list of `total` entries, read through
+        /// `item`. Rejects `limit` above `max_page` rather than truncating, so callers
+        /// never mistake a capped page for the end of the list.
+        fn page<T>(
+            offset: u64,
+            limit: u32,
+            max_page: u32,
+            total: u64,
+            item: impl FnMut(u64) -> Option<T>,
+        ) -> Result<Page<T>, Error> {
+            if limit > max_page {
+                return Err(Error::PageTooLarge);
+            }
+            let end = offset.saturating_add(limit as u64).min(total);
+            Ok(Page {
+                items: (offset.min(end)..end).filter_map(item).collect(),
+                next_offset: (end < total).then_some(end),
+                total,
+            })
+        }
+
+        #[cfg(feature = "periphery")]
+        fn export_chunk<T: Encode>(
+            offset: u64,
+            limit: u32,
+            total: u64,
+            entry: impl FnMut(u64) -> Option<T>,
+        ) -> (Vec<u8>, Option<u64>) {
+            Self::page(offset, limit.min(MAX_EXPORT_CHUNK), MAX_EXPORT_CHUNK, total, entry)
+                .map(|page| (page.items.encode(), page.next_offset))
+                .unwrap_or_default()
+        }
+
+        fn record_admin_change(&mut self, function_id: u8, old_value: Vec<u8>, new_value: Vec<u8>) {
+            let entry = AdminAuditLog {
+                timestamp: self.env().block_timestamp(),
+                block: self.env().block_number(),
+                function_id,
+                old_value,
+                new_value,
+            };
+            self.admin_audit.insert(&self.audit_log_counter, &entry);
+            self.audit_log_counter += 1;
+            self.bump_state_version();
+        }
+
+        /// Admin check plus a deadline, so a delayed admin transaction cannot apply
+        /// in a later, unintended context (as EIP-2612 does for permits). The
+        /// deadline is fixed by the signer, never derived from the execution block.
+        fn ensure_admin_until(&self, valid_until_block: BlockNumber) -> Result<(), Error> {
+            self.ensure_admin()?;
+            if self.env().block_number() > valid_until_block {
+                return Err(Error::AdminOperationExpired);
+            }
+            Ok(())
+        }
+
+        fn ensure_admin(&self) -> Result<(), Error> {
+            if self.env().caller() != self.admin {
+                return Err(Error::Unauthorized);
+            }
+            Ok(())
+        }
+
+        /// Increment an id counter, refusing to reuse a value at or below its high
+        /// watermark. A counter that went backwards would otherwise re-derive old ids.
+        fn bump_counter(counter: &mut u64, high_watermark: &mut u64) -> Result<(), Error> {
+            let next = Self::next_counter(*counter, *high_watermark)?;
+            *counter = next;
+            *high_watermark = next;
+            Ok(())
+        }
+
+        /// The value `bump_counter` would move `counter` to, without moving it
+        fn next_counter(counter: u64, high_watermark: u64) -> Result<u64, Error> {
+            let next = counter.checked_add(1).ok_or(Error::CounterOverflow)?;
+            if next <= high_watermark {
+                return Err(Error::CounterOverflow);
+            }
+            Ok(next)
+        }
+
+        /// Bytes of an address as mixed into id derivations: the 20 raw H160 bytes in
+        /// big-endian (as displayed) order, with no length prefix. This is pinned by
+        /// `fixtures/id_derivation.json`; `generate_contract_id` hashes ids only.
+        fn encode_address_for_hash(addr: &Address) -> [u8; 20] {
+            let mut out = [0u8; 20];
+            out.copy_from_slice(addr.as_bytes());
+            out
+        }
+
+        /// Order id for the given parameters at order counter value `counter`
+        fn derive_order_id(
+            &self,
+            maker: &Address,
+            amount: Balance,
+            hashlock: &[u8; 32],
+            timelock: BlockNumber,
+            swap_id: &[u8; 32],
+            counter: u64,
+        ) -> [u8; 32] {
+            let maker = Self::encode_address_for_hash(maker);
+            let data = quote::order_id_preimage(&maker, amount, hashlock, timelock, swap_id, counter, self.id_scheme());
+            self.compute_sha256(data.as_bytes())
+        }
+
+        /// The block timestamp is mixed in for entropy only: a collator may set it to
+        /// anything within loose bounds, so uniqueness rests on the block number here
+        /// and on the fill counter in `generate_contract_id`. No deadline reads it.
+        fn generate_fill_id(
+            &self,
+            order_id: &[u8; 32],
+            taker: &Address,
+            fill_amount: Balance,
+        ) -> [u8; 32] {
+            let data = quote::fill_id_preimage(
+                order_id,
+                &Self::encode_address_for_hash(taker),
+                fill_amount,
+                self.env().block_timestamp(),
+                self.env().block_number(),
+                self.id_scheme(),
+            );
+            self.compute_sha256(data.as_bytes())
+        }
+
+        fn generate_contract_id(&mut self, order_id: &[u8; 32], fill_id: &[u8; 32]) -> Result<[u8; 32], Error> {
+            Self::bump_counter(&mut self.fill_counter, &mut self.fill_counter_high)?;
+            let timestamp = self.env().block_timestamp();
+            Ok(self.derive_contract_id(order_id, fill_id, timestamp, self.fill_counter))
+        }
+
+        /// Contract id of a fill created at `timestamp` with fill counter value `sequence`
+        fn derive_contract_id(
+            &self,
+            order_id: &[u8; 32],
+            fill_id: &[u8; 32],
+            timestamp: u64,
+            sequence: u64,
+        ) -> [u8; 32] {
+            let data = quote::contract_id_preimage(order_id, fill_id, timestamp, sequence, self.id_scheme());
+            self.compute_sha256(data.as_bytes())
+        }
+
+        /// The deployment salt, where storage version 3 or later mixes it into ids
+        fn id_salt(&self) -> Option<&[u8; 32]> {
+            (self.storage_version >= StorageVersion::V3).then_some(&self.deployment_salt)
+        }
+
+        /// Id derivation of this deployment's storage version
+        fn id_scheme(&self) -> quote::IdScheme<'_> {
+            quote::IdScheme {
+                salt: self.id_salt(),
+                tagged: self.storage_version >= StorageVersion::V4,
+            }
+        }
+
+        fn derive_deployment_salt(address: &Address) -> [u8; 32] {
+            use ink::env::hash::{Sha2x256, HashOutput};
+            let mut data = Vec::from(DEPLOYMENT_SALT_PREFIX);
+            data.extend_from_slice(&Self::encode_address_for_hash(address));
+            let mut output = <Sha2x256 as HashOutput>::Type::default();
+            ink::env::hash_bytes::<Sha2x256>(&data, &mut output);
+            output
+        }
+
+        fn hash_preimage(algorithm: HashAlgorithm, preimage: &[u8; 32]) -> [u8; 32] {
+            use ink::env::hash::{Blake2x256, HashOutput, Keccak256, Sha2x256};
+            let mut output = <Sha2x256 as HashOutput>::Type::default();
+            match algorithm {
+                HashAlgorithm::Sha256 => ink::env::hash_bytes::<Sha2x256>(preimage, &mut output),
+                HashAlgorithm::Keccak256 => ink::env::hash_bytes::<Keccak256>(preimage, &mut output),
+                HashAlgorithm::Blake2x256 => ink::env::hash_bytes::<Blake2x256>(preimage, &mut output),
+            }
+            output
+        }
+
+        fn compute_sha256(&self, data: &[u8]) -> [u8; 32] {
+            use ink::env::hash::{Sha2x256, HashOutput};
+            let mut output = <Sha2x256 as HashOutput>::Type::default();
+            ink::env::hash_bytes::<Sha2x256>(data, &mut output);
+            output
+        }
+
+        /// Message signed for `payload` on this deployment; see `SignedPayload`
+        #[cfg_attr(not(feature = "periphery"), allow(dead_code))]
+        fn signing_digest<T: SignedPayload>(&self, payload: &T) -> [u8; 32] {
+            use ink::env::hash::{Blake2x256, HashOutput};
+            let mut data = Vec::from(SIGNING_PREFIX);
+            self.get_signing_domain().encode_to(&mut data);
+            T::TYPE_NAME.encode_to(&mut data);
+            payload.encode_to(&mut data);
+
+            let mut output = <Blake2x256 as HashOutput>::Type::default();
+            ink::env::hash_bytes::<Blake2x256>(&data, &mut output);
+            output
+        }
+
+        /// The single check behind every signature-based message: `signature` must be
+        /// `expected_signer`'s signature over the domain-separated digest of `payload`
+        #[cfg_attr(not(feature = "periphery"), allow(dead_code))]
+        fn verify_signed<T: SignedPayload>(
+            &self,
+            payload: &T,
+            signature: &MultiSignature,
+            expected_signer: &Signer,
+        ) -> Result<(), Error> {
+            let digest = self.signing_digest(payload);
+            match (signature, expected_signer) {
+                (MultiSignature::Sr25519(signature), Signer::Sr25519(public_key)) => self
+                    .env()
+                    .sr25519_verify(signature, &digest, public_key)
+                    .map_err(|_| Error::InvalidSignature),
+                (MultiSignature::Ecdsa(signature), Signer::Ecdsa(expected_address)) => {
+                    let public_key = self
+                        .env()
+                        .ecdsa_recover(signature, &digest)
+                        .map_err(|_| Error::InvalidSignature)?;
+                    let address = self
+                        .env()
+                        .ecdsa_to_eth_address(&public_key)
+                        .map_err(|_| Error::InvalidSignature)?;
+                    if address != *expected_address {
+                        return Err(Error::InvalidSignature);
+                    }
+                    Ok(())
+                }
+                _ => Err(Error::InvalidSignature),
+            }
+        }
+    }
+
+    // Periphery: indices, aggregate views, tags, address mappings, the reveal log,
+    // stats consumers, the duplicate guard and batch helpers. Messages here move
+    // funds only by calling core messages, and the core calls in only through
+    // cfg-gated statements (mostly the hooks at the end of this block), so a build
+    // without the `periphery` feature is the escrow core with unchanged selectors.
+    impl PolkadotPartialFills {
+        /// Map cross-chain address for account. After the account's first mapping,
+        /// updates are limited to one per `mapping_cooldown_blocks`.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn map_address(&mut self, cross_address: CrossChainAddress) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            let caller = self.env().caller();
+            self.set_mapping(caller, cross_address)
+        }
+
+        /// `map_address` with proof of control: `signature` is a `MapAddressPayload`
+        /// naming the caller, signed by the key behind `cross_address` (ecdsa for
+        /// `Ethereum`, sr25519 for `Substrate`). `Raw` addresses cannot be proven.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn map_address_with_proof(
+            &mut self,
+            cross_address: CrossChainAddress,
+            valid_until_block: BlockNumber,
+            signature: MultiSignature,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            let caller = self.env().caller();
+            if self.env().block_number() > valid_until_block {
+                return Err(Error::SignatureExpired);
+            }
+
+            let signer = match cross_address {
+                CrossChainAddress::Ethereum(address) => Signer::Ecdsa(address),
+                CrossChainAddress::Substrate(public_key) => Signer::Sr25519(public_key),
+                CrossChainAddress::Raw(_) => return Err(Error::InvalidSignature),
+            };
+            let payload = MapAddressPayload {
+                account: caller,
+                cross_address,
+                valid_until_block,
+            };
+            self.verify_signed(&payload, &signature, &signer)?;
+
+            self.set_mapping(caller, payload.cross_address)?;
+            // Emitted after `AddressMapped`: the attestation covers the new mapping
+            self.attest_mapping(caller);
+            Ok(())
+        }
+
+        /// Refresh the attestation of the caller's mapping with a new proof of control:
+        /// `signature` is a `RenewMappingPayload` for the current mapping. The mapping
+        /// itself is unchanged, so the update cooldown does not apply.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn renew_mapping_proof(
+            &mut self,
+            valid_until_block: BlockNumber,
+            signature: MultiSignature,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            let caller = self.env().caller();
+            if self.env().block_number() > valid_until_block {
+                return Err(Error::SignatureExpired);
+            }
+
+            let cross_address = self
+                .periphery
+                .address_mappings
+                .get(&caller)
+                .ok_or(Error::MappingNotFound)?;
+            let signer = match cross_address {
+                CrossChainAddress::Ethereum(address) => Signer::Ecdsa(address),
+                CrossChainAddress::Substrate(public_key) => Signer::Sr25519(public_key),
+                CrossChainAddress::Raw(_) => return Err(Error::InvalidSignature),
+            };
+            let payload = RenewMappingPayload {
+                account: caller,
+                cross_address,
+                valid_until_block,
+            };
+            self.verify_signed(&payload, &signature, &signer)?;
+
+            self.attest_mapping(caller);
+            Ok(())
+        }
+
+        /// Cancel several of the caller's orders at once; fails as a whole if any cancel fails
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn batch_cancel_orders(&mut self, order_ids: Vec<[u8; 32]>) -> Result<u32, Error> {
+            self.ensure_not_retired()?;
+            if order_ids.len() > MAX_BATCH_CANCEL {
+                return Err(Error::BatchTooLarge);
+            }
+
+            for order_id in order_ids.iter() {
+                self.cancel_order(*order_id)?;
+            }
+
+            Ok(order_ids.len() as u32)
+        }
+
+        /// Attach free-form tags to an order (maker only)
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn set_order_tags(&mut self, order_id: [u8; 32], tags: Vec<u8>) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            let caller = self.env().caller();
+            let order = self.get_order_or_error(&order_id)?;
+
+            if caller != order.maker {
+                return Err(Error::Unauthorized);
+            }
+
+            if tags.len() > MAX_ORDER_TAGS_LEN {
+                return Err(Error::TagsTooLong);
+            }
+
+            self.periphery.order_tags.insert(&order_id, &tags);
+            self.touch_order(order_id);
+            Ok(())
+        }
+
+        /// Orders sharing `swap_id`, at most `MAX_INDEX_PAGE` per page
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_orders_by_swap_id(
+            &self,
+            swap_id: [u8; 32],
+            offset: u64,
+            limit: u32,
+        ) -> Result<Page<[u8; 32]>, Error> {
+            self.id_list_page(IdList::SwapOrders(swap_id), offset, limit, MAX_INDEX_PAGE)
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_group_orders(&self, group_id: [u8; 32]) -> Vec<[u8; 32]> {
+            self.periphery.group_orders.get(&group_id).unwrap_or_default()
+        }
+
+        /// Funds `account` has at stake, summed over at most `MAX_POSITION_SCAN` of its
+        /// orders and of its fills
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_account_position(&self, account: Address) -> AccountPosition {
+            let order_count = self.id_list_len(IdList::MakerOrders(account)) as usize;
+            let fill_count = self.periphery.taker_fill_count.get(&account).unwrap_or(0) as usize;
+
+            let mut position = AccountPosition {
+                credits: self.credits.get(&account).unwrap_or(0),
+                truncated: order_count > MAX_POSITION_SCAN || fill_count > MAX_POSITION_SCAN,
+                ..Default::default()
+            };
+            let scanned = MAX_POSITION_SCAN as u32;
+            for order_id in self.id_list_range(IdList::MakerOrders(account), 0, scanned).iter() {
+                let Some(order) = self.orders.get(order_id) else { continue };
+                if !order.cancelled {
+                    position.orders_escrowed += order.total_amount.saturating_sub(order.filled_amount);
+                }
+            }
+            for fill_id in self.taker_fill_ids(account, 0, MAX_POSITION_SCAN as u32) {
+                let Some(fill) = self.fills.get(&fill_id) else { continue };
+                if fill.is_pending() {
+                    position.fills_claimable += fill.fill_amount;
+                    position.deposits_held += fill.deposit;
+                }
+            }
+            position
+        }
+
+        /// Order ids `maker` created and has not cleaned up, at most `MAX_INDEX_PAGE`
+        /// per page
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_maker_orders(
+            &self,
+            maker: Address,
+            offset: u64,
+            limit: u32,
+        ) -> Result<Page<[u8; 32]>, Error> {
+            self.id_list_page(IdList::MakerOrders(maker), offset, limit, MAX_INDEX_PAGE)
+        }
+
+        /// Live orders of `maker` with their state, for a maker's own order book: the
+        /// entries of `get_maker_orders` neither cancelled nor fully filled, at most
+        /// `MAX_OPEN_ORDERS_PAGE` per page
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_orders_by_maker(
+            &self,
+            maker: Address,
+            offset: u64,
+            limit: u32,
+        ) -> Result<Page<([u8; 32], PartialFillOrder)>, Error> {
+            let page = self.id_list_page(IdList::MakerOrders(maker), offset, limit, MAX_OPEN_ORDERS_PAGE)?;
+            Ok(page.filter_map(|order_id| {
+                self.orders
+                    .get(&order_id)
+                    .filter(|order| !Self::is_terminal(order))
+                    .map(|order| (order_id, order))
+            }))
+        }
+
+        /// Fill ids `taker` took, in fill order, at most `MAX_INDEX_PAGE` per page
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_taker_fills(
+            &self,
+            taker: Address,
+            offset: u64,
+            limit: u32,
+        ) -> Result<Page<[u8; 32]>, Error> {
+            let count = self.periphery.taker_fill_count.get(&taker).unwrap_or(0);
+            Self::page(offset, limit, MAX_INDEX_PAGE, count as u64, |index| {
+                self.periphery.taker_fills.get((taker, index as u32))
+            })
+        }
+
+        /// Fills `taker` took with their state, in fill order, at most
+        /// `MAX_TAKER_FILLS_PAGE` per page: the entries of `get_taker_fills` not yet
+        /// cleaned up, so a taker can find the fills it still has to withdraw
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_fills_by_taker(
+            &self,
+            taker: Address,
+            offset: u64,
+            limit: u32,
+        ) -> Result<Page<([u8; 32], FillExecution)>, Error> {
+            let count = self.periphery.taker_fill_count.get(&taker).unwrap_or(0);
+            let page = Self::page(offset, limit, MAX_TAKER_FILLS_PAGE, count as u64, |index| {
+                self.periphery.taker_fills.get((taker, index as u32))
+            })?;
+            Ok(page.filter_map(|fill_id| self.load_fill(&fill_id).map(|fill| (fill_id, fill))))
+        }
+
+        /// Escrowed, filled and remaining amounts summed over a group, with each order's status
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_group_summary(&self, group_id: [u8; 32]) -> Option<GroupSummary> {
+            let order_ids = self.periphery.group_orders.get(&group_id)?;
+
+            let mut summary = GroupSummary {
+                order_count: 0,
+                total_escrowed: 0,
+                total_filled: 0,
+                total_remaining: 0,
+                orders: Vec::new(),
+            };
+            for order_id in order_ids {
+                if let Some(order) = self.orders.get(&order_id) {
+                    summary.order_count += 1;
+                    summary.total_escrowed += order.total_amount;
+                    summary.total_filled += order.filled_amount;
+                    summary.total_remaining += self.get_remaining_amount(order_id);
+                    summary.orders.push((order_id, self.compute_order_status(&order)));
+                }
+            }
+
+            Some(summary)
+        }
+
+        /// Orders neither cancelled nor fully filled, at most `MAX_INDEX_PAGE` per page
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_active_orders(&self, offset: u64, limit: u32) -> Result<Page<[u8; 32]>, Error> {
+            self.id_list_page(IdList::ActiveOrders, offset, limit, MAX_INDEX_PAGE)
+        }
+
+        /// Fillable orders (active or partially filled, not paused) from one page of
+        /// the active index, at most `MAX_OPEN_ORDERS_PAGE` index entries per page. The
+        /// filter is applied within the page, so a page can return fewer than `limit`
+        /// orders while later pages still hold matches; callers follow `next_offset`.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_open_orders(
+            &self,
+            offset: u64,
+            limit: u32,
+            filter: Option<OrderFilter>,
+        ) -> Result<Page<([u8; 32], PartialFillOrder)>, Error> {
+            let filter = filter.unwrap_or_default();
+            let page = self.id_list_page(IdList::ActiveOrders, offset, limit, MAX_OPEN_ORDERS_PAGE)?;
+
+            Ok(page.filter_map(|order_id| {
+                self.orders
+                    .get(&order_id)
+                    .filter(|order| self.is_open(order) && self.matches_filter(order, &filter))
+                .filter(|_| filter.exclude_idle_blocks.map_or(true, |blocks| !self.idle_for(order_id, blocks)))
+                    .map(|order| (order_id, order))
+            }))
+        }
+
+        /// Up to `limit` open orders of one chain pair ranked by the rate a fill would
+        /// get in this block, highest first unless `ascending`; `limit` is at most
+        /// `MAX_SORTED_ORDERS`. Only the first `MAX_CORRIDOR_SCAN` orders of the
+        /// corridor index are ranked. Equal prices keep corridor index order.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_orders_sorted_by_price(
+            &self,
+            source_chain: u32,
+            dest_chain: u32,
+            ascending: bool,
+            limit: u32,
+        ) -> Result<Vec<PartialFillOrder>, Error> {
+            if limit > MAX_SORTED_ORDERS {
+                return Err(Error::PageTooLarge);
+            }
+            let corridor = IdList::CorridorOrders(source_chain, dest_chain);
+            let mut orders: Vec<(Balance, PartialFillOrder)> = self
+                .id_list_range(corridor, 0, MAX_CORRIDOR_SCAN)
+                .into_iter()
+                .filter_map(|order_id| self.orders.get(&order_id))
+                .filter(|order| self.is_open(order))
+                .map(|order| (self.current_rate(&order), order))
+                .collect();
+
+            if ascending {
+                orders.sort_by(|a, b| a.0.cmp(&b.0));
+            } else {
+                orders.sort_by(|a, b| b.0.cmp(&a.0));
+            }
+            orders.truncate(limit as usize);
+            Ok(orders.into_iter().map(|(_, order)| order).collect())
+        }
+
+        /// Liquidity aggregates of one chain pair, without reading its orders. Orders
+        /// from before the aggregates existed count once `backfill_indices` has
+        /// indexed them. Expired orders count until cancelled or cleaned up.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_corridor_depth(&self, source_chain: u32, dest_chain: u32) -> CorridorDepth {
+            self.periphery.corridor_depths.get((source_chain, dest_chain)).unwrap_or_default()
+        }
+
+        /// Time-to-fill and time-to-settle sums of one chain pair, from which its
+        /// average latencies follow
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_corridor_latency_stats(&self, source_chain: u32, dest_chain: u32) -> CorridorLatency {
+            self.periphery.corridor_latencies.get((source_chain, dest_chain)).unwrap_or_default()
+        }
+
+        /// Items needing attention within `horizon_blocks`, from one page of at most
+        /// `MAX_EXPIRING_PAGE` entries of the active order index: unsettled fills whose
+        /// withdrawal window is closing, and orders and fills that are already
+        /// refundable. Fully filled orders are archived, so their pending fills are not
+        /// covered by this scan.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_expiring_soon(
+            &self,
+            horizon_blocks: BlockNumber,
+            offset: u64,
+            limit: u32,
+        ) -> Result<Page<ExpiringItem>, Error> {
+            let current_block = self.env().block_number();
+            let page = self.id_list_page(IdList::ActiveOrders, offset, limit, MAX_EXPIRING_PAGE)?;
+
+            let mut items = Vec::new();
+            for order_id in page.items {
+                let Some(order) = self.orders.get(&order_id) else {
+                    continue;
+                };
+                let expired = current_block >= order.timelock;
+                let blocks_remaining = order.timelock.saturating_sub(current_block);
+
+                if expired && !order.cancelled && order.filled_amount < order.total_amount {
+                    items.push(ExpiringItem {
+                        id: order_id,
+                        kind: OrderOrFill::Order,
+                        blocks_remaining: 0,
+                        action: ExpiryAction::RefundAvailable,
+                    });
+                }
+
+                if !expired && blocks_remaining > horizon_blocks {
+                    continue;
+                }
+                for fill_id in self.order_fills.get(&order_id).unwrap_or_default() {
+                    match self.fills.get(&fill_id) {
+                        Some(fill) if fill.is_pending() => items.push(ExpiringItem {
+                            id: fill_id,
+                            kind: OrderOrFill::Fill,
+                            blocks_remaining,
+                            action: if expired {
+                                ExpiryAction::RefundAvailable
+                            } else {
+                                ExpiryAction::WithdrawClosing
+                            },
+                        }),
+                        _ => {}
+                    }
+                }
+            }
+            Ok(Page { items, next_offset: page.next_offset, total: page.total })
+        }
+
+        /// Cancelled or fully filled orders not yet cleaned up, at most `MAX_INDEX_PAGE`
+        /// per page
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_archived_orders(&self, offset: u64, limit: u32) -> Result<Page<[u8; 32]>, Error> {
+            self.id_list_page(IdList::ArchivedOrders, offset, limit, MAX_INDEX_PAGE)
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_order_tags(&self, order_id: [u8; 32]) -> Option<Vec<u8>> {
+            self.periphery.order_tags.get(&order_id)
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_order_fill_price_stats(&self, order_id: [u8; 32]) -> Option<OrderFillPriceStats> {
+            let order = self.orders.get(&order_id)?;
+            let fill_ids = self.order_fills.get(&order_id).unwrap_or_default();
+            Some(self.compute_fill_price_stats(&order, &fill_ids))
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_fill_timing_info(&self, fill_id: [u8; 32]) -> Option<FillTimingInfo> {
+            let fill = self.load_fill(&fill_id)?;
+            let order = self.orders.get(&fill.order_id)?;
+            Some(self.build_timing_info(fill_id, &fill, &order))
+        }
+
+        /// Order, fills (capped at `MAX_FULL_STATE_FILLS`), stats, timing, tags and
+        /// status in one SCALE-encoded value
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_order_full_state(&self, order_id: [u8; 32]) -> Option<OrderFullState> {
+            let order = self.orders.get(&order_id)?;
+            let fill_ids = self.order_fills.get(&order_id).unwrap_or_default();
+
+            let mut fills = Vec::new();
+            let mut timing_info = Vec::new();
+            for fill_id in fill_ids.iter().take(MAX_FULL_STATE_FILLS) {
+                if let Some(fill) = self.load_fill(fill_id) {
+                    timing_info.push(self.build_timing_info(*fill_id, &fill, &order));
+                    fills.push(fill);
+                }
+            }
+
+            Some(OrderFullState {
+                stats: self.compute_fill_price_stats(&order, &fill_ids),
+                tags: self.periphery.order_tags.get(&order_id),
+                remaining: self.get_remaining_amount(order_id),
+                status: self.compute_order_status(&order),
+                last_activity_block: self.order_last_activity.get(&order_id),
+                anti_snipe: self.anti_snipe.get(&order_id),
+                order,
+                fills,
+                timing_info,
+            })
+        }
+
+        /// Reveals logged at or after `index`, in reveal order, for relayer catch-up; at
+        /// most `MAX_REVEALS_PAGE` per page
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_reveals_since(
+            &self,
+            index: Cursor,
+            limit: u32,
+        ) -> Result<Page<(u64, [u8; 32], [u8; 32])>, Error> {
+            Self::page(index, limit, MAX_REVEALS_PAGE, self.periphery.reveal_counter, |i| {
+                self.periphery.reveal_log.get(i).map(|(hashlock, secret)| (i, hashlock, secret))
+            })
+        }
+
+        /// Order creations, fill settlements and reveals logged at or after `cursor`,
+        /// interleaved in the order they happened and tagged with their event nonce;
+        /// at most `MAX_UPDATES_PAGE` per page. One cursor catches an indexer up on
+        /// all three after downtime.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_updates_since(
+            &self,
+            cursor: Cursor,
+            limit: u32,
+        ) -> Result<Page<(u64, Update)>, Error> {
+            Self::page(cursor, limit, MAX_UPDATES_PAGE, self.periphery.event_nonce, |i| {
+                self.periphery.update_log.get(i).map(|update| (i, update))
+            })
+        }
+
+        /// Nonce the next `get_updates_since` record will carry
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_event_nonce(&self) -> u64 {
+            self.periphery.event_nonce
+        }
+
+        /// Up to `MAX_EXPORT_CHUNK` entries of one table from `offset`, SCALE-encoded
+        /// as `ExportKind` describes, and the offset of the next chunk (`None` after
+        /// the last), for off-chain reconciliation. Larger limits are cut to the
+        /// maximum. Entries come in index order, which only holds while nothing
+        /// changes, so exporters compare `state_root_hint` before and after.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn export_state_chunk(
+            &self,
+            kind: ExportKind,
+            offset: u64,
+            limit: u32,
+        ) -> (Vec<u8>, Option<u64>) {
+            let entry_at = |list: IdList, pos: u64| self.periphery.id_lists.get((list, pos as u32));
+            let list_len = |list: IdList| self.id_list_len(list) as u64;
+            match kind {
+                ExportKind::Orders => {
+                    let active = list_len(IdList::ActiveOrders);
+                    let total = active + list_len(IdList::ArchivedOrders);
+                    Self::export_chunk(offset, limit, total, |pos| {
+                        let order_id = match pos.checked_sub(active) {
+                            None => entry_at(IdList::ActiveOrders, pos)?,
+                            Some(pos) => entry_at(IdList::ArchivedOrders, pos)?,
+                        };
+                        let order = self.orders.get(&order_id)?;
+                        Some((order_id, order, self.order_last_activity.get(&order_id)))
+                    })
+                }
+                ExportKind::Fills => {
+                    Self::export_chunk(offset, limit, list_len(IdList::PendingFills), |pos| {
+                        let fill_id = entry_at(IdList::PendingFills, pos)?;
+                        Some((fill_id, self.get_fill(fill_id)?))
+                    })
+                }
+                ExportKind::Credits => {
+                    Self::export_chunk(offset, limit, list_len(IdList::Creditors), |pos| {
+                        let account = Self::key_account(&entry_at(IdList::Creditors, pos)?);
+                        Some((account, self.credits.get(&account)?))
+                    })
+                }
+                ExportKind::Mappings => {
+                    Self::export_chunk(offset, limit, list_len(IdList::MappedAccounts), |pos| {
+                        let account = Self::key_account(&entry_at(IdList::MappedAccounts, pos)?);
+                        Some((account, self.periphery.address_mappings.get(&account)?))
+                    })
+                }
+            }
+        }
+
+        /// Read before and after an `export_state_chunk` run to tell whether activity
+        /// interleaved with it. It moves with every message that writes an order, a
+        /// fill, a reservation, a credit, an address mapping or an admin setting.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn state_root_hint(&self) -> u64 {
+            self.periphery.state_version
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_reveal_counter(&self) -> u64 {
+            self.periphery.reveal_counter
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_cross_address(&self, account: Address) -> Option<CrossChainAddress> {
+            self.periphery.address_mappings.get(&account)
+        }
+
+        /// `get_cross_address` with the mapping's attestation; `None` as the second
+        /// element for a mapping set without proof. Expired proofs are still returned.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_cross_address_with_attestation(
+            &self,
+            account: Address,
+        ) -> Option<(CrossChainAddress, Option<MappingAttestation>)> {
+            let cross_address = self.periphery.address_mappings.get(&account)?;
+            Some((cross_address, self.periphery.mapping_attestations.get(&account)))
+        }
+
+        /// The account's mapping unless its proof has lapsed, in which case the account
+        /// reads as unmapped until it renews. Mappings set without proof never lapse.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn resolve_cross_address(&self, account: Address) -> Option<CrossChainAddress> {
+            let current_block = self.env().block_number();
+            match self.periphery.mapping_attestations.get(&account) {
+                Some(attestation) if attestation.is_expired_at(current_block) => None,
+                _ => self.periphery.address_mappings.get(&account),
+            }
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_mapping_attestation_ttl(&self) -> Option<BlockNumber> {
+            self.periphery.mapping_attestation_ttl
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_mapping_cooldown_blocks(&self) -> BlockNumber {
+            self.periphery.mapping_cooldown_blocks
+        }
+
+        /// Display-ready ASCII for cross-address bytes of an order on `chain_id`:
+        /// EIP-55 hex for Ethereum accounts, `0x` hex for Substrate keys and unknown
+        /// shapes, SS58 text unchanged. Meant for debugging over RPC.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn format_cross_address(&self, chain_id: u32, bytes: Vec<u8>) -> Vec<u8> {
+            address_format::format_address(chain_id, &bytes)
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_stats_consumers(&self) -> Vec<Address> {
+            self.periphery.stats_consumers.clone()
+        }
+
+        /// Active order of `maker` with the same economic parameters, if any. Lets clients
+        /// pre-check a submission that `reject_duplicate_orders` would refuse.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn find_duplicate(
+            &self,
+            maker: Address,
+            total_amount: Balance,
+            hashlock: [u8; 32],
+            dest_chain: u32,
+            dest_amount_per_unit: Balance,
+        ) -> Option<[u8; 32]> {
+            let fingerprint = self.params_fingerprint(
+                &maker,
+                total_amount,
+                &hashlock,
+                dest_chain,
+                dest_amount_per_unit,
+            );
+            self.active_duplicate(&fingerprint)
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn is_rejecting_duplicate_orders(&self) -> bool {
+            self.periphery.reject_duplicate_orders
+        }
+
+        /// Re-broadcast every unsettled fill so relayers that missed `OrderFilled`
+        /// can catch up. Permissionless, at most once per `FILL_ANNOUNCEMENT_INTERVAL`
+        /// blocks; returns the number of fills announced.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn announce_pending_fills(&mut self) -> Result<u32, Error> {
+            self.ensure_not_retired()?;
+            let current_block = self.env().block_number();
+            if self.periphery.last_announcement_block != 0
+                && current_block < self.periphery.last_announcement_block + FILL_ANNOUNCEMENT_INTERVAL
+            {
+                return Err(Error::AnnouncementTooSoon);
+            }
+
+            let fill_ids = self.id_list_range(IdList::PendingFills, 0, MAX_ANNOUNCED_FILLS as u32);
+            let order_ids: Vec<[u8; 32]> = fill_ids
+                .iter()
+                .map(|fill_id| self.fills.get(fill_id).map(|fill| fill.order_id).unwrap_or_default())
+                .collect();
+
+            self.periphery.last_announcement_block = current_block;
+            let count = fill_ids.len() as u32;
+            self.env().emit_event(PendingFillsAnnounced {
+                fill_ids,
+                order_ids,
+                block: current_block,
+            });
+            Ok(count)
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn get_last_announcement_block(&self) -> BlockNumber {
+            self.periphery.last_announcement_block
+        }
+
+        /// Populate the maker, swap_id and active/archived indices for orders created
+        /// before those indices existed. Idempotent, bounded by `MAX_BACKFILL_BATCH`.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn backfill_indices(
+            &mut self,
+            order_ids: Vec<[u8; 32]>,
+            valid_until_block: BlockNumber,
+        ) -> Result<u32, Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            if order_ids.len() > MAX_BACKFILL_BATCH {
+                return Err(Error::BatchTooLarge);
+            }
+
+            let mut count = 0u32;
+            for order_id in order_ids {
+                if let Some(order) = self.orders.get(&order_id) {
+                    self.index_order(order_id, &order);
+                    count += 1;
+                }
+            }
+
+            self.env().emit_event(IndicesBackfilled { count });
+            Ok(count)
+        }
+
+        /// Refuse new orders duplicating the economic parameters of an active one
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn set_reject_duplicate_orders(
+            &mut self,
+            enabled: bool,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let old_enabled = self.periphery.reject_duplicate_orders;
+            self.periphery.reject_duplicate_orders = enabled;
+            self.record_admin_change(
+                admin_fn::SET_REJECT_DUPLICATE_ORDERS,
+                old_enabled.encode(),
+                enabled.encode(),
+            );
+            Ok(())
+        }
+
+        /// Contracts notified after every fill and withdrawal
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn set_stats_consumers(
+            &mut self,
+            consumers: Vec<Address>,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            if consumers.len() > MAX_STATS_CONSUMERS {
+                return Err(Error::TooManyConsumers);
+            }
+
+            let old_consumers = core::mem::replace(&mut self.periphery.stats_consumers, consumers);
+            self.record_admin_change(
+                admin_fn::SET_STATS_CONSUMERS,
+                old_consumers.encode(),
+                self.periphery.stats_consumers.encode(),
+            );
+            Ok(())
+        }
+
+        /// Blocks an account must wait between two `map_address` updates
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn set_mapping_cooldown(
+            &mut self,
+            blocks: BlockNumber,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let old_blocks = self.periphery.mapping_cooldown_blocks;
+            self.periphery.mapping_cooldown_blocks = blocks;
+            self.record_admin_change(
+                admin_fn::SET_MAPPING_COOLDOWN,
+                old_blocks.encode(),
+                blocks.encode(),
+            );
+            Ok(())
+        }
+
+        /// Blocks a mapping proof stays fresh before `resolve_cross_address` treats the
+        /// mapping as absent; `None` lets proofs stand indefinitely. Applies to proofs
+        /// given or renewed from now on.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn set_mapping_attestation_ttl(
+            &mut self,
+            ttl: Option<BlockNumber>,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let old_ttl = core::mem::replace(&mut self.periphery.mapping_attestation_ttl, ttl);
+            self.record_admin_change(
+                admin_fn::SET_MAPPING_ATTESTATION_TTL,
+                old_ttl.encode(),
+                ttl.encode(),
+            );
+            Ok(())
+        }
+
+        /// Remove an abusive cross-chain mapping. The account's next mapping is not
+        /// treated as its first, so the cooldown still applies to it.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn purge_mapping(
+            &mut self,
+            account: Address,
+            valid_until_block: BlockNumber,
+        ) -> Result<(), Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            let old_mapping = self.periphery.address_mappings.take(&account);
+            self.periphery.mapping_attestations.remove(&account);
+            self.id_list_remove(IdList::MappedAccounts, Self::account_key(&account));
+            self.record_admin_change(
+                admin_fn::PURGE_MAPPING,
+                (account, old_mapping).encode(),
+                (account, None::<CrossChainAddress>).encode(),
+            );
+
+            self.env().emit_event(MappingPurged { account });
+            Ok(())
+        }
+
+        /// Re-encode a batch of orders still stored in the V1 layout as V2, filling
+        /// the new fields with defaults, and index them. V1 deployments kept no order
+        /// lists, so the ids come from the caller, at most `MAX_MIGRATION_BATCH` per
+        /// call. Orders that already decode as V2 are left alone, so the call is
+        /// idempotent. The storage version moves to V2 only with `last_batch`.
+        /// Returns the number of orders rewritten.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn migrate_storage_to_v2(
+            &mut self,
+            order_ids: Vec<[u8; 32]>,
+            last_batch: bool,
+            valid_until_block: BlockNumber,
+        ) -> Result<u32, Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            if order_ids.len() > MAX_MIGRATION_BATCH {
+                return Err(Error::BatchTooLarge);
+            }
+
+            let orders_key = ink::storage::traits::StorageKey::key(&self.orders);
+            let mut migrated_count = 0u32;
+            for order_id in order_ids {
+                // V2 only appends fields, so a value is V1 exactly when its stored
+                // size matches its V1 decoding
+                let slot = (orders_key, order_id);
+                let Some(stored_size) = ink::env::contains_contract_storage(&slot) else {
+                    continue;
+                };
+                if let Ok(Some(v1)) = ink::env::get_contract_storage::<_, PartialFillOrderV1>(&slot) {
+                    if v1.encoded_size() as u32 == stored_size {
+                        let order = PartialFillOrderV2::from(v1);
+                        self.orders.insert(&order_id, &order);
+                        self.index_order(order_id, &order);
+                        migrated_count += 1;
+                    }
+                }
+            }
+
+            self.bump_state_version();
+
+            // A later version already includes V2's layout
+            let from_version = self.storage_version;
+            if last_batch {
+                self.storage_version = from_version.max(StorageVersion::V2);
+            }
+
+            self.env().emit_event(StorageMigrated {
+                from_version: from_version.0,
+                to_version: self.storage_version.0,
+                migrated_count,
+            });
+            Ok(migrated_count)
+        }
+
+        /// Rewrite a batch of fills still stored as `FillExecutionV1` in the
+        /// `StoredFill` layout, at most `MAX_MIGRATION_BATCH` per call. Fills taken
+        /// before the layout change cannot be read until migrated. A stored
+        /// `FillExecutionV1` is larger than any `StoredFill`, so fills already
+        /// migrated are told apart by size and left alone, and the call is
+        /// idempotent. Returns the number of fills rewritten.
+        #[cfg(feature = "periphery")]
+        #[ink(message)]
+        pub fn migrate_fills(
+            &mut self,
+            fill_ids: Vec<[u8; 32]>,
+            valid_until_block: BlockNumber,
+        ) -> Result<u32, Error> {
+            self.ensure_not_retired()?;
+            self.ensure_admin_until(valid_until_block)?;
+            if fill_ids.len() > MAX_MIGRATION_BATCH {
+                return Err(Error::BatchTooLarge);
+            }
+
+            let fills_key = ink::storage::traits::StorageKey::key(&self.fills);
+            let mut migrated_count = 0u32;
+            for fill_id in fill_ids {
+                let slot = (fills_key, fill_id);
+                let Some(stored_size) = ink::env::contains_contract_storage(&slot) else {
+                    continue;
+                };
+                let Ok(Some(v1)) = ink::env::get_contract_storage::<_, FillExecutionV1>(&slot) else {
+                    continue;
+                };
+                if v1.encoded_size() as u32 != stored_size {
+                    continue;
+                }
+                let mut flags = StoredFill::LEGACY_ID;
+                if v1.withdrawn {
+                    flags |= StoredFill::WITHDRAWN;
+                }
+                if v1.refunded {
+                    flags |= StoredFill::REFUNDED;
+                }
+                self.fills.insert(&fill_id, &StoredFill {
+                    order_id: v1.order_id,
+                    taker: v1.taker,
+                    fill_amount: v1.fill_amount,
+                    timestamp: v1.timestamp,
+                    deposit: v1.deposit,
+                    requested_amount: v1.requested_amount,
+                    sequence: 0,
+                    flags,
+                });
+                self.legacy_contract_ids.insert(&fill_id, &v1.contract_id);
+                if let Some(preimage) = v1.preimage {
+                    self.fill_preimages.insert(&fill_id, &preimage);
+                }
+                migrated_count += 1;
+            }
+            self.bump_state_version();
+
+            self.env().emit_event(FillsMigrated { migrated_count });
+            Ok(migrated_count)
+        }
+
+        #[cfg(feature = "periphery")]
+        fn is_terminal(order: &PartialFillOrder) -> bool {
+            order.cancelled || order.filled_amount >= order.total_amount
+        }
+
+        /// Record an order in every secondary index. Safe to call repeatedly.
+        #[cfg(feature = "periphery")]
+        fn index_order(&mut self, order_id: [u8; 32], order: &PartialFillOrder) {
+            self.id_list_insert(IdList::MakerOrders(order.maker), order_id);
+            self.id_list_insert(IdList::SwapOrders(order.swap_id), order_id);
+
+            if Self::is_terminal(order) {
+                self.archive_order(order_id);
+            } else {
+                self.id_list_insert(IdList::ActiveOrders, order_id);
+            }
+            self.sync_corridor_depth(order_id, order);
+        }
+
+        /// Bring the order's corridor aggregates in line with its current state
+        #[cfg(feature = "periphery")]
+        fn sync_corridor_depth(&mut self, order_id: [u8; 32], order: &PartialFillOrder) {
+            let remaining = (!Self::is_terminal(order)).then(|| order.total_amount - order.filled_amount);
+            self.update_corridor_depth(order_id, order, remaining);
+        }
+
+        /// Count `remaining` of the order in its corridor, or drop the order from it
+        /// on `None`. Only a leaving order at the best or worst rate costs a scan.
+        #[cfg(feature = "periphery")]
+        fn update_corridor_depth(
+            &mut self,
+            order_id: [u8; 32],
+            order: &PartialFillOrder,
+            remaining: Option<Balance>,
+        ) {
+            let corridor = (order.source_chain, order.dest_chain);
+            let previous = self.periphery.corridor_contributions.get(&order_id);
+            if previous.is_none() && remaining.is_none() {
+                return;
+            }
+            let mut depth = self.periphery.corridor_depths.get(corridor).unwrap_or_default();
+            depth.total_remaining = depth.total_remaining.saturating_sub(previous.unwrap_or(0));
+            let rate = Self::ranked_rate(order);
+            match remaining {
+                Some(remaining) => {
+                    depth.total_remaining += remaining;
+                    self.periphery.corridor_contributions.insert(&order_id, &remaining);
+                    if previous.is_none() {
+                        depth.order_count += 1;
+                        if let Some(rate) = rate {
+                            depth.best_rate = Some(depth.best_rate.map_or(rate, |best| best.max(rate)));
+                            depth.worst_rate = Some(depth.worst_rate.map_or(rate, |worst| worst.min(rate)));
+                        }
+                        self.id_list_insert(IdList::CorridorOrders(corridor.0, corridor.1), order_id);
+                    }
+                }
+                None => {
+                    depth.order_count -= 1;
+                    self.periphery.corridor_contributions.remove(&order_id);
+                    self.id_list_remove(IdList::CorridorOrders(corridor.0, corridor.1), order_id);
+                    if rate.is_some() && (depth.best_rate == rate || depth.worst_rate == rate) {
+                        self.recompute_corridor_rates(corridor, &mut depth);
+                    }
+                }
+            }
+
+            if depth.order_count == 0 {
+                self.periphery.corridor_depths.remove(corridor);
+            } else {
+                self.periphery.corridor_depths.insert(corridor, &depth);
+            }
+        }
+
+        /// Best and worst rate over the first `MAX_CORRIDOR_SCAN` orders of a corridor
+        #[cfg(feature = "periphery")]
+        fn recompute_corridor_rates(&self, corridor: (u32, u32), depth: &mut CorridorDepth) {
+            let list = IdList::CorridorOrders(corridor.0, corridor.1);
+            depth.best_rate = None;
+            depth.worst_rate = None;
+            for order_id in self.id_list_range(list, 0, MAX_CORRIDOR_SCAN) {
+                let Some(rate) = self.orders.get(&order_id).as_ref().and_then(Self::ranked_rate) else { continue };
+                depth.best_rate = Some(depth.best_rate.map_or(rate, |best| best.max(rate)));
+                depth.worst_rate = Some(depth.worst_rate.map_or(rate, |worst| worst.min(rate)));
+            }
+            depth.rates_partial = self.id_list_len(list) > MAX_CORRIDOR_SCAN;
+        }
+
+        /// The fixed rate a corridor's best and worst rate track; `None` for an auction
+        #[cfg(feature = "periphery")]
+        fn ranked_rate(order: &PartialFillOrder) -> Option<Balance> {
+            (order.order_type == OrderType::FixedPrice).then_some(order.dest_amount_per_unit)
+        }
+
+        /// Current members of `group_id`, provided the maker owns the group and it has room
+        #[cfg(feature = "periphery")]
+        fn group_with_room(&self, group_id: [u8; 32], maker: &Address) -> Result<Vec<[u8; 32]>, Error> {
+            let members = self.periphery.group_orders.get(&group_id).unwrap_or_default();
+            // Members purged by `cleanup_order` no longer tell the owner
+            if let Some(first) = members.iter().find_map(|id| self.orders.get(id)) {
+                if first.maker != *maker {
+                    return Err(Error::Unauthorized);
+                }
+            }
+            if members.len() >= MAX_GROUP_ORDERS {
+                return Err(Error::GroupFull);
+            }
+            Ok(members)
+        }
+
+        /// Move an order from the active list to the archived list. Safe to call repeatedly.
+        #[cfg(feature = "periphery")]
+        fn archive_order(&mut self, order_id: [u8; 32]) {
+            self.forget_fingerprint(order_id);
+            self.id_list_remove(IdList::ActiveOrders, order_id);
+            self.id_list_insert(IdList::ArchivedOrders, order_id);
+        }
+
+        /// Drop the duplicate-guard entry of an order. Safe to call repeatedly.
+        #[cfg(feature = "periphery")]
+        fn forget_fingerprint(&mut self, order_id: [u8; 32]) {
+            if let Some(fingerprint) = self.periphery.order_fingerprints.get(&order_id) {
+                if self.periphery.active_fingerprints.get(&fingerprint) == Some(order_id) {
+                    self.periphery.active_fingerprints.remove(&fingerprint);
+                }
+                self.periphery.order_fingerprints.remove(&order_id);
+            }
+        }
+
+        #[cfg(feature = "periphery")]
+        fn id_list_len(&self, list: IdList) -> u32 {
+            self.periphery.id_list_lens.get(list).unwrap_or(0)
+        }
+
+        #[cfg(feature = "periphery")]
+        fn id_list_contains(&self, list: IdList, id: [u8; 32]) -> bool {
+            self.periphery.id_positions.contains((list, id))
+        }
+
+        /// Append `id` unless the list already holds it
+        #[cfg(feature = "periphery")]
+        fn id_list_insert(&mut self, list: IdList, id: [u8; 32]) {
+            if self.id_list_contains(list, id) {
+                return;
+            }
+            let len = self.id_list_len(list);
+            self.periphery.id_lists.insert((list, len), &id);
+            self.periphery.id_positions.insert((list, id), &len);
+            self.periphery.id_list_lens.insert(list, &(len + 1));
+        }
+
+        /// Drop `id` by moving the list's last id into its position
+        #[cfg(feature = "periphery")]
+        fn id_list_remove(&mut self, list: IdList, id: [u8; 32]) {
+            let Some(pos) = self.periphery.id_positions.take((list, id)) else {
+                return;
+            };
+            let last = self.id_list_len(list) - 1;
+            if pos != last {
+                if let Some(moved) = self.periphery.id_lists.get((list, last)) {
+                    self.periphery.id_lists.insert((list, pos), &moved);
+                    self.periphery.id_positions.insert((list, moved), &pos);
+                }
+            }
+            self.periphery.id_lists.remove((list, last));
+            self.periphery.id_list_lens.insert(list, &last);
+        }
+
+        /// Ids at positions `offset..offset + count`; one read per id
+        #[cfg(feature = "periphery")]
+        fn id_list_range(&self, list: IdList, offset: u32, count: u32) -> Vec<[u8; 32]> {
+            let end = offset.saturating_add(count).min(self.id_list_len(list));
+            (offset..end)
+                .filter_map(|pos| self.periphery.id_lists.get((list, pos)))
+                .collect()
+        }
+
+        #[cfg(feature = "periphery")]
+        fn id_list_page(
+            &self,
+            list: IdList,
+            offset: u64,
+            limit: u32,
+            max_page: u32,
+        ) -> Result<Page<[u8; 32]>, Error> {
+            Self::page(offset, limit, max_page, self.id_list_len(list) as u64, |pos| {
+                self.periphery.id_lists.get((list, pos as u32))
+            })
+        }
+
+        /// Id of `account` in the account lists: its 20 bytes, zero-padded
+        #[cfg(feature = "periphery")]
+        fn account_key(account: &Address) -> [u8; 32] {
+            let mut key = [0u8; 32];
+            key[..20].copy_from_slice(account.as_bytes());
+            key
+        }
+
+        #[cfg(feature = "periphery")]
+        fn key_account(key: &[u8; 32]) -> Address {
+            Address::from_slice(&key[..20])
+        }
+
+        #[cfg(feature = "periphery")]
+        fn track_pending_fill(&mut self, fill_id: [u8; 32]) {
+            self.id_list_insert(IdList::PendingFills, fill_id);
+        }
+
+        #[cfg(feature = "periphery")]
+        fn untrack_pending_fill(&mut self, fill_id: [u8; 32]) {
+            self.id_list_remove(IdList::PendingFills, fill_id);
+        }
+
+        #[cfg(feature = "periphery")]
+        fn is_open(&self, order: &PartialFillOrder) -> bool {
+            matches!(
+                self.compute_order_status(order),
+                OrderStatus::Active | OrderStatus::PartiallyFilled
+            )
+        }
+
+        #[cfg(feature = "periphery")]
+        fn matches_filter(&self, order: &PartialFillOrder, filter: &OrderFilter) -> bool {
+            let remaining = order.total_amount.saturating_sub(order.filled_amount);
+            filter.source_chain.map_or(true, |chain| order.source_chain == chain)
+                && filter.dest_chain.map_or(true, |chain| order.dest_chain == chain)
+                && filter.min_remaining.map_or(true, |min| remaining >= min)
+                && filter.maker.map_or(true, |maker| order.maker == maker)
+                && (!filter.allow_partial_only || order.allow_partial_fills)
+        }
+
+        /// Fill ids `taker` took, in fill order, from `offset`
+        #[cfg(feature = "periphery")]
+        fn taker_fill_ids(&self, taker: Address, offset: u32, limit: u32) -> Vec<[u8; 32]> {
+            let count = self.periphery.taker_fill_count.get(&taker).unwrap_or(0);
+            let end = offset.saturating_add(limit).min(count);
+            (offset..end).filter_map(|index| self.periphery.taker_fills.get((taker, index))).collect()
+        }
+
+        #[cfg(feature = "periphery")]
+        fn compute_fill_price_stats(
+            &self,
+            order: &PartialFillOrder,
+            fill_ids: &[[u8; 32]],
+        ) -> OrderFillPriceStats {
+            let mut stats = OrderFillPriceStats::default();
+
+            for fill_id in fill_ids {
+                let Some(fill) = self.fills.get(fill_id) else { continue };
+                if fill.has(StoredFill::REFUNDED) {
+                    continue;
+                }
+
+                let rate = self.fill_rate(fill_id, order);
+                if stats.fill_count == 0 || rate < stats.min_rate {
+                    stats.min_rate = rate;
+                }
+                if rate > stats.max_rate {
+                    stats.max_rate = rate;
+                }
+                stats.fill_count += 1;
+                stats.total_source_amount += fill.fill_amount;
+                stats.total_dest_amount += Self::compute_dest_amount(order, rate, fill.fill_amount);
+            }
+
+            stats
+        }
+
+        #[cfg(feature = "periphery")]
+        fn build_timing_info(
+            &self,
+            fill_id: [u8; 32],
+            fill: &FillExecution,
+            order: &PartialFillOrder,
+        ) -> FillTimingInfo {
+            FillTimingInfo {
+                fill_id,
+                timestamp: fill.timestamp,
+                created_block: self.fill_created_at.get(&fill_id),
+                expires_at: order.timelock,
+                blocks_remaining: order.timelock.saturating_sub(self.env().block_number()),
+            }
+        }
+
+        /// Push an event to every registered consumer. Consumer failures are ignored
+        /// so that statistics can never block settlement.
+        #[cfg(all(feature = "periphery", not(test)))]
+        fn notify_stats_consumers(&self, event_type: u8, data: StatsEventData) {
+            use ink::env::call::{build_call, ExecutionInput, Selector};
+
+            for consumer in self.periphery.stats_consumers.iter() {
+                let selector = Selector::new(ink::selector_bytes!("IEventConsumer::consume_event"));
+                let _ = build_call::<Environment>()
+                    .call(*consumer)
+                    .exec_input(
+                        ExecutionInput::new(selector)
+                            .push_arg(event_type)
+                            .push_arg(data.encode()),
+                    )
+                    .returns::<Result<(), EventConsumerError>>()
+                    .try_invoke();
+            }
+        }
+
+        #[cfg(all(feature = "periphery", test))]
+        fn notify_stats_consumers(&self, event_type: u8, data: StatsEventData) {
+            for consumer in self.periphery.stats_consumers.iter() {
+                consumer_mock::record(*consumer, event_type, data.encode());
+            }
+        }
+
+        /// sha256(maker ++ total ++ hashlock ++ dest_chain ++ rate); `total` is the
+        /// amount requested at creation, before fees
+        #[cfg(feature = "periphery")]
+        fn params_fingerprint(
+            &self,
+            maker: &Address,
+            total_amount: Balance,
+            hashlock: &[u8; 32],
+            dest_chain: u32,
+            dest_amount_per_unit: Balance,
+        ) -> [u8; 32] {
+            let mut data = Vec::new();
+            data.extend_from_slice(&Self::encode_address_for_hash(maker));
+            data.extend_from_slice(&total_amount.to_le_bytes());
+            data.extend_from_slice(hashlock);
+            data.extend_from_slice(&dest_chain.to_le_bytes());
+            data.extend_from_slice(&dest_amount_per_unit.to_le_bytes());
+
+            self.compute_sha256(&data)
+        }
+
+        /// Order recorded under `fingerprint`, while it can still be filled
+        #[cfg(feature = "periphery")]
+        fn active_duplicate(&self, fingerprint: &[u8; 32]) -> Option<[u8; 32]> {
+            let order_id = self.periphery.active_fingerprints.get(fingerprint)?;
+            let order = self.orders.get(&order_id)?;
+            if Self::is_terminal(&order) || self.env().block_number() >= order.timelock {
+                return None;
+            }
+            Some(order_id)
+        }
+
+        /// Duplicate guard for a new order; returns the fingerprint `on_order_created` links
+        #[cfg(feature = "periphery")]
+        fn admit_order(
+            &self,
+            maker: &Address,
+            total_amount: Balance,
+            hashlock: &[u8; 32],
+            dest_chain: u32,
+            dest_amount_per_unit: Balance,
+        ) -> Result<[u8; 32], Error> {
+            let fingerprint = self.params_fingerprint(
+                maker,
+                total_amount,
+                hashlock,
+                dest_chain,
+                dest_amount_per_unit,
+            );
+            if self.periphery.reject_duplicate_orders
+                && self.active_duplicate(&fingerprint).is_some()
+            {
+                return Err(Error::DuplicateOrder);
+            }
+            Ok(fingerprint)
+        }
+
+        #[cfg(feature = "periphery")]
+        fn on_order_created(
+            &mut self,
+            order_id: [u8; 32],
+            order: &PartialFillOrder,
+            fingerprint: [u8; 32],
+            group: Option<([u8; 32], Vec<[u8; 32]>)>,
+        ) {
+            self.index_order(order_id, order);
+            self.log_update(Update::OrderCreated { order_id });
+            self.periphery.active_fingerprints.insert(&fingerprint, &order_id);
+            self.periphery.order_fingerprints.insert(&order_id, &fingerprint);
+            if let Some((group_id, mut members)) = group {
+                members.push(order_id);
+                self.periphery.group_orders.insert(&group_id, &members);
+            }
+        }
+
+        /// Record that `account` proved control of its mapping in the current block
+        #[cfg(feature = "periphery")]
+        fn attest_mapping(&mut self, account: Address) {
+            let attested_at_block = self.env().block_number();
+            let attestation = MappingAttestation {
+                attested_at_block,
+                valid_until_block: self
+                    .periphery
+                    .mapping_attestation_ttl
+                    .map(|ttl| attested_at_block.saturating_add(ttl)),
+            };
+            self.periphery.mapping_attestations.insert(account, &attestation);
+
+            self.env().emit_event(MappingAttested {
+                account,
+                attested_at_block,
+                valid_until_block: attestation.valid_until_block,
+            });
+        }
+
+        /// Store `account`'s mapping, limited to one update per `mapping_cooldown_blocks`
+        /// after the first
+        #[cfg(feature = "periphery")]
+        fn set_mapping(
+            &mut self,
+            account: Address,
+            cross_address: CrossChainAddress,
+        ) -> Result<(), Error> {
+            let current_block = self.env().block_number();
+            if let Some(last_update) = self.periphery.last_mapping_update.get(&account) {
+                if current_block < last_update.saturating_add(self.periphery.mapping_cooldown_blocks) {
+                    return Err(Error::MappingCooldownActive);
+                }
+            }
+
+            self.periphery.address_mappings.insert(account, &cross_address);
+            self.bump_state_version();
+            self.id_list_insert(IdList::MappedAccounts, Self::account_key(&account));
+            self.periphery.last_mapping_update.insert(account, &current_block);
+            // A proof covered the previous address only
+            self.periphery.mapping_attestations.remove(&account);
+
+            self.env().emit_event(AddressMapped {
+                account,
+                cross_address,
+            });
+
+            Ok(())
+        }
+
+        /// Drop the indices and side records of an order `cleanup_order` purged. Group
+        /// membership and the taker fill index are kept; their readers skip missing ids.
+        #[cfg(feature = "periphery")]
+        fn on_order_cleaned(&mut self, order_id: [u8; 32], order: &PartialFillOrder) {
+            self.forget_fingerprint(order_id);
+            self.periphery.order_tags.remove(&order_id);
+
+            self.id_list_remove(IdList::MakerOrders(order.maker), order_id);
+            self.id_list_remove(IdList::SwapOrders(order.swap_id), order_id);
+            self.id_list_remove(IdList::ActiveOrders, order_id);
+            self.id_list_remove(IdList::ArchivedOrders, order_id);
+            self.update_corridor_depth(order_id, order, None);
+        }
+
+        /// Runs after the fill and the updated order are stored
+        #[cfg(feature = "periphery")]
+        fn on_fill_created(
+            &mut self,
+            fill_id: [u8; 32],
+            fill: &FillExecution,
+            order: &PartialFillOrder,
+            dest_amount: Balance,
+        ) {
+            self.track_pending_fill(fill_id);
+            let taker_fill_count = self.periphery.taker_fill_count.get(&fill.taker).unwrap_or(0);
+            self.periphery.taker_fills.insert((fill.taker, taker_fill_count), &fill_id);
+            self.periphery.taker_fill_count.insert(&fill.taker, &(taker_fill_count + 1));
+            if Self::is_terminal(order) {
+                self.archive_order(fill.order_id);
+            }
+            self.sync_corridor_depth(fill.order_id, order);
+            if let Some(created_at) = self.order_created_at.get(&fill.order_id) {
+                let blocks = self.env().block_number().saturating_sub(created_at);
+                self.record_corridor_latency(order, |latency| latency.with_fill(blocks));
+            }
+
+            self.notify_stats_consumers(
+                STATS_EVENT_FILL,
+                (order.source_chain, order.dest_chain, fill.fill_amount, dest_amount),
+            );
+        }
+
+        /// Runs after the payout of a withdrawn fill
+        #[cfg(feature = "periphery")]
+        fn on_fill_withdrawn(
+            &mut self,
+            fill_id: [u8; 32],
+            fill: &FillExecution,
+            order: &PartialFillOrder,
+        ) {
+            self.untrack_pending_fill(fill_id);
+            self.log_update(Update::FillSettled { order_id: fill.order_id, fill_id, withdrawn: true });
+            if let Some(filled_at) = self.fill_created_at.get(&fill_id) {
+                let blocks = self.env().block_number().saturating_sub(filled_at);
+                self.record_corridor_latency(order, |latency| latency.with_settle(blocks));
+            }
+
+            let dest_amount = Self::compute_dest_amount(order, self.fill_rate(&fill_id, order), fill.fill_amount);
+            self.notify_stats_consumers(
+                STATS_EVENT_WITHDRAW,
+                (order.source_chain, order.dest_chain, fill.fill_amount, dest_amount),
+            );
+        }
+
+        /// Add a sample to the order's corridor latency; dropped if a sum would overflow
+        #[cfg(feature = "periphery")]
+        fn record_corridor_latency(
+            &mut self,
+            order: &PartialFillOrder,
+            add: impl FnOnce(CorridorLatency) -> Option<CorridorLatency>,
+        ) {
+            let corridor = (order.source_chain, order.dest_chain);
+            let latency = self.periphery.corridor_latencies.get(corridor).unwrap_or_default();
+            if let Some(latency) = add(latency) {
+                self.periphery.corridor_latencies.insert(corridor, &latency);
+            }
+        }
+
+        /// Runs after the refunded order is stored. A refund on a fully filled order
+        /// leaves part of it unfilled again, so it moves back to the active list.
+        #[cfg(feature = "periphery")]
+        fn on_fill_refunded(
+            &mut self,
+            fill_id: [u8; 32],
+            order_id: [u8; 32],
+            order: &PartialFillOrder,
+        ) {
+            self.untrack_pending_fill(fill_id);
+            self.log_update(Update::FillSettled { order_id, fill_id, withdrawn: false });
+            if !Self::is_terminal(order) {
+                self.id_list_remove(IdList::ArchivedOrders, order_id);
+                self.index_order(order_id, order);
+            }
+        }
+
+        #[cfg(feature = "periphery")]
+        fn log_reveal(&mut self, hashlock: [u8; 32], secret: [u8; 32]) {
+            let reveal_index = self.periphery.reveal_counter;
+            self.periphery.reveal_log.insert(&reveal_index, &(hashlock, secret));
+            self.periphery.reveal_counter += 1;
+            self.log_update(Update::Reveal { reveal_index, hashlock, secret });
+        }
+
+        #[cfg(feature = "periphery")]
+        fn log_update(&mut self, update: Update) {
+            self.periphery.update_log.insert(&self.periphery.event_nonce, &update);
+            self.periphery.event_nonce += 1;
+        }
+
+        /// Move `state_root_hint`; called wherever exported state is written
+        #[cfg(feature = "periphery")]
+        fn bump_state_version(&mut self) {
+            self.periphery.state_version += 1;
+        }
+
+        #[cfg(not(feature = "periphery"))]
+        fn bump_state_version(&mut self) {}
+    }
+
+    /// Records `IEventConsumer` calls in off-chain tests
+    #[cfg(all(test, feature = "periphery"))]
+    mod consumer_mock {
+        use super::*;
+        use std::cell::RefCell;
+
+        thread_local! {
+            static CALLS: RefCell<Vec<(Address, u8, Vec<u8>)>> = RefCell::new(Vec::new());
+        }
+
+        pub fn record(consumer: Address, event_type: u8, data: Vec<u8>) {
+            CALLS.with(|c| c.borrow_mut().push((consumer, event_type, data)));
+        }
+
+        pub fn calls() -> Vec<(Address, u8, Vec<u8>)> {
+            CALLS.with(|c| c.borrow().clone())
+        }
+    }
+
+    /// Records XCM transfers in place of executing them in off-chain tests
+    #[cfg(all(feature = "xcm", test))]
+    mod xcm_mock {
+        use super::*;
+        use std::cell::RefCell;
+
+        thread_local! {
+            static SENT: RefCell<Vec<(u32, [u8; 32], Balance)>> = RefCell::new(Vec::new());
+        }
+
+        pub fn send(para_id: u32, beneficiary: [u8; 32], amount: Balance) {
+            SENT.with(|s| s.borrow_mut().push((para_id, beneficiary, amount)));
+        }
+
+        /// Transfers recorded so far, as `(para_id, beneficiary, amount)`
+        pub fn sent() -> Vec<(u32, [u8; 32], Balance)> {
+            SENT.with(|s| s.borrow().clone())
+        }
+    }
+
+    /// In-memory PSP22 ledger standing in for token contracts in off-chain tests
+    #[cfg(all(feature = "psp22", test))]
+    mod psp22_mock {
+        use super::*;
+        use std::cell::RefCell;
+
+        thread_local! {
+            static BALANCES: RefCell<Vec<((Address, Address), Balance)>> = RefCell::new(Vec::new());
+        }
+
+        pub fn balance_of(token: Address, owner: Address) -> Balance {
+            BALANCES.with(|b| {
+                b.borrow()
+                    .iter()
+                    .find(|(key, _)| *key == (token, owner))
+                    .map(|(_, v)| *v)
+                    .unwrap_or(0)
+            })
+        }
+
+        pub fn mint(token: Address, owner: Address, value: Balance) {
+            set_balance(token, owner, balance_of(token, owner) + value);
+        }
+
+        pub fn transfer(token: Address, from: Address, to: Address, value: Balance) -> Result<(), Error> {
+            let from_balance = balance_of(token, from);
+            if from_balance < value {
+                return Err(Error::TransferFailed);
+            }
+            set_balance(token, from, from_balance - value);
+            set_balance(token, to, balance_of(token, to) + value);
+            Ok(())
+        }
+
+        fn set_balance(token: Address, owner: Address, value: Balance) {
+            BALANCES.with(|b| {
+                let mut balances = b.borrow_mut();
+                match balances.iter_mut().find(|(key, _)| *key == (token, owner)) {
+                    Some(entry) => entry.1 = value,
+                    None => balances.push(((token, owner), value)),
+                }
+            })
+        }
+    }
+
+    /// Storage-access benchmarks for the heaviest messages.
+    ///
+    /// ink! has no native weight benchmarking and no per-message weight
+    /// attributes, so these measure storage reads/writes in the off-chain
+    /// engine and convert them into a ref-time estimate with the same
+    /// per-access costs FRAME uses for RocksDB. Run with `--nocapture` to print
+    /// the table used to size SDK gas limits.
+    #[cfg(test)]
+    mod benchmarks {
+        use super::*;
+
+        type TestEnv = ink::env::DefaultEnvironment;
+
+        const BASE_WEIGHT: u64 = 5_000_000_000;
+        const READ_WEIGHT: u64 = 25_000_000;
+        const WRITE_WEIGHT: u64 = 100_000_000;
+
+        const FILL_COUNTS: [u32; 4] = [0, 1, 5, 10];
+        const CROSS_ADDRESS_LENS: [usize; 3] = [0, 32, 128];
+
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        struct Estimate {
+            reads: usize,
+            writes: usize,
+        }
+
+        impl Estimate {
+            fn weight(&self) -> u64 {
+                BASE_WEIGHT + READ_WEIGHT * self.reads as u64 + WRITE_WEIGHT * self.writes as u64
+            }
+        }
+
+        fn storage_rw() -> (usize, usize) {
+            let contract = ink::env::test::callee::<TestEnv>();
+            ink::env::test::get_contract_storage_rw::<TestEnv>(&contract)
+        }
+
+        /// Storage accesses performed by `call` alone
+        fn measure<R>(call: impl FnOnce() -> R) -> Estimate {
+            let (reads_before, writes_before) = storage_rw();
+            call();
+            let (reads_after, writes_after) = storage_rw();
+            Estimate {
+                reads: reads_after - reads_before,
+                writes: writes_after - writes_before,
+            }
+        }
+
+        thread_local! {
+            static NONCE: core::cell::Cell<u8> = core::cell::Cell::new(0);
+        }
+
+        /// Fresh 32-byte seed for swap ids and secrets; off-chain contracts in one test share storage
+        fn next_seed() -> [u8; 32] {
+            NONCE.with(|n| {
+                n.set(n.get().wrapping_add(1));
+                [n.get(); 32]
+            })
+        }
+
+        fn timelock() -> BlockNumber {
+            ink::env::block_number::<TestEnv>() + 400
+        }
+
+        /// Contract holding one order with `fills` fills taken by bob
+        fn setup(fills: u32, cross_len: usize) -> (PolkadotPartialFills, [u8; 32], Vec<[u8; 32]>) {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let mut contract = PolkadotPartialFills::new();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(100_000u128.into());
+
+            let cross = (cross_len > 0).then(|| vec![0xcd; cross_len]);
+            let order_id = contract.create_partial_fill_order(
+                100_000, 10, [0x01; 32], timelock(), next_seed(), 1, 2,
+                1_000_000_000_000, true, 20, cross.clone(), cross
+            ).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let fill_ids = (0..fills)
+                .map(|i| contract.fill_order(order_id, 100 + i as Balance, accounts.bob).unwrap())
+                .collect();
+            (contract, order_id, fill_ids)
+        }
+
+        fn bench_create(cross_len: usize) -> Estimate {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let (mut contract, _, _) = setup(0, 0);
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            let cross = (cross_len > 0).then(|| vec![0xcd; cross_len]);
+            let (timelock, swap_id) = (timelock(), next_seed());
+            measure(|| contract.create_partial_fill_order(
+                100_000, 10, [0x01; 32], timelock, swap_id, 1, 2,
+                1_000_000_000_000, true, 20, cross.clone(), cross
+            ).unwrap())
+        }
+
+        fn bench_fill(fills: u32, cross_len: usize) -> Estimate {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let (mut contract, order_id, _) = setup(fills, cross_len);
+            measure(|| contract.fill_order(order_id, 50, accounts.charlie).unwrap())
+        }
+
+        fn bench_withdraw(fills: u32, cross_len: usize) -> Estimate {
+            let (mut contract, order_id, _) = setup(0, cross_len);
+            let secret = next_seed();
+            let mut order = contract.orders.get(&order_id).unwrap();
+            order.hashlock = contract.compute_sha256(&secret);
+            contract.orders.insert(&order_id, &order);
+
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let mut fill_ids = Vec::new();
+            for i in 0..fills.max(1) {
+                fill_ids.push(contract.fill_order(order_id, 100 + i as Balance, accounts.bob).unwrap());
+            }
+            measure(|| contract.withdraw_fill(fill_ids[0], secret, None, None).unwrap())
+        }
+
+        fn bench_refund(fills: u32, cross_len: usize) -> Estimate {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let (mut contract, order_id, fill_ids) = setup(fills.max(1), cross_len);
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            let expiry = contract.get_order(order_id).unwrap().timelock;
+            ink::env::test::set_block_number::<TestEnv>(expiry);
+            measure(|| contract.refund_fill(fill_ids[0], None).unwrap())
+        }
+
+        #[cfg(feature = "periphery")]
+        fn bench_batch_cancel(orders: u32, cross_len: usize) -> Estimate {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let (mut contract, first, _) = setup(0, cross_len);
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            let mut order_ids = vec![first];
+            for _ in 1..orders.max(1) {
+                order_ids.push(contract.create_partial_fill_order(
+                    100_000, 10, [0x01; 32], timelock(), next_seed(), 1, 2,
+                    1_000_000_000_000, true, 20, None, None
+                ).unwrap());
+            }
+            measure(|| contract.batch_cancel_orders(order_ids).unwrap())
+        }
+
+        fn run_all() -> Vec<(&'static str, u32, usize, Estimate)> {
+            let mut results = Vec::new();
+            for cross_len in CROSS_ADDRESS_LENS {
+                results.push(("create_partial_fill_order", 0, cross_len, bench_create(cross_len)));
+                for fills in FILL_COUNTS {
+                    results.push(("fill_order", fills, cross_len, bench_fill(fills, cross_len)));
+                    results.push(("withdraw_fill", fills, cross_len, bench_withdraw(fills, cross_len)));
+                    results.push(("refund_fill", fills, cross_len, bench_refund(fills, cross_len)));
+                    #[cfg(feature = "periphery")]
+                    results.push(("batch_cancel_orders", fills, cross_len, bench_batch_cancel(fills, cross_len)));
+                }
+            }
+            results
+        }
+
+        #[ink::test]
+        fn benchmarks_are_deterministic() {
+            ink::env::test::set_block_number::<TestEnv>(100);
+            let first = run_all();
+            let second = run_all();
+            assert_eq!(first, second);
+
+            for (name, fills, cross_len, estimate) in first.iter() {
+                assert!(estimate.reads > 0, "{name} fills={fills} cross={cross_len} performed no reads");
+                assert!(estimate.writes > 0, "{name} fills={fills} cross={cross_len} performed no writes");
+                assert!(estimate.weight() > BASE_WEIGHT);
+            }
+        }
+
+        /// Bytes a fill occupies in contract storage, its preimage slot included
+        fn stored_fill_bytes(contract: &PolkadotPartialFills, fill_id: [u8; 32]) -> u32 {
+            use ink::storage::traits::StorageKey;
+            let slot = |key: u32| ink::env::contains_contract_storage(&(key, fill_id)).unwrap_or(0);
+            slot(StorageKey::key(&contract.fills)) + slot(StorageKey::key(&contract.fill_preimages))
+        }
+
+        #[ink::test]
+        fn fill_storage_is_compact() {
+            ink::env::test::set_block_number::<TestEnv>(100);
+            ink::env::test::set_block_timestamp::<TestEnv>(1_700_000_000_000);
+            let (mut contract, order_id, fill_ids) = setup(1, 0);
+
+            // `FillExecution` is what each fill used to store in full
+            let open = contract.get_fill(fill_ids[0]).unwrap().encoded_size() as u32;
+            let open_packed = stored_fill_bytes(&contract, fill_ids[0]);
+            assert!(open_packed * 2 <= open);
+
+            let secret = next_seed();
+            let mut order = contract.orders.get(&order_id).unwrap();
+            order.hashlock = contract.compute_sha256(&secret);
+            contract.orders.insert(&order_id, &order);
+            contract.withdraw_fill(fill_ids[0], secret, None, None).unwrap();
+
+            let withdrawn = contract.get_fill(fill_ids[0]).unwrap().encoded_size() as u32;
+            let withdrawn_packed = stored_fill_bytes(&contract, fill_ids[0]);
+            assert!(withdrawn_packed < withdrawn);
+            // Withdrawal only flips a flag bit and adds the preimage slot
+            assert_eq!(withdrawn_packed, open_packed + 32);
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn batch_cancel_scales_with_order_count() {
+            ink::env::test::set_block_number::<TestEnv>(100);
+            let one = bench_batch_cancel(1, 0).weight();
+            let five = bench_batch_cancel(5, 0).weight();
+            let ten = bench_batch_cancel(10, 0).weight();
+            assert!(one < five && five < ten);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use polkadot_swap_quote::RATE_SCALE;
+
+        type TestEnv = ink::env::DefaultEnvironment;
+
+        #[ink::test]
+        fn test_partial_fill_order_creation() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let result = contract.create_partial_fill_order(
+                1000,                    // total_amount
+                100,                     // min_fill_amount
+                [0x01; 32],             // hashlock
+                500,                     // timelock
+                [0x02; 32],             // swap_id
+                1,                       // source_chain
+                2,                       // dest_chain
+                1_000_000_000_000,      // dest_amount_per_unit (1:1 ratio scaled)
+                true,                    // allow_partial_fills
+                5,                       // max_fills
+                None,                    // sender_cross_address
+                None,                    // receiver_cross_address
+            );
+
+            assert!(result.is_ok());
+            
+            let order_id = result.unwrap();
+            assert!(contract.order_exists(order_id));
+            
+            let order = contract.get_order(order_id).unwrap();
+            assert_eq!(order.maker, accounts.alice);
+            assert_eq!(order.total_amount, 1000); // gross; fees are charged per fill
+            assert_eq!(order.filled_amount, 0);
+            assert_eq!(order.min_fill_amount, 100);
+            assert!(order.allow_partial_fills);
+            assert_eq!(order.max_fills, 5);
+            assert_eq!(order.current_fills, 0);
+        }
+
+        #[ink::test]
+        fn test_partial_fill_execution() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            
+            // Create order
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2, 
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            // Fill order partially
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let fill_result = contract.fill_order(order_id, 200, accounts.charlie);
+            assert!(fill_result.is_ok());
+
+            let fill_id = fill_result.unwrap();
+            let fill = contract.get_fill(fill_id).unwrap();
+            assert_eq!(fill.taker, accounts.bob);
+            assert_eq!(fill.receiver, accounts.charlie);
+            assert_eq!(fill.fill_amount, 200);
+            assert!(!fill.withdrawn);
+            assert!(!fill.refunded);
+
+            // Check order state
+            let order = contract.get_order(order_id).unwrap();
+            assert_eq!(order.filled_amount, 200);
+            assert_eq!(order.current_fills, 1);
+            assert_eq!(contract.get_remaining_amount(order_id), 800); // 1000 - 200
+        }
+
+        #[ink::test]
+        fn test_multiple_partial_fills() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            
+            // Create order
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2, 
+                1_000_000_000_000, true, 3, None, None
+            ).unwrap();
+
+            // First fill
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let fill1_id = contract.fill_order(order_id, 200, accounts.charlie).unwrap();
+
+            // Second fill
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            let fill2_id = contract.fill_order(order_id, 300, accounts.bob).unwrap();
+
+            // Check order state
+            let order = contract.get_order(order_id).unwrap();
+            assert_eq!(order.filled_amount, 500); // 200 + 300
+            assert_eq!(order.current_fills, 2);
+            assert_eq!(contract.get_remaining_amount(order_id), 500); // 1000 - 500
+
+            // Check fills tracking
+            let order_fills = contract.get_order_fills(order_id, 0, 10).unwrap().items;
+            assert_eq!(order_fills.len(), 2);
+            assert!(order_fills.contains(&fill1_id));
+            assert!(order_fills.contains(&fill2_id));
+        }
+
+        #[ink::test]
+        fn test_fill_withdrawal_with_secret() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            
+            // Create order
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2, 
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            // Fill order
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let fill_id = contract.fill_order(order_id, 200, accounts.charlie).unwrap();
+
+            // Withdraw with correct secret
+            let withdraw_result = contract.withdraw_fill(fill_id, secret, None, None);
+            assert!(withdraw_result.is_ok());
+
+            let fill = contract.get_fill(fill_id).unwrap();
+            assert!(fill.withdrawn);
+            assert_eq!(fill.preimage, Some(secret));
+        }
+
+        #[ink::test]
+        fn test_fill_receiver() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let native = |account| -> Balance {
+                ink::env::test::get_account_balance::<TestEnv>(account).unwrap().try_into().unwrap()
+            };
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            // Bob fills once for Charlie, once for himself through the zero address
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let for_charlie = contract.fill_order(order_id, 200, accounts.charlie).unwrap();
+            let event = ink::env::test::recorded_events().last().unwrap();
+            assert_eq!(event.topics[0][..], signature_of::<OrderFilled>()[..]);
+            let (_, _, taker, receiver) =
+                <([u8; 32], [u8; 32], Address, Address)>::decode(&mut &event.data[..]).unwrap();
+            assert_eq!((taker, receiver), (accounts.bob, accounts.charlie));
+            let for_bob = contract.fill_order(order_id, 300, Address::zero()).unwrap();
+            let fill = contract.get_fill(for_charlie).unwrap();
+            assert_eq!((fill.taker, fill.receiver), (accounts.bob, accounts.charlie));
+            let fill = contract.get_fill(for_bob).unwrap();
+            assert_eq!((fill.taker, fill.receiver), (accounts.bob, accounts.bob));
+
+            // Only the taker withdraws, even a fill paying someone else
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            assert_eq!(
+                contract.withdraw_fill(for_charlie, secret, None, None),
+                Err(Error::UnauthorizedWithdraw)
+            );
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let (bob, charlie) = (native(accounts.bob), native(accounts.charlie));
+            contract.withdraw_fill(for_charlie, secret, None, None).unwrap();
+            assert_eq!(native(accounts.charlie) - charlie, 200);
+            assert_eq!(native(accounts.bob), bob);
+
+            // The batch withdrawal pays the caller, so it leaves fills for others alone
+            let for_django = contract.fill_order(order_id, 100, accounts.django).unwrap();
+            let bob = native(accounts.bob);
+            assert_eq!(contract.withdraw_order_fills(order_id, secret, None), Ok(1));
+            assert_eq!(native(accounts.bob) - bob, 300);
+            assert!(contract.get_fill(for_bob).unwrap().withdrawn);
+            assert!(!contract.get_fill(for_django).unwrap().withdrawn);
+            assert_eq!(contract.withdraw_order_fills(order_id, secret, None), Err(Error::FillNotFound));
+        }
+
+        #[ink::test]
+        fn test_validation_rejects_consistently() {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            // (total_amount, min_fill_amount, timelock, source_chain, dest_chain, max_fills, expected)
+            let cases: [(Balance, Balance, BlockNumber, u32, u32, u32, Error); 7] = [
+                (1000, 100, 100, 1, 2, 5, Error::InvalidTimelock),
+                (1000, 100, 150, 1, 2, 5, Error::TimelockTooShort),
+                (1000, 100, 20_000, 1, 2, 5, Error::TimelockTooLong),
+                (1000, 100, 500, 1, 1, 5, Error::InvalidChainId),
+                (0, 0, 500, 1, 2, 5, Error::InvalidFillAmount),
+                (1000, 1001, 500, 1, 2, 5, Error::InvalidFillAmount),
+                (1000, 100, 500, 1, 2, 0, Error::InvalidFillAmount),
+            ];
+
+            for (total, min_fill, timelock, source, dest, max_fills, expected) in cases {
+                let mut contract = PolkadotPartialFills::new();
+                let result = contract.create_partial_fill_order(
+                    total, min_fill, [0x01; 32], timelock, [0x02; 32], source, dest,
+                    1_000_000_000_000, true, max_fills, None, None
+                );
+                assert_eq!(result, Err(expected));
+            }
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_reveal_log_catch_up() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let mut expected = Vec::new();
+            for i in 0..4u8 {
+                let secret = [0x10 + i; 32];
+                let hashlock = contract.compute_sha256(&secret);
+
+                ink::env::test::set_caller::<TestEnv>(accounts.alice);
+                ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+                let order_id = contract.create_partial_fill_order(
+                    1000, 100, hashlock, 500, [i; 32], 1, 2,
+                    1_000_000_000_000, true, 5, None, None
+                ).unwrap();
+
+                ink::env::test::set_caller::<TestEnv>(accounts.bob);
+                let fill_id = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+                contract.withdraw_fill(fill_id, secret, None, None).unwrap();
+                expected.push((i as u64, hashlock, secret));
+            }
+
+            assert_eq!(contract.get_reveal_counter(), 4);
+            assert_eq!(contract.get_revealed_secret(expected[1].1), Some(expected[1].2));
+            assert_eq!(contract.get_reveals_since(2, 10).unwrap().items, expected[2..].to_vec());
+            let first = contract.get_reveals_since(0, 2).unwrap();
+            assert_eq!(first.items, expected[..2].to_vec());
+            assert_eq!((first.next_offset, first.total), (Some(2), 4));
+            assert!(contract.get_reveals_since(4, 10).unwrap().items.is_empty());
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_updates_since_interleaves_record_kinds() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_block_number::<TestEnv>(100);
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+
+            let create = |contract: &mut PolkadotPartialFills, swap_id: [u8; 32]| {
+                ink::env::test::set_caller::<TestEnv>(accounts.alice);
+                ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+                contract.create_partial_fill_order(
+                    1000, 100, hashlock, 500, swap_id, 1, 2,
+                    1_000_000_000_000, true, 5, None, None
+                ).unwrap()
+            };
+            let first = create(&mut contract, [0x01; 32]);
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let withdrawn = contract.fill_order(first, 200, accounts.bob).unwrap();
+            let refunded = contract.fill_order(first, 200, accounts.bob).unwrap();
+            let second = create(&mut contract, [0x02; 32]);
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            contract.withdraw_fill(withdrawn, secret, None, None).unwrap();
+            ink::env::test::set_block_number::<TestEnv>(500);
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.refund_fill(refunded, None).unwrap();
+
+            let expected = vec![
+                (0, Update::OrderCreated { order_id: first }),
+                (1, Update::OrderCreated { order_id: second }),
+                (2, Update::Reveal { reveal_index: 0, hashlock, secret }),
+                (3, Update::FillSettled { order_id: first, fill_id: withdrawn, withdrawn: true }),
+                (4, Update::FillSettled { order_id: first, fill_id: refunded, withdrawn: false }),
+            ];
+            assert_eq!(contract.get_event_nonce(), 5);
+            assert_eq!(contract.get_updates_since(0, 10).unwrap().items, expected);
+
+            // Resuming from each page's cursor walks the log exactly once
+            let mut cursor: Cursor = 0;
+            let mut seen = Vec::new();
+            loop {
+                let page = contract.get_updates_since(cursor, 2).unwrap();
+                seen.extend(page.items);
+                match page.next_offset {
+                    Some(next) => cursor = next,
+                    None => break,
+                }
+            }
+            assert_eq!(seen, expected);
+            assert!(contract.get_updates_since(5, 10).unwrap().items.is_empty());
+            assert_eq!(
+                contract.get_updates_since(0, MAX_UPDATES_PAGE + 1).err(),
+                Some(Error::PageTooLarge)
+            );
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_order_full_state() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                2_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            contract.set_order_tags(order_id, b"otc".to_vec()).unwrap();
+
+            let mut fill_ids = Vec::new();
+            for (taker, amount) in [(accounts.bob, 100), (accounts.charlie, 200), (accounts.django, 300)] {
+                ink::env::test::set_caller::<TestEnv>(taker);
+                fill_ids.push(contract.fill_order(order_id, amount, taker).unwrap());
+            }
+
+            let state = contract.get_order_full_state(order_id).unwrap();
+            assert_eq!(state.order, contract.get_order(order_id).unwrap());
+            assert_eq!(state.fills.len(), 3);
+            for (fill, fill_id) in state.fills.iter().zip(fill_ids.iter()) {
+                assert_eq!(Some(fill.clone()), contract.get_fill(*fill_id));
+            }
+            assert_eq!(state.timing_info.len(), 3);
+            assert_eq!(state.timing_info[2], contract.get_fill_timing_info(fill_ids[2]).unwrap());
+            assert_eq!(state.timing_info[0].blocks_remaining, 400);
+            assert_eq!(Some(state.stats.clone()), contract.get_order_fill_price_stats(order_id));
+            assert_eq!(state.stats.fill_count, 3);
+            assert_eq!(state.stats.total_source_amount, 600);
+            assert_eq!(state.stats.total_dest_amount, 1200);
+            assert_eq!(state.tags, Some(b"otc".to_vec()));
+            assert_eq!(state.remaining, contract.get_remaining_amount(order_id));
+            assert_eq!(state.remaining, 400);
+            assert_eq!(state.status, OrderStatus::PartiallyFilled);
+
+            assert!(contract.get_order_full_state([0xff; 32]).is_none());
+        }
+
+        #[cfg(feature = "psp22")]
+        #[ink::test]
+        fn test_protocol_fee_in_psp22_token() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let stablecoin = accounts.frank;
+            psp22_mock::mint(stablecoin, accounts.alice, 50);
+            ink::env::test::set_callee::<TestEnv>(accounts.eve);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.set_fee_token(Some(stablecoin), BlockNumber::MAX).unwrap();
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            // Full native amount escrowed, fee pulled in the stablecoin
+            let order = contract.get_order(order_id).unwrap();
+            assert_eq!(order.total_amount, 1000);
+            assert_eq!(order.fee, 3);
+            assert_eq!(contract.get_protocol_fees(), 0);
+            assert_eq!(contract.get_psp22_protocol_fees(stablecoin), 3);
+            assert_eq!(psp22_mock::balance_of(stablecoin, accounts.alice), 47);
+            assert_eq!(contract.get_fill_fee(order_id, 1000), Some(0));
+
+            // Admin drains the token fees
+            contract.withdraw_psp22_fees(stablecoin, BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_psp22_protocol_fees(stablecoin), 0);
+            assert_eq!(psp22_mock::balance_of(stablecoin, accounts.alice), 50);
+
+            // Maker without stablecoin cannot create an order
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let result = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x03; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            );
+            assert_eq!(result, Err(Error::TransferFailed));
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.withdraw_psp22_fees(stablecoin, BlockNumber::MAX), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn test_timelock_bounded_by_destination_chain() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            // 800 - 100 = 700 blocks, 4200s at 6s blocks: fine while chain 2 is unregistered
+            assert!(contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 800, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).is_ok());
+            assert_eq!(contract.estimate_timelock_seconds(700), 4200);
+
+            contract.register_chain(2, ChainConfig { max_counterpart_timelock: 3600 }, BlockNumber::MAX).unwrap();
+
+            let result = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 800, [0x03; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            );
+            assert_eq!(result, Err(Error::TimelockIncompatibleWithDestination));
+
+            // Exactly at the destination maximum is accepted
+            assert!(contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 700, [0x04; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).is_ok());
+
+            // Faster blocks shrink the estimate back under the limit
+            contract.set_block_time_ms(5000, BlockNumber::MAX).unwrap();
+            assert!(contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 800, [0x05; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).is_ok());
+        }
+
+        #[ink::test]
+        fn test_order_hash_tracks_state_changes() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            let order = contract.get_order(order_id).unwrap();
+            let hash_before = contract.compute_order_hash(order_id).unwrap();
+            assert_eq!(hash_before, contract.compute_sha256(&order.encode()));
+            assert!(contract.verify_order_hash(order_id, hash_before));
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            contract.fill_order(order_id, 200, accounts.bob).unwrap();
+
+            let hash_after = contract.compute_order_hash(order_id).unwrap();
+            assert_ne!(hash_before, hash_after);
+            assert!(!contract.verify_order_hash(order_id, hash_before));
+            assert!(contract.verify_order_hash(order_id, hash_after));
+
+            assert_eq!(contract.compute_order_hash([0xff; 32]), None);
+            assert!(!contract.verify_order_hash([0xff; 32], hash_after));
+        }
+
+        #[ink::test]
+        fn test_event_topic_layout() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let fill_id = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            contract.withdraw_fill(fill_id, secret, None, None).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.cancel_order(order_id).unwrap();
+
+            // Snapshot of topic counts: the event signature plus each indexed field
+            let events: Vec<_> = ink::env::test::recorded_events().collect();
+            let topic_counts: Vec<usize> = events.iter().map(|e| e.topics.len()).collect();
+            assert_eq!(topic_counts, vec![4, 4, 4, 2]);
+
+            // The secret is data only, never a topic
+            let withdrawn = &events[2];
+            assert!(withdrawn.topics.iter().all(|t| t[..] != secret[..]));
+            assert_eq!(contract.get_event_layout_version(), 3);
+        }
+
+        #[ink::test]
+        fn test_sunset_mode() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let at = |block| ink::env::test::set_block_number::<TestEnv>(block);
+            let as_caller = |caller| ink::env::test::set_caller::<TestEnv>(caller);
+            let send = |value: Balance| ink::env::test::set_value_transferred::<TestEnv>(value.into());
+            as_caller(accounts.alice);
+            send(1000);
+            at(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let create = |contract: &mut PolkadotPartialFills, swap_id| {
+                contract.create_partial_fill_order(
+                    1000, 100, hashlock, 500, swap_id, 1, 2,
+                    RATE_SCALE, true, 5, None, None
+                )
+            };
+            let order_id = create(&mut contract, [0x02; 32]).unwrap();
+            as_caller(accounts.bob);
+            send(0);
+            let withdrawn = contract.fill_order(order_id, 400, accounts.bob).unwrap();
+            let refunded = contract.fill_order(order_id, 300, accounts.bob).unwrap();
+
+            // Only the admin, and only once
+            assert_eq!(contract.enter_sunset_mode(BlockNumber::MAX), Err(Error::Unauthorized));
+            as_caller(accounts.alice);
+            contract.enter_sunset_mode(BlockNumber::MAX).unwrap();
+            assert_eq!(ink::env::test::recorded_events().last().unwrap().topics[0][..], signature_of::<SunsetEntered>()[..]);
+            assert_eq!(contract.get_sunset_state(), SunsetState::Sunset);
+            assert_eq!(contract.get_health().sunset, SunsetState::Sunset);
+            assert_eq!(contract.enter_sunset_mode(BlockNumber::MAX), Err(Error::SunsetActive));
+
+            // No new business
+            send(1000);
+            assert_eq!(create(&mut contract, [0x03; 32]), Err(Error::SunsetActive));
+            let params = CreateOrderParams {
+                total_amount: 1000,
+                min_fill_amount: 100,
+                hashlock,
+                timelock: 500,
+                swap_id: [0x03; 32],
+                source_chain: 1,
+                dest_chain: 2,
+                dest_amount_per_unit: RATE_SCALE,
+                allow_partial_fills: true,
+                max_fills: 5,
+                sender_cross_address: None,
+                receiver_cross_address: None,
+                options: OrderOptions::default(),
+            };
+            assert_eq!(contract.validate_create(params, 1000), Err(Error::SunsetActive));
+            as_caller(accounts.charlie);
+            send(DEFAULT_RESERVATION_BOND);
+            assert_eq!(contract.reserve(order_id, 100), Err(Error::SunsetActive));
+
+            // Existing orders fill, settle and cancel as before
+            as_caller(accounts.bob);
+            send(0);
+            at(110);
+            let late = contract.fill_order(order_id, 100, accounts.bob).unwrap();
+            contract.withdraw_fill(withdrawn, secret, None, None).unwrap();
+            as_caller(accounts.alice);
+            assert_eq!(
+                contract.finalize_sunset(BlockNumber::MAX),
+                Err(Error::LiabilitiesOutstanding { liabilities: contract.get_total_liabilities() })
+            );
+            contract.cancel_order(order_id).unwrap();
+            at(500);
+            contract.refund_fill(refunded, None).unwrap();
+            contract.refund_fill(late, None).unwrap();
+            assert_eq!(contract.get_total_locked(), 0);
+            // Fees still owed, the withdrawn 400's, hold finalization back until swept
+            assert_eq!(
+                contract.finalize_sunset(BlockNumber::MAX),
+                Err(Error::LiabilitiesOutstanding { liabilities: 1 })
+            );
+            assert_eq!(contract.withdraw_protocol_fees(None, BlockNumber::MAX), Ok(true));
+
+            as_caller(accounts.bob);
+            assert_eq!(contract.finalize_sunset(BlockNumber::MAX), Err(Error::Unauthorized));
+            as_caller(accounts.alice);
+            contract.finalize_sunset(BlockNumber::MAX).unwrap();
+            assert_eq!(ink::env::test::recorded_events().last().unwrap().topics[0][..], signature_of::<SunsetFinalized>()[..]);
+            assert_eq!(contract.get_sunset_state(), SunsetState::Retired);
+
+            // Retired for good: views only
+            assert_eq!(contract.finalize_sunset(BlockNumber::MAX), Err(Error::ContractRetired));
+            assert_eq!(contract.enter_sunset_mode(BlockNumber::MAX), Err(Error::ContractRetired));
+            assert_eq!(contract.update_admin(accounts.bob, BlockNumber::MAX), Err(Error::ContractRetired));
+            assert_eq!(contract.set_order_fill_paused(order_id, true), Err(Error::ContractRetired));
+            assert_eq!(contract.withdraw_credit(1), Err(Error::ContractRetired));
+            assert_eq!(create(&mut contract, [0x04; 32]), Err(Error::ContractRetired));
+            assert!(contract.get_fill(withdrawn).unwrap().withdrawn);
+            assert_eq!(contract.get_health().sunset, SunsetState::Retired);
+        }
+
+        #[ink::test]
+        fn test_emergency_pause() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let at = |block| ink::env::test::set_block_number::<TestEnv>(block);
+            let as_caller = |caller| ink::env::test::set_caller::<TestEnv>(caller);
+            let send = |value: Balance| ink::env::test::set_value_transferred::<TestEnv>(value.into());
+            as_caller(accounts.alice);
+            send(1000);
+            at(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let create = |contract: &mut PolkadotPartialFills, swap_id| {
+                contract.create_partial_fill_order(
+                    1000, 100, hashlock, 500, swap_id, 1, 2,
+                    RATE_SCALE, true, 5, None, None
+                )
+            };
+            let order_id = create(&mut contract, [0x02; 32]).unwrap();
+            let cancelled = create(&mut contract, [0x03; 32]).unwrap();
+            as_caller(accounts.bob);
+            send(0);
+            let withdrawn = contract.fill_order(order_id, 400, accounts.bob).unwrap();
+            let refunded = contract.fill_order(order_id, 300, accounts.bob).unwrap();
+
+            // Only the admin
+            assert_eq!(contract.pause(BlockNumber::MAX), Err(Error::Unauthorized));
+            as_caller(accounts.alice);
+            contract.pause(BlockNumber::MAX).unwrap();
+            let event = ink::env::test::recorded_events().last().unwrap();
+            assert_eq!(event.topics[0][..], signature_of::<ProtocolPaused>()[..]);
+            assert!(event.data.ends_with(&(100 as BlockNumber).encode()));
+            assert!(contract.is_paused());
+            assert!(contract.get_health().paused);
+            let events_before = ink::env::test::recorded_events().count();
+            contract.pause(BlockNumber::MAX).unwrap();
+            assert_eq!(ink::env::test::recorded_events().count(), events_before);
+
+            // Creation, fills and reservations stop
+            send(1000);
+            assert_eq!(create(&mut contract, [0x04; 32]), Err(Error::ContractPaused));
+            let params = CreateOrderParams {
+                total_amount: 1000,
+                min_fill_amount: 100,
+                hashlock,
+                timelock: 500,
+                swap_id: [0x04; 32],
+                source_chain: 1,
+                dest_chain: 2,
+                dest_amount_per_unit: RATE_SCALE,
+                allow_partial_fills: true,
+                max_fills: 5,
+                sender_cross_address: None,
+                receiver_cross_address: None,
+                options: OrderOptions::default(),
+            };
+            assert_eq!(contract.validate_create(params, 1000), Err(Error::ContractPaused));
+            as_caller(accounts.bob);
+            send(0);
+            assert_eq!(contract.fill_order(order_id, 100, accounts.bob), Err(Error::ContractPaused));
+            assert_eq!(
+                contract.get_max_fillable_amount(order_id, accounts.bob),
+                Err(Error::ContractPaused)
+            );
+            as_caller(accounts.charlie);
+            send(DEFAULT_RESERVATION_BOND);
+            assert_eq!(contract.reserve(order_id, 100), Err(Error::ContractPaused));
+
+            // Every way out of escrow stays open
+            as_caller(accounts.bob);
+            send(0);
+            contract.withdraw_fill(withdrawn, secret, None, None).unwrap();
+            as_caller(accounts.alice);
+            contract.cancel_order(cancelled).unwrap();
+            at(500);
+            contract.refund_fill(refunded, None).unwrap();
+            assert!(contract.get_fill(refunded).unwrap().refunded);
+
+            // Unpausing resumes new business
+            at(510);
+            as_caller(accounts.bob);
+            assert_eq!(contract.unpause(BlockNumber::MAX), Err(Error::Unauthorized));
+            as_caller(accounts.alice);
+            contract.unpause(BlockNumber::MAX).unwrap();
+            let event = ink::env::test::recorded_events().last().unwrap();
+            assert_eq!(event.topics[0][..], signature_of::<ProtocolUnpaused>()[..]);
+            assert!(event.data.ends_with(&(510 as BlockNumber).encode()));
+            assert!(!contract.is_paused());
+            send(1000);
+            assert!(contract.create_partial_fill_order(
+                1000, 100, hashlock, 900, [0x05; 32], 1, 2,
+                RATE_SCALE, true, 5, None, None
+            ).is_ok());
+        }
+
+        #[ink::test]
+        fn test_incident_snapshot() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let withdrawn_id = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            let pending_id = contract.fill_order(order_id, 300, accounts.bob).unwrap();
+            contract.withdraw_fill(withdrawn_id, secret, None, None).unwrap();
+
+            let snapshot = contract.get_incident_snapshot(Some(order_id));
+            assert_eq!(snapshot.block, 100);
+            assert_eq!(snapshot.health, contract.get_health());
+            assert_eq!(snapshot.config.admin, contract.get_admin());
+            assert_eq!(snapshot.config.protocol_fee_bps, contract.get_protocol_fee_bps());
+            assert_eq!(snapshot.config.min_timelock, contract.get_min_timelock());
+            assert_eq!(snapshot.config.retention_blocks, contract.get_retention_blocks());
+            assert_eq!(snapshot.config.storage_version, contract.get_storage_version());
+            let report = contract.check_migration(None).unwrap();
+            assert_eq!(
+                (snapshot.accounting.order_counter, snapshot.accounting.fill_counter),
+                (report.order_counter, report.fill_counter)
+            );
+            assert_eq!(snapshot.accounting.total_locked, contract.get_total_locked());
+            assert_eq!(snapshot.accounting.total_credits, contract.get_total_credits());
+            assert_eq!(snapshot.accounting.protocol_fees, contract.get_protocol_fees());
+            assert_eq!(snapshot.accounting.total_liabilities, contract.get_total_liabilities());
+
+            let order = snapshot.order.unwrap();
+            assert_eq!(Some(order.order), contract.get_order(order_id));
+            assert_eq!(Some(order.status), contract.get_order_status(order_id));
+            assert_eq!(
+                order.fills,
+                vec![contract.get_fill(withdrawn_id).unwrap(), contract.get_fill(pending_id).unwrap()]
+            );
+            assert_eq!(order.accounting.remaining, contract.get_remaining_amount(order_id));
+            assert_eq!(order.accounting.reserved, contract.get_reserved_amount(order_id));
+            assert_eq!((order.accounting.withdrawn, order.accounting.pending), (200, 300));
+            assert_eq!(order.accounting.refunded, 0);
+            assert_eq!(order.accounting.deposits_held, contract.get_fill(pending_id).unwrap().deposit);
+
+            // The order section is only filled in for a known order
+            assert_eq!(contract.get_incident_snapshot(None).order, None);
+            assert_eq!(contract.get_incident_snapshot(Some([0x99; 32])).order, None);
+            assert_eq!(contract.get_incident_snapshot(None).health, snapshot.health);
+        }
+
+        #[ink::test]
+        fn test_staged_upgrade() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            contract.fill_order(order_id, 300, accounts.bob).unwrap();
+
+            // Every version reads its own storage back
+            let report = contract.check_migration(Some(order_id)).unwrap();
+            assert_eq!(report.storage_version, StorageVersion::CURRENT);
+            assert_eq!(report.admin, accounts.alice);
+            assert_eq!((report.order_counter, report.fill_counter), (1, 2));
+            assert_eq!((report.sample_order, report.sample_fills), (Some(order_id), 2));
+            assert_eq!(contract.check_migration(None).unwrap().sample_fills, 0);
+            assert_eq!(contract.check_migration(Some([0x99; 32])), Err(Error::OrderNotFound));
+
+            let v2 = ink::H256::from([0x02; 32]);
+            let v3 = ink::H256::from([0x03; 32]);
+
+            assert_eq!(contract.upgrade_staged(v2, BlockNumber::MAX), Err(Error::Unauthorized));
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            assert_eq!(contract.finalize_upgrade(BlockNumber::MAX), Err(Error::NoPendingProposal));
+            assert_eq!(contract.abort_upgrade(BlockNumber::MAX), Err(Error::NoPendingProposal));
+
+            // Abort: staged code is dropped without ever running
+            contract.upgrade_staged(v3, BlockNumber::MAX).unwrap();
+            assert_eq!(
+                contract.get_staged_upgrade(),
+                Some(StagedUpgrade { code_hash: v3, staged_at: 100, ready_at: 7_300, checked: false })
+            );
+            assert_eq!(contract.finalize_upgrade(BlockNumber::MAX), Err(Error::ProposalNotReady));
+            contract.abort_upgrade(BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_staged_upgrade(), None);
+            assert_eq!(contract.finalize_upgrade(BlockNumber::MAX), Err(Error::NoPendingProposal));
+            assert_eq!(
+                ink::env::test::recorded_events().last().unwrap().topics[0][..],
+                signature_of::<UpgradeAborted>()[..]
+            );
+
+            // The check only runs against an instance of the staged code; early
+            // finalization after a passing check is covered end to end
+            ink::env::test::set_block_number::<TestEnv>(200);
+            contract.upgrade_staged(v2, BlockNumber::MAX).unwrap();
+            assert_eq!(
+                contract.check_staged_upgrade(Address::from([0x22; 20]), Some(order_id), BlockNumber::MAX),
+                Err(Error::UpgradeCodeMismatch)
+            );
+            assert!(!contract.get_staged_upgrade().unwrap().checked);
+            assert_eq!(contract.finalize_upgrade(BlockNumber::MAX), Err(Error::ProposalNotReady));
+
+            // Finalize unchecked: only after the full delay, and restaging restarts it
+            ink::env::test::set_block_number::<TestEnv>(300);
+            contract.upgrade_staged(v2, BlockNumber::MAX).unwrap();
+            ink::env::test::set_block_number::<TestEnv>(7_299);
+            assert_eq!(contract.finalize_upgrade(BlockNumber::MAX), Err(Error::ProposalNotReady));
+            ink::env::test::set_block_number::<TestEnv>(7_300);
+            let events_before = ink::env::test::recorded_events().count();
+            contract.finalize_upgrade(BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_staged_upgrade(), None);
+            assert_eq!(events_since(events_before), vec![signature_of::<UpgradeFinalized>()]);
+            assert!(ink::env::test::recorded_events().last().unwrap().data.ends_with(&[0]));
+
+            let audit = contract.get_admin_audit_log(0, 10).unwrap().items;
+            let functions: Vec<u8> = audit.iter().map(|entry| entry.function_id).collect();
+            assert_eq!(
+                functions,
+                vec![
+                    admin_fn::STAGE_UPGRADE,
+                    admin_fn::STAGE_UPGRADE,
+                    admin_fn::STAGE_UPGRADE,
+                    admin_fn::STAGE_UPGRADE,
+                    admin_fn::FINALIZE_UPGRADE,
+                ]
+            );
+        }
+
+        #[ink::test]
+        fn test_min_timelock_governance_delay() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_block_number::<TestEnv>(100);
+            assert_eq!(contract.apply_min_timelock(), Err(Error::NoPendingProposal));
+
+            contract.propose_min_timelock(300, BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_pending_min_timelock(), Some((300, 700)));
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.propose_min_timelock(50, BlockNumber::MAX), Err(Error::Unauthorized));
+
+            // Anyone may apply, but not before the delay has passed
+            ink::env::test::set_block_number::<TestEnv>(699);
+            assert_eq!(contract.apply_min_timelock(), Err(Error::ProposalNotReady));
+            assert_eq!(contract.get_min_timelock(), 100);
+
+            ink::env::test::set_block_number::<TestEnv>(700);
+            contract.apply_min_timelock().unwrap();
+            assert_eq!(contract.get_min_timelock(), 300);
+            assert_eq!(contract.get_pending_min_timelock(), None);
+
+            // A 200-block lock was valid under the old minimum but not the new one
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            let result = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 900, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            );
+            assert_eq!(result, Err(Error::TimelockTooShort));
+            assert!(contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 1000, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).is_ok());
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_backfill_indices_for_legacy_orders() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            // Orders written straight to storage, as if created before the indices existed
+            let legacy = PartialFillOrder {
+                maker: accounts.bob,
+                total_amount: 1000,
+                filled_amount: 0,
+                min_fill_amount: 100,
+                hashlock: [0x01; 32],
+                timelock: 500,
+                cancelled: false,
+                swap_id: [0x02; 32],
+                source_chain: 1,
+                dest_chain: 2,
+                dest_amount_per_unit: 1_000_000_000_000,
+                fee: 3,
+                allow_partial_fills: true,
+                max_fills: 5,
+                current_fills: 0,
+                sender_cross_address: None,
+                receiver_cross_address: None,
+                vesting_schedule: None,
+                hash_algorithm: HashAlgorithm::Sha256,
+                fills_paused: false,
+                fee_bps_applied: 30,
+                source_decimals: None,
+                dest_decimals: None,
+                rearm_count: 0,
+                completion_callback: None,
+                mispriced: false,
+                intent_hash: [0; 32],
+                on_max_fills: MaxFillsPolicy::Hold,
+                order_type: OrderType::FixedPrice,
+                creation_block: 0,
+            };
+            let cancelled = PartialFillOrder { cancelled: true, ..legacy.clone() };
+            contract.orders.insert(&[0xa1; 32], &legacy);
+            contract.orders.insert(&[0xa2; 32], &cancelled);
+
+            assert!(contract.get_active_orders(0, 10).unwrap().items.is_empty());
+
+            let ids = vec![[0xa1; 32], [0xa2; 32], [0xff; 32]];
+            assert_eq!(contract.backfill_indices(ids.clone(), BlockNumber::MAX), Ok(2));
+            assert_eq!(contract.backfill_indices(ids, BlockNumber::MAX), Ok(2));
+
+            assert_eq!(contract.get_active_orders(0, 10).unwrap().items, vec![[0xa1; 32]]);
+            assert_eq!(contract.get_archived_orders(0, 10).unwrap().items, vec![[0xa2; 32]]);
+            assert_eq!(
+                contract.get_orders_by_swap_id([0x02; 32], 0, 10).unwrap().items,
+                vec![[0xa1; 32], [0xa2; 32]]
+            );
+            assert_eq!(contract.id_list_len(IdList::MakerOrders(accounts.bob)), 2);
+
+            assert_eq!(contract.backfill_indices(vec![[0u8; 32]; 51], BlockNumber::MAX), Err(Error::BatchTooLarge));
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.backfill_indices(vec![], BlockNumber::MAX), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn test_vesting_schedule_limits_fills() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(150);
+
+            let options = OrderOptions {
+                vesting_schedule: Some(VestingSchedule {
+                    unlock_amount_per_block: 10,
+                    unlock_start_block: 200,
+                }),
+                ..Default::default()
+            };
+            let order_id = contract.create_partial_fill_order_with_options(
+                1000, 10, [0x01; 32], 600, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 10, None, None, options
+            ).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+
+            // Nothing unlocked yet
+            ink::env::test::set_block_number::<TestEnv>(200);
+            assert_eq!(contract.get_unlocked_amount(order_id), 0);
+            assert_eq!(contract.fill_order(order_id, 10, accounts.bob), Err(Error::FillExceedsUnlocked));
+
+            ink::env::test::set_block_number::<TestEnv>(210);
+            assert_eq!(contract.get_unlocked_amount(order_id), 100);
+            assert_eq!(contract.fill_order(order_id, 101, accounts.bob), Err(Error::FillExceedsUnlocked));
+            contract.fill_order(order_id, 100, accounts.bob).unwrap();
+
+            ink::env::test::set_block_number::<TestEnv>(220);
+            assert_eq!(contract.get_unlocked_amount(order_id), 200);
+            assert_eq!(contract.fill_order(order_id, 101, accounts.bob), Err(Error::FillExceedsUnlocked));
+            contract.fill_order(order_id, 100, accounts.bob).unwrap();
+            assert_eq!(contract.get_order(order_id).unwrap().filled_amount, 200);
+
+            // Unlocking stops at the order size
+            ink::env::test::set_block_number::<TestEnv>(500);
+            assert_eq!(contract.get_unlocked_amount(order_id), 1000);
+        }
+
+        #[ink::test]
+        fn test_credit_netting() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            assert_eq!(contract.get_total_locked(), 1000);
+            assert_eq!(contract.get_total_liabilities(), 1000);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let fill1 = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            let fill2 = contract.fill_order(order_id, 300, accounts.bob).unwrap();
+            let fill3 = contract.fill_order(order_id, 100, accounts.bob).unwrap();
+
+            // Credits accrue across withdrawals; liabilities only move on real transfers
+            contract.withdraw_fill(fill1, secret, Some(PayoutRoute::Credit), None).unwrap();
+            contract.withdraw_fill(fill2, secret, Some(PayoutRoute::Credit), None).unwrap();
+            assert_eq!(contract.get_credit(accounts.bob), 500);
+            assert_eq!(contract.get_total_credits(), 500);
+            assert_eq!(contract.get_total_locked(), 500);
+            assert_eq!(contract.get_total_liabilities(), 1000);
+
+            contract.withdraw_fill(fill3, secret, None, None).unwrap();
+            assert_eq!(contract.get_total_liabilities(), 900);
+
+            // Partial credit withdrawal
+            contract.withdraw_credit(150).unwrap();
+            assert_eq!(contract.get_credit(accounts.bob), 350);
+            assert_eq!(contract.get_total_liabilities(), 750);
+            assert_eq!(contract.withdraw_credit(351), Err(Error::InsufficientCredit));
+            assert_eq!(contract.withdraw_credit(0), Err(Error::InsufficientCredit));
+
+            // Credit covers what the value sent falls short of the fill deposit, and
+            // moves back into escrow
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.set_deposit_schedule(vec![(0, 1000)], BlockNumber::MAX).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(5u128.into());
+            let fill4 = contract.fill_order(order_id, 150, accounts.bob).unwrap();
+            assert_eq!(contract.get_fill(fill4).unwrap().deposit, 15);
+            assert_eq!(contract.get_credit(accounts.bob), 340);
+            assert_eq!(contract.get_total_credits(), 340);
+            assert_eq!(contract.get_total_locked(), 415);
+            assert_eq!(contract.get_total_liabilities(), 755);
+
+            // Without credit the shortfall is refused as before
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            assert_eq!(
+                contract.fill_order(order_id, 150, accounts.charlie),
+                Err(Error::InsufficientFunds { required: 15, received: 5 })
+            );
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_batch_cancel_orders() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let mut order_ids = Vec::new();
+            for i in 0..3u8 {
+                order_ids.push(contract.create_partial_fill_order(
+                    1000, 100, [0x01; 32], 500, [i; 32], 1, 2,
+                    1_000_000_000_000, true, 5, None, None
+                ).unwrap());
+            }
+
+            let events_before = ink::env::test::recorded_events().count();
+            assert_eq!(contract.batch_cancel_orders(order_ids.clone()), Ok(3));
+            assert_eq!(events_since(events_before), vec![signature_of::<OrderCancelled>(); 3]);
+            for order_id in order_ids.iter() {
+                assert!(contract.get_order(*order_id).unwrap().cancelled);
+            }
+            assert_eq!(contract.get_total_locked(), 0);
+            assert_eq!(contract.batch_cancel_orders(vec![[0u8; 32]; 21]), Err(Error::BatchTooLarge));
+        }
+
+        #[ink::test]
+        fn test_withdrawal_memo() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let tagged = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            let untagged = contract.fill_order(order_id, 300, accounts.bob).unwrap();
+
+            // One byte over the bound is refused and leaves the fill pending
+            let oversized = vec![0x61; MAX_WITHDRAWAL_MEMO_LEN + 1];
+            assert_eq!(
+                contract.withdraw_fill(tagged, secret, None, Some(oversized)),
+                Err(Error::MemoTooLong)
+            );
+            assert!(!contract.get_fill(tagged).unwrap().withdrawn);
+
+            // A memo at the bound is kept with the fill and carried by the event
+            let memo = vec![0x61; MAX_WITHDRAWAL_MEMO_LEN];
+            contract.withdraw_fill(tagged, secret, None, Some(memo.clone())).unwrap();
+            assert_eq!(contract.get_fill_memo(tagged), Some(memo.clone()));
+            let event = ink::env::test::recorded_events().last().unwrap();
+            assert!(event.data.ends_with(&Some(memo).encode()));
+
+            // Without one, nothing is stored and the payout is the same
+            contract.withdraw_fill(untagged, secret, None, None).unwrap();
+            assert_eq!(contract.get_fill_memo(untagged), None);
+            let event = ink::env::test::recorded_events().last().unwrap();
+            assert_eq!(event.data.last(), Some(&0));
+            assert_eq!(contract.get_total_locked(), 500);
+        }
+
+        #[ink::test]
+        fn test_settlement_digest_golden_vector() {
+            const FIXTURES: &str = include_str!("../fixtures/settlement.json");
+
+            let secret = [0x42; 32];
+            let mut summary = SettlementSummary {
+                chain_id: 1000,
+                contract: Address::from([0x33; 20]),
+                order_id: [0x01; 32],
+                fill_id: [0x02; 32],
+                taker: Address::from([0x22; 20]),
+                hashlock: PolkadotPartialFills::new().compute_sha256(&secret),
+                secret,
+                amount: 200,
+                receiver: Some(vec![0x44; 20]),
+                block: 480,
+            };
+            assert_eq!(
+                to_hex(&summary.digest()),
+                "e986bbfadb1a97e6020de74a12b4ed6065d973fe0afa701869ad5ebb8fa8781b"
+            );
+            assert!(FIXTURES.contains(&format!("\"packed\": \"0x{}\"", to_hex(&summary.encode_packed()))));
+            assert!(FIXTURES.contains(&format!("\"digest\": \"0x{}\"", to_hex(&summary.digest()))));
+
+            // An order without a receiver hashes as 32 zero bytes in its place
+            summary.receiver = None;
+            assert_eq!(
+                to_hex(&summary.digest()),
+                "c9f716a8f0a291d3b15b644bb5340b210bead72e41bc52a58b4668aab3dffc99"
+            );
+            assert!(FIXTURES.contains(&format!("\"digest\": \"0x{}\"", to_hex(&summary.digest()))));
+        }
+
+        #[ink::test]
+        fn test_settlement_summary_after_withdrawal() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, Some(vec![0x44; 20])
+            ).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let fill_id = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            let refunded = contract.fill_order(order_id, 100, accounts.bob).unwrap();
+
+            // Nothing to sign while the fill is pending
+            assert_eq!(contract.get_settlement_summary(fill_id), None);
+            assert_eq!(contract.sign_worthy_digest(fill_id), None);
+            assert_eq!(contract.get_settlement_summary([0x99; 32]), None);
+
+            ink::env::test::set_block_number::<TestEnv>(120);
+            contract.withdraw_fill(fill_id, secret, None, None).unwrap();
+            let summary = contract.get_settlement_summary(fill_id).unwrap();
+            assert_eq!(summary.chain_id, contract.get_home_chain_id());
+            assert_eq!(summary.contract, ink::env::test::callee::<TestEnv>());
+            assert_eq!(summary.order_id, order_id);
+            assert_eq!(summary.taker, accounts.bob);
+            assert_eq!(summary.hashlock, hashlock);
+            assert_eq!(summary.secret, secret);
+            assert_eq!(summary.amount, 200);
+            assert_eq!(summary.receiver, Some(vec![0x44; 20]));
+            assert_eq!(summary.block, 120);
+            assert_eq!(contract.sign_worthy_digest(fill_id), Some(summary.digest()));
+
+            // A refunded fill never settles
+            ink::env::test::set_block_number::<TestEnv>(500);
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.refund_fill(refunded, None).unwrap();
+            assert_eq!(contract.get_settlement_summary(refunded), None);
+        }
+
+        #[ink::test]
+        fn test_order_commitment_golden_vector() {
+            const FIXTURES: &str = include_str!("../fixtures/commitment.json");
+
+            let mut commitment = OrderCommitment {
+                chain_id: 1000,
+                contract: Address::from([0x33; 20]),
+                order_id: [0x01; 32],
+                maker: Address::from([0x11; 20]),
+                total_amount: 997,
+                min_fill_amount: 100,
+                hashlock: PolkadotPartialFills::new().compute_sha256(&[0x42; 32]),
+                hash_algorithm: HashAlgorithm::Sha256,
+                timelock: 500,
+                source_chain: 1,
+                dest_chain: 2,
+                dest_amount_per_unit: 1_000_000_000_000,
+                order_type: OrderType::FixedPrice,
+                sender: Some(vec![0x55; 32]),
+                receiver: Some(vec![0x44; 20]),
+            };
+            assert_eq!(
+                to_hex(&commitment.digest()),
+                "16fb4acd1c17bee0ad99113752e16a67c37c160e8f80d3c2568f4551e182bc9b"
+            );
+            assert!(FIXTURES.contains(&format!("\"packed\": \"0x{}\"", to_hex(&commitment.encode_packed()))));
+            assert!(FIXTURES.contains(&format!("\"digest\": \"0x{}\"", to_hex(&commitment.digest()))));
+
+            // Unset cross-chain addresses hash as 32 zero bytes in their place
+            let unset = OrderCommitment {
+                hash_algorithm: HashAlgorithm::Keccak256,
+                sender: None,
+                receiver: None,
+                ..commitment.clone()
+            };
+            assert_eq!(
+                to_hex(&unset.digest()),
+                "c57a353a90ed5f6a19cd81ecf90afbe45b6bc825617034e9e2e72cd7259a8ca5"
+            );
+            assert!(FIXTURES.contains(&format!("\"digest\": \"0x{}\"", to_hex(&unset.digest()))));
+
+            // An auction commits to the rate it decays to
+            let auction = OrderCommitment {
+                order_type: OrderType::DutchAuction { start_rate: 1_000_000_000_000, end_rate: 500_000_000_000 },
+                ..commitment.clone()
+            };
+            assert_eq!(
+                to_hex(&auction.digest()),
+                "7b3ed2dce66e930e4c6e0912d864bc708278658fe6b4f709e5706f05fe1cfa84"
+            );
+            assert!(FIXTURES.contains(&format!("\"packed\": \"0x{}\"", to_hex(&auction.encode_packed()))));
+
+            // Every committed field moves the digest
+            let original = commitment.digest();
+            let changes: [fn(&mut OrderCommitment); 16] = [
+                |c| c.chain_id += 1,
+                |c| c.contract = Address::from([0x34; 20]),
+                |c| c.order_id[31] ^= 1,
+                |c| c.maker = Address::from([0x12; 20]),
+                |c| c.total_amount += 1,
+                |c| c.min_fill_amount += 1,
+                |c| c.hashlock[0] ^= 1,
+                |c| c.hash_algorithm = HashAlgorithm::Blake2x256,
+                |c| c.timelock += 1,
+                |c| c.source_chain += 1,
+                |c| c.dest_chain += 1,
+                |c| c.dest_amount_per_unit += 1,
+                |c| c.order_type = OrderType::DutchAuction { start_rate: 1_000_000_000_000, end_rate: 1_000_000_000_000 },
+                |c| c.order_type = OrderType::DutchAuction { start_rate: 1_000_000_000_000, end_rate: 1 },
+                |c| c.sender = None,
+                |c| c.receiver = Some(vec![0x45; 20]),
+            ];
+            for (i, change) in changes.iter().enumerate() {
+                let mut changed = commitment.clone();
+                change(&mut changed);
+                assert_ne!(changed.digest(), original, "field {i}");
+            }
+            // Moving bytes between adjacent fields changes the packed bytes too
+            commitment.sender = Some(vec![0x55; 31]);
+            assert_ne!(commitment.digest(), original);
+        }
+
+        #[ink::test]
+        fn test_order_commitment() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let secret = [0x42; 32];
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, Some(vec![0x55; 32]), Some(vec![0x44; 20])
+            ).unwrap();
+            assert_eq!(contract.get_order_commitment([0x99; 32]), None);
+            let created = contract.get_order_commitment(order_id).unwrap();
+            let order = contract.get_order(order_id).unwrap();
+            let expected = OrderCommitment {
+                chain_id: contract.get_home_chain_id(),
+                contract: ink::env::test::callee::<TestEnv>(),
+                order_id,
+                maker: accounts.alice,
+                total_amount: order.total_amount,
+                min_fill_amount: 100,
+                hashlock: [0x01; 32],
+                hash_algorithm: order.hash_algorithm,
+                timelock: 500,
+                source_chain: 1,
+                dest_chain: 2,
+                dest_amount_per_unit: 1_000_000_000_000,
+                order_type: OrderType::FixedPrice,
+                sender: Some(vec![0x55; 32]),
+                receiver: Some(vec![0x44; 20]),
+            };
+            assert_eq!(created, expected.digest());
+
+            // Announced as a topic right after the creation event
+            let event = ink::env::test::recorded_events().last().unwrap();
+            assert_eq!(event.topics[0][..], signature_of::<OrderCommitted>()[..]);
+            assert_eq!(event.topics[1][..], order_id[..]);
+            assert_eq!(event.topics[2][..], created[..]);
+
+            // Pausing, fills and their settlement leave the commitment alone
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            contract.set_order_fill_paused(order_id, true).unwrap();
+            contract.set_order_fill_paused(order_id, false).unwrap();
+            assert_eq!(contract.get_order_commitment(order_id), Some(created));
+
+            // Re-arming commits the new hashlock, carried by the intent announcement
+            let new_hashlock = contract.compute_sha256(&secret);
+            contract.rearm_order(order_id, new_hashlock).unwrap();
+            let rearmed = contract.get_order_commitment(order_id).unwrap();
+            assert_eq!(rearmed, OrderCommitment { hashlock: new_hashlock, ..expected }.digest());
+            let event = ink::env::test::recorded_events().last().unwrap();
+            assert_eq!(event.topics[0][..], signature_of::<OrderIntentChanged>()[..]);
+            assert!(event.data.ends_with(&rearmed));
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let fill_id = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            contract.fill_order(order_id, 300, accounts.bob).unwrap();
+            assert_eq!(contract.get_order_commitment(order_id), Some(rearmed));
+            contract.withdraw_fill(fill_id, secret, None, None).unwrap();
+            assert_eq!(contract.get_order_commitment(order_id), Some(rearmed));
+        }
+
+        #[ink::test]
+        fn test_zero_min_fill_orders() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let at = |block| ink::env::test::set_block_number::<TestEnv>(block);
+            let as_caller = |caller| ink::env::test::set_caller::<TestEnv>(caller);
+            let send = |value: Balance| ink::env::test::set_value_transferred::<TestEnv>(value.into());
+            as_caller(accounts.alice);
+            at(100);
+
+            // One to one without a minimum: ten one-planck fills complete the order
+            send(10);
+            let planck = contract.create_partial_fill_order(
+                10, 0, [0x01; 32], 500, [0x02; 32], 1, 2,
+                RATE_SCALE, true, 10, None, None
+            ).unwrap();
+            assert_eq!(contract.get_order(planck).unwrap().min_fill_amount, 0);
+            assert_eq!(contract.get_min_fill(planck), Some(1));
+            assert_eq!(contract.get_max_fillable_amount(planck, accounts.bob), Ok(10));
+            as_caller(accounts.bob);
+            send(0);
+            assert_eq!(contract.fill_order(planck, 0, accounts.bob), Err(Error::InvalidFillAmount));
+            for block in 101..111 {
+                at(block);
+                let fill_id = contract.fill_order(planck, 1, accounts.bob).unwrap();
+                assert_eq!(contract.get_fill(fill_id).unwrap().fill_amount, 1);
+                assert_eq!(contract.get_fill_quote(planck, 1), Some(1));
+            }
+            assert_eq!(contract.get_remaining_amount(planck), 0);
+            assert_eq!(contract.get_order_status(planck), Some(OrderStatus::FullyFilled));
+            assert_eq!(contract.get_order(planck).unwrap().current_fills, 10);
+            assert_eq!(contract.get_min_fill(planck), None);
+            assert_eq!(contract.get_max_fillable_amount(planck, accounts.bob), Err(Error::OrderCompleted));
+
+            // A third of a unit per unit: fills below four planck truncate to nothing
+            as_caller(accounts.alice);
+            send(10);
+            let third = contract.create_partial_fill_order(
+                10, 0, [0x01; 32], 500, [0x03; 32], 1, 2,
+                RATE_SCALE / 3, true, 5, None, None
+            ).unwrap();
+            assert_eq!(contract.get_min_fill(third), Some(4));
+            assert_eq!(contract.get_max_fillable_amount(third, accounts.bob), Ok(10));
+            assert_eq!(contract.get_fill_quote(third, 3), Some(0));
+            as_caller(accounts.bob);
+            send(0);
+            assert_eq!(contract.fill_order(third, 3, accounts.bob), Err(Error::FillAmountTooSmall));
+            assert_eq!(contract.get_order(third).unwrap().filled_amount, 0);
+            contract.fill_order(third, 4, accounts.bob).unwrap();
+            at(111);
+            contract.fill_order(third, 4, accounts.bob).unwrap();
+            // The two planck left are owed nothing, so only the maker can reclaim them
+            assert_eq!(contract.get_remaining_amount(third), 2);
+            assert_eq!(contract.get_min_fill(third), None);
+            at(112);
+            assert_eq!(contract.get_max_fillable_amount(third, accounts.bob), Err(Error::FillAmountTooSmall));
+            assert_eq!(contract.fill_order(third, 2, accounts.bob), Err(Error::FillAmountTooSmall));
+
+            // With decimals the floor is the smallest amount owed one destination base unit
+            as_caller(accounts.alice);
+            send(1_000_000_000);
+            let options = OrderOptions {
+                source_decimals: Some(12),
+                dest_decimals: Some(6),
+                ..Default::default()
+            };
+            let scaled = contract.create_partial_fill_order_with_options(
+                1_000_000_000, 0, [0x01; 32], 500, [0x04; 32], 1, 2,
+                3 * RATE_SCALE, true, 5, None, None, options
+            ).unwrap();
+            assert_eq!(contract.get_min_fill(scaled), Some(333_334));
+            assert_eq!(contract.get_max_fillable_amount(scaled, accounts.bob), Ok(1_000_000_000));
+            as_caller(accounts.bob);
+            send(0);
+            assert_eq!(contract.fill_order(scaled, 333_333, accounts.bob), Err(Error::FillAmountTooSmall));
+            let fill_id = contract.fill_order(scaled, 333_334, accounts.bob).unwrap();
+            assert_eq!(contract.get_fill_quote(scaled, 333_334), Some(1));
+            assert_eq!(contract.get_fill(fill_id).unwrap().fill_amount, 333_334);
+        }
+
+        #[ink::test]
+        fn test_fill_aggregation() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let at = |block| ink::env::test::set_block_number::<TestEnv>(block);
+            let as_caller = |caller| ink::env::test::set_caller::<TestEnv>(caller);
+            as_caller(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            at(100);
+
+            let config = FillAggregation { every_fills: 3, every_blocks: 50 };
+            let bad = OrderOptions {
+                aggregate_fills: Some(FillAggregation { every_fills: 0, ..config }),
+                ..Default::default()
+            };
+            assert_eq!(
+                contract.create_partial_fill_order_with_options(
+                    1000, 10, [0x01; 32], 500, [0x02; 32], 1, 2,
+                    RATE_SCALE, true, 20, None, None, bad
+                ),
+                Err(Error::InvalidAggregation)
+            );
+            let options = OrderOptions { aggregate_fills: Some(config), ..Default::default() };
+            let order_id = contract.create_partial_fill_order_with_options(
+                1000, 10, [0x01; 32], 500, [0x02; 32], 1, 2,
+                RATE_SCALE, true, 20, None, None, options
+            ).unwrap();
+            let total_amount = contract.get_order(order_id).unwrap().total_amount;
+            assert_eq!(contract.get_fill_aggregate(order_id).unwrap().since_block, 100);
+
+            let events_before = ink::env::test::recorded_events().count();
+            as_caller(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            // Two fills stay pending, the third reaches `every_fills`
+            contract.fill_order(order_id, 10, accounts.bob).unwrap();
+            contract.fill_order(order_id, 20, accounts.bob).unwrap();
+            let pending = contract.get_fill_aggregate(order_id).unwrap();
+            assert_eq!((pending.next_nonce, pending.fills_count, pending.total_amount), (0, 2, 30));
+            assert!(events_since(events_before).is_empty());
+            contract.fill_order(order_id, 30, accounts.bob).unwrap();
+            // One fill, then one landing `every_blocks` after the last aggregate
+            at(120);
+            contract.fill_order(order_id, 40, accounts.bob).unwrap();
+            at(150);
+            contract.fill_order(order_id, 50, accounts.bob).unwrap();
+            // Flushed on request
+            at(151);
+            contract.fill_order(order_id, 60, accounts.bob).unwrap();
+            assert_eq!(contract.flush_fill_aggregate(order_id), Ok(1));
+            assert_eq!(contract.flush_fill_aggregate(order_id), Ok(0));
+            assert_eq!(contract.flush_fill_aggregate([0x99; 32]), Err(Error::OrderNotFound));
+            // The completing fill closes the roll-up early
+            at(152);
+            contract.fill_order(order_id, 10, accounts.bob).unwrap();
+            contract.fill_order(order_id, total_amount - 220, accounts.bob).unwrap();
+
+            let recorded: Vec<_> = ink::env::test::recorded_events().collect();
+            assert!(!recorded.iter().any(|event| event.topics[0][..] == signature_of::<OrderFilled>()[..]));
+            let aggregates: Vec<(u32, Balance, Balance, u32, u32)> = recorded
+                .iter()
+                .filter(|event| event.topics[0][..] == signature_of::<FillsAggregated>()[..])
+                .map(|event| {
+                    let (_, count, amount, dest, from, to) =
+                        <([u8; 32], u32, Balance, Balance, u32, u32)>::decode(&mut &event.data[..]).unwrap();
+                    (count, amount, dest, from, to)
+                })
+                .collect();
+            assert_eq!(
+                aggregates,
+                vec![
+                    (3, 60, 60, 0, 2),
+                    (2, 90, 90, 3, 4),
+                    (1, 60, 60, 5, 5),
+                    (2, total_amount - 210, total_amount - 210, 6, 7),
+                ]
+            );
+
+            // Nonces chain with no gap and every fill reads back from the fill index
+            let fill_ids = contract.get_order_fills(order_id, 0, 20).unwrap().items;
+            let mut next_nonce = 0;
+            for (count, amount, _, from, to) in aggregates {
+                assert_eq!(from, next_nonce);
+                assert_eq!(to - from + 1, count);
+                let covered: Balance = fill_ids[from as usize..=to as usize]
+                    .iter()
+                    .map(|fill_id| contract.get_fill(*fill_id).unwrap().fill_amount)
+                    .sum();
+                assert_eq!(covered, amount);
+                next_nonce = to + 1;
+            }
+            assert_eq!(next_nonce as usize, fill_ids.len());
+            assert_eq!(contract.get_order(order_id).unwrap().filled_amount, total_amount);
+            assert_eq!(contract.get_fill_aggregate(order_id).unwrap().fills_count, 0);
+
+            // Non-aggregating orders keep one `OrderFilled` per fill
+            as_caller(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            let plain = contract.create_partial_fill_order(
+                1000, 10, [0x01; 32], 500, [0x03; 32], 1, 2,
+                RATE_SCALE, true, 20, None, None
+            ).unwrap();
+            assert_eq!(contract.get_fill_aggregate(plain), None);
+            as_caller(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let events_before = ink::env::test::recorded_events().count();
+            contract.fill_order(plain, 10, accounts.bob).unwrap();
+            assert_eq!(events_since(events_before), vec![signature_of::<OrderFilled>()]);
+            assert_eq!(contract.flush_fill_aggregate(plain), Ok(0));
+        }
+
+        #[ink::test]
+        fn test_anti_snipe_extension() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let at = |block| ink::env::test::set_block_number::<TestEnv>(block);
+            let as_caller = |caller| ink::env::test::set_caller::<TestEnv>(caller);
+            as_caller(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            at(100);
+
+            let with_extension = |blocks| OrderOptions { anti_snipe_extension: Some(blocks), ..Default::default() };
+            assert_eq!(
+                contract.create_partial_fill_order_with_options(
+                    1000, 10, [0x01; 32], 500, [0x02; 32], 1, 2,
+                    RATE_SCALE, true, 20, None, None, with_extension(0)
+                ),
+                Err(Error::InvalidTimelock)
+            );
+            let order_id = contract.create_partial_fill_order_with_options(
+                1000, 10, [0x01; 32], 500, [0x02; 32], 1, 2,
+                RATE_SCALE, true, 20, None, None, with_extension(20)
+            ).unwrap();
+            let plain_id = contract.create_partial_fill_order(
+                1000, 10, [0x01; 32], 500, [0x03; 32], 1, 2,
+                RATE_SCALE, true, 20, None, None
+            ).unwrap();
+            assert_eq!(contract.get_anti_snipe(order_id), Some(AntiSnipe { extension_blocks: 20, extensions_used: 0 }));
+            assert_eq!(contract.get_anti_snipe(plain_id), None);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            as_caller(accounts.bob);
+
+            // An early fill leaves the timelock alone
+            contract.fill_order(order_id, 10, accounts.bob).unwrap();
+            assert_eq!(contract.get_order(order_id).unwrap().timelock, 500);
+
+            // A fill within the extension of the timelock pushes it out, and with it
+            // the deadline of every pending fill
+            at(480);
+            let intent_hash = contract.get_intent_hash(order_id).unwrap();
+            let events_before = ink::env::test::recorded_events().count();
+            let late_fill = contract.fill_order(order_id, 10, accounts.bob).unwrap();
+            assert_eq!(
+                events_since(events_before),
+                vec![
+                    signature_of::<OrderFilled>(),
+                    signature_of::<TimelockAutoExtended>(),
+                    signature_of::<OrderIntentChanged>(),
+                ]
+            );
+            let order = contract.get_order(order_id).unwrap();
+            assert_eq!(order.timelock, 520);
+            assert_ne!(order.intent_hash, intent_hash);
+            #[cfg(feature = "periphery")]
+            assert_eq!(contract.get_fill_timing_info(late_fill).unwrap().expires_at, 520);
+            contract.fill_order(plain_id, 10, accounts.bob).unwrap();
+            assert_eq!(contract.get_order(plain_id).unwrap().timelock, 500);
+
+            // Up to the cap of extensions
+            at(500);
+            contract.fill_order(order_id, 10, accounts.bob).unwrap();
+            at(530);
+            contract.fill_order(order_id, 10, accounts.bob).unwrap();
+            assert_eq!(contract.get_order(order_id).unwrap().timelock, 560);
+            let anti_snipe = contract.get_anti_snipe(order_id).unwrap();
+            assert_eq!((anti_snipe.extensions_used, anti_snipe.extensions_left()), (MAX_TIMELOCK_EXTENSIONS, 0));
+            at(555);
+            let events_before = ink::env::test::recorded_events().count();
+            contract.fill_order(order_id, 10, accounts.bob).unwrap();
+            assert_eq!(events_since(events_before), vec![signature_of::<OrderFilled>()]);
+            assert_eq!(contract.get_order(order_id).unwrap().timelock, 560);
+        }
+
+        #[ink::test]
+        fn test_fill_aggregate_closed_by_cancel() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let options = OrderOptions {
+                aggregate_fills: Some(FillAggregation { every_fills: 10, every_blocks: 1000 }),
+                ..Default::default()
+            };
+            let order_id = contract.create_partial_fill_order_with_options(
+                1000, 10, [0x01; 32], 500, [0x02; 32], 1, 2,
+                RATE_SCALE, true, 20, None, None, options
+            ).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            contract.fill_order(order_id, 100, accounts.bob).unwrap();
+
+            // Cancelling announces the fill no later fill would have
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            let events_before = ink::env::test::recorded_events().count();
+            contract.cancel_order(order_id).unwrap();
+            assert_eq!(
+                events_since(events_before),
+                vec![signature_of::<OrderCancelled>(), signature_of::<FillsAggregated>()]
+            );
+            assert_eq!(contract.get_fill_aggregate(order_id).unwrap().next_nonce, 1);
+        }
+
+        #[ink::test]
+        fn test_order_last_activity() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let at = |block| ink::env::test::set_block_number::<TestEnv>(block);
+            let as_caller = |caller| ink::env::test::set_caller::<TestEnv>(caller);
+            as_caller(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            at(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let options = OrderOptions { on_max_fills: Some(MaxFillsPolicy::RaiseLimit), ..Default::default() };
+            let order_id = contract.create_partial_fill_order_with_options(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 2, None, None, options
+            ).unwrap();
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let last_activity = |contract: &PolkadotPartialFills| contract.get_last_activity_block(order_id);
+            assert_eq!(last_activity(&contract), Some(100));
+            assert_eq!(contract.get_last_activity_block([0x99; 32]), None);
+            assert!(!contract.is_stale(order_id, 1));
+            assert!(!contract.is_stale([0x99; 32], 0));
+
+            // Maker amendments
+            at(110);
+            contract.set_order_fill_paused(order_id, true).unwrap();
+            assert_eq!(last_activity(&contract), Some(110));
+            at(115);
+            contract.set_order_fill_paused(order_id, false).unwrap();
+            assert_eq!(last_activity(&contract), Some(115));
+            at(120);
+            contract.rearm_order(order_id, hashlock).unwrap();
+            assert_eq!(last_activity(&contract), Some(120));
+            #[cfg(feature = "periphery")]
+            {
+                at(130);
+                contract.set_order_tags(order_id, b"otc".to_vec()).unwrap();
+                assert_eq!(last_activity(&contract), Some(130));
+            }
+            at(140);
+            contract.increase_max_fills(order_id, 4).unwrap();
+            assert_eq!(last_activity(&contract), Some(140));
+
+            // A failed message leaves it alone
+            at(145);
+            as_caller(accounts.bob);
+            assert_eq!(contract.set_order_fill_paused(order_id, true), Err(Error::Unauthorized));
+            assert_eq!(last_activity(&contract), Some(140));
+
+            // Fills and their settlement
+            at(150);
+            let first = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            assert_eq!(last_activity(&contract), Some(150));
+            at(155);
+            contract.fill_order(order_id, 100, accounts.bob).unwrap();
+            as_caller(accounts.charlie);
+            let expiring = contract.fill_order(order_id, 100, accounts.charlie).unwrap();
+            assert_eq!(last_activity(&contract), Some(155));
+            at(160);
+            as_caller(accounts.bob);
+            contract.withdraw_fill(first, secret, None, None).unwrap();
+            assert_eq!(last_activity(&contract), Some(160));
+            at(170);
+            assert_eq!(contract.withdraw_order_fills(order_id, secret, None), Ok(1));
+            assert_eq!(last_activity(&contract), Some(170));
+
+            // Idle for 330 blocks until the refund
+            at(499);
+            assert!(contract.is_stale(order_id, 329));
+            assert!(!contract.is_stale(order_id, 330));
+            at(500);
+            as_caller(accounts.alice);
+            contract.refund_fill(expiring, None).unwrap();
+            assert_eq!(last_activity(&contract), Some(500));
+            assert!(!contract.is_stale(order_id, 1));
+            at(510);
+            contract.cancel_order(order_id).unwrap();
+            assert_eq!(last_activity(&contract), Some(510));
+
+            // Staleness never touches funds or the order itself
+            at(520);
+            assert!(contract.is_stale(order_id, 10));
+            assert!(contract.get_order(order_id).unwrap().cancelled);
+
+            contract.set_retention_blocks(0, BlockNumber::MAX).unwrap();
+            contract.cleanup_order(order_id).unwrap();
+            assert_eq!(last_activity(&contract), None);
+            assert!(!contract.is_stale(order_id, 0));
+        }
+
+        #[ink::test]
+        fn test_pathological_timestamps() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            ink::env::test::set_block_timestamp::<TestEnv>(u64::MAX);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let late = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+
+            // Time runs backwards, then stands still within a block
+            ink::env::test::set_block_number::<TestEnv>(101);
+            ink::env::test::set_block_timestamp::<TestEnv>(0);
+            let early = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            let same = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            assert_ne!(late, early);
+            assert_ne!(early, same);
+            assert_eq!(contract.get_fill(late).unwrap().timestamp, u64::MAX);
+            assert_eq!(contract.get_fill(early).unwrap().timestamp, 0);
+            assert_eq!(contract.get_fill_created_block(late), Some(100));
+            assert_eq!(contract.get_fill_created_block(early), Some(101));
+            assert_ne!(contract.get_fill(early).unwrap().contract_id, contract.get_fill(same).unwrap().contract_id);
+            #[cfg(feature = "periphery")]
+            {
+                let timing = contract.get_fill_timing_info(late).unwrap();
+                assert_eq!(timing.created_block, Some(100));
+                assert_eq!(timing.blocks_remaining, 399);
+            }
+
+            // No timestamp brings the timelock forward
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_block_timestamp::<TestEnv>(u64::MAX);
+            assert_eq!(contract.refund_fill(late, None), Err(Error::TimelockNotExpired));
+            assert_eq!(contract.public_refund_fill(same, None), Err(Error::TimelockNotExpired));
+            contract.set_congestion_soft_cap(Some(10), BlockNumber::MAX).unwrap();
+            let audit = contract.get_admin_audit_log(0, 1).unwrap();
+            assert_eq!((audit.items[0].timestamp, audit.items[0].block), (u64::MAX, 101));
+
+            ink::env::test::set_block_timestamp::<TestEnv>(1);
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            contract.withdraw_fill(early, secret, None, None).unwrap();
+
+            // ...and none holds it back once the block passes it
+            ink::env::test::set_block_number::<TestEnv>(500);
+            ink::env::test::set_block_timestamp::<TestEnv>(0);
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.refund_fill(late, None).unwrap();
+            contract.refund_fill(same, None).unwrap();
+        }
+
+        #[ink::test]
+        fn test_congestion_raises_min_timelock() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_block_number::<TestEnv>(100);
+            contract.set_congestion_soft_cap(Some(1), BlockNumber::MAX).unwrap();
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let create = |contract: &mut PolkadotPartialFills, timelock| {
+                ink::env::test::set_caller::<TestEnv>(accounts.alice);
+                ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+                contract.create_partial_fill_order(
+                    1000, 100, hashlock, timelock, [0x02; 32], 1, 2,
+                    1_000_000_000_000, true, 5, None, None
+                )
+            };
+            let order_id = create(&mut contract, 500).unwrap();
+
+            // One pending fill sits at the cap; the second crosses it
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let first = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            assert!(!contract.get_health().congested);
+            let events_before = ink::env::test::recorded_events().count();
+            let second = contract.fill_order(order_id, 300, accounts.bob).unwrap();
+            assert_eq!(
+                events_since(events_before),
+                vec![signature_of::<OrderFilled>(), signature_of::<CongestionModeChanged>()]
+            );
+            assert_eq!(contract.get_health(), Health {
+                total_locked: 1000,
+                pending_fill_count: 2,
+                pending_fill_value: 500,
+                congestion_soft_cap: Some(1),
+                congested: true,
+                effective_min_timelock: 150,
+                sunset: SunsetState::Live,
+                paused: false,
+            });
+
+            // Creation needs 150 blocks instead of 100 rather than failing outright
+            assert_eq!(create(&mut contract, 200), Err(Error::TimelockTooShort));
+            assert!(create(&mut contract, 250).is_ok());
+
+            // Settling a fill drops back under the cap and restores the normal minimum
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            contract.withdraw_fill(first, secret, None, None).unwrap();
+            assert_eq!(
+                ink::env::test::recorded_events().last().unwrap().topics[0][..],
+                signature_of::<CongestionModeChanged>()[..]
+            );
+            let health = contract.get_health();
+            assert_eq!((health.pending_fill_count, health.pending_fill_value), (1, 300));
+            assert_eq!((health.congested, health.effective_min_timelock), (false, 100));
+            assert!(create(&mut contract, 200).is_ok());
+
+            // Refunds settle fills too
+            ink::env::test::set_block_number::<TestEnv>(500);
+            contract.refund_fill(second, None).unwrap();
+            let health = contract.get_health();
+            assert_eq!((health.pending_fill_count, health.pending_fill_value), (0, 0));
+        }
+
+        #[ink::test]
+        fn test_beta_cap() {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_block_number::<TestEnv>(100);
+            let mut contract = PolkadotPartialFills::new();
+            // Room for exactly two 1000 orders
+            contract.set_beta_cap(2000, 1000, GraduationClock::Instantiation, BlockNumber::MAX).unwrap();
+
+            let create = |contract: &mut PolkadotPartialFills, amount: Balance, timelock| {
+                ink::env::test::set_caller::<TestEnv>(accounts.alice);
+                ink::env::test::set_value_transferred::<TestEnv>(amount.into());
+                contract.create_partial_fill_order(
+                    amount, 100, [0x01; 32], timelock, [0x02; 32], 1, 2,
+                    1_000_000_000_000, true, 5, None, None
+                )
+            };
+            assert_eq!(contract.get_account_cap_status(accounts.alice), AccountCapStatus {
+                cap: 2000,
+                current: 0,
+                graduation_block: Some(1100),
+                capped: true,
+            });
+
+            // The cap itself may be reached but not passed
+            let first = create(&mut contract, 1000, 500).unwrap();
+            let second = create(&mut contract, 1000, 500).unwrap();
+            assert_eq!(contract.get_account_cap_status(accounts.alice).current, 2000);
+            assert_eq!(
+                create(&mut contract, 1000, 500),
+                Err(Error::BetaCapExceeded { cap: 2000, current: 2000 })
+            );
+
+            // Filled and cancelled liquidity no longer counts
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let fill_id = contract.fill_order(first, 200, accounts.bob).unwrap();
+            assert_eq!(contract.get_account_cap_status(accounts.alice).current, 1800);
+            assert_eq!(
+                create(&mut contract, 201, 500),
+                Err(Error::BetaCapExceeded { cap: 2000, current: 1800 })
+            );
+            create(&mut contract, 200, 500).unwrap();
+            contract.cancel_order(second).unwrap();
+            assert_eq!(contract.get_account_cap_status(accounts.alice).current, 1000);
+
+            // A refunded fill's amount is unfilled liquidity of the live order again
+            ink::env::test::set_block_number::<TestEnv>(500);
+            contract.refund_fill(fill_id, None).unwrap();
+            assert_eq!(contract.get_account_cap_status(accounts.alice).current, 1200);
+
+            // A zero cap disables the mechanism
+            contract.set_beta_cap(0, 1000, GraduationClock::Instantiation, BlockNumber::MAX).unwrap();
+            assert!(!contract.get_account_cap_status(accounts.alice).capped);
+            create(&mut contract, 1000, 900).unwrap();
+
+            // Graduation lifts the cap once the block is reached
+            contract.set_beta_cap(2000, 1000, GraduationClock::Instantiation, BlockNumber::MAX).unwrap();
+            ink::env::test::set_block_number::<TestEnv>(1099);
+            assert_eq!(
+                create(&mut contract, 1000, 1500),
+                Err(Error::BetaCapExceeded { cap: 2000, current: 2200 })
+            );
+            ink::env::test::set_block_number::<TestEnv>(1100);
+            assert!(!contract.get_account_cap_status(accounts.alice).capped);
+            create(&mut contract, 1000, 1500).unwrap();
+            assert_eq!(contract.get_account_cap_status(accounts.alice).current, 3200);
+
+            // Counted from first use, each account has its own graduation block
+            contract.set_beta_cap(2000, 1000, GraduationClock::FirstUse, BlockNumber::MAX).unwrap();
+            let status = contract.get_account_cap_status(accounts.charlie);
+            assert_eq!((status.graduation_block, status.capped), (None, true));
+            let status = contract.get_account_cap_status(accounts.alice);
+            assert_eq!((status.graduation_block, status.capped), (Some(1100), false));
+        }
+
+        fn to_hex(bytes: &[u8]) -> String {
+            bytes.iter().map(|b| format!("{:02x}", b)).collect()
+        }
+
+        fn signature_of<E: ink::env::Event>() -> [u8; 32] {
+            E::SIGNATURE_TOPIC.unwrap()
+        }
+
+        /// Signature topics of the events recorded after the first `skip`, in emission order
+        fn events_since(skip: usize) -> Vec<[u8; 32]> {
+            ink::env::test::recorded_events()
+                .skip(skip)
+                .map(|event| event.topics[0][..].try_into().unwrap())
+                .collect()
+        }
+
+        /// Salt of a deployment at `0x33..33`, the address the fixtures are derived for
+        const DEPLOYMENT_SALT: &str = "ee238c76fe12ab3db54434433d6fd339d8d26393212f2b8fd3a8578577d4305a";
+
+        #[ink::test]
+        fn test_id_derivation_golden_vectors() {
+            const FIXTURES: &str = include_str!("../fixtures/id_derivation.json");
+            const ORDER_ID: &str = "87c3ac74d94ba8da17cde3638e0c63f864a7465ec85b5474bb327057f6f08a41";
+            const FILL_ID: &str = "50e77e08343bebe1e975f35c94ba9dbdb7595349c9d89c15ff52378def195ee4";
+            const CONTRACT_ID: &str = "4fb5f51e31a73076b42a46ec4bd05e630b342efd902830a679175217a2664eb1";
+            const UNTAGGED_ORDER_ID: &str = "9285ea8302cca332a72abeffacddcce4cbbd798ca1df22691e0bc87fda1aa29f";
+            const UNTAGGED_FILL_ID: &str = "e336e1ea784ec088bce0277dc7055b823753035a994232683d6a412c6dacae47";
+            const UNTAGGED_CONTRACT_ID: &str = "cd5ac3a8b2c88c86c29545c19b44176c58b97ed9637a692e933a9a7cb8b57dd9";
+            const UNSALTED_ORDER_ID: &str = "0d4f7a7a74679628f47826152607b6debdb8b58a4e60b7d89be9223449da5a6e";
+            const UNSALTED_FILL_ID: &str = "542920232a0f948b8c93c604400ad268437da3adfe1b05048dead286e7ca2c96";
+            const UNSALTED_CONTRACT_ID: &str = "6ebe04207407ab4ce77a921519358cb61f251fbca46a930073d4928c6c87d271";
+
+            ink::env::test::set_callee::<TestEnv>(Address::from([0x33; 20]));
+            let mut contract = PolkadotPartialFills::new();
+            ink::env::test::set_block_number::<TestEnv>(100);
+            ink::env::test::set_block_timestamp::<TestEnv>(1_700_000_000_000);
+            assert_eq!(contract.get_storage_version(), StorageVersion::CURRENT);
+            assert_eq!(contract.get_deployment_salt().map(|salt| to_hex(&salt)), Some(DEPLOYMENT_SALT.to_string()));
+
+            let maker = Address::from([0x11; 20]);
+            let taker = Address::from([0x22; 20]);
+            assert_eq!(PolkadotPartialFills::encode_address_for_hash(&maker), [0x11; 20]);
+
+            let order_id = contract.derive_order_id(&maker, 997, &[0x01; 32], 500, &[0x02; 32], 1);
+            let fill_id = contract.generate_fill_id(&order_id, &taker, 200);
+            let contract_id = contract.generate_contract_id(&order_id, &fill_id).unwrap();
+
+            assert_eq!(to_hex(&order_id), ORDER_ID);
+            assert_eq!(to_hex(&fill_id), FILL_ID);
+            assert_eq!(to_hex(&contract_id), CONTRACT_ID);
+
+            // Deployments still at storage version 3 keep deriving untagged ids
+            contract.storage_version = StorageVersion::V3;
+            let order_id = contract.derive_order_id(&maker, 997, &[0x01; 32], 500, &[0x02; 32], 1);
+            let fill_id = contract.generate_fill_id(&order_id, &taker, 200);
+            let contract_id = contract.derive_contract_id(&order_id, &fill_id, 1_700_000_000_000, 1);
+
+            assert_eq!(to_hex(&order_id), UNTAGGED_ORDER_ID);
+            assert_eq!(to_hex(&fill_id), UNTAGGED_FILL_ID);
+            assert_eq!(to_hex(&contract_id), UNTAGGED_CONTRACT_ID);
+
+            // Deployments still at storage version 2 keep deriving unsalted ids
+            contract.storage_version = StorageVersion::V2;
+            assert_eq!(contract.get_deployment_salt(), None);
+            let order_id = contract.derive_order_id(&maker, 997, &[0x01; 32], 500, &[0x02; 32], 1);
+            let fill_id = contract.generate_fill_id(&order_id, &taker, 200);
+            let contract_id = contract.derive_contract_id(&order_id, &fill_id, 1_700_000_000_000, 1);
+
+            assert_eq!(to_hex(&order_id), UNSALTED_ORDER_ID);
+            assert_eq!(to_hex(&fill_id), UNSALTED_FILL_ID);
+            assert_eq!(to_hex(&contract_id), UNSALTED_CONTRACT_ID);
+
+            // The shared fixtures consumed by the TS SDK must carry the same digests
+            assert!(FIXTURES.contains(&format!("\"deployment_salt\": \"0x{}\"", DEPLOYMENT_SALT)));
+            for digest in [ORDER_ID, FILL_ID, CONTRACT_ID] {
+                assert!(FIXTURES.contains(&format!("\"expected\": \"0x{}\"", digest)));
+            }
+            for digest in [UNTAGGED_ORDER_ID, UNTAGGED_FILL_ID, UNTAGGED_CONTRACT_ID] {
+                assert!(FIXTURES.contains(&format!("\"expected_untagged\": \"0x{}\"", digest)));
+            }
+            for digest in [UNSALTED_ORDER_ID, UNSALTED_FILL_ID, UNSALTED_CONTRACT_ID] {
+                assert!(FIXTURES.contains(&format!("\"expected_unsalted\": \"0x{}\"", digest)));
+            }
+        }
+
+        #[ink::test]
+        fn test_deployments_derive_distinct_ids() {
+            let derive = |address: [u8; 20]| {
+                ink::env::test::set_callee::<TestEnv>(Address::from(address));
+                let contract = PolkadotPartialFills::new();
+                contract.derive_order_id(&Address::from([0x11; 20]), 997, &[0x01; 32], 500, &[0x02; 32], 1)
+            };
+
+            // Identical orders on two deployments must not collide in an off-chain index
+            assert_ne!(derive([0x33; 20]), derive([0x44; 20]));
+            assert_eq!(derive([0x33; 20]), derive([0x33; 20]));
+        }
+
+        #[ink::test]
+        fn test_order_intent_golden_vector() {
+            const FIXTURES: &str = include_str!("../fixtures/order_intent.json");
+
+            let intent = OrderIntent {
+                maker: Address::from([0x11; 20]),
+                total_amount: 997,
+                min_fill_amount: 100,
+                hashlock: [0x01; 32],
+                timelock: 500,
+                swap_id: [0x02; 32],
+                source_chain: 1,
+                dest_chain: 2,
+                dest_amount_per_unit: 1_000_000_000_000,
+                allow_partial_fills: true,
+                max_fills: 5,
+                sender_cross_address: None,
+                receiver_cross_address: Some(vec![0x33; 20]),
+                vesting_schedule: None,
+                hash_algorithm: HashAlgorithm::Sha256,
+                source_decimals: Some(10),
+                dest_decimals: Some(18),
+                completion_callback: None,
+                on_max_fills: MaxFillsPolicy::Hold,
+                order_type: OrderType::FixedPrice,
+            };
+
+            assert!(FIXTURES.contains(&format!("\"encoding\": \"0x{}\"", to_hex(&intent.encode()))));
+            assert!(FIXTURES.contains(&format!("\"expected\": \"0x{}\"", to_hex(&intent.hash()))));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_order_group_summary() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let group_id = [0x55; 32];
+            let options = OrderOptions { group_id: Some(group_id), ..Default::default() };
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            // Three legs with different minimums and corridors
+            let first = contract.create_partial_fill_order_with_options(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None, options.clone()
+            ).unwrap();
+            let second = contract.create_partial_fill_order_with_options(
+                1000, 200, [0x01; 32], 500, [0x02; 32], 1, 3,
+                1_000_000_000_000, true, 5, None, None, options.clone()
+            ).unwrap();
+            let third = contract.create_partial_fill_order_with_options(
+                1000, 50, [0x01; 32], 500, [0x02; 32], 1, 4,
+                1_000_000_000_000, true, 5, None, None, options.clone()
+            ).unwrap();
+            assert_eq!(contract.get_group_orders(group_id), vec![first, second, third]);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            contract.fill_order(first, 100, accounts.bob).unwrap();
+            contract.fill_order(second, 1000, accounts.bob).unwrap();
+            contract.fill_order(third, 300, accounts.bob).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.cancel_order(third).unwrap();
+
+            let summary = contract.get_group_summary(group_id).unwrap();
+            assert_eq!(summary.order_count, 3);
+            assert_eq!(summary.total_escrowed, 3 * 1000);
+            assert_eq!(summary.total_filled, 100 + 1000 + 300);
+            assert_eq!(summary.total_remaining, 900); // cancelled leg has nothing left
+            assert_eq!(summary.orders, vec![
+                (first, OrderStatus::PartiallyFilled),
+                (second, OrderStatus::FullyFilled),
+                (third, OrderStatus::Cancelled),
+            ]);
+
+            // Groups belong to the maker that opened them
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let result = contract.create_partial_fill_order_with_options(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None, options
+            );
+            assert_eq!(result, Err(Error::Unauthorized));
+            assert_eq!(contract.get_group_orders(group_id).len(), 3);
+            assert!(contract.get_group_summary([0x56; 32]).is_none());
+        }
+
+        #[ink::test]
+        fn test_home_chain_strict_mode() {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            // Flag off: any source chain is accepted
+            let mut contract = PolkadotPartialFills::new();
+            assert_eq!(contract.get_home_chain_id(), 0);
+            assert!(!contract.is_home_chain_enforced());
+            assert!(contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).is_ok());
+
+            // Flag on: only orders escrowing on the home chain
+            contract.set_home_chain(2, true, BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_home_chain_id(), 2);
+            let result = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            );
+            assert_eq!(result, Err(Error::WrongSourceChain));
+            assert!(contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 2, 1,
+                1_000_000_000_000, true, 5, None, None
+            ).is_ok());
+
+            // Turning the flag back off keeps the id but stops enforcing it
+            contract.set_home_chain(2, false, BlockNumber::MAX).unwrap();
+            assert!(contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).is_ok());
+
+            let strict = PolkadotPartialFills::new_with_home_chain(7);
+            assert_eq!(strict.get_home_chain_id(), 7);
+            assert!(strict.is_home_chain_enforced());
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.set_home_chain(1, true, BlockNumber::MAX), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn test_default_hash_algorithm() {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let mut contract = PolkadotPartialFills::new_with_hash_algorithm(HashAlgorithm::Keccak256);
+            assert_eq!(contract.get_default_hash_algorithm(), HashAlgorithm::Keccak256);
+
+            let secret = [0x42; 32];
+            let keccak_lock = PolkadotPartialFills::hash_preimage(HashAlgorithm::Keccak256, &secret);
+            let sha_lock = contract.compute_sha256(&secret);
+            assert_ne!(keccak_lock, sha_lock);
+
+            // No algorithm given: the deployment default applies
+            let keccak_order = contract.create_partial_fill_order(
+                1000, 100, keccak_lock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            assert_eq!(contract.get_order(keccak_order).unwrap().hash_algorithm, HashAlgorithm::Keccak256);
+
+            let events_before = ink::env::test::recorded_events().count();
+            contract.set_default_hash_algorithm(HashAlgorithm::Sha256, BlockNumber::MAX).unwrap();
+            assert_eq!(ink::env::test::recorded_events().count(), events_before + 1);
+
+            let sha_order = contract.create_partial_fill_order(
+                1000, 100, sha_lock, 500, [0x03; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            // The earlier order keeps keccak256 after the default changed
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let keccak_fill = contract.fill_order(keccak_order, 200, accounts.bob).unwrap();
+            let sha_fill = contract.fill_order(sha_order, 200, accounts.bob).unwrap();
+            assert_eq!(contract.get_order(keccak_order).unwrap().hash_algorithm, HashAlgorithm::Keccak256);
+            assert_eq!(contract.get_order(sha_order).unwrap().hash_algorithm, HashAlgorithm::Sha256);
+            assert!(contract.withdraw_fill(keccak_fill, secret, None, None).is_ok());
+            assert!(contract.withdraw_fill(sha_fill, secret, None, None).is_ok());
+
+            assert_eq!(
+                contract.set_default_hash_algorithm(HashAlgorithm::Blake2x256, BlockNumber::MAX),
+                Err(Error::Unauthorized)
+            );
+        }
+
+        #[ink::test]
+        fn test_hash_algorithm_vectors() {
+            // Reference digests of a zero word and of uint256(1) as Solidity's
+            // `keccak256(abi.encode(...))`, `sha256` and blake2b-256 produce them
+            let zero = [0u8; 32];
+            let mut one = [0u8; 32];
+            one[31] = 1;
+            let vectors = [
+                (HashAlgorithm::Keccak256, zero, "290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e563"),
+                (HashAlgorithm::Keccak256, one, "b10e2d527612073b26eecdfd717e6a320cf44b4afac2b0732d9fcbe2b7fa0cf6"),
+                (HashAlgorithm::Sha256, zero, "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925"),
+                (HashAlgorithm::Sha256, one, "ec4916dd28fc4c10d78e287ca5d9cc51ee1ae73cbfde08c6b37324cbfaac8bc5"),
+                (HashAlgorithm::Blake2x256, zero, "89eb0d6a8a691dae2cd15ed0369931ce0a949ecafa5c3f93f8121833646e15c3"),
+                (HashAlgorithm::Blake2x256, one, "33e423980c9b37d048bd5fadbd4a2aeb95146922045405accc2f468d0ef96988"),
+            ];
+            for (algorithm, preimage, expected) in vectors {
+                assert_eq!(to_hex(&PolkadotPartialFills::hash_preimage(algorithm, &preimage)), expected);
+            }
+
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            // Hashlock as an Ethereum HTLC computes it for the secret [0x42; 32]
+            let eth_hashlock = [
+                0x18, 0x74, 0xb9, 0xac, 0xfa, 0xca, 0x38, 0x3a, 0x76, 0xe8, 0xf7, 0x25, 0x3b, 0xdb, 0x18, 0x39,
+                0x02, 0xf3, 0x62, 0x54, 0xb1, 0xe5, 0xe4, 0x52, 0xac, 0x78, 0x22, 0x8d, 0xb6, 0x3e, 0x93, 0xf3,
+            ];
+            let order_id = contract.create_partial_fill_order_with_options(
+                1000, 100, eth_hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None,
+                OrderOptions { hash_algorithm: Some(HashAlgorithm::Keccak256), ..Default::default() }
+            ).unwrap();
+
+            // The algorithm follows the hashlock in `PartialFillOrderCreated`,
+            // which precedes `OrderCommitted`
+            let events: Vec<_> = ink::env::test::recorded_events().collect();
+            let created = &events[events.len() - 2].data;
+            assert_eq!(created[32 + 20 + 32 + 16 + 16..][..32], eth_hashlock);
+            assert_eq!(created[32 + 20 + 32 + 16 + 16 + 32], HashAlgorithm::Keccak256 as u8);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let fill_id = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            assert_eq!(contract.withdraw_fill(fill_id, [0x41; 32], None, None), Err(Error::InvalidHashlock));
+            assert!(contract.withdraw_fill(fill_id, [0x42; 32], None, None).is_ok());
+        }
+
+        #[ink::test]
+        fn test_order_status_lifecycle() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            let create = |contract: &mut PolkadotPartialFills, hashlock| {
+                contract.create_partial_fill_order(
+                    1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                    1_000_000_000_000, true, 5, None, None
+                ).unwrap()
+            };
+            let partial = create(&mut contract, [0x01; 32]);
+            let full = create(&mut contract, [0x03; 32]);
+            let cancelled = create(&mut contract, [0x04; 32]);
+            assert_eq!(contract.get_order_status(partial), Some(OrderStatus::Active));
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            contract.fill_order(partial, 200, accounts.bob).unwrap();
+            contract.fill_order(full, 1000, accounts.bob).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.cancel_order(cancelled).unwrap();
+            assert_eq!(contract.get_order_status(partial), Some(OrderStatus::PartiallyFilled));
+            assert_eq!(contract.get_order_status(full), Some(OrderStatus::FullyFilled));
+            assert_eq!(contract.get_order_status(cancelled), Some(OrderStatus::Cancelled));
+
+            // Only the open order expires at its timelock
+            ink::env::test::set_block_number::<TestEnv>(499);
+            assert_eq!(contract.get_order_status(partial), Some(OrderStatus::PartiallyFilled));
+            ink::env::test::set_block_number::<TestEnv>(500);
+            assert_eq!(contract.get_order_status(partial), Some(OrderStatus::Expired));
+            assert_eq!(contract.get_order_status(full), Some(OrderStatus::FullyFilled));
+            assert_eq!(contract.get_order_status(cancelled), Some(OrderStatus::Cancelled));
+        }
+
+        #[ink::test]
+        fn test_taker_whitelist() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            assert!(contract.is_whitelisted_taker(order_id, accounts.charlie));
+
+            // Only the maker restricts the order
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.set_taker_whitelist(order_id, vec![accounts.bob]), Err(Error::Unauthorized));
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            let too_many = vec![accounts.bob; MAX_TAKER_WHITELIST + 1];
+            assert_eq!(contract.set_taker_whitelist(order_id, too_many), Err(Error::BatchTooLarge));
+            contract.set_taker_whitelist(order_id, vec![accounts.bob, accounts.django]).unwrap();
+            assert!(contract.is_whitelisted_taker(order_id, accounts.bob));
+            assert!(!contract.is_whitelisted_taker(order_id, accounts.charlie));
+
+            // Everyone else is refused, in fills, reservations and the fillable preview
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            assert_eq!(contract.fill_order(order_id, 200, accounts.charlie), Err(Error::UnauthorizedFill));
+            assert_eq!(contract.get_max_fillable_amount(order_id, accounts.charlie), Err(Error::UnauthorizedFill));
+            ink::env::test::set_value_transferred::<TestEnv>(DEFAULT_RESERVATION_BOND.into());
+            assert_eq!(contract.reserve(order_id, 200), Err(Error::UnauthorizedFill));
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            contract.fill_order(order_id, 200, accounts.bob).unwrap();
+
+            // The list is fixed from the first fill on
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            assert_eq!(contract.set_taker_whitelist(order_id, Vec::new()), Err(Error::TakerWhitelistLocked));
+
+            // Lifting the list before any fill opens the order to everyone again
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            let open = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x03; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            contract.set_taker_whitelist(open, vec![accounts.bob]).unwrap();
+            contract.set_taker_whitelist(open, Vec::new()).unwrap();
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            assert!(contract.fill_order(open, 200, accounts.charlie).is_ok());
+        }
+
+        #[ink::test]
+        fn test_maker_pauses_fills() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let fill_id = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            assert_eq!(contract.set_order_fill_paused(order_id, true), Err(Error::Unauthorized));
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.set_order_fill_paused(order_id, true).unwrap();
+            assert_eq!(contract.get_order_status(order_id), Some(OrderStatus::FillsPaused));
+            #[cfg(feature = "periphery")]
+            assert_eq!(
+                contract.get_order_full_state(order_id).unwrap().status,
+                OrderStatus::FillsPaused
+            );
+
+            // New fills are refused, the existing fill still settles
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            assert_eq!(contract.fill_order(order_id, 200, accounts.charlie), Err(Error::OrderFillsPaused));
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert!(contract.withdraw_fill(fill_id, secret, None, None).is_ok());
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.set_order_fill_paused(order_id, false).unwrap();
+            assert_eq!(contract.get_order_status(order_id), Some(OrderStatus::PartiallyFilled));
+
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            assert!(contract.fill_order(order_id, 200, accounts.charlie).is_ok());
+            assert_eq!(contract.get_order_status([0xff; 32]), None);
+        }
+
+        #[ink::test]
+        fn test_admin_operation_deadline() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            // Deadline already passed
+            assert_eq!(contract.update_protocol_fee(50, 99), Err(Error::AdminOperationExpired));
+            let config = ChainConfig { max_counterpart_timelock: 3600 };
+            assert_eq!(contract.register_chain(2, config.clone(), 50), Err(Error::AdminOperationExpired));
+            assert_eq!(contract.get_protocol_fee_bps(), 30);
+            assert!(contract.get_chain_config(2).is_none());
+
+            // Deadline at or after the current block
+            assert!(contract.update_protocol_fee(50, 100).is_ok());
+            assert!(contract.register_chain(2, config.clone(), 120).is_ok());
+            assert_eq!(contract.get_protocol_fee_bps(), 50);
+            assert_eq!(contract.get_chain_config(2), Some(config));
+
+            // A call signed at block 100 to be valid for ten blocks and included late
+            ink::env::test::set_block_number::<TestEnv>(111);
+            assert_eq!(contract.set_block_time_ms(5000, 100 + 10), Err(Error::AdminOperationExpired));
+            ink::env::test::set_block_number::<TestEnv>(110);
+            assert!(contract.set_block_time_ms(5000, 100 + 10).is_ok());
+
+            // Authorization is checked before the deadline
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.update_protocol_fee(50, 99), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn test_underpayment_reports_amounts() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            // Short by one planck
+            ink::env::test::set_value_transferred::<TestEnv>(999u128.into());
+            let result = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            );
+            assert_eq!(result, Err(Error::InsufficientFunds { required: 1000, received: 999 }));
+            assert_eq!(
+                contract.simulate_create(1000, 999),
+                Err(Error::InsufficientFunds { required: 1000, received: 999 })
+            );
+
+            // Off by a factor of ten, as with a wrong planck conversion
+            ink::env::test::set_value_transferred::<TestEnv>(100u128.into());
+            let result = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            );
+            assert_eq!(result, Err(Error::InsufficientFunds { required: 1000, received: 100 }));
+            assert_eq!(
+                contract.simulate_create(1000, 100),
+                Err(Error::InsufficientFunds { required: 1000, received: 100 })
+            );
+
+            assert_eq!(contract.simulate_create(1000, 0), Err(Error::NoValueTransferred));
+            assert_eq!(contract.simulate_create(1000, 1000), Ok(()));
+        }
+
+        #[ink::test]
+        fn test_overpayment_returned_to_maker() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let native = |account| -> Balance {
+                ink::env::test::get_account_balance::<TestEnv>(account).unwrap().try_into().unwrap()
+            };
+            // Charlie makes, so the maker is not the contract account the tests run as
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            ink::env::test::set_block_number::<TestEnv>(100);
+            let create = |contract: &mut PolkadotPartialFills, swap_id: [u8; 32]| {
+                contract.create_partial_fill_order(
+                    1000, 100, [0x01; 32], 500, swap_id, 1, 2,
+                    1_000_000_000_000, true, 5, None, None
+                )
+            };
+
+            ink::env::test::set_value_transferred::<TestEnv>(1200u128.into());
+            let before = native(accounts.charlie);
+            let order_id = create(&mut contract, [0x02; 32]).unwrap();
+            assert_eq!(native(accounts.charlie) - before, 200);
+            assert_eq!(contract.get_order(order_id).unwrap().total_amount, 1000);
+            assert_eq!(contract.get_total_locked(), 1000);
+
+            // Exact payment moves nothing back
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            let before = native(accounts.charlie);
+            create(&mut contract, [0x03; 32]).unwrap();
+            assert_eq!(native(accounts.charlie), before);
+
+        }
+
+        #[ink::test]
+        fn test_admin_audit_log() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let config = ChainConfig { max_counterpart_timelock: 3600 };
+            contract.update_protocol_fee(50, BlockNumber::MAX).unwrap();
+            contract.register_chain(2, config.clone(), BlockNumber::MAX).unwrap();
+            contract.set_block_time_ms(5000, BlockNumber::MAX).unwrap();
+
+            // Rejected changes are not logged
+            assert!(contract.update_protocol_fee(5000, BlockNumber::MAX).is_err());
+
+            let log = contract.get_admin_audit_log(0, 10).unwrap().items;
+            assert_eq!(log.len(), 3);
+            assert!(log.iter().all(|entry| entry.block == 100));
+
+            assert_eq!(log[0].function_id, admin_fn::UPDATE_PROTOCOL_FEE);
+            assert_eq!(log[0].old_value, 30u16.encode());
+            assert_eq!(log[0].new_value, 50u16.encode());
+
+            assert_eq!(log[1].function_id, admin_fn::REGISTER_CHAIN);
+            assert_eq!(log[1].old_value, (2u32, None::<ChainConfig>).encode());
+            assert_eq!(log[1].new_value, (2u32, Some(config)).encode());
+
+            assert_eq!(log[2].function_id, admin_fn::SET_BLOCK_TIME_MS);
+            assert_eq!(log[2].old_value, 6000u64.encode());
+            assert_eq!(log[2].new_value, 5000u64.encode());
+
+            assert_eq!(contract.get_admin_audit_log(2, 10).unwrap().items, log[2..].to_vec());
+            assert!(contract.get_admin_audit_log(3, 10).unwrap().items.is_empty());
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_open_orders_filter() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            // alice: 1->2 partial, 1->3 all-or-nothing; bob: 4->2 partial, later filled down to 497
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            let a12 = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            let a13 = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 3,
+                1_000_000_000_000, false, 1, None, None
+            ).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let b42 = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 4, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            ink::env::test::set_block_number::<TestEnv>(150);
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            contract.fill_order(b42, 503, accounts.charlie).unwrap();
+
+            let ids = |filter: OrderFilter| -> Vec<[u8; 32]> {
+                let page = contract.get_open_orders(0, 10, Some(filter)).unwrap();
+                page.items.into_iter().map(|(id, _)| id).collect()
+            };
+
+            assert_eq!(contract.get_open_orders(0, 10, None).unwrap().items.len(), 3);
+            assert_eq!(ids(OrderFilter { source_chain: Some(1), ..Default::default() }), vec![a12, a13]);
+            assert_eq!(ids(OrderFilter { dest_chain: Some(2), ..Default::default() }), vec![a12, b42]);
+            assert_eq!(ids(OrderFilter { min_remaining: Some(500), ..Default::default() }), vec![a12, a13]);
+            assert_eq!(ids(OrderFilter { maker: Some(accounts.bob), ..Default::default() }), vec![b42]);
+            assert_eq!(ids(OrderFilter { allow_partial_only: true, ..Default::default() }), vec![a12, b42]);
+
+            // Combined dimensions
+            let combined = OrderFilter {
+                dest_chain: Some(2),
+                min_remaining: Some(400),
+                allow_partial_only: true,
+                ..Default::default()
+            };
+            assert_eq!(ids(combined.clone()), vec![a12, b42]);
+            assert_eq!(ids(OrderFilter { maker: Some(accounts.alice), ..combined }), vec![a12]);
+
+            // Filtering is per page: page two (a13) matches, page three (b42) does not
+            let by_source = || Some(OrderFilter { source_chain: Some(1), ..Default::default() });
+            let page = contract.get_open_orders(1, 1, by_source()).unwrap();
+            assert_eq!(page.items.into_iter().map(|(id, _)| id).collect::<Vec<_>>(), vec![a13]);
+            assert_eq!(page.next_offset, Some(2));
+            let last = contract.get_open_orders(2, 1, by_source()).unwrap();
+            assert!(last.items.is_empty());
+            assert_eq!(last.next_offset, None);
+
+            // Idle orders drop out; b42 was last filled at 150, the others created at 100
+            ink::env::test::set_block_number::<TestEnv>(200);
+            let excluding_idle = |blocks| OrderFilter { exclude_idle_blocks: Some(blocks), ..Default::default() };
+            assert_eq!(ids(excluding_idle(101)), vec![a12, a13, b42]);
+            assert_eq!(ids(excluding_idle(51)), vec![b42]);
+            assert!(ids(excluding_idle(50)).is_empty());
+            assert_eq!(
+                contract.get_order_full_state(b42).unwrap().last_activity_block,
+                Some(150)
+            );
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_orders_sorted_by_price() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            for price in [3u128, 5, 1, 4, 2] {
+                contract.create_partial_fill_order(
+                    1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                    price * 1_000_000_000_000, true, 5, None, None
+                ).unwrap();
+            }
+            // Other pair, ignored
+            contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 3,
+                9 * 1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            let prices = |orders: Result<Vec<PartialFillOrder>, Error>| -> Vec<Balance> {
+                orders.unwrap().iter().map(|o| o.dest_amount_per_unit / 1_000_000_000_000).collect()
+            };
+
+            assert_eq!(prices(contract.get_orders_sorted_by_price(1, 2, false, 10)), vec![5, 4, 3, 2, 1]);
+            assert_eq!(prices(contract.get_orders_sorted_by_price(1, 2, true, 10)), vec![1, 2, 3, 4, 5]);
+            assert_eq!(prices(contract.get_orders_sorted_by_price(1, 2, false, 2)), vec![5, 4]);
+            assert_eq!(prices(contract.get_orders_sorted_by_price(1, 3, true, 10)), vec![9]);
+            assert!(contract.get_orders_sorted_by_price(2, 1, true, 10).unwrap().is_empty());
+
+            // An auction ranks at the rate of the current block: 6 down to 0.5 by block 500
+            let options = OrderOptions {
+                order_type: Some(OrderType::DutchAuction { start_rate: 6 * RATE_SCALE, end_rate: RATE_SCALE / 2 }),
+                ..Default::default()
+            };
+            let auction = contract.create_partial_fill_order_with_options(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                6 * RATE_SCALE, true, 5, None, None, options
+            ).unwrap();
+            let top = |contract: &PolkadotPartialFills| contract.get_orders_sorted_by_price(1, 2, false, 1).unwrap()[0].clone();
+            assert_eq!(top(&contract), contract.get_order(auction).unwrap());
+            ink::env::test::set_block_number::<TestEnv>(300);
+            assert_eq!(top(&contract).dest_amount_per_unit, 5 * RATE_SCALE);
+            let ascending = contract.get_orders_sorted_by_price(1, 2, true, 10).unwrap();
+            // 3.25 by then, between the orders at 3 and 4
+            assert_eq!(ascending[3].dest_amount_per_unit, 6 * RATE_SCALE);
+
+            // Orders past the first `MAX_CORRIDOR_SCAN` of a corridor are not ranked
+            let create = |contract: &mut PolkadotPartialFills, price: Balance| {
+                contract.create_partial_fill_order(
+                    1000, 100, [0x01; 32], 500, [0x02; 32], 1, 3,
+                    price * 1_000_000_000_000, true, 5, None, None
+                ).unwrap();
+            };
+            for _ in 1..MAX_CORRIDOR_SCAN {
+                create(&mut contract, 1);
+            }
+            create(&mut contract, 10);
+            assert_eq!(prices(contract.get_orders_sorted_by_price(1, 3, false, 1)), vec![9]);
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_corridor_latency_stats() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let at = |block| ink::env::test::set_block_number::<TestEnv>(block);
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            at(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let to_eth = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                RATE_SCALE, true, 5, None, None
+            ).unwrap();
+            let to_sol = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x03; 32], 1, 3,
+                RATE_SCALE, true, 5, None, None
+            ).unwrap();
+            assert_eq!(contract.get_order_created_block(to_eth), Some(100));
+            assert_eq!(contract.get_order_created_block([0x99; 32]), None);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            at(110);
+            let first = contract.fill_order(to_eth, 100, accounts.bob).unwrap();
+            at(130);
+            let second = contract.fill_order(to_eth, 200, accounts.bob).unwrap();
+            at(150);
+            contract.fill_order(to_sol, 300, accounts.bob).unwrap();
+            let eth = contract.get_corridor_latency_stats(1, 2);
+            assert_eq!((eth.fill_count, eth.time_to_fill_blocks), (2, 40));
+            assert_eq!(eth.average_time_to_settle(), None);
+
+            at(160);
+            contract.withdraw_fill(first, secret, None, None).unwrap();
+            at(170);
+            contract.withdraw_fill(second, secret, None, None).unwrap();
+            at(180);
+            assert_eq!(contract.withdraw_order_fills(to_sol, secret, None), Ok(1));
+
+            let eth = contract.get_corridor_latency_stats(1, 2);
+            assert_eq!(eth, CorridorLatency {
+                fill_count: 2,
+                time_to_fill_blocks: 40,
+                settle_count: 2,
+                time_to_settle_blocks: 90,
+            });
+            assert_eq!((eth.average_time_to_fill(), eth.average_time_to_settle()), (Some(20), Some(45)));
+            let sol = contract.get_corridor_latency_stats(1, 3);
+            assert_eq!((sol.average_time_to_fill(), sol.average_time_to_settle()), (Some(50), Some(30)));
+            // Direction matters
+            assert_eq!(contract.get_corridor_latency_stats(2, 1), CorridorLatency::default());
+            assert_eq!(CorridorLatency::default().average_time_to_fill(), None);
+
+            // A sum at its limit drops the sample instead of wrapping
+            let full = CorridorLatency { time_to_fill_blocks: u64::MAX, ..eth };
+            assert_eq!(full.with_fill(1), None);
+            assert_eq!(full.with_fill(0).map(|latency| latency.fill_count), Some(3));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_corridor_depth_tracks_order_lifecycle() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let unit = 1_000_000_000_000u128;
+            let mut create = |dest_chain: u32, price: Balance| {
+                contract.create_partial_fill_order(
+                    1000, 100, [0x01; 32], 500, [0x02; 32], 1, dest_chain,
+                    price * unit, true, 5, None, None
+                ).unwrap()
+            };
+            let mid = create(2, 3);
+            let best = create(2, 5);
+            let worst = create(2, 1);
+            create(3, 9);
+            let depth = |contract: &PolkadotPartialFills| contract.get_corridor_depth(1, 2);
+
+            // Each order escrows its full 1000
+            assert_eq!(depth(&contract), CorridorDepth {
+                total_remaining: 3000,
+                order_count: 3,
+                best_rate: Some(5 * unit),
+                worst_rate: Some(unit),
+                rates_partial: false,
+            });
+            assert_eq!(contract.get_corridor_depth(1, 3).order_count, 1);
+            assert_eq!(contract.get_corridor_depth(2, 1), CorridorDepth::default());
+
+            // A partial fill only drains liquidity; filling the worst order out drops it
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let fill_id = contract.fill_order(mid, 400, accounts.bob).unwrap();
+            assert_eq!(depth(&contract).total_remaining, 2600);
+            contract.fill_order(worst, 1000, accounts.bob).unwrap();
+            let after_fills = depth(&contract);
+            assert_eq!((after_fills.total_remaining, after_fills.order_count), (1600, 2));
+            assert_eq!(after_fills.worst_rate, Some(3 * unit));
+
+            // Cancelling the best order leaves one rate on both ends
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.cancel_order(best).unwrap();
+            assert_eq!(depth(&contract), CorridorDepth {
+                total_remaining: 600,
+                order_count: 1,
+                best_rate: Some(3 * unit),
+                worst_rate: Some(3 * unit),
+                rates_partial: false,
+            });
+
+            // A refund returns its amount to the corridor
+            ink::env::test::set_block_number::<TestEnv>(500);
+            contract.refund_fill(fill_id, None).unwrap();
+            assert_eq!(depth(&contract).total_remaining, 1000);
+
+            contract.cancel_order(mid).unwrap();
+            assert_eq!(depth(&contract), CorridorDepth::default());
+
+            // An auction adds liquidity but no rate, its own moving every block
+            ink::env::test::set_block_number::<TestEnv>(600);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            let options = OrderOptions {
+                order_type: Some(OrderType::DutchAuction { start_rate: 2 * unit, end_rate: unit }),
+                ..Default::default()
+            };
+            contract.create_partial_fill_order_with_options(
+                1000, 100, [0x01; 32], 1000, [0x02; 32], 1, 2,
+                2 * unit, true, 5, None, None, options
+            ).unwrap();
+            assert_eq!(depth(&contract), CorridorDepth {
+                total_remaining: 1000,
+                order_count: 1,
+                best_rate: None,
+                worst_rate: None,
+                rates_partial: false,
+            });
+        }
+
+        #[ink::test]
+        fn test_fees_charged_per_fill() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let native = |account| -> Balance {
+                ink::env::test::get_account_balance::<TestEnv>(account).unwrap().try_into().unwrap()
+            };
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            let order = contract.get_order(order_id).unwrap();
+            assert_eq!((order.total_amount, order.fee), (1000, 0));
+            assert_eq!(contract.get_total_locked(), 1000);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let withdrawn = contract.fill_order(order_id, 500, accounts.bob).unwrap();
+            let refunded = contract.fill_order(order_id, 400, accounts.bob).unwrap();
+            assert_eq!(contract.get_fill(withdrawn).unwrap().fee, 1);
+            assert_eq!(contract.get_fill(refunded).unwrap().fee, 1);
+
+            // Cancelling returns the unfilled 100 whole
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            let before = native(accounts.alice);
+            contract.cancel_order(order_id).unwrap();
+            assert_eq!(native(accounts.alice) - before, 100);
+            assert_eq!(contract.get_protocol_fees(), 0);
+
+            // The fee comes out of the taker's payout on withdrawal
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let before = native(accounts.bob);
+            contract.withdraw_fill(withdrawn, secret, None, None).unwrap();
+            assert_eq!(native(accounts.bob) - before, 499);
+            assert_eq!(contract.get_protocol_fees(), 1);
+            assert_eq!(contract.get_total_locked(), 400);
+
+            // A refunded fill was never a trade: the maker gets all of it back
+            ink::env::test::set_block_number::<TestEnv>(500);
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            let before = native(accounts.alice);
+            contract.refund_fill(refunded, None).unwrap();
+            assert_eq!(native(accounts.alice) - before, 400);
+            assert_eq!(contract.get_protocol_fees(), 1);
+            assert_eq!(contract.get_total_locked(), 0);
+            assert_eq!(contract.get_total_liabilities(), 1);
+        }
+
+        #[ink::test]
+        fn test_fee_bps_snapshot_survives_fee_change() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            assert_eq!(contract.get_order(order_id).unwrap().fee_bps_applied, 30);
+
+            contract.update_protocol_fee(100, BlockNumber::MAX).unwrap();
+
+            // Fills are charged at the 30 bps snapshot, not the new 100 bps
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let fill_id = contract.fill_order(order_id, 400, accounts.bob).unwrap();
+            assert_eq!(contract.get_fill(fill_id).unwrap().fee, 1);
+            assert_eq!(contract.get_fill_fee(order_id, 600), Some(1));
+
+            // Cancelling returns the whole unfilled amount
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            assert_eq!(contract.get_total_locked(), 1000);
+            contract.cancel_order(order_id).unwrap();
+            assert_eq!(contract.get_total_locked(), 400);
+
+            let order = contract.get_order(order_id).unwrap();
+            assert_eq!((order.fee_bps_applied, order.fee), (30, 0));
+            assert_eq!(contract.get_protocol_fees(), 0);
+
+            // Orders created after the change snapshot the new rate
+            let later = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x03; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            assert_eq!(contract.get_fill_fee(later, 400), Some(4));
+            let later = contract.get_order(later).unwrap();
+            assert_eq!((later.fee_bps_applied, later.total_amount, later.fee), (100, 1000, 0));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_migrate_v1_orders_to_v2() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            assert_eq!(contract.get_storage_version(), StorageVersion::CURRENT);
+
+            let current = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            let legacy_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x03; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            // Overwrite the second order with its V1 encoding, as an old deployment left it:
+            // the fee was taken out of the escrow at creation
+            let order = contract.get_order(legacy_id).unwrap();
+            let v1 = PartialFillOrderV1 {
+                maker: order.maker,
+                total_amount: 997,
+                filled_amount: 250,
+                min_fill_amount: order.min_fill_amount,
+                hashlock: order.hashlock,
+                timelock: order.timelock,
+                cancelled: false,
+                swap_id: order.swap_id,
+                source_chain: order.source_chain,
+                dest_chain: order.dest_chain,
+                dest_amount_per_unit: order.dest_amount_per_unit,
+                fee: 3,
+                allow_partial_fills: true,
+                max_fills: 5,
+                current_fills: 1,
+                sender_cross_address: Some(vec![0xab; 20]),
+                receiver_cross_address: None,
+            };
+            let orders_key = ink::storage::traits::StorageKey::key(&contract.orders);
+            ink::env::set_contract_storage(&(orders_key, legacy_id), &v1);
+            contract.storage_version = StorageVersion::V1;
+            // V1 deployments kept no order lists
+            contract.id_list_remove(IdList::ActiveOrders, legacy_id);
+            contract.id_list_remove(IdList::MakerOrders(order.maker), legacy_id);
+
+            let too_many = vec![[0xee; 32]; MAX_MIGRATION_BATCH + 1];
+            assert_eq!(contract.migrate_storage_to_v2(too_many, true, BlockNumber::MAX), Err(Error::BatchTooLarge));
+
+            // The version holds until the caller reports the last batch
+            assert_eq!(contract.migrate_storage_to_v2(vec![current, legacy_id], false, BlockNumber::MAX), Ok(1));
+            assert_eq!(contract.get_storage_version(), StorageVersion::V1);
+            assert!(contract.id_list_contains(IdList::ActiveOrders, legacy_id));
+            assert!(contract.id_list_contains(IdList::MakerOrders(order.maker), legacy_id));
+            assert_eq!(contract.migrate_storage_to_v2(vec![[0xff; 32]], true, BlockNumber::MAX), Ok(0));
+            assert_eq!(contract.get_storage_version(), StorageVersion::V2);
+
+            let migrated = contract.get_order(legacy_id).unwrap();
+            assert_eq!(migrated.filled_amount, 250);
+            assert_eq!(migrated.current_fills, 1);
+            assert_eq!(migrated.sender_cross_address, Some(vec![0xab; 20]));
+            assert_eq!(migrated.vesting_schedule, None);
+            assert_eq!(migrated.hash_algorithm, HashAlgorithm::Sha256);
+            assert!(!migrated.fills_paused);
+            assert_eq!(migrated.fee_bps_applied, 30);
+            // Having paid up front, its fills are not charged again
+            assert_eq!(contract.get_fill_fee(legacy_id, 747), Some(0));
+            assert_eq!(contract.get_order(current).unwrap().filled_amount, 0);
+
+            // Idempotent
+            assert_eq!(contract.migrate_storage_to_v2(vec![legacy_id], true, BlockNumber::MAX), Ok(0));
+            assert_eq!(contract.get_order(legacy_id), Some(migrated));
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.migrate_storage_to_v2(vec![], true, BlockNumber::MAX), Err(Error::Unauthorized));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_migrate_v1_fills() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let current = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            let legacy = contract.fill_order(order_id, 300, accounts.bob).unwrap();
+            let current_fill = contract.get_fill(current).unwrap();
+
+            // Overwrite the second fill with its encoding from before `StoredFill`
+            let fill = contract.get_fill(legacy).unwrap();
+            let v1 = FillExecutionV1 {
+                order_id,
+                taker: accounts.bob,
+                fill_amount: 300,
+                contract_id: [0xc1; 32],
+                withdrawn: false,
+                refunded: false,
+                preimage: None,
+                timestamp: fill.timestamp,
+                deposit: fill.deposit,
+                requested_amount: 300,
+            };
+            let fills_key = ink::storage::traits::StorageKey::key(&contract.fills);
+            ink::env::set_contract_storage(&(fills_key, legacy), &v1);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            let too_many = vec![[0xee; 32]; MAX_MIGRATION_BATCH + 1];
+            assert_eq!(contract.migrate_fills(too_many, BlockNumber::MAX), Err(Error::BatchTooLarge));
+            assert_eq!(contract.migrate_fills(vec![current, legacy, [0xff; 32]], BlockNumber::MAX), Ok(1));
+
+            let migrated = contract.get_fill(legacy).unwrap();
+            assert_eq!(migrated.contract_id, [0xc1; 32]);
+            assert_eq!((migrated.taker, migrated.fill_amount, migrated.deposit), (accounts.bob, 300, fill.deposit));
+            assert!(!migrated.withdrawn && !migrated.refunded);
+            assert_eq!(contract.get_fill(current), Some(current_fill));
+
+            // Idempotent
+            assert_eq!(contract.migrate_fills(vec![legacy], BlockNumber::MAX), Ok(0));
+            assert_eq!(contract.get_fill(legacy), Some(migrated));
+
+            // The stored contract id survives the fill being written back
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            contract.withdraw_fill(legacy, secret, None, None).unwrap();
+            let withdrawn = contract.get_fill(legacy).unwrap();
+            assert!(withdrawn.withdrawn);
+            assert_eq!((withdrawn.contract_id, withdrawn.preimage), ([0xc1; 32], Some(secret)));
+
+            assert_eq!(contract.migrate_fills(vec![legacy], BlockNumber::MAX), Err(Error::Unauthorized));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_expiring_soon_classification() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            let early = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 300, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            let soon = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 450, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            let later = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 600, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let early_fill = contract.fill_order(early, 200, accounts.bob).unwrap();
+            let soon_fill = contract.fill_order(soon, 200, accounts.bob).unwrap();
+            contract.fill_order(later, 200, accounts.bob).unwrap();
+
+            // Block 400 with a 60 block horizon: `early` expired, `soon` closes in 50,
+            // `later` in 200 lies beyond the horizon
+            ink::env::test::set_block_number::<TestEnv>(400);
+            let items = contract.get_expiring_soon(60, 0, 10).unwrap().items;
+            assert_eq!(items, vec![
+                ExpiringItem {
+                    id: early,
+                    kind: OrderOrFill::Order,
+                    blocks_remaining: 0,
+                    action: ExpiryAction::RefundAvailable,
+                },
+                ExpiringItem {
+                    id: early_fill,
+                    kind: OrderOrFill::Fill,
+                    blocks_remaining: 0,
+                    action: ExpiryAction::RefundAvailable,
+                },
+                ExpiringItem {
+                    id: soon_fill,
+                    kind: OrderOrFill::Fill,
+                    blocks_remaining: 50,
+                    action: ExpiryAction::WithdrawClosing,
+                },
+            ]);
+
+            // Horizon boundary is inclusive; settled fills drop out
+            assert_eq!(contract.get_expiring_soon(49, 0, 10).unwrap().items.len(), 2);
+            assert_eq!(contract.get_expiring_soon(200, 0, 10).unwrap().items.len(), 4);
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.refund_fill(early_fill, None).unwrap();
+            assert_eq!(contract.get_expiring_soon(60, 0, 10).unwrap().items.len(), 2);
+
+            // Paging bounds the scan to the orders on the page
+            assert_eq!(contract.get_expiring_soon(60, 1, 1).unwrap().items.len(), 1);
+            assert!(contract.get_expiring_soon(60, 2, 1).unwrap().items.is_empty());
+        }
+
+        #[ink::test]
+        fn test_fee_free_window() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            assert_eq!(contract.set_fee_free_window(120, 90, BlockNumber::MAX), Err(Error::InvalidTimelock));
+            contract.set_fee_free_window(90, 110, BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_fee_free_window(), Some((90, 110)));
+
+            let events_before = ink::env::test::recorded_events().count();
+            let first = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            // Order created and committed, then the one-off activation it caused
+            assert_eq!(
+                events_since(events_before),
+                vec![
+                    signature_of::<PartialFillOrderCreated>(),
+                    signature_of::<OrderCommitted>(),
+                    signature_of::<FeeFreeWindowActivated>(),
+                ]
+            );
+
+            ink::env::test::set_block_number::<TestEnv>(110);
+            let events_before = ink::env::test::recorded_events().count();
+            let second = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 600, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            assert_eq!(ink::env::test::recorded_events().count(), events_before + 2);
+
+            for order_id in [first, second] {
+                let order = contract.get_order(order_id).unwrap();
+                assert_eq!((order.total_amount, order.fee, order.fee_bps_applied), (1000, 0, 0));
+                assert_eq!(contract.get_fill_fee(order_id, 1000), Some(0));
+            }
+
+            // Past the window regular fees resume
+            ink::env::test::set_block_number::<TestEnv>(111);
+            let after = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 600, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            assert_eq!(contract.get_order(after).unwrap().fee_bps_applied, 30);
+            assert_eq!(contract.get_fill_fee(after, 1000), Some(3));
+        }
+
+        #[ink::test]
+        fn test_resolver_deposit_schedule() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            // Empty schedule: no deposit anywhere
+            assert_eq!(contract.get_required_deposit(order_id, 1000), 0);
+
+            // No deposit below 300, 5% from 300, 10% from 600
+            contract.set_deposit_schedule(vec![(300, 500), (600, 1000)], BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_required_deposit(order_id, 299), 0);
+            assert_eq!(contract.get_required_deposit(order_id, 300), 15);
+            assert_eq!(contract.get_required_deposit(order_id, 599), 29);
+            assert_eq!(contract.get_required_deposit(order_id, 600), 60);
+            assert_eq!(contract.get_required_deposit(order_id, 5000), 100); // clamped to 1000
+            assert_eq!(
+                contract.set_deposit_schedule(vec![(300, 10_001)], BlockNumber::MAX),
+                Err(Error::InvalidDepositSchedule)
+            );
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let small = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            assert_eq!(contract.get_fill(small).unwrap().deposit, 0);
+
+            ink::env::test::set_value_transferred::<TestEnv>(14u128.into());
+            assert_eq!(
+                contract.fill_order(order_id, 300, accounts.bob),
+                Err(Error::InsufficientFunds { required: 15, received: 14 })
+            );
+            ink::env::test::set_value_transferred::<TestEnv>(15u128.into());
+            let bonded = contract.fill_order(order_id, 300, accounts.bob).unwrap();
+            assert_eq!(contract.get_fill(bonded).unwrap().deposit, 15);
+            assert_eq!(contract.get_total_locked(), 1000 + 15);
+
+            // Completing the swap returns the deposit with the payout
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            contract.withdraw_fill(bonded, secret, Some(PayoutRoute::Credit), None).unwrap();
+            assert_eq!(contract.get_credit(accounts.bob), 315);
+            assert_eq!(contract.get_total_locked(), 1000 - 300);
+
+            // Back to an empty schedule
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.set_deposit_schedule(Vec::new(), BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_required_deposit(order_id, 500), 0);
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_stats_consumers_notified() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let stats = accounts.django;
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            contract.set_stats_consumers(vec![stats], BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_stats_consumers(), vec![stats]);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                2_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let fill_id = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            contract.withdraw_fill(fill_id, secret, None, None).unwrap();
+
+            let data: StatsEventData = (1, 2, 200, 400);
+            assert_eq!(consumer_mock::calls(), vec![
+                (stats, STATS_EVENT_FILL, data.encode()),
+                (stats, STATS_EVENT_WITHDRAW, data.encode()),
+            ]);
+        }
+
+        #[ink::test]
+        fn test_dest_amount_with_token_decimals() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(50_000_000_000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let mut create = |rate: Balance, source_decimals, dest_decimals, swap_id: [u8; 32]| {
+                let options = OrderOptions { source_decimals, dest_decimals, ..Default::default() };
+                contract.create_partial_fill_order_with_options(
+                    50_000_000_000, 100, [0x01; 32], 500, swap_id, 1, 2,
+                    rate, true, 5, None, None, options
+                ).unwrap()
+            };
+            // 7.25 USDC per DOT; DOT has 10 decimals, USDC 6
+            let dot_usdc = create(7_250_000_000_000, Some(10), Some(6), [0x02; 32]);
+            // 0.0025 ETH per DOT; ETH has 18 decimals
+            let dot_eth = create(2_500_000_000, Some(10), Some(18), [0x03; 32]);
+            // Only the USDC side given; the source falls back to 12 decimals
+            let ksm_usdc = create(7_250_000_000_000, None, Some(6), [0x04; 32]);
+            // No metadata: the rate converts base units directly
+            let legacy = create(2_000_000_000_000, None, None, [0x05; 32]);
+
+            // 2 DOT = 2 * 10^10 planck
+            let fill_amount = 20_000_000_000;
+            // 2 * 7.25 = 14.5 USDC = 14_500_000 micro-USDC
+            assert_eq!(contract.get_fill_quote(dot_usdc, fill_amount), Some(14_500_000));
+            // 2 * 0.0025 = 0.005 ETH = 5 * 10^15 wei
+            assert_eq!(contract.get_fill_quote(dot_eth, fill_amount), Some(5_000_000_000_000_000));
+            // 0.02 KSM * 7.25 = 0.145 USDC
+            assert_eq!(contract.get_fill_quote(ksm_usdc, fill_amount), Some(145_000));
+            assert_eq!(contract.get_fill_quote(legacy, fill_amount), Some(40_000_000_000));
+            assert_eq!(contract.get_fill_quote([0xff; 32], fill_amount), None);
+
+            let order = contract.get_order(dot_usdc).unwrap();
+            assert_eq!((order.source_decimals, order.dest_decimals), (Some(10), Some(6)));
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            contract.fill_order(dot_eth, fill_amount, accounts.bob).unwrap();
+            #[cfg(feature = "periphery")]
+            assert_eq!(
+                contract.get_order_fill_price_stats(dot_eth).unwrap().total_dest_amount,
+                5_000_000_000_000_000
+            );
+        }
+
+        #[ink::test]
+        fn test_dutch_auction_order() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let mut create = |rate: Balance, order_type, swap_id: [u8; 32]| {
+                let options = OrderOptions { order_type, ..Default::default() };
+                contract.create_partial_fill_order_with_options(
+                    1000, 100, [0x01; 32], 500, swap_id, 1, 2,
+                    rate, true, 5, None, None, options
+                )
+            };
+            // From 2 to 1 destination units per unit over blocks 100..500
+            let auction = OrderType::DutchAuction { start_rate: 2 * RATE_SCALE, end_rate: RATE_SCALE };
+            assert_eq!(create(RATE_SCALE, Some(auction), [0x02; 32]), Err(Error::InvalidAuctionRates));
+            let to_zero = OrderType::DutchAuction { start_rate: 2 * RATE_SCALE, end_rate: 0 };
+            assert_eq!(create(2 * RATE_SCALE, Some(to_zero), [0x02; 32]), Err(Error::InvalidAuctionRates));
+            let order_id = create(2 * RATE_SCALE, Some(auction), [0x02; 32]).unwrap();
+            let fixed = create(2 * RATE_SCALE, None, [0x03; 32]).unwrap();
+            let order = contract.get_order(order_id).unwrap();
+            assert_eq!((order.order_type, order.creation_block), (auction, 100));
+            assert_eq!(contract.get_order(fixed).unwrap().order_type, OrderType::FixedPrice);
+            assert_eq!(contract.get_current_rate(order_id), 2 * RATE_SCALE);
+            assert_eq!(contract.get_current_rate([0xff; 32]), 0);
+
+            // Each fill is priced at the rate of its block
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            ink::env::test::set_block_number::<TestEnv>(300);
+            assert_eq!(contract.get_current_rate(order_id), RATE_SCALE * 3 / 2);
+            assert_eq!(contract.get_current_rate(fixed), 2 * RATE_SCALE);
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            ink::env::test::set_block_number::<TestEnv>(400);
+            assert_eq!(contract.get_fill_quote(order_id, 100), Some(125));
+            assert_eq!(contract.get_fill_quote(fixed, 100), Some(200));
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            contract.fill_order(order_id, 400, accounts.charlie).unwrap();
+            #[cfg(feature = "periphery")]
+            {
+                let stats = contract.get_order_fill_price_stats(order_id).unwrap();
+                assert_eq!((stats.min_rate, stats.max_rate), (RATE_SCALE * 5 / 4, RATE_SCALE * 3 / 2));
+                assert_eq!(stats.total_dest_amount, 300 + 500);
+            }
+
+            // Held at the end rate from the timelock on
+            ink::env::test::set_block_number::<TestEnv>(900);
+            assert_eq!(contract.get_current_rate(order_id), RATE_SCALE);
+        }
+
+        #[ink::test]
+        fn test_order_display() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(50_000_000_000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            // 7.25 USDC per DOT
+            let options = OrderOptions {
+                source_decimals: Some(10),
+                dest_decimals: Some(6),
+                ..Default::default()
+            };
+            let dot_usdc = contract.create_partial_fill_order_with_options(
+                50_000_000_000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                7_250_000_000_000, true, 5, None, None, options
+            ).unwrap();
+            // Without metadata the native token's decimals apply
+            let legacy = contract.create_partial_fill_order(
+                50_000_000_000, 100, [0x01; 32], 500, [0x03; 32], 1, 2,
+                2_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            contract.fill_order(dot_usdc, 20_000_000_000, accounts.bob).unwrap();
+
+            // 3 DOT left, owed 21.75 USDC
+            let display = contract.get_order_display(dot_usdc).unwrap();
+            assert_eq!(display.total_amount, 50_000_000_000);
+            assert_eq!(display.filled_amount, 20_000_000_000);
+            assert_eq!(display.remaining_amount, 30_000_000_000);
+            assert_eq!(display.min_fill_amount, 100);
+            assert_eq!((display.decimals, display.dest_decimals), (10, Some(6)));
+            assert_eq!(display.dest_remaining_amount, 21_750_000);
+
+            let display = contract.get_order_display(legacy).unwrap();
+            assert_eq!((display.decimals, display.dest_decimals), (10, None));
+            assert_eq!(display.dest_remaining_amount, 100_000_000_000);
+            assert_eq!(contract.get_order_display([0xff; 32]), None);
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_announce_pending_fills() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let fill_ids: Vec<[u8; 32]> = [100, 110, 120, 130]
+                .into_iter()
+                .map(|amount| contract.fill_order(order_id, amount, accounts.bob).unwrap())
+                .collect();
+            // A settled fill is no longer pending
+            contract.withdraw_fill(fill_ids[0], secret, None, None).unwrap();
+
+            ink::env::test::set_block_number::<TestEnv>(200);
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            assert_eq!(contract.announce_pending_fills(), Ok(3));
+            assert_eq!(contract.get_last_announcement_block(), 200);
+
+            let event = ink::env::test::recorded_events().last().unwrap();
+            // The withdrawn fill's slot was taken by the last pending one
+            let pending = vec![fill_ids[3], fill_ids[1], fill_ids[2]];
+            let expected = (pending, vec![order_id; 3], 200 as BlockNumber);
+            assert!(event.data.ends_with(&expected.encode()));
+
+            ink::env::test::set_block_number::<TestEnv>(250);
+            assert_eq!(contract.announce_pending_fills(), Err(Error::AnnouncementTooSoon));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_duplicate_order_guard() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let create = |contract: &mut PolkadotPartialFills, swap_id: [u8; 32]| {
+                contract.create_partial_fill_order(
+                    1000, 100, [0x01; 32], 500, swap_id, 1, 2,
+                    1_000_000_000_000, true, 5, None, None
+                )
+            };
+
+            // Flag off: a double submit goes through
+            let first = create(&mut contract, [0x02; 32]).unwrap();
+            let second = create(&mut contract, [0x03; 32]).unwrap();
+            assert_ne!(first, second);
+
+            contract.set_reject_duplicate_orders(true, BlockNumber::MAX).unwrap();
+            assert!(contract.is_rejecting_duplicate_orders());
+            let rate = 1_000_000_000_000;
+            assert_eq!(contract.find_duplicate(accounts.alice, 1000, [0x01; 32], 2, rate), Some(second));
+            assert_eq!(create(&mut contract, [0x04; 32]), Err(Error::DuplicateOrder));
+
+            // Different rate is a different order
+            assert_eq!(contract.find_duplicate(accounts.alice, 1000, [0x01; 32], 2, rate * 2), None);
+
+            // Cancelling both clears the fingerprint
+            contract.cancel_order(first).unwrap();
+            contract.cancel_order(second).unwrap();
+            assert_eq!(contract.find_duplicate(accounts.alice, 1000, [0x01; 32], 2, rate), None);
+            assert!(create(&mut contract, [0x04; 32]).is_ok());
+        }
+
+        #[ink::test]
+        fn test_max_fillable_amount() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(2000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let order_id = contract.create_partial_fill_order(
+                2000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 2, None, None
+            ).unwrap();
+            let vesting = OrderOptions {
+                vesting_schedule: Some(VestingSchedule {
+                    unlock_amount_per_block: 10,
+                    unlock_start_block: 100,
+                }),
+                ..Default::default()
+            };
+            let vested_id = contract.create_partial_fill_order_with_options(
+                1000, 100, [0x01; 32], 500, [0x03; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None, vesting.clone()
+            ).unwrap();
+            let all_or_nothing = contract.create_partial_fill_order_with_options(
+                1000, 100, [0x01; 32], 500, [0x04; 32], 1, 2,
+                1_000_000_000_000, false, 5, None, None, vesting
+            ).unwrap();
+            let net = contract.get_order(order_id).unwrap().total_amount;
+            let bob = accounts.bob;
+            let max_fillable = |contract: &PolkadotPartialFills, id| contract.get_max_fillable_amount(id, bob);
+
+            // Remaining amount
+            assert_eq!(max_fillable(&contract, order_id), Ok(net));
+            assert_eq!(contract.get_max_fillable_amount([0xff; 32], bob), Err(Error::OrderNotFound));
+
+            // Capacity reserved by others refuses an all-or-nothing fill as `fill_order` does
+            let whole = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x05; 32], 1, 2,
+                1_000_000_000_000, false, 5, None, None
+            ).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            ink::env::test::set_value_transferred::<TestEnv>(DEFAULT_RESERVATION_BOND.into());
+            contract.reserve(whole, 400).unwrap();
+            assert_eq!(max_fillable(&contract, whole), Err(Error::PartialFillsNotAllowed));
+            ink::env::test::set_caller::<TestEnv>(bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            assert_eq!(contract.fill_order(whole, 1000, bob), Err(Error::PartialFillsNotAllowed));
+
+            // Vesting: nothing unlocked at the start block, then below the minimum fill
+            assert_eq!(max_fillable(&contract, vested_id), Err(Error::FillExceedsUnlocked));
+            ink::env::test::set_block_number::<TestEnv>(105);
+            assert_eq!(max_fillable(&contract, vested_id), Err(Error::FillAmountTooSmall));
+            ink::env::test::set_block_number::<TestEnv>(130);
+            assert_eq!(max_fillable(&contract, vested_id), Ok(300));
+            // All-or-nothing orders wait for the full unlock
+            assert_eq!(max_fillable(&contract, all_or_nothing), Err(Error::FillExceedsUnlocked));
+
+            // Vesting combined with an earlier fill
+            ink::env::test::set_caller::<TestEnv>(bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            contract.fill_order(vested_id, 250, bob).unwrap();
+            assert_eq!(max_fillable(&contract, vested_id), Err(Error::FillAmountTooSmall));
+            ink::env::test::set_block_number::<TestEnv>(140);
+            assert_eq!(max_fillable(&contract, vested_id), Ok(150));
+
+            // The same taker repeating a fill in the same block collides on the fill id
+            assert_eq!(net % 2, 0);
+            contract.fill_order(order_id, net / 2, bob).unwrap();
+            assert_eq!(max_fillable(&contract, order_id), Err(Error::FillAlreadyExists));
+            assert_eq!(contract.get_max_fillable_amount(order_id, accounts.charlie), Ok(net / 2));
+
+            // Fill count, in combination with a partially filled order
+            ink::env::test::set_block_number::<TestEnv>(141);
+            contract.fill_order(order_id, 100, bob).unwrap();
+            assert_eq!(max_fillable(&contract, order_id), Err(Error::MaxFillsReached));
+
+            // Order state
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.set_order_fill_paused(vested_id, true).unwrap();
+            assert_eq!(max_fillable(&contract, vested_id), Err(Error::OrderFillsPaused));
+            contract.set_order_fill_paused(vested_id, false).unwrap();
+            ink::env::test::set_block_number::<TestEnv>(500);
+            assert_eq!(max_fillable(&contract, vested_id), Err(Error::TimelockExpired));
+        }
+
+        #[ink::test]
+        fn test_order_raw_and_storage_key() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            let raw = contract.get_order_raw(order_id).unwrap();
+            let decoded = PartialFillOrder::decode(&mut &raw[..]).unwrap();
+            assert_eq!(Some(decoded), contract.get_order(order_id));
+            assert_eq!(contract.get_order_raw([0xff; 32]), None);
+
+            // The derived key addresses the bytes the mapping actually wrote
+            let key = contract.get_order_ink_storage_key(order_id);
+            assert_eq!(key.len(), 36);
+            assert_eq!(key[4..], order_id[..]);
+            let slot = <(u32, [u8; 32])>::decode(&mut &key[..]).unwrap();
+            assert_eq!(ink::env::contains_contract_storage(&slot), Some(raw.len() as u32));
+            let stored = ink::env::get_contract_storage::<_, PartialFillOrder>(&slot).unwrap().unwrap();
+            assert_eq!(stored.encode(), raw);
+        }
+
+        #[ink::test]
+        fn test_refund_cooldown() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            assert_eq!(contract.get_refund_cooldown_blocks(), 300);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let fill_id = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+
+            ink::env::test::set_block_number::<TestEnv>(500);
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.refund_fill(fill_id, None).unwrap();
+            assert_eq!(contract.get_refund_cooldown_until(order_id, accounts.bob), Some(800));
+
+            // Reopen the order, as a timelock extension would
+            let mut order = contract.get_order(order_id).unwrap();
+            order.timelock = 2000;
+            contract.orders.insert(&order_id, &order);
+
+            // The refunded taker is locked out, others are not
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let refill = contract.fill_order(order_id, 200, accounts.bob);
+            assert_eq!(refill, Err(Error::RefundCooldownActive));
+            let max_fillable = contract.get_max_fillable_amount(order_id, accounts.bob);
+            assert_eq!(max_fillable, Err(Error::RefundCooldownActive));
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            assert!(contract.fill_order(order_id, 200, accounts.charlie).is_ok());
+
+            ink::env::test::set_block_number::<TestEnv>(800);
+            assert_eq!(contract.get_refund_cooldown_until(order_id, accounts.bob), None);
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert!(contract.fill_order(order_id, 200, accounts.bob).is_ok());
+        }
+
+        #[ink::test]
+        fn test_refund_and_cancel_pay_escrow_exactly_once() {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let balance = |account| ink::env::test::get_account_balance::<TestEnv>(account).unwrap();
+
+            // Each round runs on a fresh instance, starting where the previous one ended
+            for (refund_first, start) in [(true, 100), (false, 500)] {
+                let mut contract = PolkadotPartialFills::new();
+                ink::env::test::set_caller::<TestEnv>(accounts.alice);
+                ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+                ink::env::test::set_block_number::<TestEnv>(start);
+                let order_id = contract.create_partial_fill_order(
+                    1000, 100, [0x01; 32], start + 400, [0x02; 32], 1, 2,
+                    1_000_000_000_000, true, 5, None, None
+                ).unwrap();
+                let escrowed = contract.get_order(order_id).unwrap().total_amount;
+
+                ink::env::test::set_caller::<TestEnv>(accounts.bob);
+                ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+                let first = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+                let second = contract.fill_order(order_id, 300, accounts.bob).unwrap();
+
+                ink::env::test::set_block_number::<TestEnv>(start + 400);
+                ink::env::test::set_caller::<TestEnv>(accounts.alice);
+                let before = balance(accounts.alice);
+                if refund_first {
+                    contract.refund_fill(first, None).unwrap();
+                    contract.cancel_order(order_id).unwrap();
+                    contract.refund_fill(second, None).unwrap();
+                } else {
+                    contract.cancel_order(order_id).unwrap();
+                    contract.refund_fill(first, None).unwrap();
+                    contract.refund_fill(second, None).unwrap();
+                }
+
+                let paid = balance(accounts.alice) - before;
+                assert_eq!(paid, escrowed.into(), "refund_first: {refund_first}");
+                assert_eq!(contract.get_total_locked(), 0);
+            }
+        }
+
+        #[ink::test]
+        fn test_refunded_capacity_is_still_escrowed() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let native = |account| -> Balance {
+                ink::env::test::get_account_balance::<TestEnv>(account).unwrap().try_into().unwrap()
+            };
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            let cancelled = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x03; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let expired = contract.fill_order(order_id, 1000, accounts.bob).unwrap();
+            let expired_on_cancelled = contract.fill_order(cancelled, 600, accounts.bob).unwrap();
+
+            // Refunding a fill of a live order pays the maker nothing: the amount stays
+            // in escrow as the order's unfilled capacity
+            ink::env::test::set_block_number::<TestEnv>(500);
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            let before = native(accounts.alice);
+            contract.refund_fill(expired, None).unwrap();
+            assert_eq!(native(accounts.alice), before);
+            assert_eq!(contract.get_remaining_amount(order_id), 1000);
+            assert_eq!(contract.get_total_locked(), 2000);
+
+            // Refilled once reopened, it is paid out of that escrow and nothing else's
+            let mut order = contract.get_order(order_id).unwrap();
+            order.timelock = 2000;
+            contract.orders.insert(&order_id, &order);
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            let refill = contract.fill_order(order_id, 1200, accounts.charlie).unwrap();
+            assert_eq!(contract.get_fill(refill).unwrap().fill_amount, 1000);
+            let before = native(accounts.charlie);
+            contract.withdraw_fill(refill, secret, None, None).unwrap();
+            assert_eq!(native(accounts.charlie) - before, 997);
+            assert_eq!(contract.get_total_locked(), 1000);
+
+            // A cancelled order already returned its remainder, so a refund there pays
+            // the maker directly and the amount can never be filled again
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.cancel_order(cancelled).unwrap();
+            let before = native(accounts.alice);
+            contract.refund_fill(expired_on_cancelled, None).unwrap();
+            assert_eq!(native(accounts.alice) - before, 600);
+            assert_eq!(contract.get_remaining_amount(cancelled), 0);
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            assert_eq!(contract.fill_order(cancelled, 600, accounts.charlie), Err(Error::OrderCancelled));
+            assert_eq!(contract.get_total_locked(), 0);
+            assert_eq!(contract.get_total_liabilities(), 3);
+        }
+
+        #[ink::test]
+        fn test_public_refund_splits_principal_and_deposit() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let native = |account| -> Balance {
+                ink::env::test::get_account_balance::<TestEnv>(account).unwrap().try_into().unwrap()
+            };
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            contract.set_deposit_schedule(vec![(300, 500)], BlockNumber::MAX).unwrap();
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            // Bob takes 400 against a 20 deposit; the order is cancelled around him
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(20u128.into());
+            let fill_id = contract.fill_order(order_id, 400, accounts.bob).unwrap();
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.cancel_order(order_id).unwrap();
+            assert_eq!(contract.get_total_locked(), 420);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            assert_eq!(contract.public_refund_fill(fill_id, None), Err(Error::TimelockNotExpired));
+            assert_eq!(contract.refund_fill(fill_id, None), Err(Error::UnauthorizedRefund));
+
+            // Charlie collects the deposit and the principal goes back to the maker
+            ink::env::test::set_block_number::<TestEnv>(500);
+            let before = native(accounts.charlie);
+            contract.public_refund_fill(fill_id, None).unwrap();
+            assert_eq!(native(accounts.charlie) - before, 20);
+            assert_eq!(contract.get_total_locked(), 0);
+            assert!(contract.get_fill(fill_id).unwrap().refunded);
+            assert_eq!(contract.public_refund_fill(fill_id, None), Err(Error::AlreadyProcessed));
+        }
+
+        #[ink::test]
+        fn test_reward_budget_caps_public_refund_rewards() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            contract.set_deposit_schedule(vec![(100, 1000)], BlockNumber::MAX).unwrap();
+            assert_eq!(contract.set_reward_cap(Some(RewardCap::FeeShare(10_001)), BlockNumber::MAX), Err(Error::InvalidFee));
+            contract.set_reward_cap(Some(RewardCap::Flat(25)), BlockNumber::MAX).unwrap();
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            assert_eq!(
+                contract.get_reward_budget(order_id),
+                Some(RewardBudget { allowance: Some(25), paid: 0, remaining: Some(25) })
+            );
+            assert_eq!(contract.get_reward_budget([0x99; 32]), None);
+
+            // Four fills of 100, each forfeiting a deposit of 10 once refunded
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(10u128.into());
+            let fills: Vec<[u8; 32]> = (0..4)
+                .map(|i| {
+                    ink::env::test::set_block_number::<TestEnv>(100 + i);
+                    contract.fill_order(order_id, 100, accounts.bob).unwrap()
+                })
+                .collect();
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+
+            // Charlie collects deposits until the budget runs out, the last in part;
+            // past it the refunds still settle, paying the whole deposit to the maker
+            ink::env::test::set_block_number::<TestEnv>(500);
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            let mut rewards = Vec::new();
+            for fill_id in fills.iter() {
+                let events_before = ink::env::test::recorded_events().count();
+                contract.public_refund_fill(*fill_id, None).unwrap();
+                assert!(contract.get_fill(*fill_id).unwrap().refunded);
+                let refunded = ink::env::test::recorded_events()
+                    .skip(events_before)
+                    .find(|event| event.topics[0][..] == signature_of::<FillRefunded>()[..])
+                    .unwrap();
+                let (_, _, _, _, maker_amount, _, _, executor_amount) =
+                    <([u8; 32], [u8; 32], Address, PayoutRoute, Balance, bool, Address, Balance)>::decode(
+                        &mut &refunded.data[..],
+                    )
+                    .unwrap();
+                rewards.push((executor_amount, maker_amount));
+            }
+            assert_eq!(rewards, vec![(10, 0), (10, 0), (5, 5), (0, 10)]);
+            assert_eq!(
+                contract.get_reward_budget(order_id),
+                Some(RewardBudget { allowance: Some(25), paid: 25, remaining: Some(0) })
+            );
+
+            // A share of the fee on the whole order instead, and no cap at all
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            let fee = contract.get_fill_fee(order_id, 1000).unwrap();
+            contract.set_reward_cap(Some(RewardCap::FeeShare(5_000)), BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_reward_budget(order_id).unwrap().allowance, Some(fee / 2));
+            contract.set_reward_cap(None, BlockNumber::MAX).unwrap();
+            assert_eq!(
+                contract.get_reward_budget(order_id),
+                Some(RewardBudget { allowance: None, paid: 25, remaining: None })
+            );
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_get_orders_by_maker() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            let create = |contract: &mut PolkadotPartialFills, swap_id: u8| {
+                contract.create_partial_fill_order(
+                    1000, 100, [0x01; 32], 500, [swap_id; 32], 1, 2,
+                    1_000_000_000_000, true, 5, None, None
+                ).unwrap()
+            };
+            let live = create(&mut contract, 1);
+            let cancelled = create(&mut contract, 2);
+            let filled = create(&mut contract, 3);
+            let later = create(&mut contract, 4);
+            contract.cancel_order(cancelled).unwrap();
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let total = contract.get_order(filled).unwrap().total_amount;
+            contract.fill_order(filled, total, accounts.bob).unwrap();
+
+            // Cancelled and fully filled orders are left out, with their state for the rest
+            let page = contract.get_orders_by_maker(accounts.alice, 0, 10).unwrap();
+            let ids: Vec<[u8; 32]> = page.items.iter().map(|(order_id, _)| *order_id).collect();
+            assert_eq!(ids.len(), 2);
+            assert!(ids.contains(&live) && ids.contains(&later));
+            assert_eq!(page.items[0].1, contract.get_order(page.items[0].0).unwrap());
+            assert_eq!((page.total, page.next_offset), (4, None));
+            assert!(contract.get_orders_by_maker(accounts.bob, 0, 10).unwrap().items.is_empty());
+
+            // Pages running past the end are cut short rather than failing
+            let tail = contract.get_orders_by_maker(accounts.alice, 3, 10).unwrap();
+            assert!(tail.items.len() <= 1 && tail.next_offset.is_none());
+            assert!(contract.get_orders_by_maker(accounts.alice, 10, 10).unwrap().items.is_empty());
+            assert_eq!(
+                contract.get_orders_by_maker(accounts.alice, 0, MAX_OPEN_ORDERS_PAGE + 1),
+                Err(Error::PageTooLarge)
+            );
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_get_fills_by_taker() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let create = |contract: &mut PolkadotPartialFills, swap_id: u8| {
+                contract.create_partial_fill_order(
+                    1000, 100, hashlock, 500, [swap_id; 32], 1, 2,
+                    1_000_000_000_000, true, 5, None, None
+                ).unwrap()
+            };
+            let first = create(&mut contract, 1);
+            let second = create(&mut contract, 2);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let withdrawn = contract.fill_order(first, 200, accounts.bob).unwrap();
+            let pending = contract.fill_order(second, 300, accounts.bob).unwrap();
+            contract.withdraw_fill(withdrawn, secret, None, None).unwrap();
+
+            // Fills across orders, in fill order, each with its current state
+            let page = contract.get_fills_by_taker(accounts.bob, 0, 10).unwrap();
+            assert_eq!(page.items, vec![
+                (withdrawn, contract.get_fill(withdrawn).unwrap()),
+                (pending, contract.get_fill(pending).unwrap()),
+            ]);
+            assert_eq!((page.total, page.next_offset), (2, None));
+            let unsettled: Vec<[u8; 32]> = page.items.iter()
+                .filter(|(_, fill)| !fill.withdrawn && !fill.refunded)
+                .map(|(fill_id, _)| *fill_id)
+                .collect();
+            assert_eq!(unsettled, vec![pending]);
+
+            let head = contract.get_fills_by_taker(accounts.bob, 0, 1).unwrap();
+            assert_eq!((head.items.len(), head.next_offset), (1, Some(1)));
+            assert!(contract.get_fills_by_taker(accounts.alice, 0, 10).unwrap().items.is_empty());
+            assert_eq!(
+                contract.get_fills_by_taker(accounts.bob, 0, MAX_TAKER_FILLS_PAGE + 1),
+                Err(Error::PageTooLarge)
+            );
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_account_position() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            contract.set_deposit_schedule(vec![(300, 500)], BlockNumber::MAX).unwrap();
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            // Bob makes an order of his own, takes three fills and settles one to credit
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let bob_order = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x03; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let credited = contract.fill_order(order_id, 100, accounts.bob).unwrap();
+            contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            ink::env::test::set_value_transferred::<TestEnv>(20u128.into());
+            contract.fill_order(order_id, 400, accounts.bob).unwrap();
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            contract.withdraw_fill(credited, secret, Some(PayoutRoute::Credit), None).unwrap();
+
+            assert_eq!(contract.get_account_position(accounts.bob), AccountPosition {
+                orders_escrowed: 1000,
+                fills_claimable: 600,
+                deposits_held: 20,
+                credits: 100,
+                truncated: false,
+            });
+            // Alice's order has 1000 - 700 left unfilled
+            assert_eq!(contract.get_account_position(accounts.alice).orders_escrowed, 300);
+            assert_eq!(contract.get_account_position(accounts.charlie), AccountPosition::default());
+
+            assert_eq!(contract.get_maker_orders(accounts.bob, 0, 10).unwrap().items, vec![bob_order]);
+            let fill_ids = contract.get_taker_fills(accounts.bob, 0, 10).unwrap().items;
+            assert_eq!(fill_ids.len(), 3);
+            assert_eq!(fill_ids[0], credited);
+            assert_eq!(contract.get_taker_fills(accounts.bob, 1, 1).unwrap().items, vec![fill_ids[1]]);
+
+            // A cancelled order no longer counts
+            contract.cancel_order(bob_order).unwrap();
+            assert_eq!(contract.get_account_position(accounts.bob).orders_escrowed, 0);
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_export_state_chunks() {
+            fn export_all<T: Decode>(contract: &PolkadotPartialFills, kind: ExportKind) -> Vec<T> {
+                let mut entries = Vec::new();
+                let mut offset = Some(0);
+                while let Some(next) = offset {
+                    let (chunk, next_offset) = contract.export_state_chunk(kind, next, 1);
+                    entries.extend(Vec::<T>::decode(&mut &chunk[..]).unwrap());
+                    offset = next_offset;
+                }
+                entries
+            }
+
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let live = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            let cancelled = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x03; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            contract.cancel_order(cancelled).unwrap();
+            contract.map_address(CrossChainAddress::Ethereum([0x11; 20])).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let withdrawn = contract.fill_order(live, 200, accounts.bob).unwrap();
+            let pending = contract.fill_order(live, 300, accounts.bob).unwrap();
+            contract.withdraw_fill(withdrawn, secret, Some(PayoutRoute::Credit), None).unwrap();
+            contract.map_address(CrossChainAddress::Substrate([0x22; 32])).unwrap();
+
+            // Walked one entry per chunk, each table matches the direct queries
+            let hint = contract.state_root_hint();
+            assert_eq!(
+                export_all::<([u8; 32], PartialFillOrder, Option<BlockNumber>)>(&contract, ExportKind::Orders),
+                vec![
+                    (live, contract.get_order(live).unwrap(), Some(100)),
+                    (cancelled, contract.get_order(cancelled).unwrap(), Some(100)),
+                ]
+            );
+            assert_eq!(
+                export_all::<([u8; 32], FillExecution)>(&contract, ExportKind::Fills),
+                vec![(pending, contract.get_fill(pending).unwrap())]
+            );
+            assert_eq!(
+                export_all::<(Address, Balance)>(&contract, ExportKind::Credits),
+                vec![(accounts.bob, contract.get_credit(accounts.bob))]
+            );
+            assert_eq!(
+                export_all::<(Address, CrossChainAddress)>(&contract, ExportKind::Mappings),
+                vec![
+                    (accounts.alice, contract.get_cross_address(accounts.alice).unwrap()),
+                    (accounts.bob, contract.get_cross_address(accounts.bob).unwrap()),
+                ]
+            );
+            assert_eq!(contract.state_root_hint(), hint);
+
+            // Oversized chunks are cut to the maximum; past the end is empty
+            let (chunk, next) = contract.export_state_chunk(ExportKind::Orders, 0, u32::MAX);
+            assert_eq!(Vec::<([u8; 32], PartialFillOrder, Option<BlockNumber>)>::decode(&mut &chunk[..]).unwrap().len(), 2);
+            assert_eq!(next, None);
+            assert_eq!(contract.export_state_chunk(ExportKind::Fills, 5, 1), (Vec::<u8>::new().encode(), None));
+
+            // Emptied credit leaves the export, and activity moves the hint
+            contract.withdraw_credit(200).unwrap();
+            assert!(export_all::<(Address, Balance)>(&contract, ExportKind::Credits).is_empty());
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x04; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            assert!(contract.state_root_hint() > hint);
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_state_root_hint_tracks_every_change() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            // A fill logs no update but still changes the exported fills
+            let hint = contract.state_root_hint();
+            let nonce = contract.get_event_nonce();
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            assert_eq!(contract.get_event_nonce(), nonce);
+            assert!(contract.state_root_hint() > hint);
+
+            // So does a cancellation
+            let hint = contract.state_root_hint();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.cancel_order(order_id).unwrap();
+            assert_eq!(contract.get_event_nonce(), nonce);
+            assert!(contract.state_root_hint() > hint);
+
+            // Views and refused messages leave it alone
+            let hint = contract.state_root_hint();
+            contract.export_state_chunk(ExportKind::Orders, 0, 10);
+            assert_eq!(contract.cancel_order(order_id), Err(Error::OrderCancelled));
+            assert_eq!(contract.state_root_hint(), hint);
+        }
+        #[ink::test]
+        fn test_fill_clamped_event() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+
+            // 700 of 1000 fits: only `OrderFilled`
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let events_before = ink::env::test::recorded_events().count();
+            let exact = contract.fill_order(order_id, 700, accounts.bob).unwrap();
+            assert_eq!(ink::env::test::recorded_events().count(), events_before + 1);
+            let fill = contract.get_fill(exact).unwrap();
+            assert_eq!((fill.requested_amount, fill.fill_amount), (700, 700));
+
+            // 1000 requested, 300 left
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            let events_before = ink::env::test::recorded_events().count();
+            let clamped = contract.fill_order(order_id, 1000, accounts.charlie).unwrap();
+            assert_eq!(
+                events_since(events_before),
+                vec![signature_of::<OrderFilled>(), signature_of::<FillClamped>()]
+            );
+            let event = ink::env::test::recorded_events().last().unwrap();
+            assert!(event.data.ends_with(&(1000 as Balance, 300 as Balance).encode()));
+            let fill = contract.get_fill(clamped).unwrap();
+            assert_eq!((fill.requested_amount, fill.fill_amount), (1000, 300));
+        }
+
+        #[ink::test]
+        fn test_rearm_order() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let leaked = [0x42; 32];
+            let fresh = [0x43; 32];
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, contract.compute_sha256(&leaked), 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            let other_id = contract.create_partial_fill_order(
+                1000, 100, contract.compute_sha256(&leaked), 500, [0x03; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+
+            // Before any fill: once, by the maker only
+            let new_hashlock = contract.compute_sha256(&fresh);
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.rearm_order(order_id, new_hashlock), Err(Error::Unauthorized));
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.rearm_order(order_id, new_hashlock).unwrap();
+            let order = contract.get_order(order_id).unwrap();
+            assert_eq!((order.hashlock, order.rearm_count), (new_hashlock, 1));
+            // The re-arm is announced before the intent change it causes
+            let events: Vec<_> = ink::env::test::recorded_events().collect();
+            let [rearmed, intent_changed] = &events[events.len() - 2..] else { unreachable!() };
+            assert_eq!(rearmed.topics[0][..], signature_of::<OrderRearmed>()[..]);
+            assert_eq!(intent_changed.topics[0][..], signature_of::<OrderIntentChanged>()[..]);
+            let old_hashlock = contract.compute_sha256(&leaked);
+            assert!(rearmed.data.ends_with(&(old_hashlock, new_hashlock).encode()));
+            assert_eq!(contract.rearm_order(order_id, [0x09; 32]), Err(Error::RearmLimitReached));
+
+            // The old secret no longer unlocks the re-armed order, the new one does
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let fill_id = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            assert_eq!(contract.withdraw_fill(fill_id, leaked, None, None), Err(Error::InvalidHashlock));
+            assert!(contract.withdraw_fill(fill_id, fresh, None, None).is_ok());
+
+            // After a fill the hashlock is final, even without a prior re-arm
+            contract.fill_order(other_id, 200, accounts.bob).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            assert_eq!(contract.rearm_order(other_id, new_hashlock), Err(Error::HashlockInUse));
+            assert_eq!(contract.get_order(other_id).unwrap().rearm_count, 0);
+        }
+
+        #[ink::test]
+        fn test_order_intent_hash() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            let secret = [0x42; 32];
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            let created = contract.get_intent_hash(order_id).unwrap();
+            assert_eq!(created, contract.get_order(order_id).unwrap().intent().hash());
+            let events: Vec<_> = ink::env::test::recorded_events().collect();
+            assert_eq!(events[events.len() - 2].topics[3][..], created[..]);
+
+            // State changes leave the terms, and so the hash, alone
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            contract.set_order_fill_paused(order_id, true).unwrap();
+            contract.set_order_fill_paused(order_id, false).unwrap();
+            assert_eq!(contract.get_intent_hash(order_id), Some(created));
+
+            // Changing the terms moves the hash and announces the new one
+            let new_hashlock = contract.compute_sha256(&secret);
+            contract.rearm_order(order_id, new_hashlock).unwrap();
+            let rearmed = contract.get_intent_hash(order_id).unwrap();
+            assert_ne!(rearmed, created);
+            assert_eq!(rearmed, contract.get_order(order_id).unwrap().intent().hash());
+            let changed = ink::env::test::recorded_events()
+                .find(|e| e.topics.len() == 3 && e.topics[2][..] == rearmed[..]);
+            assert!(changed.is_some());
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let fill_id = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            contract.withdraw_fill(fill_id, secret, None, None).unwrap();
+            assert_eq!(contract.get_intent_hash(order_id), Some(rearmed));
+            assert_eq!(contract.get_intent_hash([0xff; 32]), None);
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_address_format_helpers() {
+            use address_format::{checksum_eth, classify_address, AddressFormat};
+
+            fn from_hex(hex: &str) -> [u8; 20] {
+                let mut out = [0u8; 20];
+                for (i, byte) in out.iter_mut().enumerate() {
+                    *byte = u8::from_str_radix(&hex[2 + 2 * i..4 + 2 * i], 16).unwrap();
+                }
+                out
+            }
+            // Test vectors from EIP-55
+            for expected in [
+                "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+                "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+                "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+                "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+            ] {
+                assert_eq!(&checksum_eth(from_hex(expected))[..], expected.as_bytes());
+            }
+
+            // Alice under the generic (42) and Polkadot (0) prefixes
+            let alice_generic = b"5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+            let alice_polkadot = b"15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5";
+            assert_eq!(classify_address(0, alice_generic), AddressFormat::Ss58);
+            assert_eq!(classify_address(0, alice_polkadot), AddressFormat::Ss58);
+            assert_eq!(classify_address(0, &alice_generic[..40]), AddressFormat::Unknown);
+            // `0` and `l` are not base58
+            assert_eq!(classify_address(0, &[b'0'; 48]), AddressFormat::Unknown);
+            assert_eq!(classify_address(1, &[0x11; 20]), AddressFormat::Ethereum);
+            assert_eq!(classify_address(1, &[0x11; 32]), AddressFormat::Substrate);
+
+            let contract = PolkadotPartialFills::new();
+            let eth = from_hex("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed");
+            assert_eq!(
+                contract.format_cross_address(1, eth.to_vec()),
+                b"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed".to_vec()
+            );
+            let ss58 = contract.format_cross_address(0, alice_polkadot.to_vec());
+            assert_eq!(ss58, alice_polkadot.to_vec());
+            assert_eq!(contract.format_cross_address(0, vec![0xab, 0x01]), b"0xab01".to_vec());
+            let mut key_hex = b"0x".to_vec();
+            key_hex.extend_from_slice(&[b'2'; 64]);
+            assert_eq!(contract.format_cross_address(0, vec![0x22; 32]), key_hex);
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_map_address_cooldown_and_purge() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_block_number::<TestEnv>(100);
+            assert_eq!(contract.get_mapping_cooldown_blocks(), 10);
+
+            // The first mapping is exempt, updates wait out the cooldown
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let first = CrossChainAddress::Ethereum([0x11; 20]);
+            let second = CrossChainAddress::Ethereum([0x22; 20]);
+            contract.map_address(first.clone()).unwrap();
+            ink::env::test::set_block_number::<TestEnv>(109);
+            assert_eq!(contract.map_address(second.clone()), Err(Error::MappingCooldownActive));
+            assert_eq!(contract.get_cross_address(accounts.bob), Some(first));
+            ink::env::test::set_block_number::<TestEnv>(110);
+            contract.map_address(second.clone()).unwrap();
+            assert_eq!(contract.get_cross_address(accounts.bob), Some(second.clone()));
+
+            // Another account's first mapping is unaffected
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            contract.map_address(CrossChainAddress::Substrate([0x33; 32])).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.purge_mapping(accounts.bob, BlockNumber::MAX), Err(Error::Unauthorized));
+            assert_eq!(contract.set_mapping_cooldown(50, BlockNumber::MAX), Err(Error::Unauthorized));
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.set_mapping_cooldown(50, BlockNumber::MAX).unwrap();
+            contract.purge_mapping(accounts.bob, BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_cross_address(accounts.bob), None);
+            let entry = contract.get_admin_audit_log(0, 10).unwrap().items.pop().unwrap();
+            assert_eq!(entry.function_id, admin_fn::PURGE_MAPPING);
+            assert_eq!(entry.old_value, (accounts.bob, Some(second)).encode());
+
+            // A purged account does not regain the first-mapping exemption
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_block_number::<TestEnv>(159);
+            let remap = CrossChainAddress::Ethereum([0x44; 20]);
+            assert_eq!(contract.map_address(remap.clone()), Err(Error::MappingCooldownActive));
+            ink::env::test::set_block_number::<TestEnv>(160);
+            contract.map_address(remap).unwrap();
+        }
+
+        #[ink::test]
+        fn test_cleanup_order_retention() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            contract.set_retention_blocks(50, BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_retention_blocks(), 50);
+
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            let unfilled_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x03; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let withdrawn_id = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            let refunded_id = contract.fill_order(order_id, 300, accounts.bob).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            assert_eq!(contract.cleanup_order(order_id), Err(Error::OrderNotTerminal));
+            assert_eq!(contract.get_retention_block(order_id), None);
+
+            // Cancelled with a fill still pending is not terminal yet
+            ink::env::test::set_block_number::<TestEnv>(120);
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            contract.withdraw_fill(withdrawn_id, secret, None, None).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.cancel_order(order_id).unwrap();
+            assert!(!contract.is_cleanable(order_id));
+            assert_eq!(contract.cleanup_order(order_id), Err(Error::OrderNotTerminal));
+
+            // Terminal at the last settlement, the refund at block 600
+            ink::env::test::set_block_number::<TestEnv>(600);
+            contract.refund_fill(refunded_id, None).unwrap();
+            assert_eq!(contract.get_retention_block(order_id), Some(650));
+
+            // The revealed secret must outlive the fill's preimage
+            let registered = contract.secret_registry.take(&hashlock).unwrap();
+            assert_eq!(contract.cleanup_order(order_id), Err(Error::RetainedRecordsMissing));
+            contract.secret_registry.insert(&hashlock, &registered);
+
+            ink::env::test::set_block_number::<TestEnv>(649);
+            assert_eq!(contract.cleanup_order(order_id), Err(Error::RetentionPeriodActive));
+            ink::env::test::set_block_number::<TestEnv>(650);
+            assert!(contract.is_cleanable(order_id));
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            assert_eq!(contract.cleanup_order(order_id), Err(Error::Unauthorized));
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.cleanup_order(order_id).unwrap();
+            let event = ink::env::test::recorded_events().last().unwrap();
+            assert!(event.data.ends_with(&(order_id, 2u32).encode()));
+            assert_eq!(contract.get_order(order_id), None);
+            assert!(contract.get_order_fills(order_id, 0, 10).unwrap().items.is_empty());
+            assert_eq!(contract.get_fill(withdrawn_id), None);
+            assert_eq!(contract.get_fill_secret(withdrawn_id), None);
+            assert_eq!(contract.get_refund_cooldown_until(order_id, accounts.bob), None);
+            assert_eq!(contract.get_revealed_secret(hashlock), Some(secret));
+            assert!(!contract.is_cleanable(order_id));
+            assert_eq!(contract.cleanup_order(order_id), Err(Error::OrderNotFound));
+            #[cfg(feature = "periphery")]
+            assert!(!contract.id_list_contains(IdList::ArchivedOrders, order_id));
+
+            // An order that expired without fills is terminal at its timelock
+            assert_eq!(contract.get_retention_block(unfilled_id), Some(550));
+            assert!(contract.is_cleanable(unfilled_id));
+
+            // Cleaning it up pays the maker the escrow no cancel released
+            let balance = |account| ink::env::test::get_account_balance::<TestEnv>(account).unwrap();
+            let (before, locked) = (balance(accounts.alice), contract.get_total_locked());
+            contract.cleanup_order(unfilled_id).unwrap();
+            assert_eq!(balance(accounts.alice) - before, 1000u128.into());
+            assert_eq!(contract.get_total_locked(), locked - 1000);
+        }
+
+        // `fixtures/signing.json`: deployment 0x33..33 on chain 1000, account 0x11..11,
+        // payloads valid until block 1000
+        #[cfg(feature = "periphery")]
+        const SR25519_PUBLIC_KEY: &str = "189dac29296d31814dc8c56cf3d36a0543372bba7538fa322a4aebfebc39e056";
+        #[cfg(feature = "periphery")]
+        const SR25519_DIGEST: &str = "52688a1a19dab27792a58fa9a2c066c1fc5c6abae89546dcadb8901ed3748711";
+        #[cfg(feature = "periphery")]
+        const SR25519_SIGNATURE: &str = "ea420d17b7e7ed9fb3e2a733a38e5e5c50ef97d637437df1483ad8310225a572\
+                                         3ac60d63251b3d85182ae135054a3c9ba4f12b6816e035b84661f54fd2d2ed81";
+        #[cfg(feature = "periphery")]
+        const ECDSA_ADDRESS: &str = "5050a4f4b3f9338c3472dcc01a87c76a144b3c9c";
+        #[cfg(feature = "periphery")]
+        const ECDSA_DIGEST: &str = "a405d178193a8ec9b268656b313e5a232d1a07e79abd33f5c04cb02aed40edae";
+        #[cfg(feature = "periphery")]
+        const ECDSA_SIGNATURE: &str = "3be0747c215cf81a90fc70c1ab911d99d6685317a69e952d8dba8e8e27ca52cc\
+                                       06d5e774ef678e4686a932d2497e1562a98862a5511d9b28951bce2aa6fe69b001";
+        #[cfg(feature = "periphery")]
+        const ECDSA_RENEW_DIGEST: &str = "96ec40c9770912d05fced6636bbc1da1431ea070022a00ed3236335d3aed8dcb";
+        #[cfg(feature = "periphery")]
+        const ECDSA_RENEW_SIGNATURE: &str = "6c76154fef5c96af557d2d5bd31ec2fb709be87d5de854defe72feac389637be\
+                                             0286dea84effd564d4f4ddc3bbec39c450ea7a61c93f582b173b280f68d654fd00";
+
+        #[cfg(feature = "periphery")]
+        fn from_hex<const N: usize>(hex: &str) -> [u8; N] {
+            let mut out = [0u8; N];
+            for (i, byte) in out.iter_mut().enumerate() {
+                *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+            }
+            out
+        }
+
+        #[cfg(feature = "periphery")]
+        fn signing_fixture_contract(contract_address: Address, chain_id: u32) -> PolkadotPartialFills {
+            ink::env::test::set_callee::<TestEnv>(contract_address);
+            ink::env::test::set_caller::<TestEnv>(Address::from([0x11; 20]));
+            ink::env::test::set_block_number::<TestEnv>(100);
+            PolkadotPartialFills::new_with_home_chain(chain_id)
+        }
+
+        #[cfg(feature = "periphery")]
+        fn fixture_payloads() -> (MapAddressPayload, MapAddressPayload) {
+            let payload = |cross_address| MapAddressPayload {
+                account: Address::from([0x11; 20]),
+                cross_address,
+                valid_until_block: 1000,
+            };
+            (
+                payload(CrossChainAddress::Substrate(from_hex(SR25519_PUBLIC_KEY))),
+                payload(CrossChainAddress::Ethereum(from_hex(ECDSA_ADDRESS))),
+            )
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_signing_golden_vectors() {
+            const FIXTURES: &str = include_str!("../fixtures/signing.json");
+            let mut contract = signing_fixture_contract(Address::from([0x33; 20]), 1000);
+            assert_eq!(contract.get_signing_domain(), SigningDomain {
+                contract_address: Address::from([0x33; 20]),
+                chain_id: 1000,
+                version: 2,
+                salt: from_hex(DEPLOYMENT_SALT),
+            });
+
+            let (sr_payload, ec_payload) = fixture_payloads();
+            let sr_signature = MultiSignature::Sr25519(from_hex(SR25519_SIGNATURE));
+            let ec_signature = MultiSignature::Ecdsa(from_hex(ECDSA_SIGNATURE));
+            assert_eq!(to_hex(&contract.signing_digest(&sr_payload)), SR25519_DIGEST);
+            assert_eq!(to_hex(&contract.signing_digest(&ec_payload)), ECDSA_DIGEST);
+            let vectors = [
+                SR25519_PUBLIC_KEY, SR25519_DIGEST, SR25519_SIGNATURE,
+                ECDSA_ADDRESS, ECDSA_DIGEST, ECDSA_SIGNATURE,
+            ];
+            for value in vectors {
+                assert!(FIXTURES.contains(&format!("\"0x{}\"", value)));
+            }
+
+            let sr_signer = Signer::Sr25519(from_hex(SR25519_PUBLIC_KEY));
+            let ec_signer = Signer::Ecdsa(from_hex(ECDSA_ADDRESS));
+            assert_eq!(contract.verify_signed(&sr_payload, &sr_signature, &sr_signer), Ok(()));
+            assert_eq!(contract.verify_signed(&ec_payload, &ec_signature, &ec_signer), Ok(()));
+
+            // Both curves map the caller once the proof checks out
+            let sr_address = sr_payload.cross_address.clone();
+            let events_before = ink::env::test::recorded_events().count();
+            contract.map_address_with_proof(sr_address.clone(), 1000, sr_signature).unwrap();
+            assert_eq!(
+                events_since(events_before),
+                vec![signature_of::<AddressMapped>(), signature_of::<MappingAttested>()]
+            );
+            assert_eq!(contract.get_cross_address(Address::from([0x11; 20])), Some(sr_address));
+            ink::env::test::set_block_number::<TestEnv>(100 + DEFAULT_MAPPING_COOLDOWN);
+            let ec_address = ec_payload.cross_address.clone();
+            contract.map_address_with_proof(ec_address.clone(), 1000, ec_signature).unwrap();
+            assert_eq!(contract.get_cross_address(Address::from([0x11; 20])), Some(ec_address));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_signature_replay_rejected() {
+            // Encodes exactly like `MapAddressPayload` but is a different message type
+            let renew_payload = |payload: &MapAddressPayload| RenewMappingPayload {
+                account: payload.account,
+                cross_address: payload.cross_address.clone(),
+                valid_until_block: payload.valid_until_block,
+            };
+
+            let (sr_payload, ec_payload) = fixture_payloads();
+            let sr_signature = MultiSignature::Sr25519(from_hex(SR25519_SIGNATURE));
+            let ec_signature = MultiSignature::Ecdsa(from_hex(ECDSA_SIGNATURE));
+            let sr_signer = Signer::Sr25519(from_hex(SR25519_PUBLIC_KEY));
+            let ec_signer = Signer::Ecdsa(from_hex(ECDSA_ADDRESS));
+            let rejected = Err(Error::InvalidSignature);
+
+            // Another message type with identical payload bytes
+            let mut contract = signing_fixture_contract(Address::from([0x33; 20]), 1000);
+            let renew = renew_payload(&sr_payload);
+            assert_eq!(renew.encode(), sr_payload.encode());
+            assert_eq!(contract.verify_signed(&renew, &sr_signature, &sr_signer), rejected);
+            let renew = renew_payload(&ec_payload);
+            assert_eq!(contract.verify_signed(&renew, &ec_signature, &ec_signer), rejected);
+
+            // Another account, a different deadline, a mismatched curve or an unprovable address
+            let (sr_address, ec_address) = (sr_payload.cross_address, ec_payload.cross_address);
+            ink::env::test::set_caller::<TestEnv>(Address::from([0x22; 20]));
+            let result = contract.map_address_with_proof(sr_address.clone(), 1000, sr_signature.clone());
+            assert_eq!(result, rejected);
+            ink::env::test::set_caller::<TestEnv>(Address::from([0x11; 20]));
+            let result = contract.map_address_with_proof(ec_address.clone(), 999, ec_signature.clone());
+            assert_eq!(result, rejected);
+            let result = contract.map_address_with_proof(ec_address.clone(), 1000, sr_signature.clone());
+            assert_eq!(result, rejected);
+            let result = contract.map_address_with_proof(sr_address.clone(), 1000, ec_signature.clone());
+            assert_eq!(result, rejected);
+            let raw = CrossChainAddress::Raw(from_hex::<20>(ECDSA_ADDRESS).to_vec());
+            assert_eq!(contract.map_address_with_proof(raw, 1000, ec_signature.clone()), rejected);
+            assert_eq!(contract.get_cross_address(Address::from([0x11; 20])), None);
+
+            // The same proofs on another deployment or another chain
+            let deployments = [(Address::from([0x44; 20]), 1000), (Address::from([0x33; 20]), 2000)];
+            for (contract_address, chain_id) in deployments {
+                let mut other = signing_fixture_contract(contract_address, chain_id);
+                let result = other.map_address_with_proof(sr_address.clone(), 1000, sr_signature.clone());
+                assert_eq!(result, rejected);
+                let result = other.map_address_with_proof(ec_address.clone(), 1000, ec_signature.clone());
+                assert_eq!(result, rejected);
+            }
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_mapping_attestation_expiry_and_renewal() {
+            const FIXTURES: &str = include_str!("../fixtures/signing.json");
+            let mut contract = signing_fixture_contract(Address::from([0x33; 20]), 1000);
+            let account = Address::from([0x11; 20]);
+            let (_, ec_payload) = fixture_payloads();
+            let ec_address = ec_payload.cross_address.clone();
+            let renew = RenewMappingPayload {
+                account,
+                cross_address: ec_address.clone(),
+                valid_until_block: 1000,
+            };
+            assert_eq!(to_hex(&contract.signing_digest(&renew)), ECDSA_RENEW_DIGEST);
+            for value in [ECDSA_RENEW_DIGEST, ECDSA_RENEW_SIGNATURE] {
+                assert!(FIXTURES.contains(&format!("\"0x{}\"", value)));
+            }
+            let renew_signature = MultiSignature::Ecdsa(from_hex(ECDSA_RENEW_SIGNATURE));
+
+            // Renewing needs a mapping to renew
+            assert_eq!(contract.renew_mapping_proof(1000, renew_signature.clone()), Err(Error::MappingNotFound));
+
+            // Fresh: proven at block 100, fresh for 200 blocks
+            contract.set_mapping_attestation_ttl(Some(200), BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_mapping_attestation_ttl(), Some(200));
+            let ec_signature = MultiSignature::Ecdsa(from_hex(ECDSA_SIGNATURE));
+            contract.map_address_with_proof(ec_address.clone(), 1000, ec_signature).unwrap();
+            let attested = |attested_at_block, valid_until_block| {
+                Some((ec_address.clone(), Some(MappingAttestation { attested_at_block, valid_until_block })))
+            };
+            assert_eq!(contract.get_cross_address_with_attestation(account), attested(100, Some(300)));
+            assert_eq!(contract.resolve_cross_address(account), Some(ec_address.clone()));
+
+            // Near expiry: the proof still holds at its last block
+            ink::env::test::set_block_number::<TestEnv>(300);
+            assert_eq!(contract.resolve_cross_address(account), Some(ec_address.clone()));
+
+            // Expired: absent for settlement, still visible with its metadata
+            ink::env::test::set_block_number::<TestEnv>(301);
+            assert_eq!(contract.resolve_cross_address(account), None);
+            assert_eq!(contract.get_cross_address(account), Some(ec_address.clone()));
+            assert_eq!(contract.get_cross_address_with_attestation(account), attested(100, Some(300)));
+
+            // A mapping signature does not renew, nor a renewal past its deadline
+            let map_signature = MultiSignature::Ecdsa(from_hex(ECDSA_SIGNATURE));
+            assert_eq!(contract.renew_mapping_proof(1000, map_signature), Err(Error::InvalidSignature));
+            assert_eq!(contract.renew_mapping_proof(999, renew_signature.clone()), Err(Error::InvalidSignature));
+
+            // Renewed: fresh again from the renewal block, with no cooldown in the way
+            contract.renew_mapping_proof(1000, renew_signature.clone()).unwrap();
+            assert_eq!(contract.get_cross_address_with_attestation(account), attested(301, Some(501)));
+            assert_eq!(contract.resolve_cross_address(account), Some(ec_address.clone()));
+            ink::env::test::set_block_number::<TestEnv>(1001);
+            assert_eq!(contract.renew_mapping_proof(1000, renew_signature), Err(Error::SignatureExpired));
+
+            // Remapping without proof drops the attestation along with the old address
+            let unproven = CrossChainAddress::Ethereum([0x44; 20]);
+            contract.map_address(unproven.clone()).unwrap();
+            assert_eq!(contract.get_cross_address_with_attestation(account), Some((unproven.clone(), None)));
+            assert_eq!(contract.resolve_cross_address(account), Some(unproven));
+        }
+
+        #[ink::test]
+        fn test_reservation_hidden_from_other_fillers() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let balance = |account| ink::env::test::get_account_balance::<TestEnv>(account).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            contract.set_reservation_params(20, 50, BlockNumber::MAX).unwrap();
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            // Charlie, a router, holds half of the 1000 while its user confirms; no
+            // single reservation may hold more
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            ink::env::test::set_value_transferred::<TestEnv>(49u128.into());
+            assert_eq!(
+                contract.reserve(order_id, 500),
+                Err(Error::InsufficientFunds { required: 50, received: 49 })
+            );
+            ink::env::test::set_value_transferred::<TestEnv>(50u128.into());
+            assert_eq!(contract.reserve(order_id, 501), Err(Error::ReservationTooLarge));
+            assert_eq!(contract.reserve(order_id, 500), Ok(120));
+            assert_eq!(contract.get_reserved_amount(order_id), 500);
+            assert_eq!(contract.get_total_locked(), 1000 + 50);
+
+            assert_eq!(contract.get_max_fillable_amount(order_id, accounts.bob), Ok(500));
+            assert_eq!(contract.get_max_fillable_amount(order_id, accounts.charlie), Ok(1000));
+            assert_eq!(contract.get_remaining_amount(order_id), 1000);
+
+            // Bob is clamped to the unreserved part
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let fill_id = contract.fill_order(order_id, 600, accounts.bob).unwrap();
+            assert_eq!(contract.get_fill(fill_id).unwrap().fill_amount, 500);
+            assert_eq!(
+                contract.get_max_fillable_amount(order_id, accounts.bob),
+                Err(Error::CapacityReserved)
+            );
+
+            // Charlie's fill consumes the reservation and pays the bond back
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            let before = balance(accounts.charlie);
+            let events_before = ink::env::test::recorded_events().count();
+            contract.fill_order(order_id, 500, accounts.charlie).unwrap();
+            assert_eq!(
+                events_since(events_before),
+                vec![signature_of::<OrderFilled>(), signature_of::<ReservationReleased>()]
+            );
+            assert_eq!(balance(accounts.charlie) - before, 50u128.into());
+            assert_eq!(contract.get_reservation(order_id, accounts.charlie), None);
+            assert_eq!(contract.get_reserved_amount(order_id), 0);
+            assert_eq!(contract.get_total_locked(), 1000);
+        }
+
+        #[ink::test]
+        fn test_reservation_expiry_releases_capacity() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let balance = |account| ink::env::test::get_account_balance::<TestEnv>(account).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            contract.set_reservation_params(20, 50, BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_reservation_params(), (20, 50));
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            ink::env::test::set_value_transferred::<TestEnv>(50u128.into());
+            contract.reserve(order_id, 500).unwrap();
+            ink::env::test::set_block_number::<TestEnv>(119);
+            assert_eq!(contract.get_max_fillable_amount(order_id, accounts.bob), Ok(500));
+
+            ink::env::test::set_block_number::<TestEnv>(120);
+            assert_eq!(contract.get_reserved_amount(order_id), 0);
+            assert_eq!(contract.get_max_fillable_amount(order_id, accounts.bob), Ok(1000));
+
+            // The lapsed record keeps the bond until Charlie claims it
+            assert_eq!(
+                contract.get_reservation(order_id, accounts.charlie),
+                Some(Reservation { amount: 500, expires_at: 120, bond: 50 })
+            );
+            assert_eq!(contract.reserve(order_id, 100), Err(Error::ReservationExists));
+            let before = balance(accounts.charlie);
+            contract.release_reservation(order_id).unwrap();
+            assert_eq!(balance(accounts.charlie) - before, 50u128.into());
+            assert_eq!(contract.get_total_locked(), 1000);
+            assert_eq!(contract.release_reservation(order_id), Err(Error::ReservationNotFound));
+
+            // Releasing and reserving again does not keep the capacity held: Charlie
+            // waits out the reservation lifetime first, while others may reserve
+            assert_eq!(contract.reserve(order_id, 500), Err(Error::ReservationCooldownActive));
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.reserve(order_id, 500), Ok(140));
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            ink::env::test::set_block_number::<TestEnv>(140);
+            assert_eq!(contract.reserve(order_id, 500), Ok(160));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_refund_moves_filled_order_back_to_active() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let fill_id = contract.fill_order(order_id, 1000, accounts.bob).unwrap();
+            assert_eq!(contract.get_archived_orders(0, 10).unwrap().items, vec![order_id]);
+
+            ink::env::test::set_block_number::<TestEnv>(500);
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.refund_fill(fill_id, None).unwrap();
+            assert_eq!(contract.get_active_orders(0, 10).unwrap().items, vec![order_id]);
+            assert!(contract.get_archived_orders(0, 10).unwrap().items.is_empty());
+        }
+
+        #[ink::test]
+        fn test_core_views_enforce_max_page() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2, 1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            contract.set_refund_cooldown(300, BlockNumber::MAX).unwrap();
+
+            let fills = contract.get_order_fills(order_id, 0, MAX_ORDER_FILLS_PAGE).unwrap();
+            assert_eq!(fills, Page { items: vec![], next_offset: None, total: 0 });
+            assert_eq!(
+                contract.get_order_fills(order_id, 0, MAX_ORDER_FILLS_PAGE + 1),
+                Err(Error::PageTooLarge)
+            );
+            let log = contract.get_admin_audit_log(0, MAX_AUDIT_PAGE).unwrap();
+            assert_eq!((log.items.len(), log.next_offset, log.total), (1, None, 1));
+            assert_eq!(contract.get_admin_audit_log(0, MAX_AUDIT_PAGE + 1), Err(Error::PageTooLarge));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_periphery_views_enforce_max_page() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            // One order more than the smallest page, so that page has a successor
+            let count = MAX_OPEN_ORDERS_PAGE + 1;
+            let total = count as u64;
+            let mut order_ids = Vec::new();
+            for _ in 0..count {
+                order_ids.push(contract.create_partial_fill_order(
+                    1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                    1_000_000_000_000, true, 5, None, None
+                ).unwrap());
+            }
+
+            let open = contract.get_open_orders(0, MAX_OPEN_ORDERS_PAGE, None).unwrap();
+            assert_eq!(open.items.len(), MAX_OPEN_ORDERS_PAGE as usize);
+            assert_eq!((open.next_offset, open.total), (Some(MAX_OPEN_ORDERS_PAGE as u64), total));
+            let next = open.next_offset.unwrap();
+            let rest = contract.get_open_orders(next, MAX_OPEN_ORDERS_PAGE, None).unwrap();
+            assert_eq!(rest.items.len(), 1);
+            assert_eq!(rest.next_offset, None);
+
+            let active = contract.get_active_orders(0, MAX_INDEX_PAGE).unwrap();
+            assert_eq!(active.items, order_ids);
+            assert_eq!(active.next_offset, None);
+
+            let (alice, swap_id) = (accounts.alice, [0x02; 32]);
+            let too_large = Some(Error::PageTooLarge);
+            assert_eq!(contract.get_open_orders(0, MAX_OPEN_ORDERS_PAGE + 1, None).err(), too_large);
+            assert_eq!(contract.get_active_orders(0, MAX_INDEX_PAGE + 1).err(), too_large);
+            assert_eq!(contract.get_archived_orders(0, MAX_INDEX_PAGE + 1).err(), too_large);
+            let by_swap = contract.get_orders_by_swap_id(swap_id, 0, MAX_INDEX_PAGE + 1);
+            assert_eq!(by_swap.err(), too_large);
+            assert_eq!(contract.get_maker_orders(alice, 0, MAX_INDEX_PAGE + 1).err(), too_large);
+            assert_eq!(contract.get_taker_fills(accounts.bob, 0, MAX_INDEX_PAGE + 1).err(), too_large);
+            assert_eq!(contract.get_reveals_since(0, MAX_REVEALS_PAGE + 1).err(), too_large);
+            assert_eq!(contract.get_expiring_soon(60, 0, MAX_EXPIRING_PAGE + 1).err(), too_large);
+            let sorted = contract.get_orders_sorted_by_price(1, 2, true, MAX_SORTED_ORDERS + 1);
+            assert_eq!(sorted.err(), too_large);
+
+            assert!(contract.get_archived_orders(0, MAX_INDEX_PAGE).unwrap().items.is_empty());
+            let by_swap = contract.get_orders_by_swap_id(swap_id, 0, MAX_INDEX_PAGE).unwrap();
+            assert_eq!(by_swap.total, total);
+            assert_eq!(contract.get_maker_orders(alice, 0, MAX_INDEX_PAGE).unwrap().total, total);
+            assert_eq!(contract.get_taker_fills(accounts.bob, 0, MAX_INDEX_PAGE).unwrap().total, 0);
+            assert_eq!(contract.get_reveals_since(0, MAX_REVEALS_PAGE).unwrap().total, 0);
+            let expiring = contract.get_expiring_soon(60, 0, MAX_EXPIRING_PAGE).unwrap();
+            assert_eq!(expiring.next_offset, Some(MAX_EXPIRING_PAGE as u64));
+            let sorted = contract.get_orders_sorted_by_price(1, 2, true, MAX_SORTED_ORDERS).unwrap();
+            assert_eq!(sorted.len(), MAX_SORTED_ORDERS as usize);
+        }
+
+        #[ink::test]
+        fn test_fee_math_at_zero_bps() {
+            assert_eq!(PolkadotPartialFills::bps_of(1000, 0), 0);
+            assert_eq!(PolkadotPartialFills::bps_of(Balance::MAX, 0), 0);
+            assert_eq!(PolkadotPartialFills::bps_of(1000, 30), 3);
+            // No intermediate overflow at the largest amounts
+            assert_eq!(PolkadotPartialFills::bps_of(Balance::MAX, 1000), Balance::MAX / 10);
+            assert_eq!(PolkadotPartialFills::bps_of(9_999, 1), 0);
+        }
+
+        /// The browser quoting build is tested against the same fixtures; a case
+        /// passing there and failing here means the two have drifted
+        #[ink::test]
+        fn test_quote_fixtures_match_contract_views() {
+            use serde_json::Value;
+            const FIXTURES: &str = include_str!("../fixtures/quotes.json");
+
+            let fixtures: Value = serde_json::from_str(FIXTURES).expect("fixtures are JSON");
+            let balance = |value: &Value| -> Balance { value.as_str().unwrap().parse().unwrap() };
+            let number = |value: &Value| value.as_u64().unwrap() as u32;
+            let error_name = |error: Error| format!("{:?}", error);
+            let params = |case: &Value, options| CreateOrderParams {
+                total_amount: balance(&case["total_amount"]),
+                min_fill_amount: balance(&case["min_fill_amount"]),
+                hashlock: [0x01; 32],
+                timelock: number(&case["timelock"]),
+                swap_id: [0x02; 32],
+                source_chain: number(&case["source_chain"]),
+                dest_chain: number(&case["dest_chain"]),
+                dest_amount_per_unit: RATE_SCALE,
+                allow_partial_fills: true,
+                max_fills: number(&case["max_fills"]),
+                sender_cross_address: None,
+                receiver_cross_address: None,
+                options,
+            };
+
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            let config = &fixtures["config"];
+            ink::env::test::set_block_number::<TestEnv>(number(&config["current_block"]));
+            assert_eq!(contract.get_protocol_fee_bps(), config["fee_bps"].as_u64().unwrap() as u16);
+            assert_eq!(contract.get_min_timelock(), number(&config["min_timelock"]));
+            assert_eq!(contract.max_timelock, number(&config["max_timelock"]));
+
+            // Fees are charged per fill: creation escrows the full amount, and a fill of
+            // `amount` keeps `fee` out of the taker's payout
+            let largest = serde_json::json!({
+                "total_amount": "1000000000000", "min_fill_amount": "1", "timelock": 500,
+                "source_chain": 1, "dest_chain": 2, "max_fills": 5,
+            });
+            ink::env::test::set_value_transferred::<TestEnv>(1_000_000_000_000u128.into());
+            let order_id = contract.create_order(params(&largest, OrderOptions::default())).unwrap();
+            for case in fixtures["fees"].as_array().unwrap() {
+                let amount = balance(&case["amount"]);
+                let shape = serde_json::json!({
+                    "total_amount": case["amount"], "min_fill_amount": "1", "timelock": 500,
+                    "source_chain": 1, "dest_chain": 2, "max_fills": 5,
+                });
+                let preview = contract.validate_create(params(&shape, OrderOptions::default()), amount).unwrap();
+                assert_eq!((preview.net_amount, preview.fee), (amount, 0), "{}", case);
+                let fee = contract.get_fill_fee(order_id, amount).unwrap();
+                assert_eq!(fee, balance(&case["fee"]), "{}", case);
+                assert_eq!(amount - fee, balance(&case["net"]), "{}", case);
+            }
+
+            for case in fixtures["creates"].as_array().unwrap() {
+                let value = balance(&case["total_amount"]);
+                let outcome = contract.validate_create(params(case, OrderOptions::default()), value);
+                let outcome = outcome.err().map_or("Ok".to_string(), error_name);
+                assert_eq!(outcome, case["expected"].as_str().unwrap(), "{}", case);
+            }
+
+            // Fills quote escrowed amounts, so create them fee-free
+            contract.set_fee_free_window(0, 1000, BlockNumber::MAX).unwrap();
+            for case in fixtures["fills"].as_array().unwrap() {
+                let terms = &case["terms"];
+                let total_amount = balance(&terms["total_amount"]);
+                ink::env::test::set_caller::<TestEnv>(accounts.alice);
+                ink::env::test::set_value_transferred::<TestEnv>(total_amount.into());
+                let order_id = contract.create_partial_fill_order_with_options(
+                    total_amount,
+                    balance(&terms["min_fill_amount"]),
+                    [0x01; 32],
+                    500,
+                    [0x02; 32],
+                    1,
+                    2,
+                    balance(&terms["dest_amount_per_unit"]),
+                    terms["allow_partial_fills"].as_bool().unwrap(),
+                    5,
+                    None,
+                    None,
+                    OrderOptions {
+                        source_decimals: terms["source_decimals"].as_u64().map(|d| d as u8),
+                        dest_decimals: terms["dest_decimals"].as_u64().map(|d| d as u8),
+                        ..Default::default()
+                    },
+                ).unwrap();
+                assert_eq!(contract.get_order(order_id).unwrap().total_amount, total_amount);
+
+                ink::env::test::set_caller::<TestEnv>(accounts.bob);
+                ink::env::test::set_value_transferred::<TestEnv>(total_amount.into());
+                let expected = &case["expected"];
+                match contract.fill_order(order_id, balance(&case["requested"]), accounts.bob) {
+                    Ok(fill_id) => {
+                        let fill_amount = contract.get_fill(fill_id).unwrap().fill_amount;
+                        assert_eq!(fill_amount, balance(&expected["fill_amount"]), "{}", case);
+                        assert_eq!(
+                            contract.get_fill_quote(order_id, fill_amount),
+                            Some(balance(&expected["dest_amount"])),
+                            "{}",
+                            case
+                        );
+                    }
+                    Err(error) => assert_eq!(Some(error_name(error).as_str()), expected["error"].as_str(), "{}", case),
+                }
+            }
+        }
+
+        #[ink::test]
+        fn test_price_band_flags_or_rejects() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            // DOT to ETH at roughly one to one in base units, a factor of two either way
+            let band = (RATE_SCALE / 2, RATE_SCALE * 2);
+            contract.set_price_band(1, 2, Some(band), BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_price_band(1, 2), Some(band));
+            assert_eq!(contract.get_price_band(2, 1), None);
+
+            let create = |contract: &mut PolkadotPartialFills, dest_chain, rate, options| {
+                contract.create_partial_fill_order_with_options(
+                    1000, 100, [0x01; 32], 500, [0x02; 32], 1, dest_chain,
+                    rate, true, 5, None, None, options
+                )
+            };
+            // `mispriced` is followed by the one-byte `on_max_fills` in
+            // `PartialFillOrderCreated`, which precedes `OrderCommitted`
+            let mispriced_event = || {
+                let events: Vec<_> = ink::env::test::recorded_events().collect();
+                let data = &events[events.len() - 2].data;
+                data[data.len() - 2] == 1
+            };
+            let off_by_1e6 = RATE_SCALE * 1_000_000;
+
+            // Soft mode: out-of-band orders are created but flagged, in the order and the event
+            let inside = create(&mut contract, 2, RATE_SCALE, OrderOptions::default()).unwrap();
+            assert!(!contract.get_order(inside).unwrap().mispriced);
+            assert!(!mispriced_event());
+            let scaled_twice = create(&mut contract, 2, off_by_1e6, OrderOptions::default()).unwrap();
+            assert!(contract.get_order(scaled_twice).unwrap().mispriced);
+            assert!(mispriced_event());
+            // Bounds are inclusive
+            let at_bound = create(&mut contract, 2, band.1, OrderOptions::default()).unwrap();
+            assert!(!contract.get_order(at_bound).unwrap().mispriced);
+            // With decimals the whole-token rate is rescaled to base units first: 12 to 18
+            // decimals turns a one to one rate into 10^6 base units per unit
+            let decimals = OrderOptions {
+                source_decimals: Some(12),
+                dest_decimals: Some(18),
+                ..Default::default()
+            };
+            let whole_tokens = create(&mut contract, 2, RATE_SCALE, decimals).unwrap();
+            assert!(contract.get_order(whole_tokens).unwrap().mispriced);
+            // Corridors without a band accept any rate
+            let unbanded = create(&mut contract, 3, off_by_1e6, OrderOptions::default()).unwrap();
+            assert!(!contract.get_order(unbanded).unwrap().mispriced);
+            // An auction is checked at both ends of its rates
+            let decaying = OrderOptions {
+                order_type: Some(OrderType::DutchAuction { start_rate: RATE_SCALE, end_rate: RATE_SCALE / 4 }),
+                ..Default::default()
+            };
+            let decays_out = create(&mut contract, 2, RATE_SCALE, decaying).unwrap();
+            assert!(contract.get_order(decays_out).unwrap().mispriced);
+
+            #[cfg(feature = "periphery")]
+            {
+                let book = contract.get_open_orders(0, 10, None).unwrap().items;
+                let flagged: Vec<_> =
+                    book.iter().filter(|(_, order)| order.mispriced).map(|(id, _)| *id).collect();
+                assert_eq!(flagged, vec![scaled_twice, whole_tokens, decays_out]);
+            }
+
+            // Hard mode rejects what soft mode flagged and still accepts in-band orders
+            contract.set_reject_mispriced(true, BlockNumber::MAX).unwrap();
+            assert!(contract.get_reject_mispriced());
+            let params = |rate| CreateOrderParams {
+                total_amount: 1000,
+                min_fill_amount: 100,
+                hashlock: [0x01; 32],
+                timelock: 500,
+                swap_id: [0x02; 32],
+                source_chain: 1,
+                dest_chain: 2,
+                dest_amount_per_unit: rate,
+                allow_partial_fills: true,
+                max_fills: 5,
+                sender_cross_address: None,
+                receiver_cross_address: None,
+                options: OrderOptions::default(),
+            };
+            let preview = contract.validate_create(params(off_by_1e6), 1000);
+            assert_eq!(preview, Err(Error::PriceOutsideBand));
+            assert_eq!(
+                create(&mut contract, 2, off_by_1e6, OrderOptions::default()),
+                Err(Error::PriceOutsideBand)
+            );
+            assert!(!contract.validate_create(params(RATE_SCALE), 1000).unwrap().mispriced);
+            assert!(create(&mut contract, 2, RATE_SCALE, OrderOptions::default()).is_ok());
+
+            // Clearing the band lifts both modes
+            contract.set_price_band(1, 2, None, BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_price_band(1, 2), None);
+            assert!(create(&mut contract, 2, off_by_1e6, OrderOptions::default()).is_ok());
+        }
+
+        #[ink::test]
+        fn test_value_scale() {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let native = |account| -> Balance {
+                ink::env::test::get_account_balance::<TestEnv>(account).unwrap().try_into().unwrap()
+            };
+
+            // Instances in one test share storage, so each scale gets its own hashlock
+            for (scale, secret) in [(1u128, [0x07; 32]), (1_000_000, [0x08; 32])] {
+                ink::env::test::set_caller::<TestEnv>(accounts.alice);
+                let mut contract = PolkadotPartialFills::new_with_value_scale(scale).unwrap();
+                assert_eq!(contract.get_value_scale(), scale);
+                ink::env::test::set_block_number::<TestEnv>(100);
+
+                // 1000 units sent as 1000 * scale native value
+                let hashlock = contract.compute_sha256(&secret);
+                ink::env::test::set_value_transferred::<TestEnv>((1000 * scale).into());
+                let order_id = contract.create_partial_fill_order(
+                    1000, 100, hashlock, 500, [0x02; 32], 1, 2, 1_000_000_000_000, true, 5, None, None
+                ).unwrap();
+                let order = contract.get_order(order_id).unwrap();
+                assert_eq!((order.total_amount, order.fee), (1000, 0));
+
+                // Dust below one unit is refused, not rounded away
+                let dusty = (1000 * scale + scale / 2).into();
+                ink::env::test::set_value_transferred::<TestEnv>(dusty);
+                let result = contract.create_partial_fill_order(
+                    1000, 100, [0x43; 32], 500, [0x02; 32], 1, 2, 1_000_000_000_000, true, 5,
+                    None, None
+                );
+                let expected = if scale == 1 { Ok(()) } else { Err(Error::ValuePrecisionLoss) };
+                assert_eq!(result.map(|_| ()), expected);
+
+                // Payouts leave as `amount * scale` native value, the fee in whole units
+                ink::env::test::set_caller::<TestEnv>(accounts.bob);
+                ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+                let fill_id = contract.fill_order(order_id, 400, accounts.bob).unwrap();
+                let callee = ink::env::test::callee::<TestEnv>();
+                ink::env::test::set_account_balance::<TestEnv>(callee, (2000 * scale).into());
+                let before = native(accounts.bob);
+                contract.withdraw_fill(fill_id, secret, None, None).unwrap();
+                assert_eq!(native(accounts.bob) - before, 399 * scale);
+                assert_eq!(contract.get_protocol_fees(), 1);
+            }
+        }
+
+        #[ink::test]
+        fn test_withdraw_order_fills() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let native = |account| -> Balance {
+                ink::env::test::get_account_balance::<TestEnv>(account).unwrap().try_into().unwrap()
+            };
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 8, None, None
+            ).unwrap();
+
+            // Bob fills three times, Charlie once in between
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let settled = contract.fill_order(order_id, 100, accounts.bob).unwrap();
+            let first = contract.fill_order(order_id, 150, accounts.bob).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            let other = contract.fill_order(order_id, 200, accounts.charlie).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let second = contract.fill_order(order_id, 350, accounts.bob).unwrap();
+            contract.withdraw_fill(settled, secret, None, None).unwrap();
+
+            assert_eq!(
+                contract.withdraw_order_fills(order_id, [0x43; 32], None),
+                Err(Error::InvalidHashlock)
+            );
+
+            let events_before = ink::env::test::recorded_events().count();
+            let before = native(accounts.bob);
+            assert_eq!(contract.withdraw_order_fills(order_id, secret, None), Ok(2));
+            // 350 pays a fee of 1, 150 nothing
+            assert_eq!(native(accounts.bob) - before, 150 + 349);
+            assert_eq!(contract.get_protocol_fees(), 1);
+            assert!(contract.get_fill(first).unwrap().withdrawn);
+            assert!(contract.get_fill(second).unwrap().withdrawn);
+            assert!(!contract.get_fill(other).unwrap().withdrawn);
+            assert_eq!(contract.get_fill_secret(second), Some(secret));
+            // Two `FillWithdrawn`, then the summary
+            assert_eq!(
+                events_since(events_before),
+                vec![
+                    signature_of::<FillWithdrawn>(),
+                    signature_of::<FillWithdrawn>(),
+                    signature_of::<OrderFillsWithdrawn>(),
+                ]
+            );
+
+            // Nothing left for Bob; Charlie's fill is still his to take
+            assert_eq!(contract.withdraw_order_fills(order_id, secret, None), Err(Error::FillNotFound));
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            ink::env::test::set_block_number::<TestEnv>(500);
+            assert_eq!(
+                contract.withdraw_order_fills(order_id, secret, None),
+                Err(Error::TimelockExpired)
+            );
+            ink::env::test::set_block_number::<TestEnv>(499);
+            assert_eq!(contract.withdraw_order_fills(order_id, secret, Some(PayoutRoute::Credit)), Ok(1));
+            assert_eq!(contract.get_credit(accounts.charlie), 200);
+            assert_eq!(contract.get_total_locked(), 1000 - 800);
+        }
+
+        #[ink::test]
+        fn test_max_fills_policies() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let native = |account| -> Balance {
+                ink::env::test::get_account_balance::<TestEnv>(account).unwrap().try_into().unwrap()
+            };
+            ink::env::test::set_block_number::<TestEnv>(100);
+
+            // Each order takes two fills of 200 out of 1000, leaving 600
+            let capped = |contract: &mut PolkadotPartialFills, policy, swap_id| {
+                ink::env::test::set_caller::<TestEnv>(accounts.alice);
+                ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+                let options = OrderOptions { on_max_fills: policy, ..Default::default() };
+                let order_id = contract.create_partial_fill_order_with_options(
+                    1000, 100, [0x01; 32], 500, swap_id, 1, 2,
+                    1_000_000_000_000, true, 2, None, None, options
+                ).unwrap();
+                ink::env::test::set_caller::<TestEnv>(accounts.bob);
+                ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+                contract.fill_order(order_id, 200, accounts.bob).unwrap();
+                contract.fill_order(order_id, 200, accounts.bob).unwrap();
+                order_id
+            };
+
+            // Hold, the default: the remainder sits idle behind the cap
+            let held = capped(&mut contract, None, [0x01; 32]);
+            let order = contract.get_order(held).unwrap();
+            assert_eq!(order.on_max_fills, MaxFillsPolicy::Hold);
+            assert_eq!(contract.get_remaining_amount(held), 600);
+            assert_eq!(contract.get_order_status(held), Some(OrderStatus::PartiallyFilled));
+            assert_eq!(contract.fill_order(held, 100, accounts.bob), Err(Error::MaxFillsReached));
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            assert_eq!(contract.increase_max_fills(held, 3), Err(Error::MaxFillsNotRaisable));
+
+            // AutoRefundRemainder: the capping fill pays the remainder back at once,
+            // reporting the fill before the shrunk terms and the returned remainder
+            let before = native(accounts.alice);
+            let events_before = ink::env::test::recorded_events().count();
+            let refunding = capped(&mut contract, Some(MaxFillsPolicy::AutoRefundRemainder), [0x02; 32]);
+            assert_eq!(native(accounts.alice) - before, 600);
+            assert_eq!(
+                events_since(events_before + 3),
+                vec![
+                    signature_of::<OrderFilled>(),
+                    signature_of::<OrderIntentChanged>(),
+                    signature_of::<OrderRemainderReturned>(),
+                ]
+            );
+            let order = contract.get_order(refunding).unwrap();
+            assert_eq!((order.total_amount, order.filled_amount), (400, 400));
+            assert_eq!(order.intent_hash, order.intent().hash());
+            assert_eq!(contract.get_order_status(refunding), Some(OrderStatus::FullyFilled));
+            assert_eq!(contract.get_total_locked(), 1000 + 400);
+
+            // RaiseLimit: the maker lifts the cap and fills resume
+            let raising = capped(&mut contract, Some(MaxFillsPolicy::RaiseLimit), [0x03; 32]);
+            assert_eq!(contract.fill_order(raising, 100, accounts.bob), Err(Error::MaxFillsReached));
+            assert_eq!(contract.increase_max_fills(raising, 3), Err(Error::Unauthorized));
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            assert_eq!(contract.increase_max_fills(raising, 2), Err(Error::InvalidFillAmount));
+            let intent_hash = contract.get_intent_hash(raising).unwrap();
+            let events_before = ink::env::test::recorded_events().count();
+            contract.increase_max_fills(raising, 3).unwrap();
+            assert_eq!(
+                events_since(events_before),
+                vec![signature_of::<MaxFillsIncreased>(), signature_of::<OrderIntentChanged>()]
+            );
+            assert_eq!(contract.get_order(raising).unwrap().max_fills, 3);
+            assert_ne!(contract.get_intent_hash(raising), Some(intent_hash));
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert!(contract.fill_order(raising, 100, accounts.bob).is_ok());
+        }
+
+        #[ink::test]
+        fn test_payout_routes() {
+            let mut contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let native = |account| -> Balance {
+                ink::env::test::get_account_balance::<TestEnv>(account).unwrap().try_into().unwrap()
+            };
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            let secret = [0x42; 32];
+            let hashlock = contract.compute_sha256(&secret);
+            let order_id = contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            ).unwrap();
+            assert!(contract.is_payout_route_available(PayoutRoute::Direct));
+            assert!(contract.is_payout_route_available(PayoutRoute::Credit));
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let fills: Vec<_> = (1..=5)
+                .map(|i| contract.fill_order(order_id, 100 + i, accounts.bob).unwrap())
+                .collect();
+            let payout = |contract: &PolkadotPartialFills, id| {
+                let fill = contract.get_fill(id).unwrap();
+                fill.fill_amount + fill.deposit
+            };
+
+            // Withdrawals: no route means Direct
+            let before = native(accounts.bob);
+            contract.withdraw_fill(fills[0], secret, None, None).unwrap();
+            assert_eq!(native(accounts.bob) - before, payout(&contract, fills[0]));
+            contract.withdraw_fill(fills[1], secret, Some(PayoutRoute::Credit), None).unwrap();
+            assert_eq!(contract.get_credit(accounts.bob), payout(&contract, fills[1]));
+
+            let xcm = PayoutRoute::Xcm { para_id: 2000, beneficiary: [0x0b; 32] };
+            #[cfg(not(feature = "xcm"))]
+            {
+                assert!(!contract.is_payout_route_available(xcm));
+                let result = contract.withdraw_fill(fills[4], secret, Some(xcm), None);
+                assert_eq!(result, Err(Error::PayoutRouteUnavailable));
+                assert!(!contract.get_fill(fills[4]).unwrap().withdrawn);
+            }
+            #[cfg(feature = "xcm")]
+            {
+                assert!(contract.is_payout_route_available(xcm));
+                contract.withdraw_fill(fills[4], secret, Some(xcm), None).unwrap();
+                let expected = (2000, [0x0b; 32], payout(&contract, fills[4]));
+                assert_eq!(xcm_mock::sent(), vec![expected]);
+            }
+
+            // Refunds of a cancelled order pay the fill back to the maker
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_block_number::<TestEnv>(500);
+            contract.cancel_order(order_id).unwrap();
+            let before = native(accounts.alice);
+            contract.refund_fill(fills[2], Some(PayoutRoute::Direct)).unwrap();
+            assert_eq!(native(accounts.alice) - before, payout(&contract, fills[2]));
+            contract.refund_fill(fills[3], Some(PayoutRoute::Credit)).unwrap();
+            assert_eq!(contract.get_credit(accounts.alice), payout(&contract, fills[3]));
+
+            // Protocol fees: 3 from each fill of a whole 1000 order once withdrawn
+            let settle_fee = |contract: &mut PolkadotPartialFills, swap_id| {
+                ink::env::test::set_caller::<TestEnv>(accounts.alice);
+                ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+                let order_id = contract.create_partial_fill_order(
+                    1000, 100, hashlock, 900, swap_id, 1, 2,
+                    1_000_000_000_000, true, 5, None, None
+                ).unwrap();
+                ink::env::test::set_caller::<TestEnv>(accounts.bob);
+                ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+                let fill_id = contract.fill_order(order_id, 1000, accounts.bob).unwrap();
+                contract.withdraw_fill(fill_id, secret, None, None).unwrap();
+                ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            };
+            settle_fee(&mut contract, [0x03; 32]);
+            let credited = contract.get_credit(accounts.alice);
+            contract.withdraw_protocol_fees(Some(PayoutRoute::Credit), BlockNumber::MAX).unwrap();
+            assert_eq!(contract.get_credit(accounts.alice) - credited, 3);
+            settle_fee(&mut contract, [0x04; 32]);
+            let before = native(accounts.alice);
+            contract.withdraw_protocol_fees(None, BlockNumber::MAX).unwrap();
+            assert_eq!(native(accounts.alice) - before, 3);
+            assert_eq!(contract.get_protocol_fees(), 0);
+        }
+    }
+
+    /// One test per `Error` variant, each driving exactly the path that raises it.
+    /// `covering_test` has no wildcard arm, so a new variant fails to compile until
+    /// it is given a test here, or in `e2e_tests` when only a node can raise it.
+    #[cfg(test)]
+    mod error_coverage_tests {
+        use super::*;
+
+        type TestEnv = ink::env::DefaultEnvironment;
+        type Accounts = ink::env::test::DefaultAccounts<TestEnv>;
+
+        const SECRET: [u8; 32] = [0x42; 32];
+
+        #[allow(dead_code)]
+        fn covering_test(error: Error) -> Option<&'static str> {
+            Some(match error {
+                Error::OrderAlreadyExists => "test_error_order_already_exists",
+                Error::FillAlreadyExists => "test_error_fill_already_exists",
+                Error::OrderNotFound => "test_error_order_not_found",
+                Error::FillNotFound => "test_error_fill_not_found",
+                Error::InvalidTimelock => "test_error_invalid_timelock",
+                Error::InsufficientFunds { .. } => "test_error_insufficient_funds",
+                Error::UnauthorizedFill => "test_error_unauthorized_fill",
+                Error::UnauthorizedWithdraw => "test_error_unauthorized_withdraw",
+                Error::UnauthorizedRefund => "test_error_unauthorized_refund",
+                Error::InvalidHashlock => "test_error_invalid_hashlock",
+                Error::AlreadyProcessed => "test_error_already_processed",
+                Error::TimelockNotExpired => "test_error_timelock_not_expired",
+                Error::TimelockExpired => "test_error_timelock_expired",
+                Error::TransferFailed => "test_error_transfer_failed",
+                Error::InvalidChainId => "test_error_invalid_chain_id",
+                Error::InvalidFillAmount => "test_error_invalid_fill_amount",
+                Error::OrderCancelled => "test_error_order_cancelled",
+                Error::OrderCompleted => "test_error_order_completed",
+                Error::PartialFillsNotAllowed => "test_error_partial_fills_not_allowed",
+                Error::MaxFillsReached => "test_error_max_fills_reached",
+                Error::FillAmountTooSmall => "test_error_fill_amount_too_small",
+                Error::InvalidFee => "test_error_invalid_fee",
+                Error::Unauthorized => "test_error_unauthorized",
+                Error::ConversionError => "test_error_conversion_error",
+                Error::TimelockTooShort => "test_error_timelock_too_short",
+                Error::TimelockTooLong => "test_error_timelock_too_long",
+                Error::TagsTooLong => "test_error_tags_too_long",
+                Error::TimelockIncompatibleWithDestination => {
+                    "test_error_timelock_incompatible_with_destination"
+                }
+                Error::NoPendingProposal => "test_error_no_pending_proposal",
+                Error::ProposalNotReady => "test_error_proposal_not_ready",
+                Error::BatchTooLarge => "test_error_batch_too_large",
+                Error::FillExceedsUnlocked => "test_error_fill_exceeds_unlocked",
+                Error::InsufficientCredit => "test_error_insufficient_credit",
+                Error::GroupFull => "test_error_group_full",
+                Error::CounterOverflow => "test_error_counter_overflow",
+                Error::WrongSourceChain => "test_error_wrong_source_chain",
+                Error::OrderFillsPaused => "test_error_order_fills_paused",
+                Error::AdminOperationExpired => "test_error_admin_operation_expired",
+                Error::NoValueTransferred => "test_error_no_value_transferred",
+                Error::InvalidDepositSchedule => "test_error_invalid_deposit_schedule",
+                Error::TooManyConsumers => "test_error_too_many_consumers",
+                Error::InvalidDecimals => "test_error_invalid_decimals",
+                Error::AnnouncementTooSoon => "test_error_announcement_too_soon",
+                Error::DuplicateOrder => "test_error_duplicate_order",
+                Error::RefundCooldownActive => "test_error_refund_cooldown_active",
+                Error::HashlockInUse => "test_error_hashlock_in_use",
+                Error::RearmLimitReached => "test_error_rearm_limit_reached",
+                Error::MappingCooldownActive => "test_error_mapping_cooldown_active",
+                Error::OrderNotTerminal => "test_error_order_not_terminal",
+                Error::RetainedRecordsMissing => "test_error_retained_records_missing",
+                Error::RetentionPeriodActive => "test_error_retention_period_active",
+                Error::InvalidSignature => "test_error_invalid_signature",
+                Error::SignatureExpired => "test_error_signature_expired",
+                Error::CapacityReserved => "test_error_capacity_reserved",
+                Error::ReservationExists => "test_error_reservation_exists",
+                Error::ReservationNotFound => "test_error_reservation_not_found",
+                Error::ReservationLimitReached => "test_error_reservation_limit_reached",
+                Error::PageTooLarge => "test_error_page_too_large",
+                Error::InvalidPriceBand => "test_error_invalid_price_band",
+                Error::PriceOutsideBand => "test_error_price_outside_band",
+                Error::ValuePrecisionLoss => "test_error_value_precision_loss",
+                Error::InvalidValueScale => "test_error_invalid_value_scale",
+                Error::PayoutRouteUnavailable => "test_error_payout_route_unavailable",
+                Error::FeesNotStuck => "test_error_fees_not_stuck",
+                Error::FeeSweepBackoff => "e2e_tests::refused_fee_sweep_backs_off",
+                Error::MaxFillsNotRaisable => "test_error_max_fills_not_raisable",
+                Error::MappingNotFound => "test_error_mapping_not_found",
+                Error::MemoTooLong => "test_error_memo_too_long",
+                Error::BetaCapExceeded { .. } => "test_error_beta_cap_exceeded",
+                Error::UpgradeCodeMismatch => "test_error_upgrade_code_mismatch",
+                Error::MigrationCheckFailed => "e2e_tests::staged_upgrade_checked_against_deployed_code",
+                // Raised by the host only when switching to code it does not hold:
+                // never off-chain, and on a node only after `UPGRADE_DELAY` blocks
+                Error::UpgradeFailed => return None,
+                Error::SunsetActive => "test_error_sunset_active",
+                Error::ContractRetired => "test_error_contract_retired",
+                Error::NotInSunset => "test_error_not_in_sunset",
+                Error::LiabilitiesOutstanding { .. } => "test_error_liabilities_outstanding",
+                Error::ContractPaused => "test_error_contract_paused",
+                Error::InvalidAuctionRates => "test_error_invalid_auction_rates",
+                Error::TakerWhitelistLocked => "test_error_taker_whitelist_locked",
+                Error::ReservationCooldownActive => "test_error_reservation_cooldown_active",
+                Error::ReservationTooLarge => "test_error_reservation_too_large",
+                Error::InvalidCompletionCallback => "test_error_invalid_completion_callback",
+                Error::FeeTokensOutstanding { .. } => "test_error_fee_tokens_outstanding",
+                Error::NoFeesToWithdraw => "test_error_no_fees_to_withdraw",
+                Error::InvalidAggregation => "test_error_invalid_aggregation",
+            })
+        }
+
+        /// Fresh contract at block 100 with alice as caller and admin
+        fn setup() -> (PolkadotPartialFills, Accounts) {
+            let contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            ink::env::test::set_block_number::<TestEnv>(100);
+            (contract, accounts)
+        }
+
+        /// Alice creates a 1000 order locked until block 500
+        fn create_order(
+            contract: &mut PolkadotPartialFills,
+            allow_partial_fills: bool,
+            max_fills: u32,
+        ) -> [u8; 32] {
+            let hashlock = contract.compute_sha256(&SECRET);
+            contract.create_partial_fill_order(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, allow_partial_fills, max_fills, None, None
+            ).unwrap()
+        }
+
+        fn create_with_timelock(contract: &mut PolkadotPartialFills, timelock: BlockNumber) -> Result<[u8; 32], Error> {
+            contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], timelock, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            )
+        }
+
+        /// Overwrite stored order state directly to reach states messages cannot
+        fn mutate_order(contract: &mut PolkadotPartialFills, order_id: [u8; 32], f: impl FnOnce(&mut PartialFillOrder)) {
+            let mut order = contract.orders.get(&order_id).unwrap();
+            f(&mut order);
+            contract.orders.insert(&order_id, &order);
+        }
+
+        /// Order plus a 200 fill by bob
+        fn create_filled_order(contract: &mut PolkadotPartialFills, accounts: &Accounts) -> ([u8; 32], [u8; 32]) {
+            let order_id = create_order(contract, true, 5);
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let fill_id = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            (order_id, fill_id)
+        }
+
+        #[ink::test]
+        fn test_error_order_already_exists() {
+            let (mut contract, _) = setup();
+            create_order(&mut contract, true, 5);
+
+            // Replaying the counter re-derives the id of the order already stored
+            contract.order_counter = 0;
+            contract.order_counter_high = 0;
+            let hashlock = contract.compute_sha256(&SECRET);
+            assert_eq!(
+                contract.create_partial_fill_order(
+                    1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                    1_000_000_000_000, true, 5, None, None
+                ),
+                Err(Error::OrderAlreadyExists)
+            );
+        }
+
+        #[ink::test]
+        fn test_error_fill_already_exists() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+
+            // Fill ids are derived without a counter: same taker, amount and block collide
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            assert_eq!(contract.fill_order(order_id, 200, accounts.bob), Err(Error::FillAlreadyExists));
+        }
+
+        #[ink::test]
+        fn test_lookup_id() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let fill_id = contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            let contract_id = contract.get_fill(fill_id).unwrap().contract_id;
+
+            assert_eq!(contract.lookup_id(order_id), IdKind::Order);
+            assert_eq!(contract.lookup_id(fill_id), IdKind::Fill);
+            assert_eq!(contract.lookup_id(contract_id), IdKind::Contract { fill_id });
+            assert_eq!(contract.lookup_id([0xff; 32]), IdKind::Unknown);
+
+            // Tagged preimages keep the three namespaces apart
+            assert_ne!(order_id, fill_id);
+            assert_ne!(fill_id, contract_id);
+        }
+
+        #[ink::test]
+        fn test_error_order_not_found() {
+            let (mut contract, accounts) = setup();
+            assert_eq!(contract.fill_order([0xff; 32], 200, accounts.bob), Err(Error::OrderNotFound));
+        }
+
+        #[ink::test]
+        fn test_error_fill_not_found() {
+            let (mut contract, _) = setup();
+            assert_eq!(contract.withdraw_fill([0xff; 32], SECRET, None, None), Err(Error::FillNotFound));
+        }
+
+        #[ink::test]
+        fn test_error_invalid_timelock() {
+            let (mut contract, _) = setup();
+            assert_eq!(create_with_timelock(&mut contract, 100), Err(Error::InvalidTimelock));
+        }
+
+        #[ink::test]
+        fn test_error_insufficient_funds() {
+            let (mut contract, _) = setup();
+            ink::env::test::set_value_transferred::<TestEnv>(999u128.into());
+            assert_eq!(
+                create_with_timelock(&mut contract, 500),
+                Err(Error::InsufficientFunds { required: 1000, received: 999 })
+            );
+        }
+
+        #[ink::test]
+        fn test_error_unauthorized_fill() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            contract.set_taker_whitelist(order_id, vec![accounts.bob]).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            assert_eq!(contract.fill_order(order_id, 200, accounts.charlie), Err(Error::UnauthorizedFill));
+        }
+
+        #[ink::test]
+        fn test_error_unauthorized_withdraw() {
+            let (mut contract, accounts) = setup();
+            let (_, fill_id) = create_filled_order(&mut contract, &accounts);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            assert_eq!(contract.withdraw_fill(fill_id, SECRET, None, None), Err(Error::UnauthorizedWithdraw));
+        }
+
+        #[ink::test]
+        fn test_error_unauthorized_refund() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.cancel_order(order_id), Err(Error::UnauthorizedRefund));
+        }
+
+        #[ink::test]
+        fn test_error_invalid_hashlock() {
+            let (mut contract, accounts) = setup();
+            let (_, fill_id) = create_filled_order(&mut contract, &accounts);
+
+            assert_eq!(contract.withdraw_fill(fill_id, [0x43; 32], None, None), Err(Error::InvalidHashlock));
+        }
+
+        #[ink::test]
+        fn test_error_already_processed() {
+            let (mut contract, accounts) = setup();
+            let (_, fill_id) = create_filled_order(&mut contract, &accounts);
+
+            let credit = Some(PayoutRoute::Credit);
+            contract.withdraw_fill(fill_id, SECRET, credit, None).unwrap();
+            assert_eq!(contract.withdraw_fill(fill_id, SECRET, credit, None), Err(Error::AlreadyProcessed));
+        }
+
+        #[ink::test]
+        fn test_error_timelock_not_expired() {
+            let (mut contract, accounts) = setup();
+            let (_, fill_id) = create_filled_order(&mut contract, &accounts);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            assert_eq!(contract.refund_fill(fill_id, None), Err(Error::TimelockNotExpired));
+        }
+
+        #[ink::test]
+        fn test_error_timelock_expired() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+
+            ink::env::test::set_block_number::<TestEnv>(500);
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.fill_order(order_id, 200, accounts.bob), Err(Error::TimelockExpired));
+        }
+
+        #[cfg(feature = "psp22")]
+        #[ink::test]
+        fn test_error_transfer_failed() {
+            let (mut contract, accounts) = setup();
+            ink::env::test::set_callee::<TestEnv>(accounts.eve);
+
+            // Fee token set but the maker holds none of it
+            contract.set_fee_token(Some(accounts.frank), BlockNumber::MAX).unwrap();
+            assert_eq!(create_with_timelock(&mut contract, 500), Err(Error::TransferFailed));
+        }
+
+        #[ink::test]
+        fn test_error_invalid_chain_id() {
+            let (mut contract, _) = setup();
+            let result = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 1,
+                1_000_000_000_000, true, 5, None, None
+            );
+            assert_eq!(result, Err(Error::InvalidChainId));
+        }
+
+        #[ink::test]
+        fn test_error_invalid_fill_amount() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.fill_order(order_id, 0, accounts.bob), Err(Error::InvalidFillAmount));
+        }
+
+        #[ink::test]
+        fn test_error_order_cancelled() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            mutate_order(&mut contract, order_id, |order| order.cancelled = true);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.fill_order(order_id, 200, accounts.bob), Err(Error::OrderCancelled));
         }
 
-        /// Fill order (partial or full)
-        #[ink(message)]
-        pub fn fill_order(
-            &mut self,
-            order_id: [u8; 32],
-            mut fill_amount: Balance,
-            receiver: Address,
-        ) -> Result<[u8; 32], Error> {
-            let taker = self.env().caller();
-            let mut order = self.get_order_or_error(&order_id)?;
+        #[ink::test]
+        fn test_error_order_completed() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
 
-            self.validate_fill_request(&order, fill_amount)?;
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            contract.fill_order(order_id, 1000, accounts.bob).unwrap();
+            assert_eq!(contract.fill_order(order_id, 100, accounts.bob), Err(Error::OrderCompleted));
+        }
 
-            let remaining_amount = order.total_amount - order.filled_amount;
-            if fill_amount > remaining_amount {
-                fill_amount = remaining_amount;
+        #[ink::test]
+        fn test_error_partial_fills_not_allowed() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, false, 5);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.fill_order(order_id, 200, accounts.bob), Err(Error::PartialFillsNotAllowed));
+        }
+
+        #[ink::test]
+        fn test_error_max_fills_reached() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 1);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            assert_eq!(contract.fill_order(order_id, 300, accounts.bob), Err(Error::MaxFillsReached));
+        }
+
+        #[ink::test]
+        fn test_error_fill_amount_too_small() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.fill_order(order_id, 99, accounts.bob), Err(Error::FillAmountTooSmall));
+        }
+
+        #[ink::test]
+        fn test_error_invalid_fee() {
+            let (mut contract, _) = setup();
+            assert_eq!(contract.update_protocol_fee(1001, BlockNumber::MAX), Err(Error::InvalidFee));
+        }
+
+        #[ink::test]
+        fn test_error_unauthorized() {
+            let (mut contract, accounts) = setup();
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.update_admin(accounts.bob, BlockNumber::MAX), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn test_error_conversion_error() {
+            let (mut contract, _) = setup();
+            // Native value is a U256; anything past u128::MAX cannot become a Balance
+            let too_large = ink::primitives::U256::from(u128::MAX) + ink::primitives::U256::from(1u8);
+            ink::env::test::set_value_transferred::<TestEnv>(too_large);
+            assert_eq!(create_with_timelock(&mut contract, 500), Err(Error::ConversionError));
+        }
+
+        #[ink::test]
+        fn test_error_timelock_too_short() {
+            let (mut contract, _) = setup();
+            assert_eq!(create_with_timelock(&mut contract, 150), Err(Error::TimelockTooShort));
+        }
+
+        #[ink::test]
+        fn test_error_timelock_too_long() {
+            let (mut contract, _) = setup();
+            assert_eq!(create_with_timelock(&mut contract, 100 + 14_401), Err(Error::TimelockTooLong));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_error_tags_too_long() {
+            let (mut contract, _) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            let tags = vec![0u8; MAX_ORDER_TAGS_LEN + 1];
+            assert_eq!(contract.set_order_tags(order_id, tags), Err(Error::TagsTooLong));
+        }
+
+        #[ink::test]
+        fn test_error_timelock_incompatible_with_destination() {
+            let (mut contract, _) = setup();
+            // 400 blocks at 6s is 2400s, past the destination's 2000s limit
+            contract.register_chain(2, ChainConfig { max_counterpart_timelock: 2_000 }, BlockNumber::MAX).unwrap();
+            assert_eq!(create_with_timelock(&mut contract, 500), Err(Error::TimelockIncompatibleWithDestination));
+        }
+
+        #[ink::test]
+        fn test_error_no_pending_proposal() {
+            let (mut contract, _) = setup();
+            assert_eq!(contract.apply_min_timelock(), Err(Error::NoPendingProposal));
+        }
+
+        #[ink::test]
+        fn test_error_proposal_not_ready() {
+            let (mut contract, _) = setup();
+            contract.propose_min_timelock(50, BlockNumber::MAX).unwrap();
+            assert_eq!(contract.apply_min_timelock(), Err(Error::ProposalNotReady));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_error_batch_too_large() {
+            let (mut contract, _) = setup();
+            let order_ids = vec![[0xff; 32]; MAX_BATCH_CANCEL + 1];
+            assert_eq!(contract.batch_cancel_orders(order_ids), Err(Error::BatchTooLarge));
+        }
+
+        #[ink::test]
+        fn test_error_fill_exceeds_unlocked() {
+            let (mut contract, accounts) = setup();
+            let hashlock = contract.compute_sha256(&SECRET);
+            let options = OrderOptions {
+                vesting_schedule: Some(VestingSchedule {
+                    unlock_amount_per_block: 10,
+                    unlock_start_block: 90,
+                }),
+                ..Default::default()
+            };
+            let order_id = contract.create_partial_fill_order_with_options(
+                1000, 50, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None, options
+            ).unwrap();
+
+            // 10 blocks elapsed: 100 unlocked
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.fill_order(order_id, 101, accounts.bob), Err(Error::FillExceedsUnlocked));
+        }
+
+        #[ink::test]
+        fn test_error_insufficient_credit() {
+            let (mut contract, accounts) = setup();
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.withdraw_credit(1), Err(Error::InsufficientCredit));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_error_group_full() {
+            let (mut contract, _) = setup();
+            let options = OrderOptions { group_id: Some([0x77; 32]), ..Default::default() };
+            for _ in 0..MAX_GROUP_ORDERS {
+                contract.create_partial_fill_order_with_options(
+                    1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                    1_000_000_000_000, true, 5, None, None, options.clone()
+                ).unwrap();
+            }
+            let result = contract.create_partial_fill_order_with_options(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None, options
+            );
+            assert_eq!(result, Err(Error::GroupFull));
+        }
+
+        #[ink::test]
+        fn test_error_counter_overflow() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            assert_eq!(contract.get_counter_high_watermarks(), (1, 0));
+
+            // A counter that moved backwards would re-derive an existing id
+            contract.order_counter -= 1;
+            assert_eq!(create_with_timelock(&mut contract, 500), Err(Error::CounterOverflow));
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            contract.fill_order(order_id, 200, accounts.bob).unwrap();
+            contract.fill_counter = 0;
+            assert_eq!(contract.fill_order(order_id, 300, accounts.bob), Err(Error::CounterOverflow));
+            assert_eq!(contract.get_counter_high_watermarks(), (1, 1));
+        }
+
+        #[ink::test]
+        fn test_error_wrong_source_chain() {
+            setup();
+            let mut contract = PolkadotPartialFills::new_with_home_chain(3);
+            assert_eq!(create_with_timelock(&mut contract, 500), Err(Error::WrongSourceChain));
+        }
+
+        #[ink::test]
+        fn test_error_order_fills_paused() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            contract.set_order_fill_paused(order_id, true).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.fill_order(order_id, 200, accounts.bob), Err(Error::OrderFillsPaused));
+        }
+
+        #[ink::test]
+        fn test_error_admin_operation_expired() {
+            let (mut contract, _) = setup();
+            assert_eq!(contract.update_protocol_fee(50, 99), Err(Error::AdminOperationExpired));
+        }
+
+        #[ink::test]
+        fn test_error_no_value_transferred() {
+            let (mut contract, _) = setup();
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            assert_eq!(create_with_timelock(&mut contract, 500), Err(Error::NoValueTransferred));
+        }
+
+        #[ink::test]
+        fn test_error_invalid_deposit_schedule() {
+            let (mut contract, _) = setup();
+            let unsorted = vec![(500, 100), (200, 50)];
+            assert_eq!(contract.set_deposit_schedule(unsorted, BlockNumber::MAX), Err(Error::InvalidDepositSchedule));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_error_too_many_consumers() {
+            let (mut contract, accounts) = setup();
+            let consumers = vec![accounts.django; MAX_STATS_CONSUMERS + 1];
+            assert_eq!(contract.set_stats_consumers(consumers, BlockNumber::MAX), Err(Error::TooManyConsumers));
+        }
+
+        #[ink::test]
+        fn test_error_invalid_decimals() {
+            let (mut contract, _) = setup();
+            let options = OrderOptions {
+                dest_decimals: Some(MAX_TOKEN_DECIMALS + 1),
+                ..Default::default()
+            };
+            let hashlock = contract.compute_sha256(&SECRET);
+            let result = contract.create_partial_fill_order_with_options(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None, options
+            );
+            assert_eq!(result, Err(Error::InvalidDecimals));
+        }
+
+        #[ink::test]
+        fn test_error_taker_whitelist_locked() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            contract.fill_order(order_id, 200, accounts.bob).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            assert_eq!(
+                contract.set_taker_whitelist(order_id, vec![accounts.bob]),
+                Err(Error::TakerWhitelistLocked)
+            );
+        }
+
+        #[ink::test]
+        fn test_error_invalid_auction_rates() {
+            let (mut contract, _) = setup();
+            let options = OrderOptions {
+                order_type: Some(OrderType::DutchAuction { start_rate: 2_000_000_000_000, end_rate: 0 }),
+                ..Default::default()
+            };
+            let hashlock = contract.compute_sha256(&SECRET);
+            let result = contract.create_partial_fill_order_with_options(
+                1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None, options
+            );
+            assert_eq!(result, Err(Error::InvalidAuctionRates));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_error_announcement_too_soon() {
+            let (mut contract, _) = setup();
+            assert_eq!(contract.announce_pending_fills(), Ok(0));
+            ink::env::test::set_block_number::<TestEnv>(199);
+            assert_eq!(contract.announce_pending_fills(), Err(Error::AnnouncementTooSoon));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_error_duplicate_order() {
+            let (mut contract, _) = setup();
+            contract.set_reject_duplicate_orders(true, BlockNumber::MAX).unwrap();
+            create_order(&mut contract, true, 5);
+            let hashlock = contract.compute_sha256(&SECRET);
+            let result = contract.create_partial_fill_order(
+                1000, 100, hashlock, 600, [0x03; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None
+            );
+            assert_eq!(result, Err(Error::DuplicateOrder));
+        }
+
+        #[ink::test]
+        fn test_error_refund_cooldown_active() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            contract.refund_cooldowns.insert((order_id, accounts.bob), &200);
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let result = contract.fill_order(order_id, 100, accounts.bob);
+            assert_eq!(result, Err(Error::RefundCooldownActive));
+        }
+
+        #[ink::test]
+        fn test_error_hashlock_in_use() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            contract.fill_order(order_id, 100, accounts.bob).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            assert_eq!(contract.rearm_order(order_id, [0x07; 32]), Err(Error::HashlockInUse));
+        }
+
+        #[ink::test]
+        fn test_error_rearm_limit_reached() {
+            let (mut contract, _) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            contract.rearm_order(order_id, [0x07; 32]).unwrap();
+            assert_eq!(contract.rearm_order(order_id, [0x08; 32]), Err(Error::RearmLimitReached));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_error_mapping_cooldown_active() {
+            let (mut contract, _) = setup();
+            contract.map_address(CrossChainAddress::Ethereum([0x11; 20])).unwrap();
+            let result = contract.map_address(CrossChainAddress::Ethereum([0x22; 20]));
+            assert_eq!(result, Err(Error::MappingCooldownActive));
+        }
+
+        #[ink::test]
+        fn test_error_order_not_terminal() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            assert_eq!(contract.cleanup_order(order_id), Err(Error::OrderNotTerminal));
+
+            // Cancelled, but a fill is still pending
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            contract.fill_order(order_id, 100, accounts.bob).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.cancel_order(order_id).unwrap();
+            assert_eq!(contract.cleanup_order(order_id), Err(Error::OrderNotTerminal));
+        }
+
+        #[ink::test]
+        fn test_error_retained_records_missing() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            let fill_id = contract.fill_order(order_id, 100, accounts.bob).unwrap();
+            contract.withdraw_fill(fill_id, SECRET, None, None).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            contract.cancel_order(order_id).unwrap();
+            ink::env::test::set_block_number::<TestEnv>(100 + DEFAULT_RETENTION_BLOCKS);
+
+            contract.secret_registry.remove(&contract.compute_sha256(&SECRET));
+            assert_eq!(contract.cleanup_order(order_id), Err(Error::RetainedRecordsMissing));
+        }
+
+        #[ink::test]
+        fn test_error_retention_period_active() {
+            let (mut contract, _) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            contract.cancel_order(order_id).unwrap();
+            ink::env::test::set_block_number::<TestEnv>(100 + DEFAULT_RETENTION_BLOCKS - 1);
+            assert_eq!(contract.cleanup_order(order_id), Err(Error::RetentionPeriodActive));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_error_invalid_signature() {
+            let (mut contract, _) = setup();
+            let result = contract.map_address_with_proof(
+                CrossChainAddress::Ethereum([0x11; 20]),
+                200,
+                MultiSignature::Ecdsa([0x01; 65]),
+            );
+            assert_eq!(result, Err(Error::InvalidSignature));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_error_signature_expired() {
+            let (mut contract, _) = setup();
+            let result = contract.map_address_with_proof(
+                CrossChainAddress::Ethereum([0x11; 20]),
+                99,
+                MultiSignature::Ecdsa([0x01; 65]),
+            );
+            assert_eq!(result, Err(Error::SignatureExpired));
+        }
+
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn test_error_mapping_not_found() {
+            let (mut contract, _) = setup();
+            let result = contract.renew_mapping_proof(200, MultiSignature::Ecdsa([0x01; 65]));
+            assert_eq!(result, Err(Error::MappingNotFound));
+        }
+
+        #[ink::test]
+        fn test_error_memo_too_long() {
+            let (mut contract, accounts) = setup();
+            let (_, fill_id) = create_filled_order(&mut contract, &accounts);
+
+            let memo = vec![0u8; MAX_WITHDRAWAL_MEMO_LEN + 1];
+            assert_eq!(contract.withdraw_fill(fill_id, SECRET, None, Some(memo)), Err(Error::MemoTooLong));
+        }
+
+        #[ink::test]
+        fn test_error_beta_cap_exceeded() {
+            let (mut contract, _) = setup();
+            contract.set_beta_cap(500, 1000, GraduationClock::Instantiation, BlockNumber::MAX).unwrap();
+
+            let hashlock = contract.compute_sha256(&SECRET);
+            assert_eq!(
+                contract.create_partial_fill_order(
+                    1000, 100, hashlock, 500, [0x02; 32], 1, 2,
+                    1_000_000_000_000, true, 5, None, None
+                ),
+                Err(Error::BetaCapExceeded { cap: 500, current: 0 })
+            );
+        }
+
+        #[ink::test]
+        fn test_error_upgrade_code_mismatch() {
+            let (mut contract, _) = setup();
+            contract.upgrade_staged(ink::H256::from([0x02; 32]), BlockNumber::MAX).unwrap();
+
+            let instance = Address::from([0x44; 20]);
+            assert_eq!(
+                contract.check_staged_upgrade(instance, None, BlockNumber::MAX),
+                Err(Error::UpgradeCodeMismatch)
+            );
+        }
+
+        #[ink::test]
+        fn test_error_sunset_active() {
+            let (mut contract, _) = setup();
+            contract.enter_sunset_mode(BlockNumber::MAX).unwrap();
+            assert_eq!(create_with_timelock(&mut contract, 500), Err(Error::SunsetActive));
+            assert_eq!(contract.enter_sunset_mode(BlockNumber::MAX), Err(Error::SunsetActive));
+        }
+
+        #[ink::test]
+        fn test_error_contract_retired() {
+            let (mut contract, accounts) = setup();
+            contract.enter_sunset_mode(BlockNumber::MAX).unwrap();
+            contract.finalize_sunset(BlockNumber::MAX).unwrap();
+            assert_eq!(contract.fill_order([0xff; 32], 200, accounts.bob), Err(Error::ContractRetired));
+            assert_eq!(contract.get_max_fillable_amount([0xff; 32], accounts.bob), Err(Error::ContractRetired));
+        }
+
+        #[ink::test]
+        fn test_error_not_in_sunset() {
+            let (mut contract, _) = setup();
+            assert_eq!(contract.finalize_sunset(BlockNumber::MAX), Err(Error::NotInSunset));
+        }
+
+        #[ink::test]
+        fn test_error_liabilities_outstanding() {
+            let (mut contract, _) = setup();
+            create_order(&mut contract, true, 5);
+            contract.enter_sunset_mode(BlockNumber::MAX).unwrap();
+            // The whole 1000 still in escrow
+            assert_eq!(
+                contract.finalize_sunset(BlockNumber::MAX),
+                Err(Error::LiabilitiesOutstanding { liabilities: 1000 })
+            );
+        }
+
+        #[ink::test]
+        fn test_error_contract_paused() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            contract.pause(BlockNumber::MAX).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.fill_order(order_id, 200, accounts.bob), Err(Error::ContractPaused));
+        }
+
+        #[ink::test]
+        fn test_error_capacity_reserved() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            ink::env::test::set_value_transferred::<TestEnv>(DEFAULT_RESERVATION_BOND.into());
+            contract.reserve(order_id, 500).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.django);
+            contract.reserve(order_id, 500).unwrap();
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(contract.reserve(order_id, 1), Err(Error::CapacityReserved));
+            ink::env::test::set_value_transferred::<TestEnv>(0u128.into());
+            assert_eq!(contract.fill_order(order_id, 100, accounts.bob), Err(Error::CapacityReserved));
+        }
+
+        #[ink::test]
+        fn test_error_reservation_exists() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            ink::env::test::set_value_transferred::<TestEnv>(DEFAULT_RESERVATION_BOND.into());
+            contract.reserve(order_id, 100).unwrap();
+            assert_eq!(contract.reserve(order_id, 100), Err(Error::ReservationExists));
+        }
+
+        #[ink::test]
+        fn test_error_reservation_cooldown_active() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            ink::env::test::set_value_transferred::<TestEnv>(DEFAULT_RESERVATION_BOND.into());
+            contract.reserve(order_id, 500).unwrap();
+            contract.release_reservation(order_id).unwrap();
+            assert_eq!(contract.reserve(order_id, 500), Err(Error::ReservationCooldownActive));
+        }
+
+        #[ink::test]
+        fn test_error_reservation_too_large() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            ink::env::test::set_value_transferred::<TestEnv>(DEFAULT_RESERVATION_BOND.into());
+            assert_eq!(contract.reserve(order_id, 501), Err(Error::ReservationTooLarge));
+        }
+
+        #[ink::test]
+        fn test_error_invalid_completion_callback() {
+            let (mut contract, accounts) = setup();
+            let create = |contract: &mut PolkadotPartialFills, callee, selector, swap_id| {
+                let options = OrderOptions {
+                    completion_callback: Some(CompletionCallback { contract: callee, selector }),
+                    ..Default::default()
+                };
+                contract.create_partial_fill_order_with_options(
+                    1000, 100, [0x01; 32], 500, swap_id, 1, 2,
+                    1_000_000_000_000, true, 5, None, None, options
+                )
+            };
+
+            // Any message other than `on_order_completed`, such as a PSP22 transfer
+            let transfer = ink::selector_bytes!("PSP22::transfer");
+            assert_eq!(create(&mut contract, accounts.django, transfer, [0x02; 32]), Err(Error::InvalidCompletionCallback));
+            // The escrow itself
+            let escrow = ink::env::address();
+            assert_eq!(
+                create(&mut contract, escrow, ON_ORDER_COMPLETED_SELECTOR, [0x03; 32]),
+                Err(Error::InvalidCompletionCallback)
+            );
+            // The fee token
+            #[cfg(feature = "psp22")]
+            {
+                contract.set_fee_token(Some(accounts.frank), BlockNumber::MAX).unwrap();
+                assert_eq!(
+                    create(&mut contract, accounts.frank, ON_ORDER_COMPLETED_SELECTOR, [0x04; 32]),
+                    Err(Error::InvalidCompletionCallback)
+                );
+                contract.set_fee_token(None, BlockNumber::MAX).unwrap();
             }
+            assert!(create(&mut contract, accounts.django, ON_ORDER_COMPLETED_SELECTOR, [0x05; 32]).is_ok());
+        }
+
+        #[cfg(feature = "psp22")]
+        #[ink::test]
+        fn test_error_fee_tokens_outstanding() {
+            let (mut contract, accounts) = setup();
+            let stablecoin = accounts.frank;
+            psp22_mock::mint(stablecoin, accounts.alice, 50);
+            contract.set_fee_token(Some(stablecoin), BlockNumber::MAX).unwrap();
+            let order_id = create_order(&mut contract, true, 5);
+            contract.enter_sunset_mode(BlockNumber::MAX).unwrap();
+            contract.cancel_order(order_id).unwrap();
+            assert_eq!(contract.get_total_liabilities(), 0);
+
+            // The stablecoin fee would be stranded once retired
+            assert_eq!(
+                contract.finalize_sunset(BlockNumber::MAX),
+                Err(Error::FeeTokensOutstanding { tokens: 1 })
+            );
+            contract.withdraw_psp22_fees(stablecoin, BlockNumber::MAX).unwrap();
+            contract.finalize_sunset(BlockNumber::MAX).unwrap();
+        }
+
+        #[ink::test]
+        fn test_error_reservation_not_found() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
+            assert_eq!(contract.release_reservation(order_id), Err(Error::ReservationNotFound));
+        }
 
-            if fill_amount < order.min_fill_amount && remaining_amount > order.min_fill_amount {
-                return Err(Error::FillAmountTooSmall);
+        #[ink::test]
+        fn test_error_reservation_limit_reached() {
+            let (mut contract, _) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            ink::env::test::set_value_transferred::<TestEnv>(DEFAULT_RESERVATION_BOND.into());
+            for reserver in 1..=MAX_RESERVATIONS_PER_ORDER as u8 {
+                ink::env::test::set_caller::<TestEnv>(Address::from([reserver; 20]));
+                contract.reserve(order_id, 1).unwrap();
             }
+            ink::env::test::set_caller::<TestEnv>(Address::from([0xff; 20]));
+            assert_eq!(contract.reserve(order_id, 1), Err(Error::ReservationLimitReached));
+        }
+
+        #[ink::test]
+        fn test_error_page_too_large() {
+            let (mut contract, _) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            assert_eq!(
+                contract.get_order_fills(order_id, 0, MAX_ORDER_FILLS_PAGE + 1),
+                Err(Error::PageTooLarge)
+            );
+        }
+
+        #[ink::test]
+        fn test_error_invalid_price_band() {
+            let (mut contract, _) = setup();
+            let inverted = contract.set_price_band(1, 2, Some((2, 1)), BlockNumber::MAX);
+            assert_eq!(inverted, Err(Error::InvalidPriceBand));
+        }
+
+        #[ink::test]
+        fn test_error_price_outside_band() {
+            let (mut contract, _) = setup();
+            contract.set_price_band(1, 2, Some((1, 1)), BlockNumber::MAX).unwrap();
+            contract.set_reject_mispriced(true, BlockNumber::MAX).unwrap();
+            let result = contract.create_partial_fill_order(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2, 2, true, 5, None, None
+            );
+            assert_eq!(result, Err(Error::PriceOutsideBand));
+        }
+
+        #[ink::test]
+        fn test_error_value_precision_loss() {
+            let mut contract = PolkadotPartialFills::new_with_value_scale(1_000_000).unwrap();
+            ink::env::test::set_block_number::<TestEnv>(100);
+            ink::env::test::set_value_transferred::<TestEnv>((1000u128 * 1_000_000 + 1).into());
+            assert_eq!(create_with_timelock(&mut contract, 500), Err(Error::ValuePrecisionLoss));
+        }
+
+        #[ink::test]
+        fn test_error_invalid_value_scale() {
+            let result = PolkadotPartialFills::new_with_value_scale(0);
+            assert_eq!(result.err(), Some(Error::InvalidValueScale));
+        }
+
+        #[cfg(not(feature = "xcm"))]
+        #[ink::test]
+        fn test_error_payout_route_unavailable() {
+            let (mut contract, accounts) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let fill_id = contract.fill_order(order_id, 100, accounts.bob).unwrap();
+            let route = PayoutRoute::Xcm { para_id: 2000, beneficiary: [0x0b; 32] };
+            let result = contract.withdraw_fill(fill_id, SECRET, Some(route), None);
+            assert_eq!(result, Err(Error::PayoutRouteUnavailable));
+        }
 
-            if !order.allow_partial_fills && fill_amount < remaining_amount {
-                return Err(Error::PartialFillsNotAllowed);
-            }
+        #[ink::test]
+        fn test_error_fees_not_stuck() {
+            let (mut contract, accounts) = setup();
+            create_order(&mut contract, true, 5);
+            assert_eq!(contract.redirect_stuck_fees(accounts.bob, BlockNumber::MAX), Err(Error::FeesNotStuck));
+        }
 
-            // Create fill execution
-            let fill_id = self.generate_fill_id(&order_id, &taker, fill_amount);
-            
-            if self.fills.contains(&fill_id) {
-                return Err(Error::OrderAlreadyExists);
-            }
+        #[ink::test]
+        fn test_error_max_fills_not_raisable() {
+            let (mut contract, _) = setup();
+            let order_id = create_order(&mut contract, true, 5);
+            assert_eq!(contract.increase_max_fills(order_id, 6), Err(Error::MaxFillsNotRaisable));
+        }
 
-            let contract_id = self.generate_contract_id(&order_id, &fill_id);
+        #[ink::test]
+        fn test_error_no_fees_to_withdraw() {
+            let (mut contract, accounts) = setup();
+            assert_eq!(contract.withdraw_protocol_fees(None, BlockNumber::MAX), Err(Error::NoFeesToWithdraw));
+            #[cfg(feature = "psp22")]
+            assert_eq!(
+                contract.withdraw_psp22_fees(accounts.frank, BlockNumber::MAX),
+                Err(Error::NoFeesToWithdraw)
+            );
+            // Nothing stays claimable once swept
+            let order_id = create_order(&mut contract, true, 5);
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            let fill_id = contract.fill_order(order_id, 1000, accounts.bob).unwrap();
+            contract.withdraw_fill(fill_id, SECRET, None, None).unwrap();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            assert_eq!(contract.withdraw_protocol_fees(None, BlockNumber::MAX), Ok(true));
+            assert_eq!(contract.withdraw_protocol_fees(None, BlockNumber::MAX), Err(Error::NoFeesToWithdraw));
+        }
 
-            let fill = FillExecution {
-                order_id,
-                taker,
-                fill_amount,
-                contract_id,
-                withdrawn: false,
-                refunded: false,
-                preimage: None,
-                timestamp: self.env().block_timestamp(),
+        #[ink::test]
+        fn test_error_invalid_aggregation() {
+            let (mut contract, _) = setup();
+            let options = OrderOptions {
+                aggregate_fills: Some(FillAggregation { every_fills: 0, every_blocks: 50 }),
+                ..Default::default()
             };
+            let result = contract.create_partial_fill_order_with_options(
+                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None, options
+            );
+            assert_eq!(result, Err(Error::InvalidAggregation));
+        }
+    }
 
-            self.fills.insert(&fill_id, &fill);
-
-            // Update order state
-            order.filled_amount += fill_amount;
-            order.current_fills += 1;
-            self.orders.insert(&order_id, &order);
+    /// Parameterized edge-case coverage of `validate_order_params`. Every row names
+    /// its inputs and the exact outcome; block 100 with the default 100..=14400
+    /// timelock window throughout.
+    #[cfg(test)]
+    mod fuzz_tests {
+        use super::*;
 
-            // Add to order fills tracking
-            let mut order_fill_list = self.order_fills.get(&order_id).unwrap_or_default();
-            order_fill_list.push(fill_id);
-            self.order_fills.insert(&order_id, &order_fill_list);
+        type TestEnv = ink::env::DefaultEnvironment;
 
-            let dest_amount = (fill_amount * order.dest_amount_per_unit) / 1_000_000_000_000; // Scale by 1e12
+        const NOW: BlockNumber = 100;
+        const MIN_TIMELOCK: BlockNumber = 100;
+        const MAX_TIMELOCK: BlockNumber = 14_400;
+        const VALID_TIMELOCK: BlockNumber = NOW + MIN_TIMELOCK;
 
-            self.env().emit_event(OrderFilled {
-                order_id,
-                fill_id,
-                taker,
-                fill_amount,
-                dest_amount,
-                contract_id,
-            });
+        const INVALID_TIMELOCK: Result<(), Error> = Err(Error::InvalidTimelock);
+        const INVALID_AMOUNT: Result<(), Error> = Err(Error::InvalidFillAmount);
+        const INVALID_CHAIN: Result<(), Error> = Err(Error::InvalidChainId);
 
-            Ok(fill_id)
+        struct Case {
+            name: &'static str,
+            total_amount: Balance,
+            min_fill_amount: Balance,
+            timelock: BlockNumber,
+            source_chain: u32,
+            dest_chain: u32,
+            max_fills: u32,
+            expected: Result<(), Error>,
         }
 
-        /// Withdraw filled amount using preimage
-        #[ink(message)]
-        pub fn withdraw_fill(
-            &mut self,
-            fill_id: [u8; 32],
-            preimage: [u8; 32],
-        ) -> Result<(), Error> {
-            let caller = self.env().caller();
-            let mut fill = self.get_fill_or_error(&fill_id)?;
-            let order = self.get_order_or_error(&fill.order_id)?;
-
-            self.validate_fill_withdrawal(&fill, &order, &caller)?;
-            self.validate_preimage(&order, &preimage)?;
+        /// Valid baseline; each case overrides the fields under test
+        fn case(name: &'static str, expected: Result<(), Error>) -> Case {
+            Case {
+                name,
+                total_amount: 1000,
+                min_fill_amount: 100,
+                timelock: VALID_TIMELOCK,
+                source_chain: 1,
+                dest_chain: 2,
+                max_fills: 5,
+                expected,
+            }
+        }
 
-            fill.withdrawn = true;
-            fill.preimage = Some(preimage);
-            self.fills.insert(&fill_id, &fill);
+        fn setup() -> PolkadotPartialFills {
+            let contract = PolkadotPartialFills::new();
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            ink::env::test::set_block_number::<TestEnv>(NOW);
+            contract
+        }
 
-            self.execute_transfer(fill.taker, fill.fill_amount)?;
+        fn run(contract: &PolkadotPartialFills, cases: &[Case]) {
+            for c in cases {
+                let result = contract.validate_order_params(
+                    c.total_amount,
+                    c.min_fill_amount,
+                    c.timelock,
+                    c.source_chain,
+                    c.dest_chain,
+                    c.max_fills,
+                );
+                assert_eq!(result, c.expected, "case `{}`", c.name);
+            }
+        }
 
-            self.env().emit_event(FillWithdrawn {
-                fill_id,
-                secret: preimage,
-                taker: fill.taker,
-            });
+        fn timelock_cases() -> Vec<Case> {
+            vec![
+                Case { timelock: 0, ..case("timelock zero", INVALID_TIMELOCK) },
+                Case { timelock: NOW - 1, ..case("timelock in the past", INVALID_TIMELOCK) },
+                Case { timelock: NOW, ..case("timelock at current block", INVALID_TIMELOCK) },
+                Case { timelock: NOW + 1, ..case("timelock next block", Err(Error::TimelockTooShort)) },
+                Case {
+                    timelock: NOW + MIN_TIMELOCK - 1,
+                    ..case("timelock one below minimum", Err(Error::TimelockTooShort))
+                },
+                Case { timelock: NOW + MIN_TIMELOCK, ..case("timelock at minimum", Ok(())) },
+                Case { timelock: NOW + MIN_TIMELOCK + 1, ..case("timelock above minimum", Ok(())) },
+                Case { timelock: NOW + MAX_TIMELOCK, ..case("timelock at maximum", Ok(())) },
+                Case {
+                    timelock: NOW + MAX_TIMELOCK + 1,
+                    ..case("timelock one above maximum", Err(Error::TimelockTooLong))
+                },
+                Case { timelock: BlockNumber::MAX, ..case("max timelock", Err(Error::TimelockTooLong)) },
+            ]
+        }
 
-            Ok(())
+        fn amount_cases() -> Vec<Case> {
+            vec![
+                Case { total_amount: 0, min_fill_amount: 0, ..case("all zero", INVALID_AMOUNT) },
+                Case { total_amount: 0, min_fill_amount: 1, ..case("zero total", INVALID_AMOUNT) },
+                Case { total_amount: 1, min_fill_amount: 1, ..case("smallest order", Ok(())) },
+                Case { min_fill_amount: 0, ..case("zero min fill", Ok(())) },
+                Case { total_amount: 1, min_fill_amount: 0, ..case("smallest order without minimum", Ok(())) },
+                Case { min_fill_amount: 1, ..case("min fill one", Ok(())) },
+                Case { min_fill_amount: 999, ..case("min fill one below total", Ok(())) },
+                Case { min_fill_amount: 1000, ..case("min fill equal to total", Ok(())) },
+                Case { min_fill_amount: 1001, ..case("min fill above total", INVALID_AMOUNT) },
+                Case {
+                    min_fill_amount: Balance::MAX,
+                    ..case("min fill max value", INVALID_AMOUNT)
+                },
+                Case { total_amount: Balance::MAX, min_fill_amount: 1, ..case("max total", Ok(())) },
+                Case {
+                    total_amount: Balance::MAX,
+                    min_fill_amount: Balance::MAX,
+                    ..case("max total and min", Ok(()))
+                },
+                Case {
+                    total_amount: Balance::MAX - 1,
+                    min_fill_amount: Balance::MAX,
+                    ..case("min fill just above max-1 total", INVALID_AMOUNT)
+                },
+            ]
         }
 
-        /// Refund fill after timelock expires
-        #[ink(message)]
-        pub fn refund_fill(&mut self, fill_id: [u8; 32]) -> Result<(), Error> {
-            let caller = self.env().caller();
-            let mut fill = self.get_fill_or_error(&fill_id)?;
-            let mut order = self.get_order_or_error(&fill.order_id)?;
+        fn chain_and_fill_config_cases() -> Vec<Case> {
+            vec![
+                Case { dest_chain: 1, ..case("same chain", INVALID_CHAIN) },
+                Case { source_chain: 0, dest_chain: 0, ..case("both chains zero", INVALID_CHAIN) },
+                Case { source_chain: 0, dest_chain: u32::MAX, ..case("extreme chain ids", Ok(())) },
+                Case { max_fills: 0, ..case("zero max fills", INVALID_AMOUNT) },
+                Case { max_fills: 1, ..case("single fill", Ok(())) },
+                Case { max_fills: u32::MAX, ..case("max fills max value", Ok(())) },
+            ]
+        }
 
-            self.validate_fill_refund(&fill, &order, &caller)?;
+        /// When several parameters are invalid, the first validator in
+        /// `validate_order_params` decides the error
+        fn precedence_cases() -> Vec<Case> {
+            vec![
+                Case {
+                    timelock: NOW,
+                    total_amount: 0,
+                    dest_chain: 1,
+                    ..case("timelock before chains and amounts", Err(Error::InvalidTimelock))
+                },
+                Case {
+                    timelock: NOW + MAX_TIMELOCK + 1,
+                    max_fills: 0,
+                    ..case("timelock before fill config", Err(Error::TimelockTooLong))
+                },
+                Case {
+                    dest_chain: 1,
+                    total_amount: 0,
+                    ..case("chains before amounts", Err(Error::InvalidChainId))
+                },
+                Case {
+                    min_fill_amount: 2000,
+                    max_fills: 0,
+                    ..case("amounts before fill config", INVALID_AMOUNT)
+                },
+            ]
+        }
 
-            fill.refunded = true;
-            self.fills.insert(&fill_id, &fill);
+        #[ink::test]
+        fn fuzz_timelock_boundaries() {
+            run(&setup(), &timelock_cases());
+        }
 
-            // Update order filled amount (subtract refunded amount)
-            order.filled_amount -= fill.fill_amount;
-            order.current_fills -= 1;
-            self.orders.insert(&fill.order_id, &order);
+        #[ink::test]
+        fn fuzz_amount_boundaries() {
+            run(&setup(), &amount_cases());
+        }
 
-            self.execute_transfer(order.maker, fill.fill_amount)?;
+        #[ink::test]
+        fn fuzz_chain_and_fill_config() {
+            run(&setup(), &chain_and_fill_config_cases());
+        }
 
-            self.env().emit_event(FillRefunded {
-                fill_id,
-                maker: order.maker,
-            });
+        #[ink::test]
+        fn fuzz_validator_precedence() {
+            run(&setup(), &precedence_cases());
+        }
 
-            Ok(())
+        #[ink::test]
+        fn fuzz_destination_timelock() {
+            let mut contract = setup();
+            // 6s blocks: 1200s is exactly 200 blocks of lock
+            contract.register_chain(3, ChainConfig { max_counterpart_timelock: 1200 }, BlockNumber::MAX).unwrap();
+            run(&contract, &[
+                Case { dest_chain: 3, timelock: NOW + 200, ..case("fits destination", Ok(())) },
+                Case {
+                    dest_chain: 3,
+                    timelock: NOW + 201,
+                    ..case("exceeds destination", Err(Error::TimelockIncompatibleWithDestination))
+                },
+                Case { dest_chain: 4, timelock: NOW + 201, ..case("unregistered destination", Ok(())) },
+            ]);
         }
 
-        /// Cancel order and refund remaining amount
-        #[ink(message)]
-        pub fn cancel_order(&mut self, order_id: [u8; 32]) -> Result<(), Error> {
-            let caller = self.env().caller();
-            let mut order = self.get_order_or_error(&order_id)?;
+        #[ink::test]
+        fn fuzz_home_chain() {
+            let contract = PolkadotPartialFills::new_with_home_chain(7);
+            ink::env::test::set_block_number::<TestEnv>(NOW);
+            run(&contract, &[
+                Case { source_chain: 7, ..case("home source", Ok(())) },
+                Case { source_chain: 1, ..case("foreign source", Err(Error::WrongSourceChain)) },
+                Case { source_chain: 7, dest_chain: 7, ..case("home to home", INVALID_CHAIN) },
+            ]);
+        }
 
-            if caller != order.maker {
-                return Err(Error::UnauthorizedRefund);
+        /// The public entry point reaches the same verdict as the validator for
+        /// every case that can be funded
+        #[ink::test]
+        fn fuzz_create_matches_validation() {
+            let mut contract = setup();
+            ink::env::test::set_value_transferred::<TestEnv>(10_000u128.into());
+
+            let cases = timelock_cases()
+                .into_iter()
+                .chain(amount_cases())
+                .chain(chain_and_fill_config_cases())
+                .chain(precedence_cases())
+                .filter(|c| c.total_amount <= 10_000 && c.total_amount > 0);
+            for (i, c) in cases.enumerate() {
+                let result = contract.create_partial_fill_order(
+                    c.total_amount, c.min_fill_amount, [0x01; 32], c.timelock, [i as u8; 32],
+                    c.source_chain, c.dest_chain, 1_000_000_000_000, true, c.max_fills, None, None
+                );
+                assert_eq!(result.map(|_| ()), c.expected, "case `{}`", c.name);
             }
+        }
 
-            if order.cancelled {
-                return Err(Error::OrderCancelled);
+        fn create_params(c: &Case, swap_id: [u8; 32], options: OrderOptions) -> CreateOrderParams {
+            CreateOrderParams {
+                total_amount: c.total_amount,
+                min_fill_amount: c.min_fill_amount,
+                hashlock: [0x01; 32],
+                timelock: c.timelock,
+                swap_id,
+                source_chain: c.source_chain,
+                dest_chain: c.dest_chain,
+                dest_amount_per_unit: 1_000_000_000_000,
+                allow_partial_fills: true,
+                max_fills: c.max_fills,
+                sender_cross_address: None,
+                receiver_cross_address: None,
+                options,
             }
+        }
 
-            order.cancelled = true;
-            self.orders.insert(&order_id, &order);
+        /// `validate_create` followed by the real creation with the same value sent:
+        /// both must fail identically, or succeed with the previewed id
+        fn assert_preview_matches(
+            contract: &mut PolkadotPartialFills,
+            name: &str,
+            params: CreateOrderParams,
+            value: Balance,
+        ) {
+            let preview = contract.validate_create(params.clone(), value);
+            ink::env::test::set_value_transferred::<TestEnv>(value.into());
+            let created = contract.create_partial_fill_order_with_options(
+                params.total_amount, params.min_fill_amount, params.hashlock, params.timelock,
+                params.swap_id, params.source_chain, params.dest_chain, params.dest_amount_per_unit,
+                params.allow_partial_fills, params.max_fills, params.sender_cross_address,
+                params.receiver_cross_address, params.options,
+            );
+            assert_eq!(preview.map(|p| p.order_id), created, "case `{}` with value {}", name, value);
+        }
 
-            let remaining_amount = order.total_amount - order.filled_amount;
-            if remaining_amount > 0 {
-                self.execute_transfer(order.maker, remaining_amount)?;
+        #[ink::test]
+        fn fuzz_validate_create_matches_create() {
+            let mut contract = setup();
+
+            let cases = timelock_cases()
+                .into_iter()
+                .chain(amount_cases())
+                .chain(chain_and_fill_config_cases())
+                .chain(precedence_cases())
+                // Fee math on the valid near-`Balance::MAX` rows is out of scope here
+                .filter(|c| c.expected.is_err() || c.total_amount <= 10_000);
+            for (i, c) in cases.enumerate() {
+                let short = c.total_amount.saturating_sub(1);
+                for value in [0, short, c.total_amount, c.total_amount.saturating_add(1)] {
+                    let params = create_params(&c, [i as u8; 32], OrderOptions::default());
+                    assert_preview_matches(&mut contract, c.name, params, value);
+                }
             }
 
-            self.env().emit_event(OrderCancelled { order_id });
+            let base = case("options", Ok(()));
+            let option_cases = [
+                OrderOptions {
+                    vesting_schedule: Some(VestingSchedule {
+                        unlock_amount_per_block: 0,
+                        unlock_start_block: NOW,
+                    }),
+                    ..Default::default()
+                },
+                OrderOptions { source_decimals: Some(MAX_TOKEN_DECIMALS + 1), ..Default::default() },
+                OrderOptions { dest_decimals: Some(u8::MAX), ..Default::default() },
+            ];
+            for options in option_cases {
+                let params = create_params(&base, [0xee; 32], options);
+                assert_preview_matches(&mut contract, "options", params, 1000);
+            }
 
-            Ok(())
+            // A counter that moved backwards fails the preview as it fails creation
+            contract.order_counter -= 1;
+            let params = create_params(&base, [0xef; 32], OrderOptions::default());
+            assert_preview_matches(&mut contract, "counter behind watermark", params, 1000);
         }
 
-        // View functions
-        #[ink(message)]
-        pub fn get_order(&self, order_id: [u8; 32]) -> Option<PartialFillOrder> {
-            self.orders.get(&order_id)
-        }
+        #[ink::test]
+        fn fuzz_validate_create_preview() {
+            let mut contract = setup();
+            let params = create_params(&case("preview", Ok(())), [0x02; 32], OrderOptions::default());
 
-        #[ink(message)]
-        pub fn get_fill(&self, fill_id: [u8; 32]) -> Option<FillExecution> {
-            self.fills.get(&fill_id)
-        }
+            let preview = contract.validate_create(params.clone(), 1000).unwrap();
+            assert_eq!(preview.fee, 0);
+            assert_eq!(preview.net_amount, 1000);
+            assert_eq!(preview.fee_bps_applied, 30);
+            // Previewing changes nothing: the same call yields the same id
+            assert_eq!(contract.validate_create(params.clone(), 1000), Ok(preview));
 
-        #[ink(message)]
-        pub fn get_order_fills(&self, order_id: [u8; 32]) -> Vec<[u8; 32]> {
-            self.order_fills.get(&order_id).unwrap_or_default()
+            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
+            let order_id = contract.create_partial_fill_order(
+                params.total_amount, params.min_fill_amount, params.hashlock, params.timelock,
+                params.swap_id, params.source_chain, params.dest_chain, params.dest_amount_per_unit,
+                true, params.max_fills, None, None
+            ).unwrap();
+            assert_eq!(order_id, preview.order_id);
+            assert_eq!(contract.get_order(order_id).unwrap().total_amount, preview.net_amount);
         }
 
-        #[ink(message)]
-        pub fn order_exists(&self, order_id: [u8; 32]) -> bool {
-            self.orders.contains(&order_id)
+        #[cfg(feature = "periphery")]
+        #[ink::test]
+        fn fuzz_validate_create_periphery_guards() {
+            let mut contract = setup();
+            contract.set_reject_duplicate_orders(true, BlockNumber::MAX).unwrap();
+            let base = case("guards", Ok(()));
+
+            let params = create_params(&base, [0x03; 32], OrderOptions::default());
+            assert_preview_matches(&mut contract, "first order", params, 1000);
+            // Same maker, amount, hashlock and destination terms as the open order
+            let params = create_params(&base, [0x04; 32], OrderOptions::default());
+            assert_preview_matches(&mut contract, "duplicate order", params, 1000);
+
+            ink::env::test::set_caller::<TestEnv>(ink::env::test::default_accounts::<TestEnv>().bob);
+            let group = OrderOptions { group_id: Some([0x77; 32]), ..Default::default() };
+            for i in 0..=MAX_GROUP_ORDERS {
+                // Distinct amounts keep the duplicate guard out of the way
+                let c = Case {
+                    total_amount: 100 + i as Balance,
+                    min_fill_amount: 1,
+                    ..case("group", Ok(()))
+                };
+                let params = create_params(&c, [0x05; 32], group.clone());
+                assert_preview_matches(&mut contract, "group member", params, 1000);
+            }
         }
+    }
 
-        #[ink(message)]
-        pub fn get_remaining_amount(&self, order_id: [u8; 32]) -> Balance {
-            if let Some(order) = self.orders.get(&order_id) {
-                if order.cancelled || order.filled_amount >= order.total_amount {
-                    return 0;
+    /// Randomized soak of the whole order lifecycle, ignored by default. Run it with
+    /// `cargo test --release soak -- --ignored`.
+    ///
+    /// About 50k creates, fills, withdrawals, refunds, cancels, cleanups and
+    /// reservations are drawn from a seeded generator over roughly a week of 6s
+    /// blocks. Every 1k operations the escrow accounting, the order indices and the
+    /// counters are checked against a model of what should still be in storage.
+    /// Every cleanup must shrink storage by at least the order and its fills, and
+    /// the stored orders must end as those created less those cleaned up.
+    #[cfg(test)]
+    mod soak_tests {
+        use super::*;
+        use std::collections::{BTreeMap, HashMap, HashSet};
+
+        type TestEnv = ink::env::DefaultEnvironment;
+
+        const OPERATIONS: usize = 50_000;
+        const CHECK_INTERVAL: usize = 1_000;
+        const SEED: u64 = 0x7468_616c_6169_7661;
+        /// Fills, cancels and reservations pick among the most recent orders, withdrawals
+        /// among the most recent pending fills; older ones have mostly expired
+        const RECENT: usize = 32;
+        const RESERVATION_BOND: Balance = 50;
+        const STARTING_BALANCE: Balance = 1_000_000_000_000_000;
+        /// The constructor's default protocol fee
+        const DEFAULT_FEE_BPS: u16 = 30;
+        /// The zero-fee run is part of the regular suite, so it is kept short
+        const ZERO_FEE_OPERATIONS: usize = 2_000;
+        const ZERO_FEE_CHECK_INTERVAL: usize = 100;
+
+        /// splitmix64; the tests have no `rand` dependency
+        struct Rng(u64);
+
+        impl Rng {
+            fn next(&mut self) -> u64 {
+                self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                let mut z = self.0;
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                z ^ (z >> 31)
+            }
+
+            /// Uniform in `0..n`
+            fn below(&mut self, n: u64) -> u64 {
+                self.next() % n
+            }
+
+            fn index(&mut self, len: usize) -> usize {
+                self.below(len as u64) as usize
+            }
+
+            fn bytes(&mut self) -> [u8; 32] {
+                let mut out = [0u8; 32];
+                for chunk in out.chunks_mut(8) {
+                    chunk.copy_from_slice(&self.next().to_le_bytes());
                 }
-                return order.total_amount - order.filled_amount;
+                out
             }
-            0
         }
 
-        #[ink(message)]
-        pub fn is_order_complete(&self, order_id: [u8; 32]) -> bool {
-            if let Some(order) = self.orders.get(&order_id) {
-                return order.filled_amount >= order.total_amount;
-            }
-            false
+        struct SoakOrder {
+            maker: Address,
+            secret: [u8; 32],
         }
 
-        #[ink(message)]
-        pub fn get_fill_secret(&self, fill_id: [u8; 32]) -> Option<[u8; 32]> {
-            self.fills.get(&fill_id).and_then(|fill| fill.preimage)
+        struct SoakFill {
+            id: [u8; 32],
+            order_id: [u8; 32],
+            taker: Address,
         }
 
-        #[ink(message)]
-        pub fn get_cross_address(&self, account: Address) -> Option<CrossChainAddress> {
-            self.address_mappings.get(&account)
+        /// The contract together with what the soak expects to still be in storage
+        struct Soak {
+            contract: PolkadotPartialFills,
+            fee_bps: u16,
+            rng: Rng,
+            makers: [Address; 3],
+            takers: [Address; 3],
+            orders: HashMap<[u8; 32], SoakOrder>,
+            order_ids: Vec<[u8; 32]>, // creation order, cleaned up ones removed
+            pending: Vec<SoakFill>,
+            reservations: Vec<([u8; 32], Address)>,
+            outcomes: BTreeMap<&'static str, (usize, usize)>, // op => (accepted, rejected)
+            counters: (u64, u64), // (order, fill) high watermarks at the last check
         }
 
-        #[ink(message)]
-        pub fn get_admin(&self) -> Address {
-            self.admin
+        fn balance(account: Address) -> Balance {
+            let balance = ink::env::test::get_account_balance::<TestEnv>(account).unwrap();
+            balance.try_into().unwrap()
         }
 
-        #[ink(message)]
-        pub fn get_protocol_fee_bps(&self) -> u16 {
-            self.protocol_fee_bps
+        fn set_balance(account: Address, balance: Balance) {
+            ink::env::test::set_account_balance::<TestEnv>(account, balance.into());
         }
 
-        #[ink(message)]
-        pub fn get_protocol_fees(&self) -> Balance {
-            self.protocol_fees
+        fn set_caller(account: Address) {
+            ink::env::test::set_caller::<TestEnv>(account);
         }
 
-        // Admin functions
-        #[ink(message)]
-        pub fn update_admin(&mut self, new_admin: Address) -> Result<(), Error> {
-            self.ensure_admin()?;
-            self.admin = new_admin;
-            Ok(())
+        fn block_number() -> BlockNumber {
+            ink::env::block_number::<TestEnv>()
         }
 
-        #[ink(message)]
-        pub fn update_protocol_fee(&mut self, new_fee_bps: u16) -> Result<(), Error> {
-            self.ensure_admin()?;
-            if new_fee_bps > 1000 {
-                return Err(Error::InvalidFee);
-            }
-            self.protocol_fee_bps = new_fee_bps;
-            Ok(())
+        fn storage_cells() -> usize {
+            let callee = ink::env::test::callee::<TestEnv>();
+            ink::env::test::count_used_storage_cells::<TestEnv>(&callee).unwrap()
         }
 
-        #[ink(message)]
-        pub fn withdraw_protocol_fees(&mut self) -> Result<(), Error> {
-            self.ensure_admin()?;
-            
-            let fees = self.protocol_fees;
-            if fees == 0 {
-                return Err(Error::InsufficientFunds);
+        impl Soak {
+            fn new(fee_bps: u16) -> Self {
+                let accounts = ink::env::test::default_accounts::<TestEnv>();
+                // The contract needs a balance of its own, apart from alice's
+                ink::env::test::set_callee::<TestEnv>(Address::from([0xc0; 20]));
+                set_caller(accounts.alice);
+                ink::env::test::set_block_number::<TestEnv>(100);
+                let mut contract = PolkadotPartialFills::new();
+                contract.set_reservation_params(20, RESERVATION_BOND, BlockNumber::MAX).unwrap();
+                contract.update_protocol_fee(fee_bps, BlockNumber::MAX).unwrap();
+
+                let makers = [accounts.alice, accounts.bob, accounts.charlie];
+                let takers = [accounts.django, accounts.eve, accounts.frank];
+                for account in makers.iter().chain(takers.iter()) {
+                    set_balance(*account, STARTING_BALANCE);
+                }
+                set_balance(ink::env::test::callee::<TestEnv>(), 0);
+
+                Self {
+                    contract,
+                    fee_bps,
+                    rng: Rng(SEED),
+                    makers,
+                    takers,
+                    orders: HashMap::new(),
+                    order_ids: Vec::new(),
+                    pending: Vec::new(),
+                    reservations: Vec::new(),
+                    outcomes: BTreeMap::new(),
+                    counters: (0, 0),
+                }
             }
 
-            self.protocol_fees = 0;
-            if let Err(_) = self.execute_transfer(self.admin, fees) {
-                self.protocol_fees = fees; // Restore on failure
-                return Err(Error::TransferFailed);
+            /// Run `call` as `caller` sending `value`. A rejected call is undone the
+            /// way the runtime would revert it; off-chain only the value transfer needs
+            /// undoing, since every message validates before it writes.
+            fn call<R>(
+                &mut self,
+                op: &'static str,
+                caller: Address,
+                value: Balance,
+                call: impl FnOnce(&mut PolkadotPartialFills) -> Result<R, Error>,
+            ) -> Option<R> {
+                let callee = ink::env::test::callee::<TestEnv>();
+                let balances = (balance(caller), balance(callee));
+                set_caller(caller);
+                ink::env::test::transfer_in::<TestEnv>(value.into());
+
+                let result = call(&mut self.contract);
+                let outcome = self.outcomes.entry(op).or_default();
+                match result {
+                    Ok(value) => {
+                        outcome.0 += 1;
+                        Some(value)
+                    }
+                    Err(error) => {
+                        assert!(
+                            !matches!(
+                                error,
+                                Error::TransferFailed | Error::CounterOverflow | Error::ConversionError
+                            ),
+                            "{op} failed at block {}: {error:?}",
+                            block_number()
+                        );
+                        outcome.1 += 1;
+                        set_balance(caller, balances.0);
+                        set_balance(callee, balances.1);
+                        None
+                    }
+                }
             }
 
-            Ok(())
-        }
-
-        // Private helper functions
-        fn get_transferred_balance(&self) -> Result<Balance, Error> {
-            let amount = self.env().transferred_value();
-            if amount == 0u128.into() {
-                return Err(Error::InsufficientFunds);
+            fn recent_order(&mut self) -> Option<[u8; 32]> {
+                let recent = &self.order_ids[self.order_ids.len().saturating_sub(RECENT)..];
+                (!recent.is_empty()).then(|| recent[self.rng.index(recent.len())])
             }
-            amount.try_into().map_err(|_| Error::ConversionError)
-        }
-
-        fn validate_order_params(
-            &self,
-            total_amount: Balance,
-            min_fill_amount: Balance,
-            timelock: BlockNumber,
-            source_chain: u32,
-            dest_chain: u32,
-            max_fills: u32,
-        ) -> Result<(), Error> {
-            let current_block = self.env().block_number();
 
-            if timelock <= current_block {
-                return Err(Error::InvalidTimelock);
+            fn create(&mut self) {
+                let maker = self.makers[self.rng.index(3)];
+                let total_amount = 1_000 + self.rng.below(99_000) as Balance;
+                let min_fill_amount = 1 + self.rng.below(total_amount as u64 / 10) as Balance;
+                let timelock = block_number() + 100 + self.rng.below(500) as BlockNumber;
+                let secret = self.rng.bytes();
+                let hashlock = self.contract.compute_sha256(&secret);
+                // A small pool of swap ids, so swap indices hold several orders
+                let swap_id = [self.rng.below(16) as u8; 32];
+                let partial = self.rng.below(10) != 0;
+                let max_fills = 1 + self.rng.below(12) as u32;
+
+                let created = self.call("create", maker, total_amount, |c| c.create_partial_fill_order(
+                    total_amount, min_fill_amount, hashlock, timelock, swap_id, 1, 2,
+                    1_000_000_000_000, partial, max_fills, None, None
+                ));
+                if let Some(order_id) = created {
+                    if self.fee_bps == 0 {
+                        let order = self.contract.get_order(order_id).unwrap();
+                        let escrow = (order.total_amount, order.fee);
+                        assert_eq!(escrow, (total_amount, 0), "zero-fee escrow");
+                    }
+                    self.orders.insert(order_id, SoakOrder { maker, secret });
+                    self.order_ids.push(order_id);
+                }
             }
 
-            if timelock < current_block + self.min_timelock {
-                return Err(Error::TimelockTooShort);
+            fn fill(&mut self) {
+                let Some(order_id) = self.recent_order() else { return };
+                let taker = self.takers[self.rng.index(3)];
+                // Up to a quarter above what remains, to exercise clamping
+                let remaining = self.contract.get_remaining_amount(order_id) as u64;
+                let amount = 1 + self.rng.below(remaining + remaining / 4 + 1) as Balance;
+                let deposit = self.rng.below(3) as Balance * 10;
+
+                let filled = self.call("fill", taker, deposit, |c| {
+                    c.fill_order(order_id, amount, taker)
+                });
+                if let Some(id) = filled {
+                    self.pending.push(SoakFill { id, order_id, taker });
+                    self.reservations.retain(|reservation| *reservation != (order_id, taker));
+                }
             }
 
-            if timelock > current_block + self.max_timelock {
-                return Err(Error::TimelockTooLong);
+            fn withdraw(&mut self) {
+                if self.pending.is_empty() {
+                    return;
+                }
+                let index = self.pending.len() - 1 - self.rng.index(self.pending.len().min(RECENT));
+                let fill = &self.pending[index];
+                let (id, taker) = (fill.id, fill.taker);
+                let mut secret = self.orders[&fill.order_id].secret;
+                if self.rng.below(20) == 0 {
+                    secret[0] ^= 1;
+                }
+                let credit_payout = self.rng.below(4) == 0;
+                let route = credit_payout.then_some(PayoutRoute::Credit);
+                let fill = self.contract.get_fill(id).unwrap();
+                let payout = fill.fill_amount + fill.deposit;
+                let before = (balance(taker), self.contract.get_credit(taker));
+
+                let withdrawn = self.call("withdraw", taker, 0, |c| {
+                    c.withdraw_fill(id, secret, route, None)
+                });
+                if withdrawn.is_some() {
+                    self.pending.swap_remove(index);
+                    // The taker receives the gross fill plus its deposit, never less
+                    let received = if credit_payout {
+                        self.contract.get_credit(taker) - before.1
+                    } else {
+                        balance(taker) - before.0
+                    };
+                    assert_eq!(received, payout, "withdraw payout");
+                }
             }
 
-            if source_chain == dest_chain {
-                return Err(Error::InvalidChainId);
-            }
+            fn refund(&mut self) {
+                if self.pending.is_empty() {
+                    return;
+                }
+                let index = self.rng.index(self.pending.len());
+                let fill = &self.pending[index];
+                let (id, maker) = (fill.id, self.orders[&fill.order_id].maker);
 
-            if min_fill_amount == 0 || min_fill_amount > total_amount {
-                return Err(Error::InvalidFillAmount);
+                if self.call("refund", maker, 0, |c| c.refund_fill(id, None)).is_some() {
+                    self.pending.swap_remove(index);
+                }
             }
 
-            if max_fills == 0 {
-                return Err(Error::InvalidFillAmount);
+            fn cancel(&mut self) {
+                let Some(order_id) = self.recent_order() else { return };
+                let maker = self.orders[&order_id].maker;
+                self.call("cancel", maker, 0, |c| c.cancel_order(order_id));
             }
 
-            Ok(())
-        }
-
-        fn validate_fill_request(
-            &self,
-            order: &PartialFillOrder,
-            fill_amount: Balance,
-        ) -> Result<(), Error> {
-            if order.cancelled {
-                return Err(Error::OrderCancelled);
+            fn cleanup(&mut self) {
+                if self.order_ids.is_empty() {
+                    return;
+                }
+                let order_id = self.order_ids[self.rng.index(self.order_ids.len())];
+                let maker = self.orders[&order_id].maker;
+                let fills = self.contract.order_fills.get(&order_id).unwrap_or_default().len();
+                let cells = storage_cells();
+
+                if self.call("cleanup", maker, 0, |c| c.cleanup_order(order_id)).is_some() {
+                    // The order and every fill of it leave storage, along with their side entries
+                    assert!(cells - storage_cells() > fills, "cleanup storage");
+                    self.orders.remove(&order_id);
+                    self.order_ids.retain(|id| *id != order_id);
+                }
             }
 
-            let current_block = self.env().block_number();
-            if current_block >= order.timelock {
-                return Err(Error::TimelockExpired);
+            fn reserve(&mut self) {
+                let Some(order_id) = self.recent_order() else { return };
+                let reserver = self.takers[self.rng.index(3)];
+                let remaining = self.contract.get_remaining_amount(order_id) as u64;
+                let amount = 1 + self.rng.below(remaining / 2 + 1) as Balance;
+
+                let reserved = self.call("reserve", reserver, RESERVATION_BOND, |c| {
+                    c.reserve(order_id, amount)
+                });
+                if reserved.is_some() {
+                    self.reservations.push((order_id, reserver));
+                }
             }
 
-            if order.filled_amount >= order.total_amount {
-                return Err(Error::OrderCompleted);
+            fn release(&mut self) {
+                if self.reservations.is_empty() {
+                    return;
+                }
+                let index = self.rng.index(self.reservations.len());
+                let (order_id, reserver) = self.reservations[index];
+
+                if self.call("release", reserver, 0, |c| c.release_reservation(order_id)).is_some() {
+                    self.reservations.swap_remove(index);
+                }
             }
 
-            if order.current_fills >= order.max_fills {
-                return Err(Error::MaxFillsReached);
+            fn withdraw_credit(&mut self) {
+                let taker = self.takers[self.rng.index(3)];
+                let credit = self.contract.get_credit(taker);
+                if credit > 0 {
+                    self.call("withdraw_credit", taker, 0, |c| c.withdraw_credit(credit));
+                }
             }
 
-            if fill_amount == 0 {
-                return Err(Error::InvalidFillAmount);
+            fn step(&mut self) {
+                match self.rng.below(100) {
+                    0..=17 => self.create(),
+                    18..=44 => self.fill(),
+                    45..=62 => self.withdraw(),
+                    63..=70 => self.refund(),
+                    71..=76 => self.cancel(),
+                    77..=86 => self.cleanup(),
+                    87..=92 => self.reserve(),
+                    93..=95 => self.release(),
+                    _ => self.withdraw_credit(),
+                }
+                // Two blocks per operation on average, about a week in total
+                let advance = self.rng.below(5) as BlockNumber;
+                ink::env::test::set_block_number::<TestEnv>(block_number() + advance);
             }
 
-            Ok(())
-        }
+            fn check_invariants(&mut self) {
+                let c = &self.contract;
+
+                // Solvency: the balance is exactly what the contract owes
+                let callee = ink::env::test::callee::<TestEnv>();
+                assert_eq!(balance(callee), c.get_total_liabilities(), "contract balance");
+
+                // Escrow: every locked unit belongs to a stored order, fill or reservation
+                let mut escrowed = 0;
+                let mut stored_pending = HashSet::new();
+                for order_id in &self.order_ids {
+                    let order = c.get_order(*order_id).expect("order purged outside cleanup_order");
+                    if !order.cancelled {
+                        escrowed += order.total_amount - order.filled_amount;
+                    }
+                    for fill_id in c.order_fills.get(order_id).unwrap_or_default() {
+                        let fill = c.get_fill(fill_id).expect("indexed fill missing");
+                        if !fill.withdrawn && !fill.refunded {
+                            escrowed += fill.fill_amount + fill.deposit;
+                            stored_pending.insert(fill_id);
+                        }
+                    }
+                }
+                for (order_id, reserver) in &self.reservations {
+                    let reservation = c.get_reservation(*order_id, *reserver).expect("reservation");
+                    escrowed += reservation.bond;
+                }
+                assert_eq!(c.get_total_locked(), escrowed, "total_locked");
+                let pending: HashSet<[u8; 32]> = self.pending.iter().map(|fill| fill.id).collect();
+                assert_eq!(stored_pending, pending, "pending fills");
 
-        fn validate_fill_withdrawal(
-            &self,
-            fill: &FillExecution,
-            order: &PartialFillOrder,
-            caller: &Address,
-        ) -> Result<(), Error> {
-            if *caller != fill.taker {
-                return Err(Error::UnauthorizedWithdraw);
-            }
+                if self.fee_bps == 0 {
+                    assert_eq!(c.get_protocol_fees(), 0, "zero-fee protocol fees");
+                }
 
-            if fill.withdrawn || fill.refunded {
-                return Err(Error::AlreadyProcessed);
+                // Counters only move forward
+                assert!(c.order_counter <= c.order_counter_high);
+                assert!(c.fill_counter <= c.fill_counter_high);
+                let counters = (c.order_counter_high, c.fill_counter_high);
+                assert!(counters.0 >= self.counters.0 && counters.1 >= self.counters.1, "counters");
+                self.counters = counters;
+
+                #[cfg(feature = "periphery")]
+                {
+                    // Every stored order is reachable through exactly the indices it belongs to
+                    let ids = |list| c.id_list_range(list, 0, c.id_list_len(list)).into_iter().collect::<HashSet<[u8; 32]>>();
+                    let active = ids(IdList::ActiveOrders);
+                    let archived = ids(IdList::ArchivedOrders);
+                    assert_eq!(active.len() + archived.len(), self.order_ids.len(), "lifecycle index");
+                    let mut by_maker = 0;
+                    for maker in self.makers {
+                        by_maker += c.id_list_len(IdList::MakerOrders(maker)) as usize;
+                    }
+                    assert_eq!(by_maker, self.order_ids.len(), "maker index");
+                    for order_id in &self.order_ids {
+                        let order = c.get_order(*order_id).unwrap();
+                        let terminal = PolkadotPartialFills::is_terminal(&order);
+                        assert_eq!(active.contains(order_id), !terminal, "active index");
+                        assert_eq!(archived.contains(order_id), terminal, "archived index");
+                        let maker_index = IdList::MakerOrders(order.maker);
+                        assert!(c.id_list_contains(maker_index, *order_id), "maker index");
+                        let swap_index = IdList::SwapOrders(order.swap_id);
+                        assert!(c.id_list_contains(swap_index, *order_id), "swap index");
+                    }
+                    assert_eq!(ids(IdList::PendingFills), pending, "pending fill index");
+                }
             }
+        }
 
-            let current_block = self.env().block_number();
-            if current_block >= order.timelock {
-                return Err(Error::TimelockExpired);
+        #[ink::test]
+        #[ignore = "soak test; run with --ignored --release"]
+        fn soak_week_of_activity() {
+            let mut soak = Soak::new(DEFAULT_FEE_BPS);
+            for operation in 1..=OPERATIONS {
+                soak.step();
+                if operation % CHECK_INTERVAL == 0 {
+                    soak.check_invariants();
+                }
             }
 
-            Ok(())
+            // Over a week of blocks orders outlive their retention, so storage is
+            // reclaimed rather than only growing
+            let cleaned = soak.outcomes.get("cleanup").map_or(0, |(accepted, _)| *accepted);
+            assert!(cleaned > 0, "no order was cleaned up");
+            let created = soak.outcomes.get("create").map_or(0, |(accepted, _)| *accepted);
+            assert_eq!(soak.order_ids.len(), created - cleaned, "stored orders");
         }
 
-        fn validate_fill_refund(
-            &self,
-            fill: &FillExecution,
-            order: &PartialFillOrder,
-            caller: &Address,
-        ) -> Result<(), Error> {
-            if *caller != order.maker {
-                return Err(Error::UnauthorizedRefund);
+        /// Launch runs fee-free: the same lifecycle with `protocol_fee_bps = 0` must
+        /// escrow and pay out gross amounts and never accrue a protocol fee
+        #[ink::test]
+        fn zero_fee_lifecycle() {
+            let mut soak = Soak::new(0);
+            // Short enough for cleanups to happen within the run
+            soak.contract.set_retention_blocks(500, BlockNumber::MAX).unwrap();
+            for operation in 1..=ZERO_FEE_OPERATIONS {
+                soak.step();
+                if operation % ZERO_FEE_CHECK_INTERVAL == 0 {
+                    soak.check_invariants();
+                }
             }
 
-            if fill.withdrawn || fill.refunded {
-                return Err(Error::AlreadyProcessed);
+            assert_eq!(soak.contract.get_protocol_fees(), 0);
+            let ops = ["create", "fill", "withdraw", "refund", "cancel", "cleanup", "reserve", "release"];
+            for op in ops {
+                let accepted = soak.outcomes.get(op).map_or(0, |(accepted, _)| *accepted);
+                assert!(accepted > 0, "{op} never ran");
             }
+        }
+    }
 
-            let current_block = self.env().block_number();
-            if current_block < order.timelock {
-                return Err(Error::TimelockNotExpired);
-            }
+    /// Test support for reproducing on-chain incidents. A trace exported by
+    /// `lib/replay-trace.ts` is replayed call by call against a fresh instance; the
+    /// replay stops at the first failing call and dumps the state that call touched.
+    /// Order and fill ids derived on chain are remapped through the recorded
+    /// outcomes, since a fresh instance derives its own.
+    #[cfg(test)]
+    mod replay {
+        use super::*;
+        use serde_json::Value;
+        use std::collections::{HashMap, HashSet};
+        use std::fmt::Write;
 
-            Ok(())
-        }
+        type TestEnv = ink::env::DefaultEnvironment;
 
-        fn validate_preimage(
-            &self,
-            order: &PartialFillOrder,
-            preimage: &[u8; 32],
-        ) -> Result<(), Error> {
-            let hash = self.compute_sha256(preimage);
-            if hash != order.hashlock {
-                return Err(Error::InvalidHashlock);
-            }
-            Ok(())
-        }
+        /// Funds every caller once; traces never spend anywhere near this
+        const CALLER_BALANCE: Balance = 1_000_000_000_000_000_000;
 
-        fn calculate_fees(&self, amount: Balance) -> (Balance, Balance) {
-            let fee = (amount * self.protocol_fee_bps as u128) / 10000;
-            let net_amount = amount - fee;
-            (net_amount, fee)
+        pub(super) struct Replayed {
+            pub contract: PolkadotPartialFills,
+            /// Index of the first failing call and its error, `None` if all succeeded
+            pub failure: Option<(usize, Error)>,
+            /// Order, fills and accounting at the failing call
+            pub dump: String,
         }
 
-        fn get_order_or_error(&self, order_id: &[u8; 32]) -> Result<PartialFillOrder, Error> {
-            self.orders.get(order_id).ok_or(Error::OrderNotFound)
+        fn bytes<const N: usize>(value: &Value) -> [u8; N] {
+            let hex = value.as_str().and_then(|s| s.strip_prefix("0x")).expect("0x-prefixed hex");
+            assert_eq!(hex.len(), N * 2, "expected {N} bytes, got {hex}");
+            let mut out = [0u8; N];
+            for (i, byte) in out.iter_mut().enumerate() {
+                *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+            }
+            out
         }
 
-        fn get_fill_or_error(&self, fill_id: &[u8; 32]) -> Result<FillExecution, Error> {
-            self.fills.get(fill_id).ok_or(Error::FillNotFound)
+        fn hex(bytes: &[u8]) -> String {
+            bytes.iter().map(|b| format!("{:02x}", b)).collect()
         }
 
-        fn execute_transfer(&self, to: Address, amount: Balance) -> Result<(), Error> {
-            let amount_u256: ink::primitives::U256 = amount.into();
-            self.env().transfer(to, amount_u256)
-                .map_err(|_| Error::TransferFailed)
+        fn address(value: &Value) -> Address {
+            Address::from(bytes::<20>(value))
         }
 
-        fn ensure_admin(&self) -> Result<(), Error> {
-            if self.env().caller() != self.admin {
-                return Err(Error::Unauthorized);
+        /// polkadot.js renders integers as numbers, decimal strings or hex strings
+        fn integer(value: &Value) -> Balance {
+            match value {
+                Value::Number(n) => n.as_u64().expect("unsigned integer") as Balance,
+                Value::String(s) => match s.strip_prefix("0x") {
+                    Some(hex) => Balance::from_str_radix(hex, 16).unwrap(),
+                    None => s.parse().unwrap(),
+                },
+                _ => panic!("expected an integer, got {value}"),
             }
-            Ok(())
         }
 
-        fn generate_order_id(
-            &mut self,
-            maker: &Address,
-            amount: Balance,
-            hashlock: &[u8; 32],
-            timelock: BlockNumber,
-            swap_id: &[u8; 32],
-        ) -> [u8; 32] {
-            self.order_counter += 1;
-            
-            let mut data = Vec::new();
-            data.extend_from_slice(&maker.encode());
-            data.extend_from_slice(&amount.to_le_bytes());
-            data.extend_from_slice(hashlock);
-            data.extend_from_slice(&timelock.to_le_bytes());
-            data.extend_from_slice(swap_id);
-            data.extend_from_slice(&self.order_counter.to_le_bytes());
-
-            self.compute_sha256(&data)
+        fn flag(value: &Value) -> bool {
+            value.as_bool().expect("boolean")
         }
 
-        fn generate_fill_id(
-            &self,
-            order_id: &[u8; 32],
-            taker: &Address,
-            fill_amount: Balance,
-        ) -> [u8; 32] {
-            let mut data = Vec::new();
-            data.extend_from_slice(order_id);
-            data.extend_from_slice(&taker.encode());
-            data.extend_from_slice(&fill_amount.to_le_bytes());
-            data.extend_from_slice(&self.env().block_timestamp().to_le_bytes());
-            data.extend_from_slice(&self.env().block_number().to_le_bytes());
-
-            self.compute_sha256(&data)
+        /// A route argument; traces recorded before routes existed carry the old
+        /// `credit_payout` flag, or nothing for `refund_fill`
+        fn payout_route(value: Option<&Value>) -> Option<PayoutRoute> {
+            match value? {
+                Value::Null => None,
+                Value::Bool(credit) => credit.then_some(PayoutRoute::Credit),
+                Value::String(s) if s.eq_ignore_ascii_case("direct") => Some(PayoutRoute::Direct),
+                Value::String(s) if s.eq_ignore_ascii_case("credit") => Some(PayoutRoute::Credit),
+                Value::Object(map) => {
+                    let (variant, fields) = map.iter().next().expect("route variant");
+                    match variant.to_ascii_lowercase().as_str() {
+                        "direct" => Some(PayoutRoute::Direct),
+                        "credit" => Some(PayoutRoute::Credit),
+                        "xcm" => Some(PayoutRoute::Xcm {
+                            para_id: integer(&fields["paraId"]) as u32,
+                            beneficiary: bytes(&fields["beneficiary"]),
+                        }),
+                        other => panic!("unknown payout route {other}"),
+                    }
+                }
+                other => panic!("unexpected payout route {other}"),
+            }
         }
 
-        fn generate_contract_id(&mut self, order_id: &[u8; 32], fill_id: &[u8; 32]) -> [u8; 32] {
-            self.fill_counter += 1;
-            
-            let mut data = Vec::new();
-            data.extend_from_slice(order_id);
-            data.extend_from_slice(fill_id);
-            data.extend_from_slice(&self.env().block_timestamp().to_le_bytes());
-            data.extend_from_slice(&self.fill_counter.to_le_bytes());
-
-            self.compute_sha256(&data)
+        fn cross_address(value: &Value) -> Option<Vec<u8>> {
+            let hex = value.as_str()?.strip_prefix("0x").expect("0x-prefixed hex");
+            let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+            Some((0..hex.len()).step_by(2).map(byte).collect())
         }
 
-        fn compute_sha256(&self, data: &[u8]) -> [u8; 32] {
-            use ink::env::hash::{Sha2x256, HashOutput};
-            let mut output = <Sha2x256 as HashOutput>::Type::default();
-            ink::env::hash_bytes::<Sha2x256>(data, &mut output);
-            output
+        /// Unit variant name, matching how the exporter records errors
+        fn variant(error: &Error) -> String {
+            let debug = format!("{error:?}");
+            debug.split([' ', '(', '{']).next().unwrap().to_string()
         }
-    }
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
+        struct Replayer {
+            contract: PolkadotPartialFills,
+            ids: HashMap<[u8; 32], [u8; 32]>, // recorded id => replayed id
+            funded: HashSet<Address>,
+        }
 
-        type TestEnv = ink::env::DefaultEnvironment;
+        impl Replayer {
+            fn id(&self, value: &Value) -> [u8; 32] {
+                let recorded = bytes::<32>(value);
+                self.ids.get(&recorded).copied().unwrap_or(recorded)
+            }
 
-        #[ink::test]
-        fn test_partial_fill_order_creation() {
-            let mut contract = PolkadotPartialFills::new();
-            let accounts = ink::env::test::default_accounts::<TestEnv>();
-            
-            ink::env::test::set_caller::<TestEnv>(accounts.alice);
-            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
-            ink::env::test::set_block_number::<TestEnv>(100);
+            /// Runs one call; returns the id it derived, if any
+            fn dispatch(&mut self, message: &str, args: &[Value]) -> Result<Option<[u8; 32]>, Error> {
+                match message {
+                    "create_partial_fill_order" => self
+                        .contract
+                        .create_partial_fill_order(
+                            integer(&args[0]),
+                            integer(&args[1]),
+                            bytes(&args[2]),
+                            integer(&args[3]) as BlockNumber,
+                            bytes(&args[4]),
+                            integer(&args[5]) as u32,
+                            integer(&args[6]) as u32,
+                            integer(&args[7]),
+                            flag(&args[8]),
+                            integer(&args[9]) as u32,
+                            cross_address(&args[10]),
+                            cross_address(&args[11]),
+                        )
+                        .map(Some),
+                    "fill_order" => {
+                        let order_id = self.id(&args[0]);
+                        let (amount, taker) = (integer(&args[1]), address(&args[2]));
+                        self.contract.fill_order(order_id, amount, taker).map(Some)
+                    }
+                    "withdraw_fill" => {
+                        let fill_id = self.id(&args[0]);
+                        let (preimage, route) = (bytes(&args[1]), payout_route(args.get(2)));
+                        self.contract.withdraw_fill(fill_id, preimage, route, None).map(|_| None)
+                    }
+                    "refund_fill" => {
+                        let fill_id = self.id(&args[0]);
+                        self.contract.refund_fill(fill_id, payout_route(args.get(1))).map(|_| None)
+                    }
+                    "cancel_order" => {
+                        let order_id = self.id(&args[0]);
+                        self.contract.cancel_order(order_id).map(|_| None)
+                    }
+                    "cleanup_order" => {
+                        let order_id = self.id(&args[0]);
+                        self.contract.cleanup_order(order_id).map(|_| None)
+                    }
+                    "reserve" => {
+                        let order_id = self.id(&args[0]);
+                        self.contract.reserve(order_id, integer(&args[1])).map(|_| None)
+                    }
+                    "release_reservation" => {
+                        let order_id = self.id(&args[0]);
+                        self.contract.release_reservation(order_id).map(|_| None)
+                    }
+                    "withdraw_credit" => {
+                        self.contract.withdraw_credit(integer(&args[0])).map(|_| None)
+                    }
+                    other => panic!("replay does not support `{other}`"),
+                }
+            }
 
-            let result = contract.create_partial_fill_order(
-                1000,                    // total_amount
-                100,                     // min_fill_amount
-                [0x01; 32],             // hashlock
-                500,                     // timelock
-                [0x02; 32],             // swap_id
-                1,                       // source_chain
-                2,                       // dest_chain
-                1_000_000_000_000,      // dest_amount_per_unit (1:1 ratio scaled)
-                true,                    // allow_partial_fills
-                5,                       // max_fills
-                None,                    // sender_cross_address
-                None,                    // receiver_cross_address
-            );
+            /// The order a call touched, through an order id or a fill id argument
+            fn touched_order(&self, args: &[Value]) -> Option<[u8; 32]> {
+                args.iter()
+                    .filter(|arg| arg.as_str().map_or(false, |s| s.len() == 66))
+                    .map(|arg| self.id(arg))
+                    .find_map(|id| match self.contract.get_order(id) {
+                        Some(_) => Some(id),
+                        None => self.contract.get_fill(id).map(|fill| fill.order_id),
+                    })
+            }
 
-            assert!(result.is_ok());
-            
-            let order_id = result.unwrap();
-            assert!(contract.order_exists(order_id));
-            
-            let order = contract.get_order(order_id).unwrap();
-            assert_eq!(order.maker, accounts.alice);
-            assert_eq!(order.total_amount, 997); // 1000 - 3 (fee)
-            assert_eq!(order.filled_amount, 0);
-            assert_eq!(order.min_fill_amount, 100);
-            assert!(order.allow_partial_fills);
-            assert_eq!(order.max_fills, 5);
-            assert_eq!(order.current_fills, 0);
+            fn dump(&self, index: usize, call: &Value, caller: Address, error: &Error) -> String {
+                let message = call["message"].as_str().unwrap();
+                let c = &self.contract;
+                let mut out = String::new();
+                let block = ink::env::block_number::<TestEnv>();
+                writeln!(out, "call {index} `{message}` at block {block} failed: {error:?}").unwrap();
+                let args = call["args"].as_array().unwrap();
+                if let Some(order_id) = self.touched_order(args) {
+                    writeln!(out, "order 0x{}: {:?}", hex(&order_id), c.get_order(order_id)).unwrap();
+                    for fill_id in c.order_fills.get(order_id).unwrap_or_default() {
+                        writeln!(out, "fill 0x{}: {:?}", hex(&fill_id), c.get_fill(fill_id)).unwrap();
+                    }
+                }
+                writeln!(out, "total_locked {}", c.get_total_locked()).unwrap();
+                writeln!(out, "total_liabilities {}", c.get_total_liabilities()).unwrap();
+                writeln!(out, "protocol_fees {}", c.get_protocol_fees()).unwrap();
+                writeln!(out, "caller credit {}", c.get_credit(caller)).unwrap();
+                out
+            }
         }
 
-        #[ink::test]
-        fn test_partial_fill_execution() {
-            let mut contract = PolkadotPartialFills::new();
-            let accounts = ink::env::test::default_accounts::<TestEnv>();
-            
-            // Create order
-            ink::env::test::set_caller::<TestEnv>(accounts.alice);
-            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
-            ink::env::test::set_block_number::<TestEnv>(100);
-
-            let order_id = contract.create_partial_fill_order(
-                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2, 
-                1_000_000_000_000, true, 5, None, None
-            ).unwrap();
-
-            // Fill order partially
-            ink::env::test::set_caller::<TestEnv>(accounts.bob);
-            let fill_result = contract.fill_order(order_id, 200, accounts.charlie);
-            assert!(fill_result.is_ok());
-
-            let fill_id = fill_result.unwrap();
-            let fill = contract.get_fill(fill_id).unwrap();
-            assert_eq!(fill.taker, accounts.bob);
-            assert_eq!(fill.fill_amount, 200);
-            assert!(!fill.withdrawn);
-            assert!(!fill.refunded);
+        pub(super) fn replay(trace: &str) -> Replayed {
+            let trace: Value = serde_json::from_str(trace).expect("trace is JSON");
+            let calls = trace["calls"].as_array().expect("`calls` array");
+            let first_block = calls.first().map_or(0, |call| integer(&call["block"]));
+
+            ink::env::test::set_callee::<TestEnv>(address(&trace["contract"]));
+            ink::env::test::set_caller::<TestEnv>(address(&trace["deployer"]));
+            ink::env::test::set_block_number::<TestEnv>(first_block as BlockNumber);
+            let mut replayer = Replayer {
+                contract: PolkadotPartialFills::new(),
+                ids: HashMap::new(),
+                funded: HashSet::new(),
+            };
 
-            // Check order state
-            let order = contract.get_order(order_id).unwrap();
-            assert_eq!(order.filled_amount, 200);
-            assert_eq!(order.current_fills, 1);
-            assert_eq!(contract.get_remaining_amount(order_id), 797); // 997 - 200
+            for (index, call) in calls.iter().enumerate() {
+                let message = call["message"].as_str().expect("`message` name");
+                let caller = address(&call["caller"]);
+                if replayer.funded.insert(caller) {
+                    ink::env::test::set_account_balance::<TestEnv>(caller, CALLER_BALANCE.into());
+                }
+                ink::env::test::set_block_number::<TestEnv>(integer(&call["block"]) as BlockNumber);
+                ink::env::test::set_caller::<TestEnv>(caller);
+                ink::env::test::transfer_in::<TestEnv>(integer(&call["value"]).into());
+
+                let recorded = &call["outcome"];
+                match replayer.dispatch(message, call["args"].as_array().expect("`args` array")) {
+                    Ok(id) => {
+                        let recorded_ok = recorded.get("ok").is_some();
+                        assert!(recorded_ok, "call {index} `{message}` diverged: {recorded}");
+                        if let Some(id) = id {
+                            replayer.ids.insert(bytes(&recorded["ok"]), id);
+                        }
+                    }
+                    Err(error) => {
+                        assert_eq!(
+                            recorded["err"].as_str(),
+                            Some(variant(&error).as_str()),
+                            "call {index} `{message}` diverged"
+                        );
+                        let dump = replayer.dump(index, call, caller, &error);
+                        let failure = Some((index, error));
+                        return Replayed { contract: replayer.contract, failure, dump };
+                    }
+                }
+            }
+            Replayed { contract: replayer.contract, failure: None, dump: String::new() }
         }
 
         #[ink::test]
-        fn test_multiple_partial_fills() {
-            let mut contract = PolkadotPartialFills::new();
-            let accounts = ink::env::test::default_accounts::<TestEnv>();
-            
-            // Create order
-            ink::env::test::set_caller::<TestEnv>(accounts.alice);
-            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
-            ink::env::test::set_block_number::<TestEnv>(100);
+        fn replay_reproduces_paseo_withdraw_after_timelock() {
+            const TRACE: &str = include_str!("../fixtures/replay/paseo_withdraw_after_timelock.json");
+            let replayed = replay(TRACE);
+
+            // The late withdrawal fails and the maker's refund after it is not replayed
+            assert_eq!(replayed.failure, Some((4, Error::TimelockExpired)));
+            let headline = "call 4 `withdraw_fill` at block 512 failed: TimelockExpired";
+            assert!(replayed.dump.starts_with(headline));
+            assert!(replayed.dump.contains("timelock: 500"));
+            assert!(replayed.dump.contains("total_locked 700"));
+
+            // Only the taker who withdrew in time was paid
+            let c = &replayed.contract;
+            assert_eq!(c.get_total_locked(), 1000 - 300);
+            assert_eq!(c.get_protocol_fees(), 0);
+        }
+    }
 
-            let order_id = contract.create_partial_fill_order(
-                1000, 100, [0x01; 32], 500, [0x02; 32], 1, 2, 
-                1_000_000_000_000, true, 3, None, None
-            ).unwrap();
+    #[cfg(test)]
+    mod feature_matrix_tests {
+        use serde_json::Value;
 
-            // First fill
-            ink::env::test::set_caller::<TestEnv>(accounts.bob);
-            let fill1_id = contract.fill_order(order_id, 200, accounts.charlie).unwrap();
+        extern "Rust" {
+            // Emitted by `#[ink::contract]` in std builds; ink! reserves the name's prefix
+            #[link_name = "__ink_generate_metadata"]
+            fn generate_metadata() -> ink::metadata::InkProject;
+        }
 
-            // Second fill
-            ink::env::test::set_caller::<TestEnv>(accounts.charlie);
-            let fill2_id = contract.fill_order(order_id, 300, accounts.bob).unwrap();
+        #[test]
+        fn test_metadata_lists_the_messages_of_enabled_features() {
+            const FIXTURES: &str = include_str!("../fixtures/messages.json");
+            let fixtures: Value = serde_json::from_str(FIXTURES).expect("fixtures are JSON");
+
+            let mut expected = Vec::new();
+            for (feature, enabled) in [
+                ("core", true),
+                ("periphery", cfg!(feature = "periphery")),
+                ("psp22", cfg!(feature = "psp22")),
+            ] {
+                let messages = fixtures["messages"][feature].as_array().expect("feature is listed");
+                if enabled {
+                    expected.extend(messages.iter().map(|label| label.as_str().unwrap().to_string()));
+                }
+            }
 
-            // Check order state
-            let order = contract.get_order(order_id).unwrap();
-            assert_eq!(order.filled_amount, 500); // 200 + 300
-            assert_eq!(order.current_fills, 2);
-            assert_eq!(contract.get_remaining_amount(order_id), 497); // 997 - 500
+            let project = unsafe { generate_metadata() };
+            let mut compiled: Vec<String> =
+                project.spec().messages().iter().map(|message| message.label().clone()).collect();
+            compiled.sort();
+            expected.sort();
+            assert_eq!(compiled, expected);
+        }
+    }
 
-            // Check fills tracking
-            let order_fills = contract.get_order_fills(order_id);
-            assert_eq!(order_fills.len(), 2);
-            assert!(order_fills.contains(&fill1_id));
-            assert!(order_fills.contains(&fill2_id));
+    /// Runs against a node: `cargo test --features e2e-tests`
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use ink_e2e::ContractsBackend;
+        use polkadot_completion_receiver::{Behaviour, CompletionReceiver, CompletionReceiverRef};
+        use polkadot_forwarder::{Forwarder, ForwarderRef};
+
+        type E2EResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+        const AMOUNT: Balance = 1_000_000_000_000;
+
+        /// A receiver recording the completion, one reverting and one running out of
+        /// its gas budget: every order completes, and only the first receiver records it
+        #[ink_e2e::test]
+        async fn completion_callback_outcomes<Client: E2EBackend>(mut client: Client) -> E2EResult<()> {
+            let mut constructor = PolkadotPartialFillsRef::new();
+            let swap = client
+                .instantiate("polkadotrelayer", &ink_e2e::alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("swap contract instantiation failed");
+            let mut swap_calls = swap.call_builder::<PolkadotPartialFills>();
+            let block = client
+                .call(&ink_e2e::alice(), &swap_calls.get_incident_snapshot(None))
+                .dry_run()
+                .await?
+                .return_value()
+                .block;
+
+            for (swap_id, behaviour) in [
+                ([0x01; 32], Behaviour::Record),
+                ([0x02; 32], Behaviour::Revert),
+                ([0x03; 32], Behaviour::ExhaustGas),
+            ] {
+                let mut constructor = CompletionReceiverRef::with_behaviour(swap.addr, behaviour);
+                let receiver = client
+                    .instantiate("polkadot_completion_receiver", &ink_e2e::alice(), &mut constructor)
+                    .submit()
+                    .await
+                    .expect("receiver instantiation failed");
+
+                let options = OrderOptions {
+                    completion_callback: Some(CompletionCallback {
+                        contract: receiver.addr,
+                        selector: ON_ORDER_COMPLETED_SELECTOR,
+                    }),
+                    ..Default::default()
+                };
+                let create = swap_calls.create_partial_fill_order_with_options(
+                    AMOUNT, 1, [0x42; 32], block + 200, swap_id, 1, 2,
+                    1_000_000_000_000, true, 5, None, None, options,
+                );
+                let order_id = client
+                    .call(&ink_e2e::alice(), &create)
+                    .value(AMOUNT)
+                    .submit()
+                    .await
+                    .expect("order creation failed")
+                    .return_value()
+                    .expect("order rejected");
+
+                let taker = ink_e2e::address::<Environment>(ink_e2e::Sr25519Keyring::Bob);
+                client
+                    .call(&ink_e2e::bob(), &swap_calls.fill_order(order_id, AMOUNT, taker))
+                    .submit()
+                    .await
+                    .expect("completing fill failed")
+                    .return_value()
+                    .expect("completing fill rejected");
+
+                let status = client
+                    .call(&ink_e2e::alice(), &swap_calls.get_order_status(order_id))
+                    .dry_run()
+                    .await?
+                    .return_value();
+                assert_eq!(status, Some(OrderStatus::FullyFilled));
+
+                let receiver_calls = receiver.call_builder::<CompletionReceiver>();
+                let recorded = client
+                    .call(&ink_e2e::alice(), &receiver_calls.get_completion_count())
+                    .dry_run()
+                    .await?
+                    .return_value();
+                assert_eq!(recorded, u32::from(behaviour == Behaviour::Record));
+            }
+            Ok(())
         }
 
-        #[ink::test]
-        fn test_fill_withdrawal_with_secret() {
-            let mut contract = PolkadotPartialFills::new();
-            let accounts = ink::env::test::default_accounts::<TestEnv>();
-            
-            // Create order
-            ink::env::test::set_caller::<TestEnv>(accounts.alice);
-            ink::env::test::set_value_transferred::<TestEnv>(1000u128.into());
-            ink::env::test::set_block_number::<TestEnv>(100);
+        /// A fill paid to a contract that reverts on receive: the fill still settles
+        /// and the payout waits in the receiver's credit
+        #[ink_e2e::test]
+        async fn refused_payout_is_quarantined<Client: E2EBackend>(mut client: Client) -> E2EResult<()> {
+            let mut constructor = PolkadotPartialFillsRef::new();
+            let swap = client
+                .instantiate("polkadotrelayer", &ink_e2e::alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("swap contract instantiation failed");
+            let mut swap_calls = swap.call_builder::<PolkadotPartialFills>();
+            let receiver = client
+                .instantiate("polkadot_forwarder", &ink_e2e::bob(), &mut ForwarderRef::new())
+                .submit()
+                .await
+                .expect("forwarder instantiation failed");
+            let block = client
+                .call(&ink_e2e::alice(), &swap_calls.get_incident_snapshot(None))
+                .dry_run()
+                .await?
+                .return_value()
+                .block;
 
             let secret = [0x42; 32];
-            let hashlock = contract.compute_sha256(&secret);
-
-            let order_id = contract.create_partial_fill_order(
-                1000, 100, hashlock, 500, [0x02; 32], 1, 2, 
-                1_000_000_000_000, true, 5, None, None
-            ).unwrap();
+            let create = swap_calls.create_partial_fill_order(
+                AMOUNT, 1, packed_sha256(&secret), block + 200, [0x01; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None,
+            );
+            let order_id = client
+                .call(&ink_e2e::alice(), &create)
+                .value(AMOUNT)
+                .submit()
+                .await
+                .expect("order creation failed")
+                .return_value()
+                .expect("order rejected");
+            let fill_id = client
+                .call(&ink_e2e::bob(), &swap_calls.fill_order(order_id, AMOUNT / 2, receiver.addr))
+                .submit()
+                .await
+                .expect("fill failed")
+                .return_value()
+                .expect("fill rejected");
+            client
+                .call(&ink_e2e::bob(), &swap_calls.withdraw_fill(fill_id, secret, None, None))
+                .submit()
+                .await
+                .expect("withdrawal failed")
+                .return_value()
+                .expect("withdrawal rejected");
+
+            let fill = client
+                .call(&ink_e2e::alice(), &swap_calls.get_fill(fill_id))
+                .dry_run()
+                .await?
+                .return_value()
+                .expect("fill missing");
+            assert!(fill.withdrawn);
+            let credit = client
+                .call(&ink_e2e::alice(), &swap_calls.get_credit(receiver.addr))
+                .dry_run()
+                .await?
+                .return_value();
+            let total_credits = client
+                .call(&ink_e2e::alice(), &swap_calls.get_total_credits())
+                .dry_run()
+                .await?
+                .return_value();
+            assert!(credit > 0);
+            assert_eq!(credit, total_credits);
+            Ok(())
+        }
 
-            // Fill order
-            ink::env::test::set_caller::<TestEnv>(accounts.bob);
-            let fill_id = contract.fill_order(order_id, 200, accounts.charlie).unwrap();
+        /// A contract admin that reverts on receive: the sweep keeps the fees, backs
+        /// off, and the stuck fees can be sent elsewhere
+        #[ink_e2e::test]
+        async fn refused_fee_sweep_backs_off<Client: E2EBackend>(mut client: Client) -> E2EResult<()> {
+            let mut constructor = PolkadotPartialFillsRef::new();
+            let swap = client
+                .instantiate("polkadotrelayer", &ink_e2e::alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("swap contract instantiation failed");
+            let mut swap_calls = swap.call_builder::<PolkadotPartialFills>();
+            let admin = client
+                .instantiate("polkadot_forwarder", &ink_e2e::alice(), &mut ForwarderRef::new())
+                .submit()
+                .await
+                .expect("forwarder instantiation failed");
+            let mut admin_calls = admin.call_builder::<Forwarder>();
+            let block = client
+                .call(&ink_e2e::alice(), &swap_calls.get_incident_snapshot(None))
+                .dry_run()
+                .await?
+                .return_value()
+                .block;
 
-            // Withdraw with correct secret
-            let withdraw_result = contract.withdraw_fill(fill_id, secret);
-            assert!(withdraw_result.is_ok());
+            let secret = [0x42; 32];
+            let create = swap_calls.create_partial_fill_order(
+                AMOUNT, 1, packed_sha256(&secret), block + 200, [0x01; 32], 1, 2,
+                1_000_000_000_000, true, 5, None, None,
+            );
+            let order_id = client
+                .call(&ink_e2e::alice(), &create)
+                .value(AMOUNT)
+                .submit()
+                .await
+                .expect("order creation failed")
+                .return_value()
+                .expect("order rejected");
+            let taker = ink_e2e::address::<Environment>(ink_e2e::Sr25519Keyring::Bob);
+            let fill_id = client
+                .call(&ink_e2e::bob(), &swap_calls.fill_order(order_id, AMOUNT, taker))
+                .submit()
+                .await
+                .expect("fill failed")
+                .return_value()
+                .expect("fill rejected");
+            client
+                .call(&ink_e2e::bob(), &swap_calls.withdraw_fill(fill_id, secret, None, None))
+                .submit()
+                .await
+                .expect("withdrawal failed")
+                .return_value()
+                .expect("withdrawal rejected");
+            client
+                .call(&ink_e2e::alice(), &swap_calls.update_admin(admin.addr, BlockNumber::MAX))
+                .submit()
+                .await
+                .expect("admin handover failed")
+                .return_value()
+                .expect("admin handover rejected");
+            let fees = client
+                .call(&ink_e2e::alice(), &swap_calls.get_protocol_fees())
+                .dry_run()
+                .await?
+                .return_value();
+            assert!(fees > 0);
+
+            let sweep = [
+                ink::selector_bytes!("withdraw_protocol_fees").to_vec(),
+                (None::<PayoutRoute>, BlockNumber::MAX).encode(),
+            ]
+            .concat();
+            for expected in [Ok(false), Err(Error::FeeSweepBackoff)] {
+                let output = client
+                    .call(&ink_e2e::alice(), &admin_calls.forward(swap.addr, sweep.clone()))
+                    .submit()
+                    .await
+                    .expect("forwarded sweep failed")
+                    .return_value();
+                assert_eq!(Result::<bool, Error>::decode(&mut &output[..])?, expected);
+            }
+            let kept = client
+                .call(&ink_e2e::alice(), &swap_calls.get_protocol_fees())
+                .dry_run()
+                .await?
+                .return_value();
+            assert_eq!(kept, fees);
+            let failed_at = client
+                .call(&ink_e2e::alice(), &swap_calls.get_fee_sweep_failed_at())
+                .dry_run()
+                .await?
+                .return_value();
+            assert!(failed_at.is_some());
+
+            let redirect = [
+                ink::selector_bytes!("redirect_stuck_fees").to_vec(),
+                (taker, BlockNumber::MAX).encode(),
+            ]
+            .concat();
+            let output = client
+                .call(&ink_e2e::alice(), &admin_calls.forward(swap.addr, redirect))
+                .submit()
+                .await
+                .expect("forwarded redirect failed")
+                .return_value();
+            assert_eq!(Result::<(), Error>::decode(&mut &output[..])?, Ok(()));
+            let remaining = client
+                .call(&ink_e2e::alice(), &swap_calls.get_protocol_fees())
+                .dry_run()
+                .await?
+                .return_value();
+            assert_eq!(remaining, 0);
+            let failed_at = client
+                .call(&ink_e2e::alice(), &swap_calls.get_fee_sweep_failed_at())
+                .dry_run()
+                .await?
+                .return_value();
+            assert_eq!(failed_at, None);
+            Ok(())
+        }
 
-            let fill = contract.get_fill(fill_id).unwrap();
-            assert!(fill.withdrawn);
-            assert_eq!(fill.preimage, Some(secret));
+        /// The staged code runs `check_migration` on this contract's storage before an
+        /// early switch: code of another contract fails the check, an instance of
+        /// this contract's code passes it
+        #[ink_e2e::test]
+        async fn staged_upgrade_checked_against_deployed_code<Client: E2EBackend>(
+            mut client: Client,
+        ) -> E2EResult<()> {
+            let mut constructor = PolkadotPartialFillsRef::new();
+            let swap = client
+                .instantiate("polkadotrelayer", &ink_e2e::alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("swap contract instantiation failed");
+            let mut swap_calls = swap.call_builder::<PolkadotPartialFills>();
+            let compatible = client
+                .instantiate("polkadotrelayer", &ink_e2e::alice(), &mut PolkadotPartialFillsRef::new())
+                .submit()
+                .await
+                .expect("second swap instantiation failed");
+            let incompatible = client
+                .instantiate("polkadot_forwarder", &ink_e2e::alice(), &mut ForwarderRef::new())
+                .submit()
+                .await
+                .expect("forwarder instantiation failed");
+
+            client
+                .call(&ink_e2e::alice(), &swap_calls.upgrade_staged(incompatible.code_hash, BlockNumber::MAX))
+                .submit()
+                .await
+                .expect("staging failed")
+                .return_value()
+                .expect("staging rejected");
+            let check = swap_calls.check_staged_upgrade(incompatible.addr, None, BlockNumber::MAX);
+            let result = client.call(&ink_e2e::alice(), &check).dry_run().await?.return_value();
+            assert_eq!(result, Err(Error::MigrationCheckFailed));
+
+            client
+                .call(&ink_e2e::alice(), &swap_calls.upgrade_staged(compatible.code_hash, BlockNumber::MAX))
+                .submit()
+                .await
+                .expect("restaging failed")
+                .return_value()
+                .expect("restaging rejected");
+            let check = swap_calls.check_staged_upgrade(compatible.addr, None, BlockNumber::MAX);
+            let report = client
+                .call(&ink_e2e::alice(), &check)
+                .submit()
+                .await
+                .expect("check failed")
+                .return_value()
+                .expect("check rejected");
+            assert_eq!(report.storage_version, StorageVersion::CURRENT);
+            let staged = client
+                .call(&ink_e2e::alice(), &swap_calls.get_staged_upgrade())
+                .dry_run()
+                .await?
+                .return_value()
+                .expect("staged upgrade missing");
+            assert!(staged.checked);
+
+            client
+                .call(&ink_e2e::alice(), &swap_calls.finalize_upgrade(BlockNumber::MAX))
+                .submit()
+                .await
+                .expect("finalization failed")
+                .return_value()
+                .expect("finalization rejected");
+            let staged = client
+                .call(&ink_e2e::alice(), &swap_calls.get_staged_upgrade())
+                .dry_run()
+                .await?
+                .return_value();
+            assert_eq!(staged, None);
+            Ok(())
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,161 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+/// Per-chain-pair statistics fed by `PolkadotPartialFills`.
+///
+/// The swap contract pushes a `consume_event` call to every registered consumer
+/// after each fill and withdrawal. Data is the SCALE encoding of
+/// `(source_chain: u32, dest_chain: u32, fill_amount: Balance, dest_amount: Balance)`.
+#[ink::contract]
+mod polkadot_swap_stats {
+    use ink::prelude::vec::Vec;
+    use ink::storage::Mapping;
+    use scale::{Decode, Encode};
+    use scale_info::TypeInfo;
+
+    /// Event type of a fill, matches `STATS_EVENT_FILL` in the swap contract
+    pub const EVENT_FILL: u8 = 0;
+    /// Event type of a withdrawal, matches `STATS_EVENT_WITHDRAW` in the swap contract
+    pub const EVENT_WITHDRAW: u8 = 1;
+
+    /// (source_chain, dest_chain, fill_amount, dest_amount)
+    pub type EventData = (u32, u32, Balance, Balance);
+
+    #[derive(Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum Error {
+        UnauthorizedSource,
+        MalformedEvent,
+        UnknownEventType,
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct PairStats {
+        pub fill_count: u64,
+        pub filled_volume: Balance,
+        pub dest_volume: Balance,
+        pub withdraw_count: u64,
+        pub withdrawn_volume: Balance,
+    }
+
+    #[ink::trait_definition]
+    pub trait IEventConsumer {
+        /// Receive one event from the source contract
+        #[ink(message)]
+        fn consume_event(&mut self, event_type: u8, data: Vec<u8>) -> Result<(), Error>;
+    }
+
+    #[ink(storage)]
+    pub struct PolkadotSwapStats {
+        source: Address, // the PolkadotPartialFills instance allowed to push events
+        pair_stats: Mapping<(u32, u32), PairStats>,
+        pairs: Vec<(u32, u32)>,
+    }
+
+    impl PolkadotSwapStats {
+        #[ink(constructor)]
+        pub fn new(source: Address) -> Self {
+            Self {
+                source,
+                pair_stats: Mapping::default(),
+                pairs: Vec::new(),
+            }
+        }
+
+        #[ink(message)]
+        pub fn get_source(&self) -> Address {
+            self.source
+        }
+
+        #[ink(message)]
+        pub fn get_pair_stats(&self, source_chain: u32, dest_chain: u32) -> PairStats {
+            self.pair_stats.get((source_chain, dest_chain)).unwrap_or_default()
+        }
+
+        /// Every chain pair that has seen at least one event, in first-seen order
+        #[ink(message)]
+        pub fn get_pairs(&self) -> Vec<(u32, u32)> {
+            self.pairs.clone()
+        }
+    }
+
+    impl IEventConsumer for PolkadotSwapStats {
+        #[ink(message)]
+        fn consume_event(&mut self, event_type: u8, data: Vec<u8>) -> Result<(), Error> {
+            if self.env().caller() != self.source {
+                return Err(Error::UnauthorizedSource);
+            }
+
+            let (source_chain, dest_chain, fill_amount, dest_amount) =
+                EventData::decode(&mut &data[..]).map_err(|_| Error::MalformedEvent)?;
+            let pair = (source_chain, dest_chain);
+            let mut stats = self.pair_stats.get(pair).unwrap_or_default();
+
+            match event_type {
+                EVENT_FILL => {
+                    stats.fill_count = stats.fill_count.saturating_add(1);
+                    stats.filled_volume = stats.filled_volume.saturating_add(fill_amount);
+                    stats.dest_volume = stats.dest_volume.saturating_add(dest_amount);
+                }
+                EVENT_WITHDRAW => {
+                    stats.withdraw_count = stats.withdraw_count.saturating_add(1);
+                    stats.withdrawn_volume = stats.withdrawn_volume.saturating_add(fill_amount);
+                }
+                _ => return Err(Error::UnknownEventType),
+            }
+
+            if !self.pair_stats.contains(pair) {
+                self.pairs.push(pair);
+            }
+            self.pair_stats.insert(pair, &stats);
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        type TestEnv = ink::env::DefaultEnvironment;
+
+        #[ink::test]
+        fn test_consume_fill_and_withdraw() {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            let source = accounts.alice;
+            ink::env::test::set_caller::<TestEnv>(source);
+            let mut stats = PolkadotSwapStats::new(source);
+
+            let data: EventData = (1, 2, 200, 400);
+            stats.consume_event(EVENT_FILL, data.encode()).unwrap();
+            stats.consume_event(EVENT_FILL, (1u32, 2u32, 300u128, 600u128).encode()).unwrap();
+            stats.consume_event(EVENT_WITHDRAW, data.encode()).unwrap();
+            stats.consume_event(EVENT_FILL, (2u32, 1u32, 50u128, 25u128).encode()).unwrap();
+
+            assert_eq!(stats.get_pair_stats(1, 2), PairStats {
+                fill_count: 2,
+                filled_volume: 500,
+                dest_volume: 1000,
+                withdraw_count: 1,
+                withdrawn_volume: 200,
+            });
+            assert_eq!(stats.get_pair_stats(2, 1).fill_count, 1);
+            assert_eq!(stats.get_pairs(), vec![(1, 2), (2, 1)]);
+            assert_eq!(stats.get_pair_stats(3, 4), PairStats::default());
+        }
+
+        #[ink::test]
+        fn test_consume_rejects_bad_input() {
+            let accounts = ink::env::test::default_accounts::<TestEnv>();
+            ink::env::test::set_caller::<TestEnv>(accounts.alice);
+            let mut stats = PolkadotSwapStats::new(accounts.alice);
+
+            let data: EventData = (1, 2, 200, 400);
+            assert_eq!(stats.consume_event(7, data.encode()), Err(Error::UnknownEventType));
+            assert_eq!(stats.consume_event(EVENT_FILL, vec![1, 2, 3]), Err(Error::MalformedEvent));
+
+            ink::env::test::set_caller::<TestEnv>(accounts.bob);
+            assert_eq!(stats.consume_event(EVENT_FILL, data.encode()), Err(Error::UnauthorizedSource));
+            assert!(stats.get_pairs().is_empty());
+        }
+    }
+}